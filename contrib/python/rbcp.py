@@ -0,0 +1,103 @@
+"""Run rbcp jobs from Python through the C interface of rbcp-core.
+
+Build the library with `cargo build --release -p rbcp-core --features ffi`
+and point RBCP_LIBRARY at it, or pass its path to load():
+
+    import rbcp
+
+    lib = rbcp.load("target/release/librbcp_core.so")
+    job = lib.job(["C:\\\\Source", "D:\\\\Backup", "/MIR"],
+                  on_event=lambda event: print(event))
+    result = job.run()
+    print(result["ok"], result["stats"]["files_copied"])
+
+Events are dicts, either {"event": "progress", ...} or
+{"event": "log", "message": ...}, delivered from the copying threads.
+"""
+
+import ctypes
+import json
+import os
+import sys
+
+_CALLBACK = ctypes.CFUNCTYPE(None, ctypes.c_char_p, ctypes.c_void_p)
+
+
+def _default_library():
+    if sys.platform == "win32":
+        return "rbcp_core.dll"
+    if sys.platform == "darwin":
+        return "librbcp_core.dylib"
+    return "librbcp_core.so"
+
+
+class Library:
+    def __init__(self, path):
+        self._lib = ctypes.CDLL(path)
+        lib = self._lib
+        lib.rbcp_job_new.argtypes = [
+            ctypes.c_char_p,
+            _CALLBACK,
+            ctypes.c_void_p,
+            ctypes.POINTER(ctypes.c_void_p),
+        ]
+        lib.rbcp_job_new.restype = ctypes.c_void_p
+        lib.rbcp_job_run.argtypes = [ctypes.c_void_p]
+        lib.rbcp_job_run.restype = ctypes.c_void_p
+        lib.rbcp_job_cancel.argtypes = [ctypes.c_void_p]
+        lib.rbcp_job_pause.argtypes = [ctypes.c_void_p, ctypes.c_int]
+        lib.rbcp_job_free.argtypes = [ctypes.c_void_p]
+        lib.rbcp_string_free.argtypes = [ctypes.c_void_p]
+        lib.rbcp_version.restype = ctypes.c_char_p
+
+    def version(self):
+        return self._lib.rbcp_version().decode()
+
+    def _take_string(self, pointer):
+        value = ctypes.cast(pointer, ctypes.c_char_p).value.decode()
+        self._lib.rbcp_string_free(pointer)
+        return value
+
+    def job(self, args, on_event=None):
+        """A job for the rbcp CLI arguments `args`, reporting to `on_event`"""
+        return Job(self, args, on_event)
+
+
+class Job:
+    def __init__(self, library, args, on_event):
+        self._library = library
+        lib = library._lib
+
+        def forward(event, _user_data):
+            if on_event is not None:
+                on_event(json.loads(event.decode()))
+
+        # Kept alive for as long as the job can call it
+        self._callback = _CALLBACK(forward)
+        error = ctypes.c_void_p()
+        self._job = lib.rbcp_job_new(
+            json.dumps(args).encode(), self._callback, None, ctypes.byref(error)
+        )
+        if not self._job:
+            raise ValueError(library._take_string(error.value))
+
+    def run(self):
+        """Run the job to the end, returning its result as a dict"""
+        result = self._library._lib.rbcp_job_run(self._job)
+        return json.loads(self._library._take_string(result))
+
+    def cancel(self):
+        self._library._lib.rbcp_job_cancel(self._job)
+
+    def pause(self, paused=True):
+        self._library._lib.rbcp_job_pause(self._job, int(paused))
+
+    def __del__(self):
+        if getattr(self, "_job", None):
+            self._library._lib.rbcp_job_free(self._job)
+            self._job = None
+
+
+def load(path=None):
+    """The library at `path`, RBCP_LIBRARY or the default name"""
+    return Library(path or os.environ.get("RBCP_LIBRARY") or _default_library())
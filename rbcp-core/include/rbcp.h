@@ -0,0 +1,66 @@
+/*
+ * C interface of rbcp-core, built with `cargo build --features ffi`.
+ *
+ * Options are the arguments of the rbcp CLI as a JSON array of strings.
+ * Events and results are JSON objects. Strings returned by the library are
+ * freed with rbcp_string_free; strings passed to callbacks are only valid
+ * during the call.
+ *
+ * The callback is called from the threads copying, possibly several at
+ * once, so it must be thread-safe along with its user_data.
+ *
+ * A panic inside the library never unwinds into the caller: it fails the
+ * call with "internal error (...)" as the error.
+ */
+
+#ifndef RBCP_H
+#define RBCP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RbcpJob RbcpJob;
+
+/*
+ * Receives each event of a job, one of
+ *   {"event": "progress", "state": "Copying", "files_done": ..., ...}
+ *   {"event": "log", "message": "..."}
+ */
+typedef void (*RbcpEventCallback)(const char *event, void *user_data);
+
+/*
+ * Create a job from the CLI arguments in args_json, e.g.
+ * ["C:\\Source", "D:\\Backup", "/MIR"]. callback may be NULL. Returns NULL
+ * when the arguments are invalid, with the reason in *error if error is
+ * not NULL.
+ */
+RbcpJob *rbcp_job_new(const char *args_json, RbcpEventCallback callback,
+                      void *user_data, char **error);
+
+/*
+ * Run a job to the end, blocking. Returns
+ *   {"ok": true, "stats": {...}} or {"ok": false, "error": "...", "stats": {...}}
+ */
+char *rbcp_job_run(const RbcpJob *job);
+
+/* Stop a running job as soon as possible, from any thread */
+void rbcp_job_cancel(const RbcpJob *job);
+
+/* Pause a running job if paused is not 0, or resume it, from any thread */
+void rbcp_job_pause(const RbcpJob *job, int paused);
+
+/* Free a job that is not running */
+void rbcp_job_free(RbcpJob *job);
+
+/* Free a string returned by the library */
+void rbcp_string_free(char *value);
+
+/* Version of the library, owned by the library */
+const char *rbcp_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RBCP_H */
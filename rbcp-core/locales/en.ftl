@@ -0,0 +1,592 @@
+## RBCP user-facing strings, English (base locale).
+##
+## Translations use the same message ids in <lang>.ftl. Messages missing
+## from a translation fall back to this file.
+
+## Command line usage
+
+usage-header = Usage: { $program } <source>[>subdir][;<source>...] <destination> [<file_pattern>...] [options]
+usage-estimate = { $program } estimate <source>[>subdir][;<source>...] <destination> [<file_pattern>...] [options]
+usage-bench = { $program } bench <destination> [/SIZE:mb]
+usage-selftest = { $program } self-test [/ROUNDS:n] [/SEED:n] [/DIR:path]
+usage-test-pattern = { $program } test-pattern [<file_pattern>...] --against <folder> [options]
+usage-retry-failed = { $program } --retry-failed <report.json>
+usage-skip-list = { $program } skip-list add <path>... [/HASH] [/REASON:text] | remove <path|hash>... | show [/LIST:file]
+usage-quarantine = { $program } quarantine show | release <path>...
+usage-options = Options:
+opt-s = Copy subdirectories, but not empty ones
+opt-e = Copy subdirectories, including empty ones
+opt-z = Copy files in restartable mode (slower but more robust)
+opt-b = Copy files in Backup mode (overrides permissions)
+opt-purge = Delete destination files/folders that no longer exist in source
+opt-mir = Mirror directory tree (like /PURGE plus all subdirectories)
+opt-mov = Move files (delete from source after copying)
+opt-move = Move files and directories (delete from source after copying)
+opt-a-add = Add specified attributes to copied files
+opt-a-remove = Remove specified attributes from copied files
+opt-mt = Multithreaded copying with n threads (default is 8), or AUTO to adjust the count to the throughput
+opt-mtdata = Copy the contents of at most n files at once, below the /MT threads
+opt-mtmeta = Make at most n stat, mkdir and remove calls at once, below the /MT threads
+opt-r = Number of retries on failed copies (default is 1 million)
+opt-w = Wait time between retries in seconds (default is 30)
+opt-rmeta = Number of retries of folder reads and metadata calls that fail for a moment, as on a busy network share (default is 3)
+opt-wmeta = Wait time between those retries in milliseconds (default is 500)
+opt-iotimeout = Fail and retry a file when a read of it takes longer than n seconds
+opt-status = Keep the progress and statistics of the job in a JSON file, rewritten every few seconds, for monitoring tools
+opt-html = Write the statistics, failures and changes made, or planned with /L, to a self-contained HTML page
+opt-failed = Write the files that failed to a report, which --retry-failed copies again without walking the source
+opt-skiplist = Read the files never to copy from this skip-list instead of the one in the data directory
+opt-noskiplist = Copy the files on the skip-list too
+opt-owner = Give copies the owner and group of their source (needs root, or /B on Windows)
+opt-ownermap = Copy owners, mapping the owners of the source to other accounts with the old = new lines of this file
+opt-quarantine = Skip files that failed n runs of the job in a row until released, listing them in the summary; with :SKIPLIST also put them on the skip-list
+opt-scancmd = Run this scanner on each file before copying it, with {"{}"} standing for the file; files it exits non-zero on are blocked
+opt-bitrot = Hash unchanged copies and flag those that no longer match the hash kept by earlier /VERIFY or /BITROT runs; with RECOPY, copy them again
+opt-salvage = Write unreadable parts of files as zeros instead of failing them, listing the damage in file (rbcp-damage.txt in the destination)
+opt-stall = Report the job as stalled when nothing was copied for n seconds; with RETRY, also retry the file whose read hung
+opt-deadline = Stop the job after min minutes or at the time HH:MM, leaving the rest for the next run
+opt-log = Output log to file
+opt-l = List only - don't copy, timestamp or delete any files
+opt-np = No progress - don't display % copied
+opt-nfl = No file list - don't log file names
+opt-units = Write sizes in units of 1024 (binary, the default, e.g. 1.5 GiB), of 1000 (si, e.g. 1.6 GB) or as plain bytes
+opt-bytes = Same as /UNITS:bytes, for scripts reading the summary and logs
+opt-empty = Create empty (zero-byte) copies of files
+opt-xf = Leave out files matching the pattern (repeatable)
+opt-xd = Leave out folders matching the pattern, or the folder at the full path, with their contents (repeatable)
+opt-root = Copy the source folder itself into the destination instead of only its contents
+opt-childonly = Process only direct child folders of source path
+opt-child = With /CHILDONLY, process only child folders matching the pattern (repeatable)
+opt-xchild = With /CHILDONLY, leave out child folders matching the pattern (repeatable)
+opt-maxdepth = Walk only the top n levels of the source tree, the source itself being the first
+opt-lev = Copy only the top n levels of the source tree, as robocopy does (same as /MAXDEPTH)
+opt-shred = Securely overwrite files before deletion
+opt-noscan = Skip the pre-scan used for progress totals
+opt-mem = Memory budget in MB for directory traversal (default unbounded)
+opt-index = Keep a scan index of the source to skip unchanged directories
+opt-journal = Use the NTFS change journal with /INDEX to detect changes (Windows only)
+opt-fastskip = With /INDEX, do not compare the files of directories whose time and number of entries are unchanged, comparing everything every n-th run (default 10)
+opt-job = Record a restart journal so the job can be resumed
+opt-resume = Resume an interrupted job with its original options
+opt-filesfrom = Copy only the relative paths listed in file (- for stdin)
+opt-print0 = Print copied destination paths NUL-separated to stdout, logs to stderr
+opt-progress-json = Print progress as JSON lines to stdout, logs to stderr
+opt-notify = POST a JSON summary to a webhook when the job ends
+opt-history = Record run statistics and compare with the previous run
+opt-top = Report the n largest files and directories found by the scan (default 20)
+opt-defer = Retry locked files in up to n passes at the end of the job (default 3)
+opt-ignore = Skip files and directories failing with an error of this kind (denied, notfound, locked, all), optionally only for paths matching pattern; repeatable
+opt-special = Handle dangling symlinks, FIFOs, sockets and devices: skip (default), recreate or error, for all kinds or one (kind=policy)
+opt-ignoreattr = Leave copies that differ from their source only in read-only, hidden or system attributes alone
+opt-timetol = Treat modification times up to this many seconds apart as the same, for cameras and phones whose clocks drift
+opt-fft = Assume FAT file times, treating times up to 2 seconds apart as the same
+opt-compare = Decide which copies are up to date by existence only, size, size and time (default) or a hash of their contents
+opt-sizeonly = Same as /COMPARE:SIZE, for sources whose times cannot be trusted
+opt-cloud = Handle cloud placeholders (OneDrive, Dropbox) that would be copied: skip (default), metadata to list them at the destination, or hydrate to download and copy them
+opt-links = Handle junctions, directory symlinks and hard links: follow (default), skip, recreate or error, for all kinds or one (junction, dirsymlink or hardlink=policy)
+opt-remap = Replace text in destination paths relative to the destination; repeatable, applied in order
+opt-remapre = Like /REMAP with a regular expression, the replacement may use $1 or ${name}
+opt-route = Copy files matching any of the ;-separated patterns, or file types like @images, into this destination folder; repeatable
+opt-flat = Copy all files into the destination root; colliding names get a number (SUFFIX, default) or their directories as prefix (PREFIX)
+opt-case = Force destination file and directory names to lowercase or uppercase
+opt-swap = Copy into a staging directory and swap it into place at the end
+opt-split = Write files over mb MB (default 4095, the FAT32 limit) as .partNNN files and a manifest
+opt-rejoin = Restore files written with /SPLIT from their parts
+opt-ordered = Copy one file at a time in alphabetical order, syncing each, for players that play files in copy order
+opt-sync = Sync each file (FILE), or each file and its directory (DIR), to disk before counting it as copied
+opt-merge = When several sources have the same file, copy the first, the last or the newest
+opt-eject = Flush and eject the destination drive when the job is done
+opt-verify = Check each copy against its source with a BLAKE3 hash, retrying files that differ
+opt-sample = Check some copies against their source instead: a random percentage of them in full, or the first and last 64 KB of each with ENDS
+opt-buf = Copy with buffers of kb KB (default 1024)
+opt-tuned = Use the threads and buffer size found best by the bench command for the destination
+opt-throttle = Keep the job under mbps MB/s, shared by all threads
+opt-dprofile = Use this destination profile instead of the one matching the destination, or NONE for none
+opt-netpause = Pause copying to a network destination while the connection is metered (METERED) or the VPN name is not connected (repeatable)
+opt-battery = Pause the job while on battery with less than pct percent left, or with mbps slow it to mbps MB/s instead, until plugged in
+opt-deeppause = Close the files being copied while paused, so they are not left locked, and reopen them on resuming
+opt-abortfails = Abort the job when more than n files failed
+opt-abortfree = Abort the job when the destination has less than mb MB free
+opt-keepfree = Stop the job before a file would leave less than mb MB free on the destination
+opt-abortslow = Abort the job when throughput stays under mbps MB/s for min minutes (default 10)
+opt-maxentries = Warn about each folder holding more than n entries
+opt-maxfiles = Warn when the job has more than n files
+
+## Destination profiles
+
+destination-profile = Using destination profile { $name }
+destination-profile-missing = Warning: no destination profile named { $name }, using the job's own settings
+
+## Network policy
+
+network-metered = the connection is metered
+network-vpn-down = VPN { $name } is not connected
+network-paused = Paused copying to the network: { $reason }
+network-resumed = Network connection allows copying again, resuming
+
+## Battery
+
+battery-paused = On battery with { $percent }% left, pausing until plugged in
+battery-throttled = On battery with { $percent }% left, slowing to { $mbps } MB/s until plugged in
+battery-resumed = Plugged in, no longer holding back for the battery
+
+## Filters
+peek-no-pattern = Matches none of the patterns
+filter-invalid-threads = The thread count must be at least 1
+filter-invalid-throttle = The bandwidth cap cannot be negative
+queue-job-started = The job has already started and can no longer be changed
+pattern-test-header = Files in { $dir } against { $patterns }:
+pattern-test-summary = { $matched } would be copied, { $left_out } left out
+pattern-test-none = Nothing would be copied. Patterns match whole names, so "jpg" matches no file where "*.jpg" does, and folders are only walked with /S or /E.
+
+## Job log
+
+job-started =
+    RBCP - Started: { $time }
+    Sources: { $sources }
+    Destination: { $destination }
+    Patterns: { $patterns }
+    Options: { $options }
+job-finished =
+    RBCP - Finished: { $time }
+    Sources: { $sources }
+    Destination: { $destination }
+job-elapsed = Elapsed time: { $time }
+job-speed = Average speed: { $speed }/s
+
+## Estimate
+
+estimate-title = Estimate (nothing was copied):
+estimate-files = Files to copy: { $files } of { $total }
+estimate-bytes = Size to copy: { $size }
+estimate-duration = Predicted duration: { $duration } at { $speed }/s, { $basis }
+estimate-duration-none = Predicted duration: { $duration }
+estimate-duration-unknown = Predicted duration: unknown, no previous runs and no file to probe with
+estimate-basis-history = { $runs ->
+    [one] based on the previous run
+   *[other] based on the last { $runs } runs
+}
+estimate-basis-probe = based on a bandwidth probe
+
+## Locked files
+
+defer-pass = { $files ->
+    [one] Retrying 1 locked file
+   *[other] Retrying { $files } locked files
+}, pass { $pass } of { $passes }
+
+## Swap mode
+
+swap-staging = Staging the update in { $staging }
+swap-done = Swapped the updated tree into place: { $destination }
+swap-kept = Job did not complete, { $destination } was left unchanged and the update kept in { $staging }
+eject-done = Ejected { $drive }, it is safe to remove
+eject-failed = Could not eject the destination drive: { $error }
+bench-start = Benchmarking { $destination } with { $size } MB per measurement
+bench-sample = { $threads ->
+    [one] 1 thread
+   *[other] { $threads } threads
+}, { $buffer } KB buffer: { $speed } MB/s
+bench-best = Best: /MT:{ $threads } /BUF:{ $buffer } at { $speed } MB/s, saved for /TUNED
+selftest-start = Self-test: { $rounds ->
+    [one] 1 round
+   *[other] { $rounds } rounds
+} of random copies from seed { $seed }
+selftest-round-passed = Round { $round } (seed { $seed }, { $flags }): OK
+selftest-round-failed = Round { $round } (seed { $seed }, { $flags }): { $count ->
+    [one] 1 discrepancy
+   *[other] { $count } discrepancies
+}, trees kept in { $dir }
+selftest-passed = Self-test passed: every copy matched what its options promise
+selftest-failed = Self-test failed: { $count ->
+    [one] 1 discrepancy
+   *[other] { $count } discrepancies
+} found
+
+## Elevation
+
+elevation-hint = { $files ->
+    [one] 1 file was
+   *[other] { $files } files were
+} denied access. Run rbcp as administrator to copy protected locations such as Program Files or other users' profiles.
+
+## Abort policies
+
+abort-failures = { $failed } files failed, more than the limit of { $max }
+abort-free-space = Only { $free } MB free on the destination, less than the minimum of { $min } MB
+abort-slow = Throughput was { $speed } MB/s over the last { $minutes } minutes, below the minimum of { $min } MB/s
+abort-keep-free = Stopped before { $path }, copying it would leave less than { $min } MB free on the destination ({ $free } MB free now)
+job-aborted = Job aborted: { $reason }
+
+## Entry limits
+
+limit-large-directory = Warning: { $path } holds more than { $limit } entries
+limit-many-files = Warning: The job has more than { $limit } files
+stall-detected = Stalled: nothing copied for { $seconds ->
+    [one] 1 second
+   *[other] { $seconds } seconds
+}
+stall-resumed = Copying resumed after { $seconds ->
+    [one] 1 second
+   *[other] { $seconds } seconds
+}
+damage-report = { $files ->
+    [one] 1 file was
+   *[other] { $files } files were
+} copied with unreadable parts, listed in { $report }
+deadline-set = Deadline: the job stops at { $time }
+deadline-reached = Deadline reached, stopping with { $files ->
+    [one] 1 file
+   *[other] { $files } files
+} left for the next run
+artifacts-excluded = Left out as rbcp's own files: { $artifacts }
+merge-plan = { $collisions ->
+    [one] 1 file is
+   *[other] { $collisions } files are
+} in more than one source, copying the { $precedence } copy
+auto-threads = Auto threads: { $from } -> { $to } files at once ({ $speed } MB/s)
+
+## Scenario warnings
+
+scenario-warn-purge = Files and folders in { $destination } that are not in the source will be permanently deleted, including anything you saved there yourself.
+scenario-warn-move = Files are deleted from { $source } once they have been copied. Make sure the new drive is healthy before you start.
+
+## Run history
+
+history-compare-title = Compared with the previous run ({ $time }):
+history-files-copied = Files copied: { $value } ({ $delta })
+history-files-failed = Files failed: { $value } ({ $delta })
+history-duration = Duration: { $value } s ({ $delta } s)
+history-failures-introduced = Warning: files failed in this run, none did in the previous run
+history-failures-increased = Warning: more files failed than in the previous run
+history-duration-regressed = Warning: this run took much longer than the previous run
+
+## Statistics
+
+stats-title = Statistics:
+stats-dirs-created = Directories created:
+stats-root-dirs-created = Root directories created:
+stats-files-copied = Files copied:
+stats-bytes-copied = Bytes copied:
+stats-dirs-skipped = Directories skipped:
+stats-files-skipped = Files skipped:
+stats-files-failed = Files failed:
+stats-files-salvaged = Files salvaged:
+stats-files-rotted = Copies corrupted:
+stats-errors-ignored = Errors ignored:
+stats-skip-listed = Skipped by skip-list:
+stats-attributes-updated = Attributes updated:
+stats-placeholders = Cloud placeholders skipped:
+stats-hydrated = Cloud placeholders hydrated:
+stats-sampled = Checked by sampling:
+cloud-skipped = Skipping cloud placeholder: { $path }
+cloud-listed = Listing cloud placeholder: { $path }
+cloud-hydrating = Hydrating cloud placeholder: { $path }
+stats-quarantined = Skipped in quarantine:
+stats-files-blocked = Files blocked:
+stats-dirs-removed = Directories removed:
+stats-files-removed = Files removed:
+top-files-title = Largest files:
+top-dirs-title = Largest directories:
+stats-links = Links:
+links-junction = Junctions
+links-dir-symlink = Directory symlinks
+links-hard-link = Hard links
+links-followed = { $count } followed
+stats-special = Special files:
+special-dangling = Dangling symlinks
+special-fifo = FIFOs
+special-socket = Sockets
+special-device = Devices
+special-skipped = { $count } skipped
+special-recreated = { $count } recreated
+special-failed = { $count } failed
+stats-phases = Phases: { $phases }
+stats-auto-threads = Threads (auto): started at { $start }, ended at { $end }, ranged { $min }-{ $max } over { $changes ->
+    [one] { $changes } change
+   *[other] { $changes } changes
+}
+phase-scan = Scan
+phase-copy = Copy
+phase-purge = Purge
+stats-by-category = By file type:
+stats-category-row = { $files ->
+    [one] 1 file
+   *[other] { $files } files
+}, { $size }
+stats-by-child = By child folder:
+stats-child-row = { $files ->
+    [one] 1 file
+   *[other] { $files } files
+}, { $size }, { $failed } failed in { $duration }
+category-images = Images
+category-video = Video
+category-documents = Documents
+category-code = Code
+category-other = Other
+
+## HTML report
+
+report-title = Copy report
+report-dry-run = Dry run: these are the changes the job would make. Nothing was copied or removed.
+report-job = Job
+report-sources = Sources
+report-destination = Destination
+report-started = Started
+report-elapsed = Elapsed
+report-outcome = Result
+report-options = Options
+report-outcome-completed = Completed
+report-outcome-partial = Stopped at the deadline, files left for the next run
+report-outcome-cancelled = Cancelled
+report-outcome-failed = Failed
+report-statistics = Statistics
+report-failures = { $count ->
+    [one] 1 file failed
+   *[other] { $count } files failed
+}
+report-actions = Changes to the destination ({ $count })
+report-actions-dropped = ...and { $count } more changes not listed
+report-column-action = Change
+report-column-path = Path
+report-column-size = Size
+report-action-new-file = New file
+report-action-replace-file = Replaced file
+report-action-new-dir = New folder
+report-action-remove-file = Removed file
+report-action-remove-dir = Removed folder
+html-report-written = Report written to { $path }
+failure-report-written = { $count } failed files listed in { $path }; rbcp --retry-failed "{ $path }" copies them again
+failure-report-clean = No failed files; { $path } lists none
+retry-nothing = { $path } lists no failed files to copy again
+retry-files = Copying again only the { $count } files that failed before
+skip-list-loaded = Skip-list: { $count } files, folders and contents never copied
+skip-list-unreadable = Warning: Could not read skip-list { $path }, its files may be copied: { $error }
+skip-list-skipped = Skip-listed: { $path }
+skip-list-skipped-reason = Skip-listed: { $path } ({ $reason })
+skip-list-added = Added to the skip-list: { $path }
+skip-list-present = Already on the skip-list: { $path }
+skip-list-removed = { $count ->
+    [0] { $key } is not on the skip-list
+    [one] Removed { $key } from the skip-list
+   *[other] Removed { $count } entries for { $key } from the skip-list
+}
+skip-list-empty = The skip-list { $path } is empty
+content-blocked = Blocked by content filter: { $path } ({ $reason })
+owners-unmatched = Owners without a mapping, kept as they are:
+owners-missing = Mapped accounts missing on this system, the copies keep the owner that wrote them:
+owners-files = { $files ->
+    [one] 1 file
+   *[other] { $files } files
+}
+owners-failed = Warning: Could not set the owner of { $files } files: { $error }
+owner-map-unreadable = Cannot read owner mapping { $path }: { $error }
+quarantine-title = Quarantined files, failed { $runs } runs in a row and no longer tried; rbcp quarantine release <path> tries one again:
+quarantine-new = { $path }: { $reason } (new)
+quarantine-file = { $path }: { $reason }
+quarantine-skipped = Skipped in quarantine: { $path }
+quarantine-skip-reason = Quarantined after failing { $runs } runs in a row: { $reason }
+quarantine-empty = No files are quarantined
+quarantine-released = Released { $path } from quarantine
+quarantine-not-found = { $path } is not quarantined
+quarantine-unsaved = Warning: Could not save quarantine { $path }: { $error }
+skip-list-unsaved = Warning: Could not put quarantined files on skip-list { $path }: { $error }
+skip-list-not-file = { $path } is not a file, only files are listed by their contents
+
+## GUI
+## Variables are left as {$name} for the frontend to fill in.
+
+gui-loading = Loading RBCP v2.0.2 Beta...
+gui-source-path = Source Path
+gui-source-placeholder = Select source directory...
+gui-select-folder = Select Folder
+gui-select-files = Select File(s)
+gui-select-dest-folder = Select destination folder
+gui-toggle-theme = Toggle theme
+gui-dest-path = Destination Path
+gui-dest-placeholder = Select destination directory...
+gui-advanced-options = Advanced Options
+gui-opt-recursive = Recursive (/S)
+gui-opt-mirror = Mirror (/MIR)
+gui-opt-shred = Secure Delete (/SHRED)
+gui-opt-move = Move Files (/MOV)
+gui-opt-empty = Empty Files (/EMPTY)
+gui-opt-childonly = Child Only (/CHILDONLY)
+gui-opt-eject = Eject when done
+gui-opt-open-when-done = Open Folder When Done
+gui-opt-deep-pause = Release Files on Pause (/DEEPPAUSE)
+gui-thread-count = Thread Count:
+gui-retries = Retries:
+gui-queue-limit = Jobs at Once:
+gui-priority = Priority
+gui-priority-low = Low
+gui-priority-normal = Normal
+gui-priority-high = High
+gui-theme = Theme
+gui-theme-system = Follow system
+gui-theme-dark = Dark
+gui-theme-light = Light
+gui-theme-high-contrast = High contrast
+gui-accent-color = Accent color
+gui-accent-reset = Reset
+gui-settings = Settings
+gui-export-settings = Export...
+gui-import-settings = Import...
+gui-settings-file = RBCP settings
+gui-overall-progress = Overall Progress
+gui-ready-to-copy = Ready to copy
+gui-object-count = { $done } of { $total } objects
+gui-scan-count = { $files } files in { $dirs } folders
+gui-cancel = Cancel
+gui-pause = Pause
+gui-continue = Continue
+gui-resume-jobs = Resume Jobs
+gui-hold-queue = Hold Queue
+gui-release-queue = Release Queue
+gui-start-copy = Start Copy
+gui-add-to-queue = Add to Queue
+gui-activity-log = Activity Log
+gui-clear = Clear
+gui-copy = Copy
+gui-log-search = Search
+gui-log-level = Show
+gui-log-level-all = All
+gui-log-level-warnings = Warnings and errors
+gui-log-level-errors = Errors only
+gui-log-follow = Follow
+gui-save-log = Save...
+gui-log-limit = Log Lines Kept
+gui-size-units = Sizes
+gui-size-units-binary = KiB, MiB, GiB
+gui-size-units-si = kB, MB, GB
+gui-size-units-bytes = Bytes
+gui-log-file = Log file
+gui-log-saved = Full log saved to { $path }
+gui-by-category = By file type
+gui-category-images = Images
+gui-category-video = Video
+gui-category-documents = Documents
+gui-category-code = Code
+gui-category-other = Other
+gui-category-row = {$size} · {$files}
+gui-open-destination = Open Destination
+gui-reveal-file = Reveal Current File
+gui-failed-files = Failed files, click to show one
+gui-retry-failed = Retry Failed Files
+gui-limits-title = Large Job
+gui-limits-question = The source may not be the folder you meant. Start the job anyway?
+gui-overwrite-title = Overwrite Conflict
+gui-overwrite-body = { $count } files already exist in the destination. Choose what happens to each, or to a group at once.
+gui-conflict-more = Only the first files are listed; the others are overwritten when the source copy is newer.
+gui-conflict-file = File
+gui-conflict-source = Source
+gui-conflict-destination = Destination
+gui-conflict-action = Action
+gui-conflict-copy = Copy
+gui-conflict-source-newer = Source is newer ({ $count })
+gui-conflict-destination-newer = Destination is newer ({ $count })
+gui-conflict-same = Same time ({ $count })
+gui-conflict-group-action = Action for the group: { $group }
+gui-conflict-row-action = Action for { $path }
+gui-conflict-mixed = Mixed
+gui-conflict-overwrite = Overwrite
+gui-conflict-skip = Skip
+gui-skip-all = Skip All
+gui-overwrite-all = Overwrite All
+gui-elevation-title = Access Denied
+gui-elevation-body = Some files could not be copied because administrator rights are needed.
+gui-not-now = Not Now
+gui-relaunch-admin = Relaunch as Administrator
+gui-log-relaunch-failed = Could not relaunch as administrator: {$error}
+
+gui-wizard-open = Setup wizard
+gui-wizard-title = What would you like to do?
+gui-wizard-backup = Backup a folder
+gui-wizard-backup-desc = Copy a folder to another location. Nothing is deleted.
+gui-wizard-mirror = Mirror to NAS
+gui-wizard-mirror-desc = Keep a copy of a folder on a network share up to date.
+gui-wizard-move = Move to new drive
+gui-wizard-move-desc = Move a folder to another drive, removing it from the old one.
+gui-wizard-empty = Also copy empty folders
+gui-wizard-purge = Delete files on the NAS that are no longer in the source
+gui-wizard-purge-note = Purging makes the destination an exact copy: anything only on the NAS is deleted. You will be asked to confirm this before it is enabled.
+gui-wizard-summary = These options will be used:
+gui-wizard-confirm = I understand, enable these options
+gui-wizard-skip = Skip
+gui-wizard-back = Back
+gui-wizard-next = Next
+gui-wizard-apply = Apply
+
+gui-status-ready = ready
+gui-status-scanning = scanning...
+gui-status-waiting = waiting command...
+gui-status-copying = copying...
+gui-status-finalizing = writing out buffered data...
+gui-status-paused = paused
+gui-status-stalled = stalled, waiting for the disk...
+gui-status-networkpaused = paused, waiting for the network...
+gui-status-batterypaused = paused, on low battery...
+gui-status-finished = finished
+gui-status-failed = failed
+gui-status-cancelled = cancelled
+gui-status-partial = stopped at deadline
+gui-scanning = Scanning...
+
+gui-log-missing-paths = ERROR: Source and Destination must be specified.
+gui-log-cancelled-by-user = Operation cancelled by user.
+gui-log-conflict-check-failed = Note: Could not check for conflicts: { $error }
+gui-log-limit-check-failed = Note: Could not check the size of the job: { $error }
+gui-log-queued = Copy operation added to the queue.
+gui-log-retrying = Copying the { $count } failed files again.
+gui-log-initiating = Initiating copy operation...
+gui-log-error = ERROR: { $error }
+gui-log-queued-job = Queued job ({ $state }): { $sources } -> { $destination }
+gui-job-interrupted = interrupted
+gui-job-pending = pending
+gui-job-running = running
+gui-queued-jobs = Queued Jobs
+gui-edit = Edit
+gui-save = Save
+gui-edit-job-title = Edit Queued Job
+gui-edit-patterns = Include files (patterns separated by ;)
+gui-edit-exclude-files = Exclude files (/XF)
+gui-edit-exclude-dirs = Exclude folders, by pattern or full path (/XD)
+gui-edit-threads = Threads
+gui-edit-throttle = Bandwidth cap in MB/s (0 for none)
+gui-edit-peek = { $matched } of the first { $scanned } entries would be copied
+gui-edit-peek-all = { $matched } of { $scanned } entries would be copied
+gui-edit-left-out = { $path } ({ $reason })
+gui-apply = Apply
+gui-choose-folders = Choose Folders...
+gui-folder-tree-title = Choose Folders
+gui-folder-tree-note = Untick the folders to leave out; everything in them is left out too.
+gui-folder-tree-loading = Loading...
+gui-expand-folder = Show the folders in { $name }
+gui-log-folders-chosen = Leaving out { $count } folders chosen in the source tree
+gui-test-patterns = Test Patterns...
+gui-pattern-test-title = Test Patterns
+gui-pattern-test-dir = Folder to test against
+gui-pattern-test-use = Use These Patterns
+gui-pattern-test-summary = { $matched } would be copied, { $left_out } left out
+gui-pattern-test-more = Only the first { $scanned } entries were looked at.
+gui-pattern-test-none = Nothing would be copied. Patterns match whole names, so "jpg" matches no file where "*.jpg" does.
+gui-close = Close
+gui-speed = Speed
+gui-job-speed-title = Job Speed
+gui-speed-threads = Files at once
+gui-speed-auto = Auto threads picks the files at once; setting them here ends that for this job.
+gui-log-speed-changed = { $sources } -> { $destination }: { $threads } files at once, { $throttle }
+gui-speed-unlimited = no bandwidth cap
+gui-speed-capped = capped at { $mbps } MB/s
+gui-log-job-edited = Queued job changed: { $sources } -> { $destination }
+gui-log-resuming = Resuming queued copy operations...
+gui-log-cancel-requested = Cancellation requested.
+gui-log-paused = Operation paused.
+gui-log-resumed = Operation resumed.
+gui-log-queue-held = Queue held: no more jobs start until it is released.
+gui-log-queue-released = Queue released.
+gui-log-finished = Operation finished with state: { $state }
+gui-log-settings-exported = Settings exported to { $path }
+gui-log-settings-imported = Imported { $added } new and { $replaced } updated profiles, { $history } history entries
+gui-log-portable = Portable mode: settings are stored in { $path }
+gui-log-wizard-applied = Wizard options applied: { $flags }
@@ -0,0 +1,215 @@
+//! Stopping unattended jobs that are going nowhere.
+//!
+//! An abort policy lists thresholds that make a job fail early: too many
+//! failed files, the destination running out of space, or throughput
+//! staying too low for too long. The engine checks them periodically while
+//! copying and stops the job with an error naming the threshold crossed.
+//!
+//! A free-space reserve is checked before each file instead, so a job
+//! copying to a shared drive stops before it would leave less free space
+//! than the reserve, rather than after.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::Statistics;
+use crate::tr;
+use crate::utils::free_space;
+
+/// How long throughput is watched when `min_speed_mbps` is set without a
+/// duration
+pub const DEFAULT_SLOW_MINUTES: u64 = 10;
+
+/// Thresholds that abort a job. Zero disables a threshold.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AbortPolicy {
+    /// Abort when more than this many files failed
+    pub max_failures: u64,
+    /// Abort when the destination has less free space than this, in MB
+    pub min_free_mb: u64,
+    /// Stop before a file would leave less free space than this on the
+    /// destination, in MB
+    pub keep_free_mb: u64,
+    /// Abort when throughput stays below this many MB/s...
+    pub min_speed_mbps: f64,
+    /// ...for this many minutes
+    pub slow_minutes: u64,
+}
+
+impl AbortPolicy {
+    pub fn is_active(&self) -> bool {
+        self.max_failures > 0 || self.min_free_mb > 0 || self.min_speed_mbps > 0.0
+    }
+
+    fn slow_window(&self) -> Duration {
+        let minutes = if self.slow_minutes == 0 {
+            DEFAULT_SLOW_MINUTES
+        } else {
+            self.slow_minutes
+        };
+        Duration::from_secs(minutes * 60)
+    }
+}
+
+/// Checks a running job against its abort policy
+pub struct AbortMonitor<'a> {
+    policy: &'a AbortPolicy,
+    destination: &'a Path,
+    /// Bytes copied at past checks, oldest first, covering the slow window
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl<'a> AbortMonitor<'a> {
+    pub fn new(policy: &'a AbortPolicy, destination: &'a Path) -> Self {
+        AbortMonitor {
+            policy,
+            destination,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Forget throughput samples, e.g. while the job is paused
+    pub fn reset_throughput(&mut self) {
+        self.samples.clear();
+    }
+
+    /// The reason to abort the job, if a threshold was crossed
+    pub fn check(&mut self, stats: &Statistics) -> Option<String> {
+        let policy = self.policy;
+
+        let failed = stats.files_failed.load(Ordering::Relaxed) as u64;
+        if policy.max_failures > 0 && failed > policy.max_failures {
+            return Some(tr!(
+                "abort-failures",
+                failed = failed,
+                max = policy.max_failures
+            ));
+        }
+
+        if policy.min_free_mb > 0 {
+            if let Some(free) = free_space(self.destination) {
+                let free_mb = free / (1024 * 1024);
+                if free_mb < policy.min_free_mb {
+                    return Some(tr!(
+                        "abort-free-space",
+                        free = free_mb,
+                        min = policy.min_free_mb
+                    ));
+                }
+            }
+        }
+
+        if policy.min_speed_mbps > 0.0 {
+            let now = Instant::now();
+            let bytes = stats.bytes_copied.load(Ordering::Relaxed);
+            self.samples.push_back((now, bytes));
+
+            // Keep the newest sample that is at least a full window old
+            let window = policy.slow_window();
+            while self
+                .samples
+                .get(1)
+                .is_some_and(|(at, _)| now.duration_since(*at) >= window)
+            {
+                self.samples.pop_front();
+            }
+
+            if let Some(&(since, bytes_then)) = self.samples.front() {
+                let elapsed = now.duration_since(since);
+                if elapsed >= window {
+                    let speed = (bytes - bytes_then) as f64 / elapsed.as_secs_f64() / 1_000_000.0;
+                    if speed < policy.min_speed_mbps {
+                        return Some(tr!(
+                            "abort-slow",
+                            speed = format!("{:.2}", speed),
+                            min = policy.min_speed_mbps,
+                            minutes = window.as_secs() / 60
+                        ));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Free space on the destination that copying must not use up
+pub struct FreeSpaceReserve<'a> {
+    destination: PathBuf,
+    keep_bytes: u64,
+    /// Bytes of files being copied, which may not be written yet
+    in_flight: AtomicU64,
+    /// Set when the reserve stops the job, so workers stop
+    aborted: &'a AtomicBool,
+    reason: Mutex<Option<String>>,
+}
+
+impl<'a> FreeSpaceReserve<'a> {
+    pub fn new(policy: &AbortPolicy, destination: &Path, aborted: &'a AtomicBool) -> Option<Self> {
+        (policy.keep_free_mb > 0).then(|| FreeSpaceReserve {
+            destination: destination.to_path_buf(),
+            keep_bytes: policy.keep_free_mb.saturating_mul(1024 * 1024),
+            in_flight: AtomicU64::new(0),
+            aborted,
+            reason: Mutex::new(None),
+        })
+    }
+
+    /// Claim room for a file growing the destination by `bytes`, until the
+    /// claim is dropped. When it would cut into the reserve the job is
+    /// stopped instead.
+    ///
+    /// Bytes already written of files in flight are counted twice, which
+    /// errs on the side of stopping early.
+    pub fn claim(&self, path: &Path, bytes: u64) -> Option<Claim<'_>> {
+        let claim = Claim {
+            reserve: self,
+            bytes,
+        };
+        let in_flight = self.in_flight.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        let Some(free) = free_space(&self.destination) else {
+            return Some(claim);
+        };
+        if free.saturating_sub(in_flight) >= self.keep_bytes {
+            return Some(claim);
+        }
+
+        self.aborted.store(true, Ordering::Relaxed);
+        let mut reason = self.reason.lock().unwrap();
+        if reason.is_none() {
+            *reason = Some(tr!(
+                "abort-keep-free",
+                path = path.display().to_string(),
+                free = free / (1024 * 1024),
+                min = self.keep_bytes / (1024 * 1024)
+            ));
+        }
+        None
+    }
+
+    /// Why the reserve stopped the job, if it did
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+}
+
+/// Room claimed on the destination, released when dropped
+pub struct Claim<'r> {
+    reserve: &'r FreeSpaceReserve<'r>,
+    bytes: u64,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.reserve
+            .in_flight
+            .fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
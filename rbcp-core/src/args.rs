@@ -1,11 +1,49 @@
+use rbcp_plan::{CompareOptions, CompareStrategy};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::abort::{AbortPolicy, DEFAULT_SLOW_MINUTES};
+use crate::bench::DEFAULT_BENCH_MB;
+use crate::checkpoint::Checkpoint;
+use crate::cloud::PlaceholderPolicy;
+use crate::conflicts::ConflictAction;
+use crate::congestion::AUTO_MAX_THREADS;
+use crate::content::CommandFilter;
+use crate::deadline::Deadline;
+use crate::durability::Durability;
+use crate::ignore::IgnoreRule;
+use crate::index::DEFAULT_FAST_SKIP_RUNS;
+use crate::limits::EntryLimits;
+use crate::links::{LinkKind, LinkPolicies, LinkPolicy};
+use crate::merge::Precedence;
+use crate::network::NetworkPolicy;
+use crate::notify::NotifyConfig;
+use crate::power::BatteryPolicy;
+use crate::quarantine::{Quarantine, QuarantineCommand};
+use crate::remap::{FlattenCollision, NameCase, RemapRule, RouteRule};
+use crate::retry::FailureReport;
+use crate::salvage::DEFAULT_DAMAGE_REPORT;
+use crate::selftest::SelfTest;
+use crate::skiplist::{SkipList, SkipListCommand};
+use crate::special::{SpecialFiles, SpecialKind, SpecialPolicy};
+use crate::split::DEFAULT_PART_MB;
+use crate::tr;
+use crate::utils::{matches_pattern, SizeFormat};
+use crate::verify::Sampling;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CopyOptions {
     pub sources: Vec<String>,
     pub destination: String,
     pub patterns: Vec<String>,
+    /// Files left out, by name pattern
+    pub exclude_files: Vec<String>,
+    /// Folders left out with their contents, by name pattern or full path
+    pub exclude_dirs: Vec<String>,
 
     pub recursive: bool,
     pub include_empty: bool,
@@ -20,15 +58,186 @@ pub struct CopyOptions {
     pub threads: usize,
     pub retries: usize,
     pub wait_time: u64,
+    /// Retries of a directory read, metadata call or folder creation that
+    /// fails with an error that may pass, as on a share that stops
+    /// answering for a moment
+    pub meta_retries: usize,
+    /// Wait between those retries in milliseconds
+    pub meta_wait_ms: u64,
     pub log_file: Option<String>,
     pub list_only: bool,
     pub show_progress: bool,
     pub log_file_names: bool,
+    /// How sizes are written in the summary, logs and progress
+    pub size_format: SizeFormat,
     pub empty_files: bool,
     pub child_only: bool,
     pub shred_files: bool,
     pub force_overwrite: bool,
+    /// Overwrite or skip these existing destination files, by source
+    /// file, whatever their sizes and times (chosen in the GUI's conflict
+    /// table)
+    pub conflict_actions: BTreeMap<PathBuf, ConflictAction>,
+    /// Copy each source folder itself into the destination, as Explorer
+    /// does, rather than only its contents
     pub preserve_root: bool,
+    pub no_prescan: bool,
+    pub memory_budget_mb: u64,
+    pub index_file: Option<String>,
+    pub use_journal: bool,
+    /// Runs in which /INDEX leaves the files of unchanged directories
+    /// alone, per run comparing everything (0 to disable)
+    pub fast_skip: usize,
+    pub job_name: Option<String>,
+    pub resume: bool,
+    pub files_from: Option<String>,
+    /// Files to copy again below each source instead of walking it, as
+    /// `--retry-failed` reads them from a failure report
+    pub retry_files: BTreeMap<String, Vec<String>>,
+    pub print0: bool,
+    pub json_progress: bool,
+    /// Print nothing, leaving the log to the progress callback and log
+    /// file, for callers embedding the engine
+    pub quiet: bool,
+    pub notify: Option<NotifyConfig>,
+    pub record_history: bool,
+    /// Only report what would be copied and how long it would take
+    pub estimate_only: bool,
+    /// Number of largest files and directories to report (0 to disable)
+    pub top_sizes: usize,
+    /// Thresholds that make the job fail early
+    pub abort: AbortPolicy,
+    /// Sizes of folders and of the job past which it warns
+    pub limits: EntryLimits,
+    /// Passes at the end of the job to retry locked files in, instead of
+    /// retrying them right away (0 to disable)
+    pub defer_locked: usize,
+    /// Copy into a staging directory next to the destination and swap it
+    /// into place when the job completes
+    pub swap_dest: bool,
+    /// Errors that skip a file or directory without failing it
+    pub ignore_errors: Vec<IgnoreRule>,
+    /// What to do with dangling symlinks, FIFOs, sockets and devices
+    pub special_files: SpecialFiles,
+    /// What to do with junctions, directory symlinks and hard links
+    pub links: LinkPolicies,
+    /// Leave copies differing from their source only in attributes alone
+    pub ignore_attributes: bool,
+    /// Seconds by which modification times may differ and still count as
+    /// the same
+    pub time_tolerance: u64,
+    /// What tells a copy that is up to date from one that is not
+    pub compare: CompareStrategy,
+    /// What to do with cloud placeholders that would be copied
+    pub cloud_placeholders: PlaceholderPolicy,
+    /// Rules rewriting destination paths, applied in order
+    pub remap: Vec<RemapRule>,
+    /// Put all files in the destination root, renaming colliding names as
+    /// given
+    pub flatten: Option<FlattenCollision>,
+    /// Force destination file and directory names to this case
+    pub name_case: Option<NameCase>,
+    /// Send files to destination folders by pattern or file type
+    pub routes: Vec<RouteRule>,
+    /// Write files larger than this many MB as parts and a manifest, for
+    /// destinations such as FAT32 that cannot hold them (0 to disable)
+    pub split_mb: u64,
+    /// Restore split files from their parts
+    pub rejoin: bool,
+    /// Copy the entries of each directory one at a time in alphabetical
+    /// order, syncing each file before the next, for players that play
+    /// files in the order they were written
+    pub ordered: bool,
+    /// What is synced to disk before a file counts as copied
+    pub sync: Durability,
+    /// Flush and eject the destination drive when the job is done
+    pub eject: bool,
+    /// Hash each copied file and its source and fail the file if they
+    /// differ
+    pub verify: bool,
+    /// Check only some copies, or some of each, against their source
+    pub sample: Option<Sampling>,
+    /// Size of the copy buffer in KB (0 for the default of 1 MB)
+    pub buffer_kb: usize,
+    /// Use the threads and buffer size `rbcp bench` found best for the
+    /// destination
+    pub tuned: bool,
+    /// Bandwidth cap of the job in MB/s (0 for none)
+    pub throttle_mbps: f64,
+    /// Destination profile used instead of the one found for the
+    /// destination, or NONE for no profile
+    pub destination_profile: Option<String>,
+    /// Close the files being copied while the job is paused, reopening
+    /// them where they were when it resumes
+    pub deep_pause: bool,
+    /// When copying to a network destination pauses for the connection
+    pub network: NetworkPolicy,
+    /// What the job does while the machine is low on battery
+    pub battery: BatteryPolicy,
+    /// Adjust the number of files copied at once to the throughput, with
+    /// `threads` as the highest number
+    pub auto_threads: bool,
+    /// Most files whose contents are copied at once (0 for no limit below
+    /// `threads`)
+    pub data_threads: usize,
+    /// Most stat, mkdir and remove calls made at once (0 for no limit
+    /// below `threads`)
+    pub meta_threads: usize,
+    /// Which source's copy wins when several have the same path, if set
+    pub merge: Option<Precedence>,
+    /// Subdirectory of the destination each source listed here is copied
+    /// to, relative to the destination
+    pub source_subdirs: BTreeMap<String, String>,
+    /// Child folders /CHILDONLY processes, by name pattern (all if empty)
+    pub child_include: Vec<String>,
+    /// Child folders /CHILDONLY leaves out, by name pattern
+    pub child_exclude: Vec<String>,
+    /// Levels of the source tree walked, counting the source root as the
+    /// first (0 for all)
+    pub max_depth: usize,
+    /// When the job stops, copying the files left on its next run
+    pub deadline: Option<Deadline>,
+    /// Seconds a read of a source file may take before the attempt fails
+    /// (0 for no limit)
+    pub io_timeout: u64,
+    /// Seconds without progress after which the job is reported stalled
+    /// (0 to disable)
+    pub stall_secs: u64,
+    /// Fail and retry a read hanging for `stall_secs`
+    pub stall_retry: bool,
+    /// Write unreadable parts of source files as zeros instead of failing
+    /// the files
+    pub salvage: bool,
+    /// Where /SALVAGE lists the damaged files, if not in the destination
+    pub damage_report: Option<String>,
+    /// Hash the copies that would be skipped and flag those whose contents
+    /// changed although their size and time did not
+    pub check_bitrot: bool,
+    /// Copy the files flagged by `check_bitrot` again
+    pub bitrot_recopy: bool,
+    /// HTML page the report of the job is written to, if set
+    pub html_report: Option<String>,
+    /// Where to write the files that failed, for `--retry-failed`
+    pub failure_report: Option<String>,
+    /// Skip-list read instead of the one in the data directory
+    pub skip_list: Option<String>,
+    /// Copy the files on the skip-list too
+    pub no_skip_list: bool,
+    /// Runs in a row a file must fail in to be quarantined (0 to disable)
+    pub quarantine_runs: u32,
+    /// Put quarantined files on the skip-list too
+    pub quarantine_skip_list: bool,
+    /// Scanner run on each file before it is copied, blocking it unless it
+    /// exits with 0
+    pub scan_command: Option<String>,
+    /// Give copies the owner and group of their source
+    pub copy_owner: bool,
+    /// Owners of the source by the accounts the copies get instead
+    pub owner_map: BTreeMap<String, String>,
+    /// File with more lines of `owner_map`, read when the job starts
+    pub owner_map_file: Option<String>,
+    /// JSON file kept up to date with the progress of the job, if set
+    pub status_file: Option<String>,
 }
 
 impl Default for CopyOptions {
@@ -37,6 +246,8 @@ impl Default for CopyOptions {
             sources: Vec::new(),
             destination: String::new(),
             patterns: Vec::new(),
+            exclude_files: Vec::new(),
+            exclude_dirs: Vec::new(),
             recursive: false,
             include_empty: false,
             restartable: false,
@@ -50,32 +261,153 @@ impl Default for CopyOptions {
             threads: 1,
             retries: 1_000_000,
             wait_time: 30,
+            meta_retries: 3,
+            meta_wait_ms: 500,
             log_file: None,
             list_only: false,
             show_progress: true,
             log_file_names: true,
+            size_format: SizeFormat::default(),
             empty_files: false,
             child_only: false,
             shred_files: false,
             force_overwrite: false,
+            conflict_actions: BTreeMap::new(),
             preserve_root: false,
+            no_prescan: false,
+            memory_budget_mb: 0,
+            index_file: None,
+            use_journal: false,
+            fast_skip: 0,
+            job_name: None,
+            resume: false,
+            files_from: None,
+            retry_files: BTreeMap::new(),
+            print0: false,
+            json_progress: false,
+            quiet: false,
+            notify: None,
+            record_history: false,
+            estimate_only: false,
+            top_sizes: 0,
+            abort: AbortPolicy::default(),
+            limits: EntryLimits::default(),
+            defer_locked: 0,
+            swap_dest: false,
+            ignore_errors: Vec::new(),
+            special_files: SpecialFiles::default(),
+            links: LinkPolicies::default(),
+            ignore_attributes: false,
+            time_tolerance: 0,
+            compare: CompareStrategy::default(),
+            cloud_placeholders: PlaceholderPolicy::default(),
+            remap: Vec::new(),
+            flatten: None,
+            name_case: None,
+            routes: Vec::new(),
+            split_mb: 0,
+            rejoin: false,
+            ordered: false,
+            sync: Durability::None,
+            eject: false,
+            verify: false,
+            sample: None,
+            buffer_kb: 0,
+            tuned: false,
+            throttle_mbps: 0.0,
+            destination_profile: None,
+            deep_pause: false,
+            network: NetworkPolicy::default(),
+            battery: BatteryPolicy::default(),
+            auto_threads: false,
+            data_threads: 0,
+            meta_threads: 0,
+            merge: None,
+            source_subdirs: BTreeMap::new(),
+            child_include: Vec::new(),
+            child_exclude: Vec::new(),
+            max_depth: 0,
+            deadline: None,
+            io_timeout: 0,
+            stall_secs: 0,
+            stall_retry: false,
+            salvage: false,
+            damage_report: None,
+            check_bitrot: false,
+            bitrot_recopy: false,
+            html_report: None,
+            failure_report: None,
+            skip_list: None,
+            no_skip_list: false,
+            quarantine_runs: 0,
+            quarantine_skip_list: false,
+            scan_command: None,
+            copy_owner: false,
+            owner_map: BTreeMap::new(),
+            owner_map_file: None,
+            status_file: None,
         }
     }
 }
 
 impl CopyOptions {
+    /// Whether destination paths differ from the source structure
+    pub fn rewrites_paths(&self) -> bool {
+        !self.remap.is_empty()
+            || !self.routes.is_empty()
+            || self.flatten.is_some()
+            || self.name_case.is_some()
+    }
+
+    /// How source files are compared with their copies
+    pub fn comparison(&self) -> CompareOptions {
+        CompareOptions {
+            force_overwrite: self.force_overwrite,
+            ignore_attributes: self.ignore_attributes,
+            mtime_tolerance: self.time_tolerance.saturating_mul(1_000_000_000),
+            strategy: self.compare,
+        }
+    }
+
     pub fn parse() -> Result<Self, String> {
-        let args: Vec<String> = env::args().collect();
+        Self::parse_args(env::args().skip(1).collect())
+    }
 
-        if args.len() < 3 {
+    /// Parse `args` as given on the command line after the program name
+    pub fn parse_args(args: Vec<String>) -> Result<Self, String> {
+        // Resuming a job restores the options it was started with
+        let resume_job = args.iter().find_map(|arg| {
+            arg.to_uppercase()
+                .starts_with("/RESUME:")
+                .then(|| arg[8..].to_string())
+        });
+        if let Some(job) = resume_job {
+            Checkpoint::check_name(&job)?;
+            let mut options = Checkpoint::load_options(&job)
+                .map_err(|e| format!("Cannot resume job {}: {}", job, e))?;
+            options.job_name = Some(job);
+            options.resume = true;
+            return Ok(options);
+        }
+
+        // `estimate` runs the same job without copying
+        let estimate_only = args
+            .first()
+            .is_some_and(|arg| arg.eq_ignore_ascii_case("estimate"));
+        let first_arg = if estimate_only { 1 } else { 0 };
+
+        if args.len() < first_arg + 2 {
             return Err("Not enough arguments".to_string());
         }
 
-        let mut options = CopyOptions::default();
+        let mut options = CopyOptions {
+            estimate_only,
+            ..Default::default()
+        };
         let mut positional_args = Vec::new();
 
-        // Skip the program name
-        for arg in args.iter().skip(1) {
+        // Skip the subcommand
+        for arg in args.iter().skip(first_arg) {
             if arg.starts_with('/') {
                 // It's a flag
                 let upper_arg = arg.to_uppercase();
@@ -101,14 +433,48 @@ impl CopyOptions {
                     "/L" => options.list_only = true,
                     "/NP" => options.show_progress = false,
                     "/NFL" => options.log_file_names = false,
+                    "/BYTES" => options.size_format = SizeFormat::Bytes,
                     "/EMPTY" => options.empty_files = true,
                     "/CHILDONLY" => options.child_only = true,
+                    "/ROOT" => options.preserve_root = true,
                     "/SHRED" => options.shred_files = true,
+                    "/NOSCAN" => options.no_prescan = true,
+                    "/JOURNAL" => options.use_journal = true,
+                    "/FASTSKIP" => options.fast_skip = DEFAULT_FAST_SKIP_RUNS,
+                    "/PRINT0" => options.print0 = true,
+                    "/PROGRESS:JSON" => options.json_progress = true,
+                    "/HISTORY" => options.record_history = true,
+                    "/NOSKIPLIST" => options.no_skip_list = true,
+                    "/OWNER" => options.copy_owner = true,
+                    "/IGNOREATTR" => options.ignore_attributes = true,
+                    "/FFT" => options.time_tolerance = 2,
+                    "/SIZEONLY" => options.compare = CompareStrategy::Size,
+                    "/SWAP" => options.swap_dest = true,
+                    "/SPLIT" => options.split_mb = DEFAULT_PART_MB,
+                    "/REJOIN" => options.rejoin = true,
+                    "/ORDERED" => options.ordered = true,
+                    "/EJECT" => options.eject = true,
+                    "/VERIFY" => options.verify = true,
+                    "/TUNED" => options.tuned = true,
+                    "/SALVAGE" => options.salvage = true,
+                    "/DEEPPAUSE" => options.deep_pause = true,
+                    "/BITROT" => options.check_bitrot = true,
+                    "/BITROT:RECOPY" => {
+                        options.check_bitrot = true;
+                        options.bitrot_recopy = true;
+                    }
                     _ => {
                         if let Some(stripped) = upper_arg.strip_prefix("/A+:") {
                             options.attributes_add = stripped.to_string();
                         } else if let Some(stripped) = upper_arg.strip_prefix("/A-:") {
                             options.attributes_remove = stripped.to_string();
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MTDATA:") {
+                            options.data_threads = stripped.parse::<usize>().unwrap_or(0);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MTMETA:") {
+                            options.meta_threads = stripped.parse::<usize>().unwrap_or(0);
+                        } else if upper_arg == "/MT:AUTO" {
+                            options.auto_threads = true;
+                            options.threads = AUTO_MAX_THREADS;
                         } else if upper_arg.starts_with("/MT") {
                             let threads =
                                 if upper_arg.len() > 4 && upper_arg.chars().nth(3) == Some(':') {
@@ -123,8 +489,183 @@ impl CopyOptions {
                         } else if let Some(stripped) = upper_arg.strip_prefix("/W:") {
                             let wait = stripped.parse::<u64>().unwrap_or(30);
                             options.wait_time = wait;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/RMETA:") {
+                            options.meta_retries = stripped.parse::<usize>().map_err(|_| {
+                                format!("Invalid number of metadata retries: {}", &arg[7..])
+                            })?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/WMETA:") {
+                            options.meta_wait_ms = stripped.parse::<u64>().map_err(|_| {
+                                format!(
+                                    "Invalid metadata retry wait in milliseconds: {}",
+                                    &arg[7..]
+                                )
+                            })?;
+                        } else if upper_arg == "/TOP" {
+                            options.top_sizes = 20;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/TOP:") {
+                            options.top_sizes = stripped.parse::<usize>().unwrap_or(20);
+                        } else if upper_arg == "/DEFER" {
+                            options.defer_locked = 3;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/TIMETOL:") {
+                            options.time_tolerance = stripped.parse::<u64>().map_err(|_| {
+                                format!("Invalid time tolerance in seconds: {}", &arg[9..])
+                            })?;
+                        } else if upper_arg.starts_with("/COMPARE:") {
+                            options.compare = CompareStrategy::parse(&arg[9..])?;
+                        } else if upper_arg.starts_with("/SAMPLE:") {
+                            options.sample = Some(Sampling::parse(&arg[8..])?);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/FASTSKIP:") {
+                            options.fast_skip = stripped
+                                .parse::<usize>()
+                                .map_err(|_| format!("Invalid number of runs: {}", &arg[10..]))?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/DEFER:") {
+                            options.defer_locked = stripped.parse::<usize>().unwrap_or(3);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/ABORTFAILS:") {
+                            options.abort.max_failures = stripped.parse::<u64>().unwrap_or(0);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/ABORTFREE:") {
+                            options.abort.min_free_mb = stripped.parse::<u64>().unwrap_or(0);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MAXENTRIES:") {
+                            options.limits.max_dir_entries =
+                                stripped.parse::<u64>().map_err(|_| {
+                                    format!("Invalid number of entries: {}", &arg[12..])
+                                })?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MAXFILES:") {
+                            options.limits.max_files = stripped
+                                .parse::<u64>()
+                                .map_err(|_| format!("Invalid number of files: {}", &arg[10..]))?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/BUF:") {
+                            options.buffer_kb = stripped
+                                .parse::<usize>()
+                                .map_err(|_| format!("Invalid buffer size: {}", stripped))?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/THROTTLE:") {
+                            options.throttle_mbps = stripped
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|mbps| *mbps >= 0.0)
+                                .ok_or_else(|| format!("Invalid throttle: {}", stripped))?;
+                        } else if upper_arg.starts_with("/DPROFILE:") {
+                            options.destination_profile = Some(arg[10..].to_string());
+                        } else if upper_arg == "/NETPAUSE:METERED" {
+                            options.network.metered = true;
+                        } else if upper_arg.starts_with("/NETPAUSE:VPN=") {
+                            let name = &arg[14..];
+                            if name.is_empty() {
+                                return Err("Missing VPN name in /NETPAUSE:VPN=".to_string());
+                            }
+                            options.network.vpn = Some(name.to_string());
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/BATTERY:") {
+                            options.battery = BatteryPolicy::parse(stripped)?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/DEADLINE:") {
+                            options.deadline = Some(Deadline::parse(stripped)?);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/IOTIMEOUT:") {
+                            options.io_timeout = stripped.parse::<u64>().unwrap_or(0);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/STALL:") {
+                            let (secs, action) = stripped.split_once(':').unwrap_or((stripped, ""));
+                            options.stall_secs = secs
+                                .parse::<u64>()
+                                .map_err(|_| format!("Invalid stall timeout: {}", secs))?;
+                            options.stall_retry = match action {
+                                "" => false,
+                                "RETRY" => true,
+                                _ => return Err(format!("Unknown stall action: {}", action)),
+                            };
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/QUARANTINE:") {
+                            let (runs, action) = stripped.split_once(':').unwrap_or((stripped, ""));
+                            options.quarantine_runs = runs
+                                .parse::<u32>()
+                                .ok()
+                                .filter(|runs| *runs > 0)
+                                .ok_or_else(|| format!("Invalid quarantine runs: {}", runs))?;
+                            options.quarantine_skip_list = match action {
+                                "" => false,
+                                "SKIPLIST" => true,
+                                _ => return Err(format!("Unknown quarantine action: {}", action)),
+                            };
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MERGE:") {
+                            options.merge = Some(Precedence::parse(stripped)?);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/SYNC:") {
+                            options.sync = Durability::parse(stripped)?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/SPLIT:") {
+                            options.split_mb = stripped
+                                .parse::<u64>()
+                                .map_err(|_| format!("Invalid part size: {}", stripped))?;
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/KEEPFREE:") {
+                            options.abort.keep_free_mb = stripped.parse::<u64>().unwrap_or(0);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/ABORTSLOW:") {
+                            let (speed, minutes) =
+                                stripped.split_once(':').unwrap_or((stripped, ""));
+                            options.abort.min_speed_mbps = speed.parse::<f64>().unwrap_or(0.0);
+                            options.abort.slow_minutes =
+                                minutes.parse::<u64>().unwrap_or(DEFAULT_SLOW_MINUTES);
+                        } else if upper_arg.starts_with("/XF:") {
+                            options.exclude_files.push(arg[4..].to_string());
+                        } else if upper_arg.starts_with("/XD:") {
+                            options.exclude_dirs.push(arg[4..].to_string());
+                        } else if upper_arg.starts_with("/CHILD:") {
+                            options.child_include.push(arg[7..].to_string());
+                        } else if upper_arg.starts_with("/XCHILD:") {
+                            options.child_exclude.push(arg[8..].to_string());
+                        } else if let Some(stripped) = upper_arg
+                            .strip_prefix("/MAXDEPTH:")
+                            .or_else(|| upper_arg.strip_prefix("/LEV:"))
+                        {
+                            options.max_depth = stripped.parse::<usize>().unwrap_or(0);
+                        } else if upper_arg.starts_with("/HTML:") {
+                            options.html_report = Some(arg[6..].to_string());
+                        } else if upper_arg.starts_with("/FAILED:") {
+                            options.failure_report = Some(arg[8..].to_string());
+                        } else if upper_arg.starts_with("/SCANCMD:") {
+                            CommandFilter::new(&arg[9..])?;
+                            options.scan_command = Some(arg[9..].to_string());
+                        } else if upper_arg.starts_with("/OWNERMAP:") {
+                            options.copy_owner = true;
+                            options.owner_map_file = Some(arg[10..].to_string());
+                        } else if upper_arg.starts_with("/SKIPLIST:") {
+                            options.skip_list = Some(arg[10..].to_string());
+                        } else if upper_arg.starts_with("/STATUS:") {
+                            options.status_file = Some(arg[8..].to_string());
+                        } else if upper_arg.starts_with("/SALVAGE:") {
+                            options.salvage = true;
+                            options.damage_report = Some(arg[9..].to_string());
+                        } else if upper_arg.starts_with("/IGNORE:") {
+                            options.ignore_errors.push(IgnoreRule::parse(&arg[8..])?);
+                        } else if upper_arg.starts_with("/SPECIAL:") {
+                            options.special_files.parse_into(&arg[9..])?;
+                        } else if upper_arg.starts_with("/LINKS:") {
+                            options.links.parse_into(&arg[7..])?;
+                        } else if upper_arg.starts_with("/UNITS:") {
+                            options.size_format = SizeFormat::parse(&arg[7..])?;
+                        } else if upper_arg.starts_with("/CLOUD:") {
+                            options.cloud_placeholders = PlaceholderPolicy::parse(&arg[7..])?;
+                        } else if upper_arg.starts_with("/ROUTE:") {
+                            options.routes.push(RouteRule::parse(&arg[7..])?);
+                        } else if upper_arg == "/FLAT" {
+                            options.flatten = Some(FlattenCollision::default());
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/FLAT:") {
+                            options.flatten = Some(FlattenCollision::parse(stripped)?);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/CASE:") {
+                            options.name_case = Some(NameCase::parse(stripped)?);
+                        } else if upper_arg.starts_with("/REMAP:") {
+                            options.remap.push(RemapRule::parse(&arg[7..], false)?);
+                        } else if upper_arg.starts_with("/REMAPRE:") {
+                            options.remap.push(RemapRule::parse(&arg[9..], true)?);
+                        } else if let Some(stripped) = upper_arg.strip_prefix("/MEM:") {
+                            options.memory_budget_mb = stripped.parse::<u64>().unwrap_or(0);
                         } else if upper_arg.starts_with("/LOG:") {
                             options.log_file = Some(arg[5..].to_string()); // Use original case for filename
+                        } else if upper_arg.starts_with("/INDEX:") {
+                            options.index_file = Some(arg[7..].to_string());
+                        } else if upper_arg.starts_with("/JOB:") {
+                            let job = &arg[5..];
+                            Checkpoint::check_name(job)?;
+                            options.job_name = Some(job.to_string());
+                        } else if upper_arg.starts_with("/FILESFROM:") {
+                            options.files_from = Some(arg[11..].to_string());
+                        } else if upper_arg.starts_with("/NOTIFY:") {
+                            options.notify = Some(NotifyConfig {
+                                webhook_url: Some(arg[8..].to_string()),
+                                ..Default::default()
+                            });
                         }
                     }
                 }
@@ -138,7 +679,23 @@ impl CopyOptions {
             return Err("Missing source or destination".to_string());
         }
 
-        options.sources = vec![positional_args[0].clone()];
+        // Several sources are separated by semicolons, as in the GUI, and
+        // "source>subdir" copies a source into a subdirectory
+        for source in positional_args[0].split(';').map(str::trim) {
+            let (source, subdir) = match source.split_once('>') {
+                Some((source, subdir)) => (source.trim(), Some(subdir.trim())),
+                None => (source, None),
+            };
+            if source.is_empty() {
+                continue;
+            }
+            if let Some(subdir) = subdir {
+                options
+                    .source_subdirs
+                    .insert(source.to_string(), subdir.to_string());
+            }
+            options.sources.push(source.to_string());
+        }
         options.destination = positional_args[1].clone();
 
         // Any remaining positional args are patterns
@@ -193,9 +750,17 @@ impl CopyOptions {
             result.push(format!("/A-:{}", self.attributes_remove));
         }
 
-        if self.threads != 1 {
+        if self.auto_threads {
+            result.push("/MT:AUTO".to_string());
+        } else if self.threads != 1 {
             result.push(format!("/MT:{}", self.threads));
         }
+        if self.data_threads > 0 {
+            result.push(format!("/MTDATA:{}", self.data_threads));
+        }
+        if self.meta_threads > 0 {
+            result.push(format!("/MTMETA:{}", self.meta_threads));
+        }
 
         if self.retries != 1_000_000 {
             result.push(format!("/R:{}", self.retries));
@@ -205,6 +770,14 @@ impl CopyOptions {
             result.push(format!("/W:{}", self.wait_time));
         }
 
+        if self.meta_retries != 3 {
+            result.push(format!("/RMETA:{}", self.meta_retries));
+        }
+
+        if self.meta_wait_ms != 500 {
+            result.push(format!("/WMETA:{}", self.meta_wait_ms));
+        }
+
         if self.list_only {
             result.push("/L".to_string());
         }
@@ -217,46 +790,743 @@ impl CopyOptions {
             result.push("/NFL".to_string());
         }
 
+        match self.size_format {
+            SizeFormat::Binary => {}
+            SizeFormat::Bytes => result.push("/BYTES".to_string()),
+            format => result.push(format!("/UNITS:{}", format.name())),
+        }
+
         if self.empty_files {
             result.push("/EMPTY".to_string());
         }
 
+        for pattern in &self.exclude_files {
+            result.push(format!("/XF:{}", pattern));
+        }
+
+        for pattern in &self.exclude_dirs {
+            result.push(format!("/XD:{}", pattern));
+        }
+
         if self.child_only {
             result.push("/CHILDONLY".to_string());
         }
 
+        if self.preserve_root {
+            result.push("/ROOT".to_string());
+        }
+
+        for pattern in &self.child_include {
+            result.push(format!("/CHILD:{}", pattern));
+        }
+
+        for pattern in &self.child_exclude {
+            result.push(format!("/XCHILD:{}", pattern));
+        }
+
+        if self.max_depth > 0 {
+            result.push(format!("/MAXDEPTH:{}", self.max_depth));
+        }
+
+        if let Some(deadline) = self.deadline {
+            result.push(format!("/DEADLINE:{}", deadline.name()));
+        }
+
+        if self.io_timeout > 0 {
+            result.push(format!("/IOTIMEOUT:{}", self.io_timeout));
+        }
+
+        if let Some(report) = &self.html_report {
+            result.push(format!("/HTML:{}", report));
+        }
+        if let Some(report) = &self.failure_report {
+            result.push(format!("/FAILED:{}", report));
+        }
+
+        if let Some(skip_list) = &self.skip_list {
+            result.push(format!("/SKIPLIST:{}", skip_list));
+        }
+        if self.no_skip_list {
+            result.push("/NOSKIPLIST".to_string());
+        }
+        if self.quarantine_runs > 0 {
+            let action = if self.quarantine_skip_list {
+                ":SKIPLIST"
+            } else {
+                ""
+            };
+            result.push(format!("/QUARANTINE:{}{}", self.quarantine_runs, action));
+        }
+
+        if let Some(command) = &self.scan_command {
+            result.push(format!("/SCANCMD:{}", command));
+        }
+
+        if let Some(file) = &self.owner_map_file {
+            result.push(format!("/OWNERMAP:{}", file));
+        } else if self.copy_owner {
+            result.push("/OWNER".to_string());
+        }
+
+        if let Some(status) = &self.status_file {
+            result.push(format!("/STATUS:{}", status));
+        }
+
+        if self.check_bitrot {
+            if self.bitrot_recopy {
+                result.push("/BITROT:RECOPY".to_string());
+            } else {
+                result.push("/BITROT".to_string());
+            }
+        }
+
+        if self.salvage {
+            match &self.damage_report {
+                Some(report) => result.push(format!("/SALVAGE:{}", report)),
+                None => result.push("/SALVAGE".to_string()),
+            }
+        }
+
+        if self.stall_secs > 0 {
+            let action = if self.stall_retry { ":RETRY" } else { "" };
+            result.push(format!("/STALL:{}{}", self.stall_secs, action));
+        }
+
         if self.shred_files {
             result.push("/SHRED".to_string());
         }
 
+        if self.no_prescan {
+            result.push("/NOSCAN".to_string());
+        }
+
+        if self.memory_budget_mb != 0 {
+            result.push(format!("/MEM:{}", self.memory_budget_mb));
+        }
+
+        if let Some(index_file) = &self.index_file {
+            result.push(format!("/INDEX:{}", index_file));
+        }
+
+        if self.use_journal {
+            result.push("/JOURNAL".to_string());
+        }
+
+        if self.fast_skip > 0 {
+            result.push(format!("/FASTSKIP:{}", self.fast_skip));
+        }
+
+        if let Some(job_name) = &self.job_name {
+            result.push(format!("/JOB:{}", job_name));
+        }
+
+        if let Some(files_from) = &self.files_from {
+            result.push(format!("/FILESFROM:{}", files_from));
+        }
+
+        if self.print0 {
+            result.push("/PRINT0".to_string());
+        }
+
+        if self.json_progress {
+            result.push("/PROGRESS:JSON".to_string());
+        }
+
+        if self.record_history {
+            result.push("/HISTORY".to_string());
+        }
+
+        if self.top_sizes != 0 {
+            result.push(format!("/TOP:{}", self.top_sizes));
+        }
+
+        if self.defer_locked != 0 {
+            result.push(format!("/DEFER:{}", self.defer_locked));
+        }
+
+        if self.swap_dest {
+            result.push("/SWAP".to_string());
+        }
+
+        if self.split_mb != 0 {
+            result.push(format!("/SPLIT:{}", self.split_mb));
+        }
+
+        if self.rejoin {
+            result.push("/REJOIN".to_string());
+        }
+
+        if self.ordered {
+            result.push("/ORDERED".to_string());
+        }
+
+        if self.sync != Durability::None {
+            result.push(format!("/SYNC:{}", self.sync.name()));
+        }
+        if let Some(precedence) = self.merge {
+            result.push(format!("/MERGE:{}", precedence.name()));
+        }
+
+        if self.eject {
+            result.push("/EJECT".to_string());
+        }
+
+        if self.verify {
+            result.push("/VERIFY".to_string());
+        }
+
+        if let Some(sample) = self.sample {
+            result.push(format!("/SAMPLE:{}", sample.name()));
+        }
+
+        if self.tuned {
+            result.push("/TUNED".to_string());
+        }
+
+        if self.buffer_kb != 0 {
+            result.push(format!("/BUF:{}", self.buffer_kb));
+        }
+
+        if self.throttle_mbps > 0.0 {
+            result.push(format!("/THROTTLE:{}", self.throttle_mbps));
+        }
+
+        if let Some(profile) = &self.destination_profile {
+            result.push(format!("/DPROFILE:{}", profile));
+        }
+
+        if self.deep_pause {
+            result.push("/DEEPPAUSE".to_string());
+        }
+
+        result.extend(self.network.to_flags());
+        result.extend(self.battery.to_flag());
+
+        for rule in &self.ignore_errors {
+            result.push(format!("/IGNORE:{}", rule));
+        }
+
+        for kind in SpecialKind::ALL {
+            let policy = self.special_files.policy(kind);
+            if policy != SpecialPolicy::default() {
+                result.push(format!("/SPECIAL:{}={}", kind.name(), policy.name()));
+            }
+        }
+
+        if self.ignore_attributes {
+            result.push("/IGNOREATTR".to_string());
+        }
+
+        if self.time_tolerance > 0 {
+            result.push(format!("/TIMETOL:{}", self.time_tolerance));
+        }
+
+        if self.compare != CompareStrategy::default() {
+            result.push(format!("/COMPARE:{}", self.compare.name()));
+        }
+
+        if self.cloud_placeholders != PlaceholderPolicy::default() {
+            result.push(format!("/CLOUD:{}", self.cloud_placeholders.name()));
+        }
+
+        for kind in LinkKind::ALL {
+            let policy = self.links.policy(kind);
+            if policy != LinkPolicy::default() {
+                result.push(format!("/LINKS:{}={}", kind.name(), policy.name()));
+            }
+        }
+
+        for rule in &self.remap {
+            let flag = if rule.regex { "/REMAPRE" } else { "/REMAP" };
+            result.push(format!("{}:{}={}", flag, rule.find, rule.replace));
+        }
+
+        for route in &self.routes {
+            result.push(format!("/ROUTE:{}", route));
+        }
+
+        if let Some(collision) = self.flatten {
+            result.push(format!("/FLAT:{}", collision.name()));
+        }
+
+        if let Some(case) = self.name_case {
+            result.push(format!("/CASE:{}", case.name()));
+        }
+
+        if self.abort.max_failures != 0 {
+            result.push(format!("/ABORTFAILS:{}", self.abort.max_failures));
+        }
+
+        if self.abort.min_free_mb != 0 {
+            result.push(format!("/ABORTFREE:{}", self.abort.min_free_mb));
+        }
+
+        if self.abort.keep_free_mb != 0 {
+            result.push(format!("/KEEPFREE:{}", self.abort.keep_free_mb));
+        }
+
+        if self.abort.min_speed_mbps > 0.0 {
+            result.push(format!(
+                "/ABORTSLOW:{}:{}",
+                self.abort.min_speed_mbps, self.abort.slow_minutes
+            ));
+        }
+
+        if self.limits.max_dir_entries != 0 {
+            result.push(format!("/MAXENTRIES:{}", self.limits.max_dir_entries));
+        }
+
+        if self.limits.max_files != 0 {
+            result.push(format!("/MAXFILES:{}", self.limits.max_files));
+        }
+
+        if let Some(url) = self.notify.as_ref().and_then(|n| n.webhook_url.as_ref()) {
+            result.push(format!("/NOTIFY:{}", url));
+        }
+
         result.join(" ")
     }
+
+    /// What is synced before a file counts as copied. Ordered copies sync
+    /// each file at least, so files reach the disk in order.
+    pub fn durability(&self) -> Durability {
+        if self.ordered {
+            self.sync.max(Durability::File)
+        } else {
+            self.sync
+        }
+    }
+
+    /// Size of the copy buffer in bytes
+    pub fn buffer_size(&self) -> usize {
+        match self.buffer_kb {
+            0 => 1024 * 1024,
+            kb => kb * 1024,
+        }
+    }
+
+    /// Whether the file named `name` is copied: it matches one of the
+    /// patterns and no /XF pattern
+    pub fn selects_file(&self, name: &str) -> bool {
+        self.patterns.iter().any(|p| matches_pattern(name, p)) && !self.excludes_file(name)
+    }
+
+    /// Whether /XF leaves out the file named `name`
+    pub fn excludes_file(&self, name: &str) -> bool {
+        self.file_exclusion(name).is_some()
+    }
+
+    /// The /XF pattern leaving out the file named `name`, if any
+    pub fn file_exclusion(&self, name: &str) -> Option<&str> {
+        self.exclude_files
+            .iter()
+            .find(|p| matches_pattern(name, p))
+            .map(String::as_str)
+    }
+
+    /// Whether /XD leaves out the folder at `path`
+    pub fn excludes_dir(&self, path: &Path) -> bool {
+        self.dir_exclusion(path).is_some()
+    }
+
+    /// The /XD entry leaving out the folder at `path`, if any: its full
+    /// path (ignoring case on Windows) or a pattern for its name
+    pub fn dir_exclusion(&self, path: &Path) -> Option<&str> {
+        if self.exclude_dirs.is_empty() {
+            return None;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let full = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let same = |a: &Path, b: &Path| {
+            if cfg!(windows) {
+                a.to_string_lossy()
+                    .eq_ignore_ascii_case(&b.to_string_lossy())
+            } else {
+                a == b
+            }
+        };
+        self.exclude_dirs
+            .iter()
+            .find(|p| {
+                let excluded = Path::new(p);
+                if excluded.is_absolute() {
+                    same(excluded, &full)
+                } else {
+                    matches_pattern(&name, p)
+                }
+            })
+            .map(String::as_str)
+    }
+
+    /// Whether /CHILDONLY processes the child folder named `name`
+    pub fn child_selected(&self, name: &str) -> bool {
+        (self.child_include.is_empty()
+            || self.child_include.iter().any(|p| matches_pattern(name, p)))
+            && !self.child_exclude.iter().any(|p| matches_pattern(name, p))
+    }
+
+    /// Whether the hashes of copies are kept in the destination's manifest
+    pub fn keeps_hashes(&self) -> bool {
+        self.verify || self.check_bitrot
+    }
+
+    /// Where /SALVAGE lists the damaged files, by default at the root of
+    /// `root`, the tree the job writes: the destination, or its staging
+    /// tree with /SWAP
+    pub fn damage_report_path(&self, root: &Path) -> PathBuf {
+        match &self.damage_report {
+            Some(report) => PathBuf::from(report),
+            None => root.join(DEFAULT_DAMAGE_REPORT),
+        }
+    }
+
+    /// Longest a read of a source file may take, from `/IOTIMEOUT` or
+    /// `/STALL:n:RETRY`
+    pub fn read_timeout(&self) -> Option<Duration> {
+        let secs = if self.io_timeout > 0 {
+            self.io_timeout
+        } else if self.stall_retry {
+            self.stall_secs
+        } else {
+            0
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Whether the walk enters the subdirectories of a directory `depth`
+    /// levels below the source root
+    pub fn descends_below(&self, depth: usize) -> bool {
+        self.recursive && (self.max_depth == 0 || depth + 1 < self.max_depth)
+    }
+
+    /// Whether files may be copied on several threads at once
+    pub fn parallel(&self) -> bool {
+        self.threads > 1 && !self.ordered
+    }
+
+    /// Maximum number of directory entries held in memory at once per
+    /// directory level, derived from the memory budget (unbounded if 0).
+    /// Sorting entries needs the whole directory.
+    pub fn entry_batch_size(&self) -> usize {
+        const ENTRY_MEMORY_ESTIMATE: u64 = 512;
+        const MIN_BATCH_SIZE: u64 = 64;
+
+        if self.memory_budget_mb == 0 || self.ordered {
+            return usize::MAX;
+        }
+
+        let budget_bytes = self.memory_budget_mb.saturating_mul(1024 * 1024);
+        let per_worker = budget_bytes / self.threads.max(1) as u64;
+        (per_worker / ENTRY_MEMORY_ESTIMATE).max(MIN_BATCH_SIZE) as usize
+    }
+}
+
+/// Flags listed by `print_usage`, with the message id of their description
+const USAGE_OPTIONS: &[(&str, &str)] = &[
+    ("/S", "opt-s"),
+    ("/E", "opt-e"),
+    ("/Z", "opt-z"),
+    ("/B", "opt-b"),
+    ("/PURGE", "opt-purge"),
+    ("/MIR", "opt-mir"),
+    ("/MOV", "opt-mov"),
+    ("/MOVE", "opt-move"),
+    ("/A+:[RASHCNETO]", "opt-a-add"),
+    ("/A-:[RASHCNETO]", "opt-a-remove"),
+    ("/MT[:n|AUTO]", "opt-mt"),
+    ("/MTDATA:n", "opt-mtdata"),
+    ("/MTMETA:n", "opt-mtmeta"),
+    ("/R:n", "opt-r"),
+    ("/W:n", "opt-w"),
+    ("/RMETA:n", "opt-rmeta"),
+    ("/WMETA:ms", "opt-wmeta"),
+    ("/IOTIMEOUT:n", "opt-iotimeout"),
+    ("/DEADLINE:min|HH:MM", "opt-deadline"),
+    ("/STALL:n[:RETRY]", "opt-stall"),
+    ("/SALVAGE[:file]", "opt-salvage"),
+    ("/HTML:file", "opt-html"),
+    ("/FAILED:file", "opt-failed"),
+    ("/SKIPLIST:file", "opt-skiplist"),
+    ("/NOSKIPLIST", "opt-noskiplist"),
+    ("/QUARANTINE:n[:SKIPLIST]", "opt-quarantine"),
+    ("/SCANCMD:command", "opt-scancmd"),
+    ("/OWNER", "opt-owner"),
+    ("/OWNERMAP:file", "opt-ownermap"),
+    ("/STATUS:file", "opt-status"),
+    ("/BITROT[:RECOPY]", "opt-bitrot"),
+    ("/LOG:file", "opt-log"),
+    ("/L", "opt-l"),
+    ("/NP", "opt-np"),
+    ("/NFL", "opt-nfl"),
+    ("/UNITS:binary|si|bytes", "opt-units"),
+    ("/BYTES", "opt-bytes"),
+    ("/EMPTY", "opt-empty"),
+    ("/XF:pattern", "opt-xf"),
+    ("/XD:pattern|path", "opt-xd"),
+    ("/ROOT", "opt-root"),
+    ("/CHILDONLY", "opt-childonly"),
+    ("/CHILD:pattern", "opt-child"),
+    ("/XCHILD:pattern", "opt-xchild"),
+    ("/MAXDEPTH:n", "opt-maxdepth"),
+    ("/LEV:n", "opt-lev"),
+    ("/SHRED", "opt-shred"),
+    ("/NOSCAN", "opt-noscan"),
+    ("/MEM:n", "opt-mem"),
+    ("/INDEX:file", "opt-index"),
+    ("/JOURNAL", "opt-journal"),
+    ("/FASTSKIP[:n]", "opt-fastskip"),
+    ("/JOB:name", "opt-job"),
+    ("/RESUME:name", "opt-resume"),
+    ("/FILESFROM:file", "opt-filesfrom"),
+    ("/PRINT0", "opt-print0"),
+    ("/PROGRESS:JSON", "opt-progress-json"),
+    ("/NOTIFY:url", "opt-notify"),
+    ("/HISTORY", "opt-history"),
+    ("/TOP[:n]", "opt-top"),
+    ("/DEFER[:n]", "opt-defer"),
+    ("/SWAP", "opt-swap"),
+    ("/SPLIT[:mb]", "opt-split"),
+    ("/REJOIN", "opt-rejoin"),
+    ("/ORDERED", "opt-ordered"),
+    ("/SYNC:NONE|FILE|DIR", "opt-sync"),
+    ("/MERGE:FIRST|LAST|NEWEST", "opt-merge"),
+    ("/EJECT", "opt-eject"),
+    ("/VERIFY", "opt-verify"),
+    ("/SAMPLE:pct|ENDS", "opt-sample"),
+    ("/BUF:kb", "opt-buf"),
+    ("/TUNED", "opt-tuned"),
+    ("/THROTTLE:mbps", "opt-throttle"),
+    ("/DPROFILE:name|NONE", "opt-dprofile"),
+    ("/DEEPPAUSE", "opt-deeppause"),
+    ("/NETPAUSE:METERED|VPN=name", "opt-netpause"),
+    ("/BATTERY:pct[:mbps]", "opt-battery"),
+    ("/IGNORE:kind[:pattern]", "opt-ignore"),
+    ("/SPECIAL:[kind=]policy", "opt-special"),
+    ("/LINKS:[kind=]policy", "opt-links"),
+    ("/IGNOREATTR", "opt-ignoreattr"),
+    ("/TIMETOL:seconds", "opt-timetol"),
+    ("/FFT", "opt-fft"),
+    ("/COMPARE:existence|size|time|hash", "opt-compare"),
+    ("/SIZEONLY", "opt-sizeonly"),
+    ("/CLOUD:policy", "opt-cloud"),
+    ("/REMAP:find=replace", "opt-remap"),
+    ("/REMAPRE:regex=replace", "opt-remapre"),
+    ("/ROUTE:patterns=folder", "opt-route"),
+    ("/FLAT[:SUFFIX|PREFIX]", "opt-flat"),
+    ("/CASE:LOWER|UPPER", "opt-case"),
+    ("/ABORTFAILS:n", "opt-abortfails"),
+    ("/ABORTFREE:mb", "opt-abortfree"),
+    ("/KEEPFREE:mb", "opt-keepfree"),
+    ("/ABORTSLOW:mbps[:min]", "opt-abortslow"),
+    ("/MAXENTRIES:n", "opt-maxentries"),
+    ("/MAXFILES:n", "opt-maxfiles"),
+];
+
+/// Destination and data size in MB of `bench <destination> [/SIZE:mb]`, if
+/// that is the command line
+pub fn parse_bench() -> Option<Result<(String, u64), String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("bench") {
+        return None;
+    }
+
+    let mut destination = None;
+    let mut size_mb = DEFAULT_BENCH_MB;
+    for arg in &args[2..] {
+        match arg.to_uppercase().strip_prefix("/SIZE:") {
+            Some(size) => match size.parse::<u64>() {
+                Ok(size) => size_mb = size,
+                Err(_) => return Some(Err(format!("Invalid benchmark size: {}", size))),
+            },
+            None if destination.is_none() => destination = Some(arg.clone()),
+            None => return Some(Err(format!("Unexpected argument: {}", arg))),
+        }
+    }
+    Some(
+        destination
+            .map(|destination| (destination, size_mb))
+            .ok_or_else(|| "Missing destination".to_string()),
+    )
+}
+
+/// Options of `test-pattern <pattern>... --against <dir> [flags]`, with
+/// the folder as the source, if that is the command line. The flags are
+/// those of a job, such as /S, /XF and /XD.
+pub fn parse_test_pattern() -> Option<Result<CopyOptions, String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("test-pattern") {
+        return None;
+    }
+
+    let mut dir = None;
+    // A source and destination for the job's parser, replaced below
+    let mut job_args = vec![".".to_string(), ".".to_string()];
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--against" {
+            match rest.next() {
+                Some(value) => dir = Some(value.clone()),
+                None => return Some(Err("Missing folder after --against".to_string())),
+            }
+        } else if let Some(value) = arg.strip_prefix("--against=") {
+            dir = Some(value.to_string());
+        } else {
+            job_args.push(arg.clone());
+        }
+    }
+    let Some(dir) = dir else {
+        return Some(Err("Missing --against <folder>".to_string()));
+    };
+    Some(CopyOptions::parse_args(job_args).map(|mut options| {
+        options.sources = vec![dir];
+        options.destination.clear();
+        options
+    }))
+}
+
+/// Options copying again the files a failure report lists, if the command
+/// line is `--retry-failed <report.json>`. The files still failing are
+/// written back to the report.
+pub fn parse_retry_failed() -> Option<Result<CopyOptions, String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("--retry-failed") {
+        return None;
+    }
+    let Some(path) = args.get(2) else {
+        return Some(Err(
+            "Missing failure report after --retry-failed".to_string()
+        ));
+    };
+    let report = match FailureReport::load(Path::new(path)) {
+        Ok(report) => report,
+        Err(e) => return Some(Err(format!("Cannot read {}: {}", path, e))),
+    };
+    if report.failures.is_empty() {
+        return Some(Err(tr!("retry-nothing", path = path.as_str())));
+    }
+    let mut options = report.retry_options();
+    options.failure_report = Some(path.clone());
+    Some(Ok(options))
+}
+
+/// Command of `skip-list add <path>... [/HASH] [/REASON:text]`,
+/// `skip-list remove <path|hash>...` or `skip-list show`, and the list it
+/// works on, another than the default with `/LIST:file`, if that is the
+/// command line
+pub fn parse_skip_list() -> Option<Result<(SkipListCommand, PathBuf), String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("skip-list") {
+        return None;
+    }
+    let Some(action) = args.get(2) else {
+        return Some(Err(
+            "Missing add, remove or show after skip-list".to_string()
+        ));
+    };
+
+    let mut path = SkipList::default_path();
+    let mut by_hash = false;
+    let mut reason = String::new();
+    let mut entries = Vec::new();
+    for arg in &args[3..] {
+        let upper_arg = arg.to_uppercase();
+        if upper_arg == "/HASH" {
+            by_hash = true;
+        } else if upper_arg.starts_with("/REASON:") {
+            reason = arg[8..].to_string();
+        } else if upper_arg.starts_with("/LIST:") {
+            path = PathBuf::from(&arg[6..]);
+        } else {
+            entries.push(arg.clone());
+        }
+    }
+    let command = match action.to_lowercase().as_str() {
+        "add" if !entries.is_empty() => SkipListCommand::Add {
+            paths: entries,
+            by_hash,
+            reason,
+        },
+        "remove" if !entries.is_empty() => SkipListCommand::Remove { keys: entries },
+        "show" if entries.is_empty() => SkipListCommand::Show,
+        "show" => return Some(Err(format!("Unexpected argument: {}", entries[0]))),
+        "add" | "remove" => return Some(Err(format!("Missing paths after skip-list {}", action))),
+        _ => return Some(Err(format!("Unknown skip-list command: {}", action))),
+    };
+    Some(Ok((command, path)))
+}
+
+/// Command of `quarantine show` or `quarantine release <path>...`, if
+/// that is the command line
+pub fn parse_quarantine() -> Option<Result<(QuarantineCommand, PathBuf), String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("quarantine") {
+        return None;
+    }
+    let command = match args.get(2).map(|action| action.to_lowercase()).as_deref() {
+        Some("show") if args.len() == 3 => QuarantineCommand::Show,
+        Some("show") => return Some(Err(format!("Unexpected argument: {}", args[3]))),
+        Some("release") if args.len() > 3 => QuarantineCommand::Release {
+            paths: args[3..].to_vec(),
+        },
+        Some("release") => return Some(Err("Missing paths after quarantine release".to_string())),
+        Some(action) => return Some(Err(format!("Unknown quarantine command: {}", action))),
+        None => return Some(Err("Missing show or release after quarantine".to_string())),
+    };
+    Some(Ok((command, Quarantine::default_path())))
+}
+
+/// Settings of `self-test [/ROUNDS:n] [/SEED:n] [/DIR:path]`, if that is
+/// the command line
+pub fn parse_self_test() -> Option<Result<SelfTest, String>> {
+    let args: Vec<String> = env::args().collect();
+    if !args.get(1)?.eq_ignore_ascii_case("self-test") {
+        return None;
+    }
+
+    let mut test = SelfTest::default();
+    for arg in &args[2..] {
+        let upper_arg = arg.to_uppercase();
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid number: {}", arg))
+        };
+        if let Some(rounds) = upper_arg.strip_prefix("/ROUNDS:") {
+            match number(rounds) {
+                Ok(rounds) => test.rounds = rounds as u32,
+                Err(e) => return Some(Err(e)),
+            }
+        } else if let Some(seed) = upper_arg.strip_prefix("/SEED:") {
+            match number(seed) {
+                Ok(seed) => test.seed = seed,
+                Err(e) => return Some(Err(e)),
+            }
+        } else if upper_arg.starts_with("/DIR:") {
+            test.dir = PathBuf::from(&arg[5..]);
+        } else {
+            return Some(Err(format!("Unexpected argument: {}", arg)));
+        }
+    }
+    Some(Ok(test))
 }
 
 pub fn print_usage(program_name: &str) {
+    println!("{}", tr!("usage-header", program = program_name));
+    println!("       {}", tr!("usage-estimate", program = program_name));
+    println!("       {}", tr!("usage-bench", program = program_name));
+    println!("       {}", tr!("usage-selftest", program = program_name));
     println!(
-        "Usage: {} <source> <destination> [<file_pattern>...] [options]",
-        program_name
+        "       {}",
+        tr!("usage-test-pattern", program = program_name)
     );
-    println!("Options:");
-    println!("  /S         - Copy subdirectories, but not empty ones");
-    println!("  /E         - Copy subdirectories, including empty ones");
-    println!("  /Z         - Copy files in restartable mode (slower but more robust)");
-    println!("  /B         - Copy files in Backup mode (overrides permissions)");
-    println!("  /PURGE     - Delete destination files/folders that no longer exist in source");
-    println!("  /MIR       - Mirror directory tree (like /PURGE plus all subdirectories)");
-    println!("  /MOV       - Move files (delete from source after copying)");
-    println!("  /MOVE      - Move files and directories (delete from source after copying)");
-    println!("  /A+:[RASHCNETO] - Add specified attributes to copied files");
-    println!("  /A-:[RASHCNETO] - Remove specified attributes from copied files");
-    println!("  /MT[:n]    - Multithreaded copying with n threads (default is 8)");
-    println!("  /R:n       - Number of retries on failed copies (default is 1 million)");
-    println!("  /W:n       - Wait time between retries in seconds (default is 30)");
-    println!("  /LOG:file  - Output log to file");
-    println!("  /L         - List only - don't copy, timestamp or delete any files");
-    println!("  /NP        - No progress - don't display % copied");
-    println!("  /NFL       - No file list - don't log file names");
-    println!("  /EMPTY     - Create empty (zero-byte) copies of files");
-    println!("  /CHILDONLY - Process only direct child folders of source path");
-    println!("  /SHRED     - Securely overwrite files before deletion");
+    println!(
+        "       {}",
+        tr!("usage-retry-failed", program = program_name)
+    );
+    println!("       {}", tr!("usage-skip-list", program = program_name));
+    println!("       {}", tr!("usage-quarantine", program = program_name));
+    println!("{}", tr!("usage-options"));
+    for (flag, id) in USAGE_OPTIONS {
+        println!("  {:<10} - {}", flag, tr!(id));
+    }
 }
@@ -0,0 +1,105 @@
+//! Files rbcp writes itself, kept out of the copy.
+//!
+//! A log file or scan index kept inside the destination would be purged by
+//! a mirror run, and one kept inside the source would be copied while it is
+//! being written. The same goes for rbcp's data folder and the scratch
+//! files of write probes and benchmarks. Every walk leaves these out, and
+//! the job log lists them so nothing disappears from a copy unexplained.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::args::CopyOptions;
+use crate::bitrot::HASH_MANIFEST_NAME;
+use crate::cloud::PLACEHOLDER_MANIFEST_NAME;
+use crate::links::LINK_MANIFEST_NAME;
+use crate::salvage::DEFAULT_DAMAGE_REPORT;
+use crate::status;
+use crate::utils::{data_dir, matches_pattern};
+
+/// Names of rbcp's scratch files and folders, wherever they are
+pub const ARTIFACT_PATTERNS: &[&str] = &[
+    ".rbcp-write-test-*.tmp",
+    ".rbcp-probe-*.tmp",
+    ".rbcp-bench-*",
+    ".rbcp-versions",
+    "*.rbcp-tmp",
+    HASH_MANIFEST_NAME,
+    LINK_MANIFEST_NAME,
+    PLACEHOLDER_MANIFEST_NAME,
+    DEFAULT_DAMAGE_REPORT,
+];
+
+/// Files and folders of rbcp found while walking sources and destinations
+#[derive(Debug, Default)]
+pub struct Artifacts {
+    /// Absolute paths of the job's own files
+    paths: Vec<PathBuf>,
+    /// File names of `paths`, checked before comparing whole paths
+    names: HashSet<OsString>,
+}
+
+impl Artifacts {
+    /// The log file, scan index, reports and data folder of a job with
+    /// `options`
+    pub fn new(options: &CopyOptions) -> Self {
+        let mut artifacts = Artifacts::default();
+        let own_files = [
+            &options.log_file,
+            &options.index_file,
+            &options.html_report,
+            &options.status_file,
+        ];
+        for path in own_files.into_iter().flatten() {
+            artifacts.add(Path::new(path));
+        }
+        if let Some(status) = &options.status_file {
+            artifacts.add(&status::temp_path(Path::new(status)));
+        }
+        if options.salvage {
+            artifacts.add(&options.damage_report_path(Path::new(&options.destination)));
+        }
+        artifacts.add(&data_dir());
+        artifacts
+    }
+
+    fn add(&mut self, path: &Path) {
+        let Some(name) = path.file_name() else {
+            return;
+        };
+        self.names.insert(name.to_os_string());
+        self.paths.push(absolute(path));
+    }
+
+    /// Whether the file or folder at `path` is one of rbcp's own
+    pub fn contains(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        if ARTIFACT_PATTERNS
+            .iter()
+            .any(|pattern| matches_pattern(&name.to_string_lossy(), pattern))
+        {
+            return true;
+        }
+        self.names.contains(name) && self.paths.contains(&absolute(path))
+    }
+
+    /// The excluded paths and name patterns, for the job log
+    pub fn describe(&self) -> Vec<String> {
+        self.paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .chain(ARTIFACT_PATTERNS.iter().map(|pattern| pattern.to_string()))
+            .collect()
+    }
+}
+
+/// `path` with links resolved where it exists, made absolute otherwise
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,81 @@
+//! Windows attributes of files and their copies.
+//!
+//! A copy whose size and time match its source is skipped, but the source
+//! may have been made read-only, hidden or system since. Those attributes
+//! take part in the comparison: a copy differing only in them gets the
+//! attributes of its source without its contents being copied again. The
+//! attributes that describe storage rather than the file are left out, so
+//! a file recalled from an archive tier, which keeps its size and time but
+//! loses its offline and recall attributes, is not copied again either.
+//! `/IGNOREATTR` leaves attribute-only differences alone. Elsewhere than on
+//! Windows files have no such attributes and nothing changes.
+
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+
+pub use rbcp_plan::COMPARED_ATTRIBUTES;
+
+/// Attributes of the file with `meta`, 0 where there are none
+pub fn of(meta: &Metadata) -> u32 {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        meta.file_attributes()
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = meta;
+        0
+    }
+}
+
+/// Give the file at `path` the compared attributes in `attributes`,
+/// keeping its others
+pub fn apply(path: &Path, attributes: u32) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetFileAttributesW(name: *const u16, attributes: u32) -> i32;
+        }
+
+        let current = of(&std::fs::metadata(path)?);
+        let wanted = (current & !COMPARED_ATTRIBUTES) | (attributes & COMPARED_ATTRIBUTES);
+        if wanted == current {
+            return Ok(());
+        }
+        let name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        if unsafe { SetFileAttributesW(name.as_ptr(), wanted) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (path, attributes);
+        Ok(())
+    }
+}
+
+/// Let the copy at `path` be overwritten if its attributes say read-only,
+/// as they do once they follow a read-only source
+pub fn clear_read_only(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+        match std::fs::metadata(path) {
+            Ok(meta) if of(&meta) & FILE_ATTRIBUTE_READONLY != 0 => {
+                apply(path, of(&meta) & !FILE_ATTRIBUTE_READONLY)
+            }
+            _ => Ok(()),
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Finding the fastest settings for a destination.
+//!
+//! `rbcp bench DEST` writes synthetic data to a scratch directory below
+//! DEST with different buffer sizes and thread counts and reports the
+//! throughput of each. Every file is synced before it is timed, so the
+//! figures are those of the device rather than of the write cache. Reads
+//! are not measured: data just written would be read back from the cache,
+//! and the source of a copy is a different device anyway.
+//!
+//! Buffer sizes are tried on one thread first, then thread counts with the
+//! best buffer, which takes a fraction of the time of trying every
+//! combination. The best settings are saved per destination
+//! and applied by jobs run with `/TUNED`.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::Utc;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::progress::ProgressCallback;
+use crate::tr;
+use crate::utils::data_dir;
+
+/// Buffer sizes tried, in KB
+pub const BUFFER_SIZES_KB: [usize; 5] = [64, 256, 1024, 4096, 16384];
+/// Thread counts tried
+pub const THREAD_COUNTS: [usize; 5] = [1, 2, 4, 8, 16];
+/// Data written per measurement when not set otherwise, in MB
+pub const DEFAULT_BENCH_MB: u64 = 256;
+
+/// Throughput of one combination of settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BenchSample {
+    pub threads: usize,
+    pub buffer_kb: usize,
+    /// Write throughput in MB/s
+    pub mbps: f64,
+}
+
+/// Settings that copied fastest to a destination
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tuning {
+    pub threads: usize,
+    pub buffer_kb: usize,
+    /// Throughput measured with these settings, in MB/s
+    pub mbps: f64,
+    /// Unix timestamp of the benchmark
+    pub measured_at: i64,
+}
+
+/// Benchmark of one destination
+pub struct Benchmark {
+    destination: PathBuf,
+    /// Data written per measurement, in bytes
+    bytes: u64,
+}
+
+impl Benchmark {
+    pub fn new(destination: &Path, size_mb: u64) -> Self {
+        Benchmark {
+            destination: destination.to_path_buf(),
+            bytes: size_mb.max(1) * 1024 * 1024,
+        }
+    }
+
+    /// Measure the settings, logging each result, and return the samples
+    /// with the best settings
+    pub fn run(&self, progress: &dyn ProgressCallback) -> io::Result<(Vec<BenchSample>, Tuning)> {
+        let scratch = self
+            .destination
+            .join(format!(".rbcp-bench-{}", std::process::id()));
+        fs::create_dir_all(&scratch)?;
+        let result = self.measure_all(&scratch, progress);
+        let _ = fs::remove_dir_all(&scratch);
+        let samples = result?;
+
+        let best = samples
+            .iter()
+            .max_by(|a, b| a.mbps.total_cmp(&b.mbps))
+            .copied()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::Interrupted))?;
+        Ok((
+            samples,
+            Tuning {
+                threads: best.threads,
+                buffer_kb: best.buffer_kb,
+                mbps: best.mbps,
+                measured_at: Utc::now().timestamp(),
+            },
+        ))
+    }
+
+    fn measure_all(
+        &self,
+        scratch: &Path,
+        progress: &dyn ProgressCallback,
+    ) -> io::Result<Vec<BenchSample>> {
+        let mut samples = Vec::new();
+        let mut measure = |threads, buffer_kb| -> io::Result<Option<BenchSample>> {
+            if progress.is_cancelled() {
+                return Ok(None);
+            }
+            let sample = self.measure(scratch, threads, buffer_kb)?;
+            progress.on_log(&tr!(
+                "bench-sample",
+                threads = threads,
+                buffer = buffer_kb,
+                speed = format!("{:.1}", sample.mbps)
+            ));
+            samples.push(sample);
+            Ok(Some(sample))
+        };
+
+        let mut best_buffer = BUFFER_SIZES_KB[0];
+        let mut best_mbps = 0.0;
+        for buffer_kb in BUFFER_SIZES_KB {
+            if let Some(sample) = measure(1, buffer_kb)? {
+                if sample.mbps > best_mbps {
+                    best_mbps = sample.mbps;
+                    best_buffer = buffer_kb;
+                }
+            }
+        }
+        for threads in THREAD_COUNTS.into_iter().skip(1) {
+            measure(threads, best_buffer)?;
+        }
+        Ok(samples)
+    }
+
+    /// Write the benchmark's bytes split over `threads` files at once, in
+    /// chunks of `buffer_kb`, and sync them
+    fn measure(&self, scratch: &Path, threads: usize, buffer_kb: usize) -> io::Result<BenchSample> {
+        let mut chunk = vec![0u8; buffer_kb * 1024];
+        rand::thread_rng().fill_bytes(&mut chunk);
+        let per_thread = self.bytes / threads as u64;
+
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|n| {
+                    let path = scratch.join(format!("bench-{}.tmp", n));
+                    let chunk = &chunk;
+                    scope.spawn(move || write_synced(&path, chunk, per_thread))
+                })
+                .collect();
+            workers.into_iter().try_for_each(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("benchmark thread panicked")))
+            })
+        })?;
+        let elapsed = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+        Ok(BenchSample {
+            threads,
+            buffer_kb,
+            mbps: (per_thread * threads as u64) as f64 / elapsed / 1_000_000.0,
+        })
+    }
+}
+
+/// Benchmark `destination`, logging the results, and save the best
+/// settings for `/TUNED` in the default store
+pub fn bench_destination(
+    destination: &Path,
+    size_mb: u64,
+    progress: &dyn ProgressCallback,
+) -> io::Result<Tuning> {
+    progress.on_log(&tr!(
+        "bench-start",
+        destination = destination.display().to_string(),
+        size = size_mb
+    ));
+    let (_, tuning) = Benchmark::new(destination, size_mb).run(progress)?;
+
+    let path = TuningStore::default_path();
+    let mut store = TuningStore::load(&path);
+    store.insert(destination, tuning);
+    store.save(&path)?;
+
+    progress.on_log(&tr!(
+        "bench-best",
+        threads = tuning.threads,
+        buffer = tuning.buffer_kb,
+        speed = format!("{:.1}", tuning.mbps)
+    ));
+    Ok(tuning)
+}
+
+/// Write `bytes` of `chunk` repeated to `path`, sync it and remove it
+fn write_synced(path: &Path, chunk: &[u8], bytes: u64) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    let mut remaining = bytes;
+    while remaining > 0 {
+        let len = remaining.min(chunk.len() as u64) as usize;
+        file.write_all(&chunk[..len])?;
+        remaining -= len as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+/// Best settings found by `rbcp bench`, by destination
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TuningStore {
+    pub destinations: BTreeMap<String, Tuning>,
+}
+
+impl TuningStore {
+    /// Default location of the store
+    pub fn default_path() -> PathBuf {
+        data_dir().join("tuning.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// Remember `tuning` for `destination`
+    pub fn insert(&mut self, destination: &Path, tuning: Tuning) {
+        self.destinations.insert(key(destination), tuning);
+    }
+
+    /// Settings for `destination`, from the benchmark of it or of the
+    /// closest directory above it, e.g. the root of the drive
+    pub fn find(&self, destination: &Path) -> Option<Tuning> {
+        let destination = PathBuf::from(key(destination));
+        destination
+            .ancestors()
+            .find_map(|dir| self.destinations.get(&*dir.to_string_lossy()))
+            .copied()
+    }
+}
+
+/// Key of a destination in the store, absolute where possible
+fn key(destination: &Path) -> String {
+    destination
+        .canonicalize()
+        .unwrap_or_else(|_| destination.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,227 @@
+//! Host of the scheduling service.
+//!
+//! Runs the job profiles of `rbcp_core::service::Service` until stopped:
+//! `rbcp-service [profiles-dir [status-file]]`, with the profiles directory
+//! and status file in the data directory by default. Under systemd or from
+//! a console SIGTERM or Ctrl+C stop it, cancelling a running job. Started
+//! by the Windows service control manager it reports its state there and
+//! stops when the service is stopped or the machine shuts down.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rbcp_core::profile::JobProfile;
+use rbcp_core::service::{Service, ServiceStatus};
+
+/// The service the command line asks for
+fn service() -> Service {
+    let mut args = std::env::args_os().skip(1).map(PathBuf::from);
+    let profiles_dir = args.next().unwrap_or_else(JobProfile::profiles_dir);
+    let status_path = args.next().unwrap_or_else(ServiceStatus::default_path);
+    Service::new(profiles_dir, status_path)
+}
+
+/// Run `service` until stopped, returning whether it ended without error
+fn run(service: &Service) -> bool {
+    match service.run() {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("rbcp-service: {}", e);
+            false
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    #[cfg(windows)]
+    if let Some(succeeded) = scm::dispatch() {
+        return if succeeded {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+
+    let service = service();
+    let stop = service.stop_handle();
+    if let Err(e) = ctrlc::set_handler(move || stop.cancel()) {
+        eprintln!("rbcp-service: Could not handle stop signals: {}", e);
+        return ExitCode::FAILURE;
+    }
+    if run(&service) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Running under the Windows service control manager
+#[cfg(windows)]
+mod scm {
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr::null_mut;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+
+    use rbcp_core::CancellationToken;
+
+    type Handle = *mut c_void;
+
+    /// Name passed to the control manager, which ignores it for a service
+    /// in its own process
+    const NAME: &str = "rbcp";
+    /// Time a running job is given to stop before the control manager
+    /// looks again
+    const STOP_WAIT_HINT_MS: u32 = 30_000;
+
+    const SERVICE_WIN32_OWN_PROCESS: u32 = 0x0000_0010;
+    const SERVICE_STOPPED: u32 = 1;
+    const SERVICE_STOP_PENDING: u32 = 3;
+    const SERVICE_RUNNING: u32 = 4;
+    const SERVICE_ACCEPT_STOP: u32 = 0x0000_0001;
+    const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x0000_0004;
+    const SERVICE_CONTROL_STOP: u32 = 1;
+    const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+    const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+    const NO_ERROR: u32 = 0;
+    const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+    const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
+    type ServiceMain = unsafe extern "system" fn(argc: u32, argv: *mut *mut u16);
+    type ControlHandler = unsafe extern "system" fn(
+        control: u32,
+        event: u32,
+        data: *mut c_void,
+        context: *mut c_void,
+    ) -> u32;
+
+    #[repr(C)]
+    struct ServiceTableEntry {
+        name: *mut u16,
+        main: Option<ServiceMain>,
+    }
+
+    #[repr(C)]
+    struct ServiceStatus {
+        service_type: u32,
+        current_state: u32,
+        controls_accepted: u32,
+        win32_exit_code: u32,
+        service_specific_exit_code: u32,
+        check_point: u32,
+        wait_hint: u32,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn StartServiceCtrlDispatcherW(table: *const ServiceTableEntry) -> i32;
+        fn RegisterServiceCtrlHandlerExW(
+            name: *const u16,
+            handler: ControlHandler,
+            context: *mut c_void,
+        ) -> Handle;
+        fn SetServiceStatus(handle: Handle, status: *const ServiceStatus) -> i32;
+    }
+
+    /// Stops the service, set once it started
+    static STOP: OnceLock<CancellationToken> = OnceLock::new();
+    /// Handle the state is reported to
+    static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+    static SUCCEEDED: AtomicBool = AtomicBool::new(false);
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    /// Run as a service if the control manager started the process,
+    /// returning whether it ended without error. None when started some
+    /// other way, e.g. from a console.
+    pub fn dispatch() -> Option<bool> {
+        let mut name = wide(NAME);
+        let table = [
+            ServiceTableEntry {
+                name: name.as_mut_ptr(),
+                main: Some(service_main),
+            },
+            ServiceTableEntry {
+                name: null_mut(),
+                main: None,
+            },
+        ];
+        // Returns once the service has stopped
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) {
+                return None;
+            }
+            eprintln!("rbcp-service: {}", error);
+            return Some(false);
+        }
+        Some(SUCCEEDED.load(Ordering::Relaxed))
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let service = super::service();
+        let _ = STOP.set(service.stop_handle());
+        let name = wide(NAME);
+        let handle = RegisterServiceCtrlHandlerExW(name.as_ptr(), control_handler, null_mut());
+        if handle.is_null() {
+            return;
+        }
+        STATUS_HANDLE.store(handle as usize, Ordering::Relaxed);
+
+        report(SERVICE_RUNNING, NO_ERROR);
+        let succeeded = super::run(&service);
+        SUCCEEDED.store(succeeded, Ordering::Relaxed);
+        let exit_code = if succeeded {
+            NO_ERROR
+        } else {
+            ERROR_SERVICE_SPECIFIC_ERROR
+        };
+        report(SERVICE_STOPPED, exit_code);
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event: u32,
+        _data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                if let Some(stop) = STOP.get() {
+                    stop.cancel();
+                }
+                report(SERVICE_STOP_PENDING, NO_ERROR);
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    /// Tell the control manager the service is in `state`
+    fn report(state: u32, exit_code: u32) {
+        let status = ServiceStatus {
+            service_type: SERVICE_WIN32_OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            win32_exit_code: exit_code,
+            service_specific_exit_code: u32::from(exit_code == ERROR_SERVICE_SPECIFIC_ERROR),
+            check_point: 0,
+            wait_hint: if state == SERVICE_STOP_PENDING {
+                STOP_WAIT_HINT_MS
+            } else {
+                0
+            },
+        };
+        let handle = STATUS_HANDLE.load(Ordering::Relaxed) as Handle;
+        unsafe { SetServiceStatus(handle, &status) };
+    }
+}
@@ -0,0 +1,93 @@
+//! Spotting copies that changed on their own.
+//!
+//! A file whose size and modification time are unchanged is skipped, which
+//! is right unless the disk flipped bits in it since: the skip then keeps
+//! the damage for good, and the next mirror copies it on. Runs with
+//! `/VERIFY` or `/BITROT` keep the hash of each copy in a manifest at the
+//! root of the destination, so it travels with the drive. `/BITROT` hashes
+//! the copies it would skip and flags those no longer matching their
+//! recorded hash although their size and time do; with `/BITROT:RECOPY`
+//! they are copied again from the source.
+
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::mtime_nanos;
+use crate::utils::replace_file;
+use crate::verify::Hash;
+
+/// Name of the manifest at the root of the destination
+pub const HASH_MANIFEST_NAME: &str = ".rbcp-hashes.json";
+
+/// Hash of a copy and the size and time it had when hashed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct HashRecord {
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    mtime: u64,
+    /// BLAKE3 hash in hex
+    hash: String,
+}
+
+/// Recorded hashes of the files below a destination
+pub struct HashManifest {
+    root: PathBuf,
+    /// Records by path relative to the root
+    records: Mutex<BTreeMap<String, HashRecord>>,
+}
+
+impl HashManifest {
+    /// The manifest of the destination at `root`, empty if it has none
+    pub fn load(root: &Path) -> Self {
+        let records = File::open(root.join(HASH_MANIFEST_NAME))
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        HashManifest {
+            root: root.to_path_buf(),
+            records: Mutex::new(records),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let records = self.records.lock().unwrap();
+        replace_file(&self.root.join(HASH_MANIFEST_NAME), |file| {
+            serde_json::to_writer(file, &*records).map_err(io::Error::other)
+        })
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Remember `hash` for the copy at `path`, `size` bytes long and
+    /// modified at `mtime` nanoseconds since the epoch
+    pub fn record(&self, path: &Path, size: u64, mtime: u64, hash: Hash) {
+        if let Some(key) = self.key(path) {
+            let record = HashRecord {
+                size,
+                mtime,
+                hash: hash.to_hex().to_string(),
+            };
+            self.records.lock().unwrap().insert(key, record);
+        }
+    }
+
+    /// The recorded hash of the copy at `path`, if it was taken when the
+    /// copy had the size and time of `meta`
+    pub fn expected(&self, path: &Path, meta: &Metadata) -> Option<Hash> {
+        let key = self.key(path)?;
+        let records = self.records.lock().unwrap();
+        let record = records.get(&key)?;
+        if record.size != meta.len() || record.mtime != mtime_nanos(meta) {
+            return None;
+        }
+        Hash::from_hex(&record.hash).ok()
+    }
+}
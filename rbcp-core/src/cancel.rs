@@ -0,0 +1,66 @@
+//! Stopping a job from outside the engine.
+//!
+//! A server running jobs for its clients wants a handle to cancel one,
+//! not a progress callback to write. A token is that handle: clones share
+//! one state, so the caller keeps a clone and hands another to
+//! `CopyEngine::new`. Cancelling stops the job the way the Cancel button
+//! of the GUI does. A deadline cancels it by itself once passed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    deadline: Mutex<Option<Instant>>,
+}
+
+/// Handle to cancel a job, shared by its clones
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token cancelled once `deadline` has passed
+    pub fn with_deadline(deadline: Instant) -> Self {
+        let token = Self::new();
+        token.set_deadline(Some(deadline));
+        token
+    }
+
+    /// A token cancelled `timeout` from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    /// Request cancellation of the job
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the job was cancelled or its deadline has passed
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed) || self.is_past_deadline()
+    }
+
+    /// Whether the deadline has passed, as opposed to a cancel request
+    pub fn is_past_deadline(&self) -> bool {
+        self.deadline()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.state.deadline.lock().unwrap()
+    }
+
+    /// Set or clear the deadline, for every clone of the token
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        *self.state.deadline.lock().unwrap() = deadline;
+    }
+}
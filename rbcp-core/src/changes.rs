@@ -0,0 +1,350 @@
+//! Change journal support for fast incremental runs, on Windows only.
+//!
+//! On NTFS volumes the USN change journal records every change made to the
+//! volume. Reading it from the position saved by the previous run gives the
+//! set of directories whose contents changed, including files modified in
+//! place, so the scan index knows exactly which listings must be re-read.
+//!
+//! Other platforms keep no such record for a later run to read: fanotify
+//! and inotify on Linux only tell a process watching while the changes
+//! happen, which a copy run is not. There, and where no journal is
+//! available (other filesystems or insufficient privileges), there is no
+//! cursor and the index falls back to directory timestamps.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Position in a volume's change journal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalCursor {
+    pub journal_id: u64,
+    pub next_usn: i64,
+}
+
+/// Directories changed since a journal position, as absolute paths
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    pub dirs: HashSet<String>,
+}
+
+/// Current end of the change journal of the volume containing `root`.
+/// Always `None` outside Windows.
+pub fn current_cursor(root: &Path) -> Option<JournalCursor> {
+    #[cfg(windows)]
+    {
+        usn::current_cursor(root)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = root;
+        None
+    }
+}
+
+/// Directories on the volume containing `root` that changed between
+/// `since` and `until`. Returns `None` if the journal cannot be read or no
+/// longer covers `since`.
+pub fn changes_between(
+    root: &Path,
+    since: &JournalCursor,
+    until: &JournalCursor,
+) -> Option<ChangeSet> {
+    #[cfg(windows)]
+    {
+        usn::changes_between(root, since, until)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (root, since, until);
+        None
+    }
+}
+
+#[cfg(windows)]
+mod usn {
+    use std::collections::HashSet;
+    use std::ffi::c_void;
+    use std::mem::size_of;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Path, Prefix};
+    use std::ptr::null_mut;
+
+    use super::{ChangeSet, JournalCursor};
+
+    type Handle = *mut c_void;
+
+    const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const FILE_SHARE_ALL: u32 = 0x1 | 0x2 | 0x4;
+    const OPEN_EXISTING: u32 = 3;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const FSCTL_QUERY_USN_JOURNAL: u32 = 0x0009_00F4;
+    const FSCTL_READ_USN_JOURNAL: u32 = 0x0009_00BB;
+    const FILE_ID_TYPE_FILE_ID: i32 = 0;
+    const VOLUME_NAME_DOS: u32 = 0;
+    const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct UsnJournalDataV0 {
+        usn_journal_id: u64,
+        first_usn: i64,
+        next_usn: i64,
+        lowest_valid_usn: i64,
+        max_usn: i64,
+        maximum_size: u64,
+        allocation_delta: u64,
+    }
+
+    #[repr(C)]
+    struct ReadUsnJournalDataV0 {
+        start_usn: i64,
+        reason_mask: u32,
+        return_only_on_close: u32,
+        timeout: u64,
+        bytes_to_wait_for: u64,
+        usn_journal_id: u64,
+    }
+
+    #[repr(C)]
+    struct UsnRecordV2Header {
+        record_length: u32,
+        major_version: u16,
+        minor_version: u16,
+        file_reference_number: u64,
+        parent_file_reference_number: u64,
+        usn: i64,
+    }
+
+    #[repr(C)]
+    struct FileIdDescriptor {
+        size: u32,
+        id_type: i32,
+        file_id: i64,
+        _extended: u64,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileW(
+            name: *const u16,
+            access: u32,
+            share: u32,
+            security: *mut c_void,
+            disposition: u32,
+            flags: u32,
+            template: Handle,
+        ) -> Handle;
+        fn DeviceIoControl(
+            device: Handle,
+            code: u32,
+            in_buffer: *const c_void,
+            in_size: u32,
+            out_buffer: *mut c_void,
+            out_size: u32,
+            returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn OpenFileById(
+            volume: Handle,
+            id: *const FileIdDescriptor,
+            access: u32,
+            share: u32,
+            security: *mut c_void,
+            flags: u32,
+        ) -> Handle;
+        fn GetFinalPathNameByHandleW(file: Handle, buffer: *mut u16, len: u32, flags: u32) -> u32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    /// Owned Win32 handle, closed on drop
+    struct OwnedHandle(Handle);
+
+    impl Drop for OwnedHandle {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    fn open_volume(root: &Path) -> Option<OwnedHandle> {
+        let absolute = std::path::absolute(root).ok()?;
+        let letter = match absolute.components().next()? {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter as char,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let name: Vec<u16> = std::ffi::OsStr::new(&format!("\\\\.\\{}:", letter))
+            .encode_wide()
+            .chain(Some(0))
+            .collect();
+        let handle = unsafe {
+            CreateFileW(
+                name.as_ptr(),
+                GENERIC_READ,
+                FILE_SHARE_ALL,
+                null_mut(),
+                OPEN_EXISTING,
+                0,
+                null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE || handle.is_null() {
+            None
+        } else {
+            Some(OwnedHandle(handle))
+        }
+    }
+
+    fn query_journal(volume: &OwnedHandle) -> Option<UsnJournalDataV0> {
+        let mut data = UsnJournalDataV0::default();
+        let mut returned = 0u32;
+        let ok = unsafe {
+            DeviceIoControl(
+                volume.0,
+                FSCTL_QUERY_USN_JOURNAL,
+                std::ptr::null(),
+                0,
+                &mut data as *mut _ as *mut c_void,
+                size_of::<UsnJournalDataV0>() as u32,
+                &mut returned,
+                null_mut(),
+            )
+        };
+        (ok != 0).then_some(data)
+    }
+
+    pub fn current_cursor(root: &Path) -> Option<JournalCursor> {
+        let volume = open_volume(root)?;
+        let data = query_journal(&volume)?;
+        Some(JournalCursor {
+            journal_id: data.usn_journal_id,
+            next_usn: data.next_usn,
+        })
+    }
+
+    pub fn changes_between(
+        root: &Path,
+        since: &JournalCursor,
+        until: &JournalCursor,
+    ) -> Option<ChangeSet> {
+        let volume = open_volume(root)?;
+        let data = query_journal(&volume)?;
+        if data.usn_journal_id != since.journal_id
+            || data.usn_journal_id != until.journal_id
+            || since.next_usn < data.lowest_valid_usn
+        {
+            return None;
+        }
+
+        // Collect the parent directory of every changed entry
+        let mut parents = HashSet::new();
+        let mut buffer = vec![0u64; READ_BUFFER_SIZE / size_of::<u64>()];
+        let mut request = ReadUsnJournalDataV0 {
+            start_usn: since.next_usn,
+            reason_mask: u32::MAX,
+            return_only_on_close: 0,
+            timeout: 0,
+            bytes_to_wait_for: 0,
+            usn_journal_id: since.journal_id,
+        };
+
+        while request.start_usn < until.next_usn {
+            let mut returned = 0u32;
+            let ok = unsafe {
+                DeviceIoControl(
+                    volume.0,
+                    FSCTL_READ_USN_JOURNAL,
+                    &request as *const _ as *const c_void,
+                    size_of::<ReadUsnJournalDataV0>() as u32,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    READ_BUFFER_SIZE as u32,
+                    &mut returned,
+                    null_mut(),
+                )
+            };
+            if ok == 0 {
+                return None;
+            }
+
+            let bytes = unsafe {
+                std::slice::from_raw_parts(buffer.as_ptr() as *const u8, returned as usize)
+            };
+            if bytes.len() <= size_of::<i64>() {
+                break;
+            }
+
+            let mut offset = size_of::<i64>();
+            while offset + size_of::<UsnRecordV2Header>() <= bytes.len() {
+                let header = unsafe { &*(bytes.as_ptr().add(offset) as *const UsnRecordV2Header) };
+                if header.record_length == 0 {
+                    break;
+                }
+                if header.usn >= until.next_usn {
+                    break;
+                }
+                if header.major_version == 2 {
+                    parents.insert(header.parent_file_reference_number);
+                }
+                offset += header.record_length as usize;
+            }
+
+            request.start_usn = i64::from_ne_bytes(bytes[..8].try_into().ok()?);
+        }
+
+        let mut changes = ChangeSet::default();
+        for parent in parents {
+            if let Some(path) = path_from_id(&volume, parent) {
+                changes.dirs.insert(path);
+            }
+        }
+        Some(changes)
+    }
+
+    /// Resolve a file reference number to an absolute path. Fails for
+    /// entries that no longer exist.
+    fn path_from_id(volume: &OwnedHandle, id: u64) -> Option<String> {
+        let descriptor = FileIdDescriptor {
+            size: size_of::<FileIdDescriptor>() as u32,
+            id_type: FILE_ID_TYPE_FILE_ID,
+            file_id: id as i64,
+            _extended: 0,
+        };
+        let handle = unsafe {
+            OpenFileById(
+                volume.0,
+                &descriptor,
+                0,
+                FILE_SHARE_ALL,
+                null_mut(),
+                FILE_FLAG_BACKUP_SEMANTICS,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE || handle.is_null() {
+            return None;
+        }
+        let handle = OwnedHandle(handle);
+
+        let mut buffer = vec![0u16; 32 * 1024];
+        let len = unsafe {
+            GetFinalPathNameByHandleW(
+                handle.0,
+                buffer.as_mut_ptr(),
+                buffer.len() as u32,
+                VOLUME_NAME_DOS,
+            )
+        } as usize;
+        if len == 0 || len >= buffer.len() {
+            return None;
+        }
+
+        let path = String::from_utf16_lossy(&buffer[..len]);
+        Some(path.strip_prefix(r"\\?\").unwrap_or(&path).to_string())
+    }
+}
@@ -0,0 +1,242 @@
+//! Restart journal for resuming interrupted jobs.
+//!
+//! A named job writes a checkpoint file while it runs. The first line holds
+//! the job's options, without credentials; every following line records a
+//! finished source file or the offset reached in a large file that is still
+//! being copied, with the size and time the source had. Offsets are synced
+//! to disk as they are written, after the bytes before them, while finished
+//! files are not: one lost in a crash is only compared again. After a crash or reboot the job can be
+//! resumed from the checkpoint: finished files are not evaluated again and
+//! partially copied files continue from the last recorded offset, unless
+//! the source changed since, when they start over. The checkpoint is
+//! removed once the job completes.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::index::mtime_nanos;
+use crate::utils::data_dir;
+
+/// Bytes copied between offset records for a file in progress
+pub const CHECKPOINT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Done {
+        path: String,
+    },
+    Partial {
+        path: String,
+        offset: u64,
+        /// Size and modification time of the source when it was recorded
+        #[serde(default)]
+        size: u64,
+        #[serde(default)]
+        mtime: u64,
+    },
+}
+
+/// Where a file in progress got to, and the source it was copied from
+#[derive(Clone, Copy)]
+struct Progress {
+    offset: u64,
+    size: u64,
+    mtime: u64,
+}
+
+/// Checkpoint of a running job
+pub struct Checkpoint {
+    path: PathBuf,
+    file: File,
+    /// Held while a record is written, so records do not interleave
+    writing: Mutex<()>,
+    completed: HashSet<String>,
+    partial: HashMap<String, Progress>,
+}
+
+impl Checkpoint {
+    /// Directory holding the checkpoints of all jobs
+    pub fn jobs_dir() -> PathBuf {
+        data_dir().join("jobs")
+    }
+
+    /// Check that `job` names a checkpoint in [`Checkpoint::jobs_dir`] and
+    /// not a path elsewhere
+    pub fn check_name(job: &str) -> Result<(), String> {
+        let invalid = job.is_empty()
+            || job.contains("..")
+            || job.contains(['/', '\\', ':'])
+            || Path::new(job).is_absolute();
+        if invalid {
+            return Err(format!(
+                "Invalid job name: {} (no folders or \"..\" allowed)",
+                job
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checkpoint file of the job `job`
+    pub fn path_for(job: &str) -> io::Result<PathBuf> {
+        Self::check_name(job).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Self::jobs_dir().join(format!("{}.journal", job)))
+    }
+
+    /// Name of the most recently interrupted job, if any
+    pub fn last_job() -> Option<String> {
+        fs::read_dir(Self::jobs_dir())
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "journal"))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .max_by_key(|(modified, _)| *modified)
+            .and_then(|(_, path)| Some(path.file_stem()?.to_string_lossy().to_string()))
+    }
+
+    /// Read the options an interrupted job was started with
+    pub fn load_options(job: &str) -> io::Result<CopyOptions> {
+        let file = File::open(Self::path_for(job)?)?;
+        let mut header = String::new();
+        BufReader::new(file).read_line(&mut header)?;
+        serde_json::from_str(&header).map_err(io::Error::other)
+    }
+
+    /// Start a fresh checkpoint for `job`, discarding any previous one
+    pub fn create(job: &str, options: &CopyOptions) -> io::Result<Self> {
+        let path = Self::path_for(job)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The mail password stays out of the file. Only `/RESUME` reads the
+        // options back, and the command line cannot set one anyway.
+        let mut header = options.clone();
+        if let Some(smtp) = header.notify.as_mut().and_then(|n| n.smtp.as_mut()) {
+            smtp.password = None;
+        }
+        let mut file = File::create(&path)?;
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&header).map_err(io::Error::other)?
+        )?;
+        file.sync_data()?;
+
+        Ok(Self {
+            path,
+            file,
+            writing: Mutex::new(()),
+            completed: HashSet::new(),
+            partial: HashMap::new(),
+        })
+    }
+
+    /// Reopen the checkpoint of an interrupted job and continue recording
+    pub fn resume(job: &str) -> io::Result<Self> {
+        let path = Self::path_for(job)?;
+        let mut completed = HashSet::new();
+        let mut partial = HashMap::new();
+
+        // Skip the options header. A torn last line from a crash is ignored.
+        for line in BufReader::new(File::open(&path)?).lines().skip(1) {
+            match serde_json::from_str::<Record>(&line?) {
+                Ok(Record::Done { path }) => {
+                    partial.remove(&path);
+                    completed.insert(path);
+                }
+                Ok(Record::Partial {
+                    path,
+                    offset,
+                    size,
+                    mtime,
+                }) => {
+                    partial.insert(
+                        path,
+                        Progress {
+                            offset,
+                            size,
+                            mtime,
+                        },
+                    );
+                }
+                Err(_) => {}
+            }
+        }
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            writing: Mutex::new(()),
+            completed,
+            partial,
+        })
+    }
+
+    /// Whether `src` was finished in a previous attempt of this job
+    pub fn is_completed(&self, src: &Path) -> bool {
+        self.completed.contains(&*src.to_string_lossy())
+    }
+
+    /// Whether a previous attempt of this job was copying `src` when it
+    /// stopped
+    pub fn was_interrupted(&self, src: &Path) -> bool {
+        self.partial.contains_key(&*src.to_string_lossy())
+    }
+
+    /// Offset reached in `src` by a previous attempt of this job, if the
+    /// source still has the size and time in `source` it had then
+    pub fn resume_offset(&self, src: &Path, source: &Metadata) -> Option<u64> {
+        self.partial
+            .get(&*src.to_string_lossy())
+            .filter(|progress| {
+                progress.size == source.len() && progress.mtime == mtime_nanos(source)
+            })
+            .map(|progress| progress.offset)
+    }
+
+    pub fn mark_completed(&self, src: &Path) {
+        self.append(&Record::Done {
+            path: src.to_string_lossy().to_string(),
+        });
+    }
+
+    /// Record `offset` as reached in `src`, whose metadata is `source`
+    pub fn mark_partial(&self, src: &Path, offset: u64, source: &Metadata) {
+        self.append(&Record::Partial {
+            path: src.to_string_lossy().to_string(),
+            offset,
+            size: source.len(),
+            mtime: mtime_nanos(source),
+        });
+    }
+
+    /// Remove the checkpoint once the job has completed
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)
+    }
+
+    fn append(&self, record: &Record) {
+        let Ok(mut line) = serde_json::to_string(record) else {
+            return;
+        };
+        line.push('\n');
+        let written = {
+            let _writing = self.writing.lock().unwrap_or_else(|e| e.into_inner());
+            (&self.file).write_all(line.as_bytes())
+        };
+        // An offset lost in a crash would resume from a stale one. Synced
+        // outside the lock, so other files need not wait for the disk.
+        if written.is_ok() && matches!(record, Record::Partial { .. }) {
+            let _ = self.file.sync_data();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Cloud placeholders left by OneDrive, Dropbox and the like.
+//!
+//! A synced folder holds many files only as placeholders: the name, size
+//! and times are on disk but the contents are in the cloud, fetched when
+//! the file is read. Copying such a folder as it is downloads every file,
+//! which may be hundreds of gigabytes the disk never held. Placeholders are
+//! recognised by their recall attributes on Windows
+//! (`FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS` and `_ON_OPEN`) and as dataless
+//! files on macOS, without touching their contents. `/CLOUD:policy` decides
+//! what happens to one that would be copied: `skip` (the default) leaves it
+//! out, `metadata` lists its size and time in `.rbcp-placeholders.json` at
+//! the root of the destination without creating the file, and `hydrate`
+//! downloads and copies it like any file. Either way placeholders are
+//! counted in the summary, and copies already up to date are skipped
+//! without downloading anything.
+
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::mtime_nanos;
+use crate::utils::replace_file;
+
+/// Name of the list of placeholders at the root of the destination
+pub const PLACEHOLDER_MANIFEST_NAME: &str = ".rbcp-placeholders.json";
+
+/// What to do with a cloud placeholder that would be copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceholderPolicy {
+    /// Leave it out of the copy
+    #[default]
+    Skip,
+    /// List its size and time at the destination instead of copying it
+    Metadata,
+    /// Download its contents and copy it
+    Hydrate,
+}
+
+impl PlaceholderPolicy {
+    pub const ALL: [PlaceholderPolicy; 3] = [
+        PlaceholderPolicy::Skip,
+        PlaceholderPolicy::Metadata,
+        PlaceholderPolicy::Hydrate,
+    ];
+
+    /// Name used in the `/CLOUD` option
+    pub fn name(self) -> &'static str {
+        match self {
+            PlaceholderPolicy::Skip => "skip",
+            PlaceholderPolicy::Metadata => "metadata",
+            PlaceholderPolicy::Hydrate => "hydrate",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown cloud placeholder policy: {} (expected skip, metadata or hydrate)",
+                    name
+                )
+            })
+    }
+}
+
+/// Whether the file with `meta` is a placeholder whose contents are not on
+/// disk
+pub fn is_placeholder(meta: &Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x0004_0000;
+        const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+        meta.file_attributes()
+            & (FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+            != 0
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        const SF_DATALESS: u32 = 0x4000_0000;
+        meta.st_flags() & SF_DATALESS != 0
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = meta;
+        false
+    }
+}
+
+/// Size and time of a placeholder, as listed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PlaceholderRecord {
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    mtime: u64,
+}
+
+/// Placeholders listed below a destination with `/CLOUD:metadata`
+pub struct PlaceholderList {
+    root: PathBuf,
+    /// Records by path relative to the root
+    records: Mutex<BTreeMap<String, PlaceholderRecord>>,
+}
+
+impl PlaceholderList {
+    /// The list of the destination at `root`, empty if it has none
+    pub fn load(root: &Path) -> Self {
+        let records = File::open(root.join(PLACEHOLDER_MANIFEST_NAME))
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        PlaceholderList {
+            root: root.to_path_buf(),
+            records: Mutex::new(records),
+        }
+    }
+
+    /// Write the list, removing it once nothing is listed
+    pub fn save(&self) -> io::Result<()> {
+        let records = self.records.lock().unwrap();
+        let path = self.root.join(PLACEHOLDER_MANIFEST_NAME);
+        if records.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        replace_file(&path, |file| {
+            serde_json::to_writer_pretty(file, &*records).map_err(io::Error::other)
+        })
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// List the placeholder with `meta` whose copy would go to `path`
+    pub fn record(&self, path: &Path, meta: &Metadata) {
+        if let Some(key) = self.key(path) {
+            let record = PlaceholderRecord {
+                size: meta.len(),
+                mtime: mtime_nanos(meta),
+            };
+            self.records.lock().unwrap().insert(key, record);
+        }
+    }
+
+    /// Drop the file copied to `path` from the list, if it was listed
+    pub fn remove(&self, path: &Path) {
+        if let Some(key) = self.key(path) {
+            self.records.lock().unwrap().remove(&key);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Files a job would find already in the destination.
+//!
+//! Before a copy starts the GUI lists the source files whose destination
+//! exists, sorted by which copy is newer, so they can be overwritten or
+//! skipped one by one or a group at a time instead of all in one answer.
+//! The choices travel with the job as `conflict_actions`, keyed by source
+//! file, and win over the usual comparison of size and time for those
+//! files; files not listed are compared as always.
+
+use std::cmp::Ordering;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use rbcp_plan::{compare, CompareOptions, FileChange, FileState};
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::cycles::Ancestors;
+use crate::index::mtime_nanos;
+use crate::remap::{self, PathMapper};
+
+/// Which copy of a conflicting file is newer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    SourceNewer,
+    DestinationNewer,
+    /// Same modification time, whatever the sizes
+    Same,
+}
+
+/// What happens to one existing destination file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictAction {
+    Overwrite,
+    Skip,
+}
+
+/// A source file whose destination already exists
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Path below its source, with `/` between names
+    pub path: String,
+    pub kind: ConflictKind,
+    pub source_size: u64,
+    pub destination_size: u64,
+    /// Modification times in milliseconds since 1970
+    pub source_modified: u64,
+    pub destination_modified: u64,
+    /// What the job does without a choice
+    pub default_action: ConflictAction,
+}
+
+/// The conflicts of a job, up to a limit
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConflictList {
+    pub conflicts: Vec<Conflict>,
+    /// Whether the listing stopped at the limit with files left
+    pub truncated: bool,
+}
+
+/// List up to `limit` source files of `options` whose destination exists,
+/// applying its filters as the copy does
+pub fn find_conflicts(options: &CopyOptions, limit: usize) -> Result<ConflictList, String> {
+    let mut list = ConflictList::default();
+    let destination = Path::new(&options.destination);
+    if !destination.exists() {
+        return Ok(list);
+    }
+    let paths = PathMapper::new(destination, options)?;
+
+    // Flattened names as the job gives them out
+    if options.flatten.is_some() {
+        let mut files = Vec::new();
+        for source in &options.sources {
+            let source = Path::new(source);
+            let root = paths.source_root(source);
+            if source.is_file() {
+                files.push(root.join(source.file_name().unwrap_or_default()));
+            } else {
+                remap::list_files(source, &root, 0, options, None, &mut files);
+            }
+        }
+        paths.claim_in_order(files);
+    }
+
+    for source in &options.sources {
+        let source = Path::new(source);
+        let root = paths.source_root(source);
+        if source.is_file() {
+            let name = source.file_name().unwrap_or_default();
+            let target = paths.map_file(root.join(name));
+            add_conflict(&mut list, source, source, target, options, limit);
+        } else {
+            list_dir(
+                &mut list, source, source, &root, options, &paths, 0, None, limit,
+            );
+        }
+        if list.truncated {
+            break;
+        }
+    }
+    Ok(list)
+}
+
+/// Add the conflicts below `dir`, `depth` levels below `source`, as it
+/// would be copied to `destination`
+#[allow(clippy::too_many_arguments)]
+fn list_dir(
+    list: &mut ConflictList,
+    dir: &Path,
+    source: &Path,
+    destination: &Path,
+    options: &CopyOptions,
+    paths: &PathMapper,
+    depth: usize,
+    parent: Option<&Ancestors>,
+    limit: usize,
+) {
+    // The walk reports link cycles, listing just stops at them
+    let Ok(here) = Ancestors::enter(dir, parent, None) else {
+        return;
+    };
+    let parent = here.as_ref().or(parent);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if list.truncated {
+            return;
+        }
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() {
+            if options.descends_below(depth) && !options.excludes_dir(&path) {
+                list_dir(
+                    list,
+                    &path,
+                    source,
+                    &destination.join(&name),
+                    options,
+                    paths,
+                    depth + 1,
+                    parent,
+                    limit,
+                );
+            }
+        } else if path.is_file() && options.selects_file(&name.to_string_lossy()) {
+            let target = paths.map_file(destination.join(&name));
+            add_conflict(list, &path, source, target, options, limit);
+        }
+    }
+}
+
+fn add_conflict(
+    list: &mut ConflictList,
+    path: &Path,
+    source: &Path,
+    destination: PathBuf,
+    options: &CopyOptions,
+    limit: usize,
+) {
+    let (Ok(src_meta), Ok(dst_meta)) = (fs::metadata(path), fs::metadata(&destination)) else {
+        return;
+    };
+    if !dst_meta.is_file() {
+        return;
+    }
+    if list.conflicts.len() >= limit {
+        list.truncated = true;
+        return;
+    }
+
+    let (src_time, dst_time) = (mtime_nanos(&src_meta), mtime_nanos(&dst_meta));
+    let kind = match src_time.cmp(&dst_time) {
+        Ordering::Greater => ConflictKind::SourceNewer,
+        Ordering::Less => ConflictKind::DestinationNewer,
+        Ordering::Equal => ConflictKind::Same,
+    };
+    let state = |meta: &Metadata| FileState {
+        size: meta.len(),
+        mtime: mtime_nanos(meta),
+        ..Default::default()
+    };
+    let comparison = CompareOptions {
+        force_overwrite: false,
+        ignore_attributes: true,
+        ..options.comparison()
+    };
+    let change = compare(&state(&src_meta), Some(&state(&dst_meta)), &comparison);
+    let default_action = if change == FileChange::Contents {
+        ConflictAction::Overwrite
+    } else {
+        ConflictAction::Skip
+    };
+    let relative = path
+        .strip_prefix(source)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new(path.file_name().unwrap_or_default()));
+    list.conflicts.push(Conflict {
+        source: path.to_path_buf(),
+        destination,
+        path: relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        kind,
+        source_size: src_meta.len(),
+        destination_size: dst_meta.len(),
+        source_modified: src_time / 1_000_000,
+        destination_modified: dst_time / 1_000_000,
+        default_action,
+    });
+}
@@ -0,0 +1,191 @@
+//! Choosing how many files are copied at once.
+//!
+//! The best thread count depends on the destination: a network share
+//! answers metadata requests faster the more are in flight, while a single
+//! hard disk slows down as soon as two files compete for its head. With
+//! `/MT:AUTO` the thread pool is sized for the most threads allowed, and a
+//! limit on the files handled at once starts low and is moved up or down
+//! every few seconds. Throughput going up keeps the limit moving the same
+//! way, throughput going down reverses it, and retries or failures halve
+//! it, as they are the first sign of an overloaded server.
+//!
+//! Fixed limits can also be set apart for file contents and for metadata
+//! calls: a NAS may answer 64 stats at once yet slow down with more than 4
+//! streams, which one thread count cannot express.
+
+use std::cell::RefCell;
+use std::sync::atomic::Ordering;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stats::Statistics;
+
+/// Files handled at once when an automatic job starts
+pub const AUTO_START_THREADS: usize = 4;
+/// Size of the thread pool of an automatic job, the highest limit allowed
+pub const AUTO_MAX_THREADS: usize = 32;
+/// Time between adjustments of the limit
+pub const ADJUST_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Opening, checking and closing a file costs about as much time as copying
+/// this many bytes, so many small files and few large ones compare fairly
+const FILE_COST_BYTES: u64 = 256 * 1024;
+
+/// Throughput changes smaller than this fraction are noise
+const TOLERANCE: f64 = 0.1;
+
+thread_local! {
+    /// Limits this thread holds a permit of, by address. Hashing a copy for
+    /// /VERIFY runs on the pool, so a thread holding a permit may pick up
+    /// another file while it waits, which must not wait for the same
+    /// permit in turn.
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Caps the number of files handled at once below the size of the pool
+pub struct ConcurrencyLimit {
+    /// Current limit and number of files being handled
+    state: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(limit: usize) -> Self {
+        ConcurrencyLimit {
+            state: Mutex::new((limit.max(1), 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().0
+    }
+
+    pub fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().0 = limit.max(1);
+        self.released.notify_all();
+    }
+
+    /// Wait until a file may be handled. The slot is given back when the
+    /// permit is dropped.
+    pub fn acquire(&self) -> Permit<'_> {
+        if HELD.with(|held| held.borrow().contains(&self.address())) {
+            return Permit { limit: None };
+        }
+        let mut state = self.state.lock().unwrap();
+        while state.1 >= state.0 {
+            state = self.released.wait(state).unwrap();
+        }
+        state.1 += 1;
+        HELD.with(|held| held.borrow_mut().push(self.address()));
+        Permit { limit: Some(self) }
+    }
+
+    fn address(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+/// A slot of a [`ConcurrencyLimit`], released when dropped
+pub struct Permit<'a> {
+    /// None for a permit nested in another on the same thread
+    limit: Option<&'a ConcurrencyLimit>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(limit) = self.limit {
+            HELD.with(|held| held.borrow_mut().retain(|&a| a != limit.address()));
+            limit.state.lock().unwrap().1 -= 1;
+            limit.released.notify_one();
+        }
+    }
+}
+
+/// Moves a concurrency limit towards the one copying fastest
+pub struct ThreadController {
+    max: usize,
+    last_check: Instant,
+    last_work: u64,
+    last_errors: u64,
+    /// Throughput of the previous interval, in bytes per second
+    last_rate: Option<f64>,
+    /// Whether the last change added threads
+    increasing: bool,
+}
+
+impl ThreadController {
+    pub fn new(max: usize, stats: &Statistics) -> Self {
+        ThreadController {
+            max,
+            last_check: Instant::now(),
+            last_work: work_done(stats),
+            last_errors: errors(stats),
+            last_rate: None,
+            increasing: true,
+        }
+    }
+
+    /// Forget the last interval, after time that says nothing about
+    /// throughput such as a pause
+    pub fn reset(&mut self, stats: &Statistics) {
+        *self = ThreadController::new(self.max, stats);
+    }
+
+    /// The limit to use from now on given the current one, from the work
+    /// done and the errors met since the last call
+    pub fn adjust(&mut self, current: usize, stats: &Statistics) -> usize {
+        let work = work_done(stats);
+        let errors = errors(stats);
+        let elapsed = self.last_check.elapsed().as_secs_f64().max(f64::EPSILON);
+        let rate = work.saturating_sub(self.last_work) as f64 / elapsed;
+        let new_errors = errors > self.last_errors;
+        self.last_check = Instant::now();
+        self.last_work = work;
+        self.last_errors = errors;
+
+        if new_errors {
+            // Back off, then probe upwards again from there
+            self.last_rate = None;
+            self.increasing = true;
+            return (current / 2).max(1);
+        }
+        // Nothing was copied, e.g. while walking up-to-date directories
+        if rate == 0.0 {
+            return current;
+        }
+
+        let previous = self.last_rate.replace(rate);
+        match previous {
+            Some(previous) if rate < previous * (1.0 - TOLERANCE) => {
+                self.increasing = !self.increasing;
+            }
+            // More threads that do not help only add contention
+            Some(previous) if rate <= previous * (1.0 + TOLERANCE) => {
+                if !self.increasing {
+                    return current;
+                }
+                self.increasing = false;
+            }
+            _ => {}
+        }
+
+        let step = (current / 4).max(1);
+        if self.increasing {
+            (current + step).min(self.max)
+        } else {
+            current.saturating_sub(step).max(1)
+        }
+    }
+}
+
+/// Bytes copied plus the cost of the files handled so far
+fn work_done(stats: &Statistics) -> u64 {
+    let files =
+        stats.files_copied.load(Ordering::Relaxed) + stats.files_skipped.load(Ordering::Relaxed);
+    stats.bytes_copied.load(Ordering::Relaxed) + files as u64 * FILE_COST_BYTES
+}
+
+fn errors(stats: &Statistics) -> u64 {
+    (stats.retries.load(Ordering::Relaxed) + stats.files_failed.load(Ordering::Relaxed)) as u64
+}
@@ -0,0 +1,89 @@
+//! Changing the speed of a running job.
+//!
+//! A copy started at full speed may have to make room an hour later, when
+//! a video call starts on the same link. Each engine hands out its
+//! [`JobControls`], through which the bandwidth cap and the number of
+//! files copied at once can be changed while it runs: the cap holds from
+//! the next chunk written, the file count from the next file started.
+//! Setting the file count by hand ends `/MT:AUTO` for the rest of the job.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::args::CopyOptions;
+use crate::congestion::{ConcurrencyLimit, AUTO_START_THREADS};
+use crate::throttle::Throttle;
+use crate::tr;
+
+/// Bandwidth cap and files at once of a running job
+pub struct JobControls {
+    throttle: Throttle,
+    limit: ConcurrencyLimit,
+    /// Size of the job's thread pool, above which a limit does nothing
+    max_threads: usize,
+    /// Whether the limit is still moved by /MT:AUTO
+    auto: AtomicBool,
+}
+
+impl JobControls {
+    pub(crate) fn new(options: &CopyOptions) -> Self {
+        let threads = if options.auto_threads {
+            AUTO_START_THREADS.min(options.threads)
+        } else {
+            options.threads
+        };
+        JobControls {
+            throttle: Throttle::new(options.throttle_mbps),
+            limit: ConcurrencyLimit::new(threads),
+            max_threads: options.threads.max(1),
+            auto: AtomicBool::new(options.auto_threads),
+        }
+    }
+
+    /// Bandwidth cap in MB/s set for the job, 0 for none
+    pub fn throttle_mbps(&self) -> f64 {
+        self.throttle.mbps()
+    }
+
+    /// Cap the job at `mbps` MB/s, 0 lifting the cap
+    pub fn set_throttle_mbps(&self, mbps: f64) -> Result<(), String> {
+        if !(mbps >= 0.0 && mbps.is_finite()) {
+            return Err(tr!("filter-invalid-throttle"));
+        }
+        self.throttle.set_mbps(mbps);
+        Ok(())
+    }
+
+    /// Files copied at once
+    pub fn threads(&self) -> usize {
+        self.limit.limit()
+    }
+
+    /// Most files the job can copy at once
+    pub fn max_threads(&self) -> usize {
+        self.max_threads
+    }
+
+    /// Copy at most `threads` files at once from now on, no more than
+    /// [`max_threads`](Self::max_threads)
+    pub fn set_threads(&self, threads: usize) -> Result<(), String> {
+        if threads == 0 {
+            return Err(tr!("filter-invalid-threads"));
+        }
+        self.auto.store(false, Ordering::Relaxed);
+        self.limit.set_limit(threads.min(self.max_threads));
+        Ok(())
+    }
+
+    /// Whether /MT:AUTO still picks the number of files at once
+    pub fn is_auto(&self) -> bool {
+        self.auto.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
+    pub(crate) fn limit(&self) -> &ConcurrencyLimit {
+        &self.limit
+    }
+}
@@ -1,24 +1,258 @@
 use rayon::prelude::*;
-use std::collections::HashSet;
-use std::fs::{self, File, Metadata};
-use std::io::{self, Read, Write};
-use std::path::Path;
+use rbcp_plan::{compare, CompareStrategy, FileChange, FileState};
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::abort::FreeSpaceReserve;
 use crate::args::CopyOptions;
+use crate::artifacts::Artifacts;
+use crate::attributes;
+use crate::bitrot::HashManifest;
+use crate::checkpoint::{Checkpoint, CHECKPOINT_INTERVAL};
+use crate::cloud::{self, PlaceholderList, PlaceholderPolicy};
+use crate::conflicts::ConflictAction;
+use crate::congestion::{ConcurrencyLimit, Permit};
+use crate::content::{ContentFilter, Verdict};
+use crate::cycles::{self, Ancestors, DirId};
+use crate::deadline::TimedReader;
+use crate::durability::{self, Durability};
+use crate::index::{mtime_nanos, system_time_nanos, DirRecord, FileRecord, ScanIndex};
+use crate::limits::{LimitGuard, LimitWarning};
+use crate::links::{self, LinkKind, LinkPolicy, Links};
+use crate::locks::lock_holders;
+use crate::merge::MergePlan;
+use crate::owners::{self, OwnerCopier};
 use crate::progress::{ProgressCallback, ProgressInfo, ProgressState};
+use crate::quarantine::QuarantineSet;
+use crate::remap::PathMapper;
+use crate::report::{ActionKind, ActionLog};
+use crate::salvage::{self, DamageReport, SalvageReader};
+use crate::skiplist::SkipMatcher;
+use crate::special::{SpecialKind, SpecialPolicy};
+use crate::split::{self, SplitManifest};
 use crate::stats::Statistics;
-use crate::utils::{matches_pattern, secure_remove_dir_all, securely_delete_file, Logger};
+use crate::swap;
+#[cfg(feature = "testing")]
+use crate::testing::{FaultOp, FaultPlan};
+use crate::throttle::Throttle;
+use crate::tr;
+use crate::utils::{
+    is_locked_error, is_transient_error, print_null_separated, secure_remove_dir_all,
+    securely_delete_file, Logger, PathContext,
+};
+use crate::verify::{self, Hash, Hasher, Sampling};
+
+/// Data left to write out at the end of a file below which the finalizing
+/// state is not reported, so small files do not make the state flicker
+const FINALIZE_REPORT_BYTES: u64 = 1024 * 1024;
+
+/// Source and destination of a file left for a later pass
+pub type DeferredFile = (PathBuf, PathBuf);
+
+/// Shared state of a copy run, passed down the directory tree
+#[derive(Clone, Copy)]
+pub struct CopyContext<'a> {
+    pub options: &'a CopyOptions,
+    pub logger: &'a Logger,
+    pub stats: &'a Statistics,
+    pub progress: &'a dyn ProgressCallback,
+    pub index: Option<&'a ScanIndex>,
+    pub checkpoint: Option<&'a Checkpoint>,
+    /// Locked files are added here instead of being retried, if set
+    pub deferred: Option<&'a Mutex<Vec<DeferredFile>>>,
+    /// Computes destination paths
+    pub paths: &'a PathMapper,
+    /// Free space the copy must leave on the destination, if set
+    pub reserve: Option<&'a FreeSpaceReserve<'a>>,
+    /// Caps the files handled at once, as /MT:AUTO or the user sets it
+    pub limit: Option<&'a ConcurrencyLimit>,
+    /// Caps the files whose contents are copied at once, with /MTDATA
+    pub data_limit: Option<&'a ConcurrencyLimit>,
+    /// Caps the stat, mkdir and remove calls made at once, with /MTMETA
+    pub meta_limit: Option<&'a ConcurrencyLimit>,
+    /// Source files another source's copy takes precedence over, with
+    /// /MERGE
+    pub merge: Option<&'a MergePlan>,
+    /// Levels below the source root of the directory being copied
+    pub depth: usize,
+    /// Whether the directory being copied is the root of a source or child
+    /// folder, whose destination is counted apart from the folders below
+    pub root: bool,
+    /// rbcp's own files, left alone in sources and destinations
+    pub artifacts: &'a Artifacts,
+    /// Directories above the one being copied, to break link cycles
+    pub ancestors: Option<&'a Ancestors<'a>>,
+    /// Identity of the destination, which the walk must not enter
+    pub destination_id: Option<&'a DirId>,
+    /// Lists the files copied with holes, with /SALVAGE
+    pub damage: Option<&'a DamageReport>,
+    /// Hashes of the copies, with /VERIFY or /BITROT
+    pub hashes: Option<&'a HashManifest>,
+    /// Changes to the destination, with /HTML
+    pub actions: Option<&'a ActionLog>,
+    /// Caps the bandwidth of the job, with /THROTTLE
+    pub throttle: Option<&'a Throttle>,
+    /// Files never to be copied, unless /NOSKIPLIST
+    pub skip_list: Option<&'a SkipMatcher>,
+    /// Files that failed too many runs in a row, with /QUARANTINE
+    pub quarantine: Option<&'a QuarantineSet>,
+    /// Checks each file before it is copied, with /SCANCMD or a filter given
+    /// to the engine
+    pub content_filter: Option<&'a dyn ContentFilter>,
+    /// Gives copies the owners of their source, with /OWNER
+    pub owners: Option<&'a OwnerCopier>,
+    /// Junctions, directory symlinks and hard links not followed, with
+    /// /LINKS
+    pub links: Option<&'a Links>,
+    /// Cloud placeholders listed at the destination, with /CLOUD:METADATA
+    /// or from an earlier run
+    pub placeholders: Option<&'a PlaceholderList>,
+    /// Warns about folders and a job bigger than meant, with /MAXENTRIES
+    /// or /MAXFILES
+    pub limits: Option<&'a LimitGuard>,
+    /// Faults injected into the copies, given to the engine by a test
+    #[cfg(feature = "testing")]
+    pub faults: Option<&'a FaultPlan>,
+}
+
+impl<'a> CopyContext<'a> {
+    /// Log the warning about a limit the job went past, if there is one
+    fn warn_limit(&self, warning: Option<LimitWarning>) {
+        if let Some(warning) = warning {
+            let msg = warning.to_string();
+            self.progress.on_log(&msg);
+            self.logger.log(&msg);
+            tracing::warn!(%warning, "limit exceeded");
+        }
+    }
+
+    /// Count a file of the job towards /MAXFILES
+    fn count_file(&self) {
+        if let Some(limits) = self.limits {
+            self.warn_limit(limits.add_file());
+        }
+    }
+
+    /// Check the number of entries of the directory at `path` against
+    /// /MAXENTRIES
+    fn check_entries(&self, path: &Path, entries: u64) {
+        if let Some(limits) = self.limits {
+            self.warn_limit(limits.check_dir(path, entries));
+        }
+    }
+
+    /// Count the `counted`th entry listed of the directory at `path`
+    /// towards /MAXENTRIES, warning as soon as it has too many
+    fn count_entry(&self, path: &Path, counted: u64) {
+        if let Some(limits) = self.limits {
+            self.warn_limit(limits.add_entry(path, counted));
+        }
+    }
+
+    /// Add a change to the destination to the report, if one is written
+    fn record_action(&self, kind: ActionKind, path: &Path, bytes: u64) {
+        if let Some(actions) = self.actions {
+            actions.record(kind, path, bytes);
+        }
+    }
+
+    /// Wait for a slot to copy the contents of a file, if they are limited
+    fn data_permit(&self) -> Option<Permit<'a>> {
+        self.data_limit.map(ConcurrencyLimit::acquire)
+    }
+
+    /// Apply the fault plan given to the engine to `op` on `path`
+    #[cfg(feature = "testing")]
+    fn inject(&self, op: FaultOp, path: &Path) -> io::Result<()> {
+        self.faults.map_or(Ok(()), |faults| faults.inject(op, path))
+    }
+
+    /// Wait for a slot to make metadata calls, if they are limited
+    fn meta_permit(&self) -> Option<Permit<'a>> {
+        self.meta_limit.map(ConcurrencyLimit::acquire)
+    }
+
+    /// Where the file that would be copied to `path` goes
+    fn file_destination(&self, path: PathBuf) -> PathBuf {
+        self.paths.map_file(path)
+    }
+
+    /// Where the directory that would be created at `path` goes, if it is
+    /// created before its files
+    fn dir_destination(&self, path: &Path) -> Option<PathBuf> {
+        self.paths.map_dir(path)
+    }
+
+    /// Create the parent of a rewritten destination path, which need not
+    /// be a directory the walk has created
+    fn ensure_parent(&self, path: &Path) -> io::Result<()> {
+        if !self.paths.rewrites() || self.options.list_only {
+            return Ok(());
+        }
+        match path.parent() {
+            Some(parent) if !parent.exists() => {
+                retry_meta("creating directory", parent, self, || {
+                    fs::create_dir_all(parent)
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Make the metadata call `op`, `what` `path`, again after a short wait
+/// while it fails with an error that may pass, up to /RMETA times, so a
+/// share that stops answering for a moment does not fail the walk. The
+/// error it ends with names `what` and `path`.
+fn retry_meta<T>(
+    what: &'static str,
+    path: &Path,
+    ctx: &CopyContext,
+    op: impl Fn() -> io::Result<T>,
+) -> io::Result<T> {
+    let retries = ctx.options.meta_retries;
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e)
+                if attempt < retries && is_transient_error(&e) && !ctx.progress.is_cancelled() =>
+            {
+                attempt += 1;
+                ctx.stats.add_retry();
+                ctx.logger.log(&format!(
+                    "Retry {} of {}: {} {}, Error: {}",
+                    attempt,
+                    retries,
+                    what,
+                    path.display(),
+                    e
+                ));
+                thread::sleep(Duration::from_millis(ctx.options.meta_wait_ms));
+            }
+            result => return result.context(what, path),
+        }
+    }
+}
+
+pub fn copy_directory(src_path: &Path, dst_path: &Path, ctx: &CopyContext) -> io::Result<()> {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        index,
+        ..
+    } = *ctx;
+
+    let _span = tracing::debug_span!("directory", path = %src_path.display()).entered();
 
-pub fn copy_directory(
-    src_path: &Path,
-    dst_path: &Path,
-    options: &CopyOptions,
-    logger: &Logger,
-    stats: &Statistics,
-    progress: &dyn ProgressCallback,
-) -> io::Result<()> {
     // Check for cancellation
     if progress.is_cancelled() {
         return Ok(());
@@ -51,240 +285,1280 @@ pub fn copy_directory(
             dst_path.to_path_buf()
         };
 
+        let actual_dst = ctx.file_destination(actual_dst);
+
         // Ensure parent exists
         if let Some(parent) = actual_dst.parent() {
             if !parent.exists() {
-                fs::create_dir_all(parent)?;
+                fs::create_dir_all(parent).context("creating directory", parent)?;
             }
         }
 
-        return copy_file(src_path, &actual_dst, options, logger, stats, progress);
+        return copy_file(src_path, &actual_dst, ctx);
     }
 
+    // Skip an unreadable directory if its error is ignored
+    if !options.ignore_errors.is_empty() {
+        if let Err(e) = fs::read_dir(src_path) {
+            if ignore_error(&e, src_path, ctx) {
+                return Ok(());
+            }
+        }
+    }
+
+    // A link back to a directory being walked would never end
+    let here = match Ancestors::enter(src_path, ctx.ancestors, ctx.destination_id) {
+        Ok(here) => here,
+        Err(revisit) => {
+            let msg = cycles::describe(src_path, revisit);
+            progress.on_log(&msg);
+            logger.log(&msg);
+            tracing::warn!(path = %src_path.display(), "link cycle");
+            stats.add_dir_skipped();
+            return Ok(());
+        }
+    };
+    let ctx = &CopyContext {
+        ancestors: here.as_ref().or(ctx.ancestors),
+        ..*ctx
+    };
+
     // Ensure the destination directory exists
-    if !dst_path.exists() {
+    let dst_dir = ctx.dir_destination(dst_path);
+    if let Some(dst_dir) = dst_dir.filter(|dir| !dir.exists()) {
         if !options.list_only {
-            let msg = format!("Creating directory: {}", dst_path.display());
+            let msg = format!("Creating directory: {}", dst_dir.display());
             progress.on_log(&msg);
             logger.log(&msg);
-            fs::create_dir_all(dst_path)?;
-            stats.add_dir_created();
+            let _meta = ctx.meta_permit();
+            retry_meta("creating directory", &dst_dir, ctx, || {
+                fs::create_dir_all(&dst_dir)
+            })?;
+            owners::copy_dir_owners(ctx.owners, src_path, &dst_dir);
         } else {
-            let msg = format!("Would create directory: {}", dst_path.display());
+            let msg = format!("Would create directory: {}", dst_dir.display());
             progress.on_log(&msg);
             logger.log(&msg);
+        }
+        if ctx.root {
+            stats.add_root_dir_created();
+        } else {
             stats.add_dir_created();
         }
+        ctx.record_action(ActionKind::NewDir, &dst_dir, 0);
     }
 
-    // Collect the source files and directories
-    // We collect them into a Vec to enable parallel iteration
-    let entries: Vec<_> = fs::read_dir(src_path)?.collect::<Result<Vec<_>, io::Error>>()?;
+    let process_file =
+        |path: &Path, file_name: &str, cached: Option<&FileRecord>| -> io::Result<()> {
+            // Check if file matches any pattern and no exclusion
+            if !options.selects_file(file_name) {
+                return Ok(());
+            }
+            ctx.count_file();
 
-    // We need to keep track of source filenames for the purge step
-    let src_names: HashSet<String> = entries
-        .iter()
-        .map(|e| e.file_name().to_string_lossy().to_string())
-        .collect();
+            let dst_file_path = ctx.file_destination(dst_path.join(file_name));
+            ctx.ensure_parent(&dst_file_path)?;
 
-    // Process entries in parallel if threads > 1, otherwise sequential
-    let process_entry = |entry: &fs::DirEntry| -> io::Result<()> {
-        if progress.is_cancelled() {
+            // An indexed file still as listed, whose destination copy
+            // matches it too, is skipped without comparing further. The
+            // source is looked at all the same, as a file edited in place
+            // leaves the time of its directory alone.
+            if let Some(record) = cached {
+                if !options.force_overwrite && !options.conflict_actions.contains_key(path) {
+                    let as_listed = |meta: &fs::Metadata| {
+                        meta.len() == record.size && mtime_nanos(meta) == record.mtime
+                    };
+                    let meta = ctx.meta_permit();
+                    let src_listed = fs::metadata(path).is_ok_and(|meta| as_listed(&meta));
+                    let dst_listed = src_listed
+                        && fs::metadata(&dst_file_path).is_ok_and(|meta| as_listed(&meta));
+                    drop(meta);
+                    if dst_listed {
+                        stats.add_file_skipped();
+                        return Ok(());
+                    }
+                }
+            }
+
+            copy_file(path, &dst_file_path, ctx)
+        };
+
+    let process_dir = |path: &Path, file_name: &str| -> io::Result<()> {
+        if options.excludes_dir(path) {
+            if options.log_file_names {
+                let msg = format!("Skipping excluded directory: {}", path.display());
+                progress.on_log(&msg);
+                logger.log(&msg);
+            }
+            stats.add_dir_skipped();
+            return Ok(());
+        }
+        let dst_subdir = dst_path.join(file_name);
+
+        // A link to a directory not followed is handled by its policy
+        if let Some(links) = ctx.links.filter(|_| options.links.checks_dirs()) {
+            if let Some(kind) = LinkKind::of_dir(path) {
+                if links.policy(kind) != LinkPolicy::Follow {
+                    let dst_link = ctx.file_destination(dst_subdir);
+                    ctx.ensure_parent(&dst_link)?;
+                    copy_dir_link(path, &dst_link, kind, links, ctx);
+                    return Ok(());
+                }
+            }
+        }
+
+        // Below the depth limit
+        if !options.descends_below(ctx.depth) {
+            if options.log_file_names {
+                let msg = format!("Skipping directory below depth limit: {}", path.display());
+                progress.on_log(&msg);
+                logger.log(&msg);
+            }
+            stats.add_dir_skipped();
             return Ok(());
         }
 
-        let path = entry.path();
-        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        // Skip empty directories if not including them
+        if !options.include_empty {
+            let is_empty = match path.read_dir() {
+                Ok(mut entries) => entries.next().is_none(),
+                Err(e) if ignore_error(&e, path, ctx) => return Ok(()),
+                Err(e) => return Err(e).context("reading directory", path),
+            };
+            if is_empty {
+                if options.log_file_names {
+                    let msg = format!("Skipping empty directory: {}", path.display());
+                    progress.on_log(&msg);
+                    logger.log(&msg);
+                }
+                stats.add_dir_skipped();
+                return Ok(());
+            }
+        }
+
+        let sub_ctx = CopyContext {
+            depth: ctx.depth + 1,
+            root: false,
+            ..*ctx
+        };
+        copy_directory(path, &dst_subdir, &sub_ctx)?;
+
+        // Move (delete source dir) if requested
+        if options.move_dirs && !options.list_only {
+            let is_empty = path
+                .read_dir()
+                .context("reading directory", path)?
+                .next()
+                .is_none();
+            if is_empty {
+                let _ = fs::remove_dir(path);
+            }
+        }
+        Ok(())
+    };
+
+    // Failures and deferred files, anywhere while this directory is
+    // copied, keep /FASTSKIP from trusting it on the next run
+    let troubles = || {
+        stats.files_failed.load(Ordering::Relaxed)
+            + stats.errors_ignored.load(Ordering::Relaxed)
+            + ctx
+                .deferred
+                .map_or(0, |deferred| deferred.lock().unwrap().len())
+    };
+    let troubles_before = troubles();
+    let complete = || !progress.is_cancelled() && troubles() == troubles_before;
 
-        if path.is_file() {
-            // Check if file matches any pattern
-            let matches = options
-                .patterns
-                .iter()
-                .any(|p| matches_pattern(&file_name, p));
+    // Use the indexed listing when the source directory is unchanged
+    if let Some(mut record) = index.and_then(|index| index.unchanged_dir(src_path)) {
+        ctx.check_entries(src_path, (record.files.len() + record.dirs.len()) as u64);
+        let process_cached_file = |file: &FileRecord| -> io::Result<()> {
+            if progress.is_cancelled() {
+                return Ok(());
+            }
+            process_file(&src_path.join(&file.name), &file.name, Some(file))
+        };
+        let process_cached_dir = |name: &String| -> io::Result<()> {
+            if progress.is_cancelled() || !options.recursive {
+                return Ok(());
+            }
+            process_dir(&src_path.join(name), name)
+        };
 
-            if matches {
-                let dst_file_path = dst_path.join(&file_name);
-                copy_file(&path, &dst_file_path, options, logger, stats, progress)?;
+        // With /FASTSKIP the files are known to be up to date, and only the
+        // subdirectories are looked at
+        let fast = index.is_some_and(|index| index.fast_skips(&record)) && dst_path.is_dir();
+        if fast {
+            let msg = format!("Unchanged directory: {}", src_path.display());
+            logger.log(&msg);
+            for _ in &record.files {
+                ctx.count_file();
+                stats.add_file_skipped();
             }
-        } else if path.is_dir() && options.recursive {
-            let dst_subdir = dst_path.join(&file_name);
+            if options.parallel() {
+                record.dirs.par_iter().try_for_each(process_cached_dir)?;
+            } else {
+                record.dirs.iter().try_for_each(process_cached_dir)?;
+            }
+        } else if options.parallel() {
+            record.files.par_iter().try_for_each(process_cached_file)?;
+            record.dirs.par_iter().try_for_each(process_cached_dir)?;
+        } else if options.ordered {
+            let mut files: Vec<&FileRecord> = record.files.iter().collect();
+            files.sort_by(|a, b| compare_names(&a.name, &b.name));
+            let mut dirs: Vec<&String> = record.dirs.iter().collect();
+            dirs.sort_by(|a, b| compare_names(a, b));
+            files.into_iter().try_for_each(process_cached_file)?;
+            dirs.into_iter().try_for_each(process_cached_dir)?;
+        } else {
+            record.files.iter().try_for_each(process_cached_file)?;
+            record.dirs.iter().try_for_each(process_cached_dir)?;
+        }
 
-            // Skip empty directories if not including them
-            if !options.include_empty {
-                let is_empty = path.read_dir()?.next().is_none();
-                if is_empty {
-                    if options.log_file_names {
-                        let msg = format!("Skipping empty directory: {}", path.display());
+        if let Some(index) = index {
+            record.complete = complete();
+            index.record_dir(src_path, record);
+        }
+    } else {
+        // Listing of this directory for the index, if enabled
+        let listing = index.map(|_| {
+            let mtime = fs::metadata(src_path).map(|m| mtime_nanos(&m)).unwrap_or(0);
+            Mutex::new(DirRecord {
+                mtime,
+                scanned_at: system_time_nanos(SystemTime::now()),
+                ..Default::default()
+            })
+        });
+
+        let entries = AtomicU64::new(0);
+
+        // Process entries in parallel if threads > 1, otherwise sequential
+        let process_entry = |entry: &fs::DirEntry| -> io::Result<()> {
+            if progress.is_cancelled() {
+                return Ok(());
+            }
+
+            let path = entry.path();
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            ctx.count_entry(src_path, entries.fetch_add(1, Ordering::Relaxed) + 1);
+            if let Some(listing) = &listing {
+                listing.lock().unwrap().entries += 1;
+            }
+
+            if ctx.artifacts.contains(&path) {
+                return Ok(());
+            }
+
+            if path.is_file() {
+                if let Some(listing) = &listing {
+                    if let Ok(meta) = entry.metadata() {
+                        listing.lock().unwrap().files.push(FileRecord {
+                            name: file_name.clone(),
+                            size: meta.len(),
+                            mtime: mtime_nanos(&meta),
+                        });
+                    }
+                }
+                process_file(&path, &file_name, None)?;
+            } else if path.is_dir() {
+                if let Some(listing) = &listing {
+                    listing.lock().unwrap().dirs.push(file_name.clone());
+                }
+                if options.recursive {
+                    process_dir(&path, &file_name)?;
+                }
+            } else if let Some(kind) = SpecialKind::of(entry) {
+                if options.selects_file(&file_name) {
+                    let dst_special = ctx.file_destination(dst_path.join(&file_name));
+                    ctx.ensure_parent(&dst_special)?;
+                    copy_special(&path, &dst_special, kind, ctx);
+                }
+            }
+            Ok(())
+        };
+
+        for_each_entry(src_path, ctx, process_entry)?;
+
+        if let (Some(index), Some(listing)) = (index, listing) {
+            if !progress.is_cancelled() {
+                let mut listing = listing.into_inner().unwrap();
+                listing.complete = complete();
+                index.record_dir(src_path, listing);
+            }
+        }
+    }
+
+    // Purge files/directories in destination that don't exist in source
+    if (options.purge || options.mirror) && dst_path.is_dir() {
+        let purge_started = Instant::now();
+        let process_purge = |entry: &fs::DirEntry| -> io::Result<()> {
+            if progress.is_cancelled() {
+                return Ok(());
+            }
+
+            let path = entry.path();
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+            if ctx.artifacts.contains(&path) {
+                return Ok(());
+            }
+
+            // What /XF and /XD leave out is left alone in the destination
+            let src_entry = src_path.join(&file_name);
+            let excluded = if path.is_dir() {
+                options.excludes_dir(&src_entry)
+            } else {
+                options.excludes_file(&file_name)
+            };
+            if excluded {
+                return Ok(());
+            }
+
+            let _meta = ctx.meta_permit();
+            if fs::symlink_metadata(&src_entry).is_err() {
+                if options.list_only {
+                    return list_extra(&path, ctx);
+                }
+                if path.is_file() {
+                    let size = fs::metadata(&path).map_or(0, |meta| meta.len());
+                    if options.shred_files {
+                        let msg = format!("Securely removing file: {}", path.display());
                         progress.on_log(&msg);
                         logger.log(&msg);
+                        securely_delete_file(&path, logger).context("removing file", &path)?;
+                    } else {
+                        let msg = format!("Removing file: {}", path.display());
+                        progress.on_log(&msg);
+                        logger.log(&msg);
+                        fs::remove_file(&path).context("removing file", &path)?;
                     }
-                    stats.add_dir_skipped();
-                    return Ok(());
+                    stats.add_file_removed();
+                    ctx.record_action(ActionKind::RemoveFile, &path, size);
+                } else if path.is_dir() {
+                    if options.shred_files {
+                        let msg = format!("Securely removing directory: {}", path.display());
+                        progress.on_log(&msg);
+                        logger.log(&msg);
+                        secure_remove_dir_all(&path, logger)
+                            .context("removing directory", &path)?;
+                    } else {
+                        let msg = format!("Removing directory: {}", path.display());
+                        progress.on_log(&msg);
+                        logger.log(&msg);
+                        fs::remove_dir_all(&path).context("removing directory", &path)?;
+                    }
+                    stats.add_dir_removed();
+                    ctx.record_action(ActionKind::RemoveDir, &path, 0);
+                }
+            }
+            Ok(())
+        };
+
+        for_each_entry(dst_path, ctx, process_purge)?;
+        stats.add_purge_time(purge_started.elapsed());
+    }
+
+    Ok(())
+}
+
+/// Report the extra destination entry at `path` a list-only purge would
+/// remove
+fn list_extra(path: &Path, ctx: &CopyContext) -> io::Result<()> {
+    let meta = fs::metadata(path).context("reading metadata of", path)?;
+    let (msg, kind, size) = if meta.is_dir() {
+        ctx.stats.add_dir_removed();
+        (
+            format!("Would remove directory: {}", path.display()),
+            ActionKind::RemoveDir,
+            0,
+        )
+    } else {
+        ctx.stats.add_file_removed();
+        (
+            format!("Would remove file: {}", path.display()),
+            ActionKind::RemoveFile,
+            meta.len(),
+        )
+    };
+    ctx.progress.on_log(&msg);
+    ctx.logger.log(&msg);
+    ctx.record_action(kind, path, size);
+    Ok(())
+}
+
+/// Copy an explicit list of paths relative to `src_root` into `dst_root`,
+/// without walking the source tree.
+pub fn copy_listed_files(
+    src_root: &Path,
+    dst_root: &Path,
+    files: &[String],
+    ctx: &CopyContext,
+) -> io::Result<()> {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        ..
+    } = *ctx;
+
+    let process_listed = |relative: &String| -> io::Result<()> {
+        if progress.is_cancelled() {
+            return Ok(());
+        }
+
+        // Only plain relative paths may be listed
+        let relative_path = Path::new(relative);
+        let is_relative = relative_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            let msg = format!("Skipping listed path outside the source: {}", relative);
+            progress.on_log(&msg);
+            logger.log(&msg);
+            stats.add_file_skipped();
+            return Ok(());
+        }
+
+        let src_file = src_root.join(relative_path);
+        if !src_file.is_file() {
+            let error = io::Error::from(io::ErrorKind::NotFound);
+            if ignore_error(&error, &src_file, ctx) {
+                return Ok(());
+            }
+            let msg = format!("Listed file not found: {}", src_file.display());
+            progress.on_log(&msg);
+            logger.log(&msg);
+            stats.add_failure(&src_file, "not found");
+            return Ok(());
+        }
+
+        let dst_file = ctx.file_destination(dst_root.join(relative_path));
+        if let Some(parent) = dst_file.parent() {
+            if !parent.exists() && !options.list_only {
+                fs::create_dir_all(parent).context("creating directory", parent)?;
+            }
+        }
+
+        copy_file(&src_file, &dst_file, ctx)
+    };
+
+    if options.parallel() {
+        files.par_iter().try_for_each(process_listed)
+    } else {
+        files.iter().try_for_each(process_listed)
+    }
+}
+
+/// Handle a dangling symlink or special file according to its policy.
+/// Failures are recorded in the statistics and do not stop the walk.
+fn copy_special(src_path: &Path, dst_path: &Path, kind: SpecialKind, ctx: &CopyContext) {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        ..
+    } = *ctx;
+
+    let policy = options.special_files.policy(kind);
+    let msg = match policy {
+        SpecialPolicy::Skip => format!("Skipping special file ({}): {}", kind, src_path.display()),
+        SpecialPolicy::Recreate if options.list_only => format!(
+            "Would recreate special file ({}): {} -> {}",
+            kind,
+            src_path.display(),
+            dst_path.display()
+        ),
+        SpecialPolicy::Recreate => format!(
+            "Recreating special file ({}): {} -> {}",
+            kind,
+            src_path.display(),
+            dst_path.display()
+        ),
+        SpecialPolicy::Error => format!("Special file ({}): {}", kind, src_path.display()),
+    };
+    if options.log_file_names || policy == SpecialPolicy::Error {
+        progress.on_log(&msg);
+        logger.log(&msg);
+    }
+
+    let outcome = match policy {
+        SpecialPolicy::Recreate if !options.list_only => {
+            match crate::special::recreate(src_path, dst_path, kind) {
+                Ok(()) => SpecialPolicy::Recreate,
+                Err(e) if ignore_error(&e, src_path, ctx) => return,
+                Err(e) => {
+                    let msg = format!(
+                        "Failed to recreate special file ({}): {}, Error: {}",
+                        kind,
+                        src_path.display(),
+                        e
+                    );
+                    progress.on_log(&msg);
+                    logger.log(&msg);
+                    stats.add_io_failure(src_path, &e, &e);
+                    SpecialPolicy::Error
+                }
+            }
+        }
+        SpecialPolicy::Error => {
+            stats.add_failure(src_path, format!("special file ({})", kind));
+            SpecialPolicy::Error
+        }
+        policy => policy,
+    };
+    stats.add_special(kind, outcome);
+}
+
+/// Handle the link to a directory at `src_path` according to its policy,
+/// pointing a recreated link into the destination if it points into the
+/// source. Failures are recorded in the statistics and do not stop the
+/// walk.
+fn copy_dir_link(
+    src_path: &Path,
+    dst_path: &Path,
+    kind: LinkKind,
+    links: &Links,
+    ctx: &CopyContext,
+) {
+    // The roots are as many levels up as the directory holding the link
+    let src_root = src_path.ancestors().nth(ctx.depth + 1).unwrap_or(src_path);
+    let dst_root = dst_path.ancestors().nth(ctx.depth + 1).unwrap_or(dst_path);
+    match links::dir_link_target(src_path, src_root, dst_root) {
+        Ok(target) => copy_link(src_path, dst_path, kind, &target, links, ctx),
+        Err(e) if ignore_error(&e, src_path, ctx) => {}
+        Err(e) => {
+            let msg = format!(
+                "Failed to read link ({}): {}, Error: {}",
+                kind,
+                src_path.display(),
+                e
+            );
+            ctx.progress.on_log(&msg);
+            ctx.logger.log(&msg);
+            ctx.stats.add_io_failure(src_path, &e, &e);
+            ctx.stats.add_link(kind, LinkPolicy::Error);
+        }
+    }
+}
+
+/// Handle a link of `kind` at `src_path` to `target` according to its
+/// policy, which is not to follow it. A recreated link goes in the
+/// manifest, and with /VERIFY is read back. Failures are recorded in the
+/// statistics and do not stop the walk.
+fn copy_link(
+    src_path: &Path,
+    dst_path: &Path,
+    kind: LinkKind,
+    target: &Path,
+    links: &Links,
+    ctx: &CopyContext,
+) {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        ..
+    } = *ctx;
+
+    let policy = links.policy(kind);
+    let up_to_date = policy == LinkPolicy::Recreate && links::is_linked(dst_path, kind, target);
+    let msg = match policy {
+        _ if up_to_date => format!("Link up to date ({}): {}", kind, dst_path.display()),
+        LinkPolicy::Recreate if options.list_only => format!(
+            "Would recreate link ({}): {} -> {}",
+            kind,
+            dst_path.display(),
+            target.display()
+        ),
+        LinkPolicy::Recreate => format!(
+            "Recreating link ({}): {} -> {}",
+            kind,
+            dst_path.display(),
+            target.display()
+        ),
+        LinkPolicy::Error => format!("Link ({}): {}", kind, src_path.display()),
+        _ => format!("Skipping link ({}): {}", kind, src_path.display()),
+    };
+    if options.log_file_names || policy == LinkPolicy::Error {
+        progress.on_log(&msg);
+        logger.log(&msg);
+    }
+
+    let outcome = match policy {
+        LinkPolicy::Recreate if up_to_date => {
+            links.record(dst_path, kind, target);
+            stats.add_file_skipped();
+            return;
+        }
+        LinkPolicy::Recreate if !options.list_only => {
+            let linked = links::recreate(dst_path, kind, target).and_then(|()| {
+                if options.verify && !links::is_linked(dst_path, kind, target) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "verification failed, the link does not lead to {}",
+                            target.display()
+                        ),
+                    ));
+                }
+                Ok(())
+            });
+            match linked {
+                Ok(()) => {
+                    links.record(dst_path, kind, target);
+                    ctx.record_action(ActionKind::NewFile, dst_path, 0);
+                    LinkPolicy::Recreate
+                }
+                Err(e) if ignore_error(&e, src_path, ctx) => return,
+                Err(e) => {
+                    let msg = format!(
+                        "Failed to recreate link ({}): {}, Error: {}",
+                        kind,
+                        src_path.display(),
+                        e
+                    );
+                    progress.on_log(&msg);
+                    logger.log(&msg);
+                    stats.add_io_failure(src_path, &e, &e);
+                    LinkPolicy::Error
                 }
             }
+        }
+        LinkPolicy::Error => {
+            stats.add_failure(src_path, format!("link ({})", kind));
+            LinkPolicy::Error
+        }
+        policy => policy,
+    };
+    stats.add_link(kind, outcome);
+}
+
+/// Give the copy at `dst_path` the attributes of its source, whose
+/// contents it has already. Failures are recorded in the statistics.
+fn update_attributes(src_path: &Path, src_meta: &Metadata, dst_path: &Path, ctx: &CopyContext) {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        ..
+    } = *ctx;
+
+    let verb = if options.list_only {
+        "Would update attributes"
+    } else {
+        "Updating attributes"
+    };
+    if options.log_file_names {
+        let msg = format!("{}: {} -> {}", verb, src_path.display(), dst_path.display());
+        progress.on_log(&msg);
+        logger.log(&msg);
+    }
+    if !options.list_only {
+        let detached = if options.swap_dest {
+            swap::detach(dst_path)
+        } else {
+            Ok(())
+        };
+        if let Err(e) =
+            detached.and_then(|()| attributes::apply(dst_path, attributes::of(src_meta)))
+        {
+            if ignore_error(&e, dst_path, ctx) {
+                return;
+            }
+            let msg = format!(
+                "Failed to update attributes: {}, Error: {}",
+                dst_path.display(),
+                e
+            );
+            progress.on_log(&msg);
+            logger.log(&msg);
+            stats.add_io_failure(src_path, &e, &e);
+            return;
+        }
+    }
+    stats.add_file_attributes_updated();
+}
+
+/// Whether `error` on `path` matches an ignore rule. Ignored errors are
+/// counted and written to the log file only.
+fn ignore_error(error: &io::Error, path: &Path, ctx: &CopyContext) -> bool {
+    if !ctx
+        .options
+        .ignore_errors
+        .iter()
+        .any(|rule| rule.matches(error, path))
+    {
+        return false;
+    }
+    ctx.logger.log(&format!(
+        "Ignoring error: {}, Error: {}",
+        path.display(),
+        error
+    ));
+    ctx.stats.add_error_ignored();
+    true
+}
+
+/// Describe `error`, naming the processes holding the files if it is a
+/// sharing violation
+fn describe_error(error: &io::Error, src_path: &Path, dst_path: &Path) -> String {
+    if !is_locked_error(error) {
+        return error.to_string();
+    }
+    let holders: Vec<String> = lock_holders(&[src_path, dst_path])
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    if holders.is_empty() {
+        error.to_string()
+    } else {
+        format!("{} (locked by {})", error, holders.join(", "))
+    }
+}
+
+/// The hash recorded for the copy at `dst_path`, if the copy no longer has
+/// it although its size and time are those recorded with it
+fn check_rot(
+    dst_path: &Path,
+    dst_meta: &Metadata,
+    hashes: &HashManifest,
+    ctx: &CopyContext,
+) -> Option<Hash> {
+    let expected = hashes.expected(dst_path, dst_meta)?;
+    let actual = match verify::hash_file(dst_path) {
+        Ok(actual) => actual,
+        Err(e) => {
+            let msg = format!(
+                "Warning: Could not hash {} to check it: {}",
+                dst_path.display(),
+                e
+            );
+            ctx.progress.on_log(&msg);
+            ctx.logger.log(&msg);
+            return None;
+        }
+    };
+    if actual == expected {
+        return None;
+    }
+
+    let msg = format!(
+        "Warning: {} changed although its size and time did not (hashes to {} instead of {})",
+        dst_path.display(),
+        actual.to_hex(),
+        expected.to_hex()
+    );
+    ctx.progress.on_log(&msg);
+    ctx.logger.log(&msg);
+    tracing::warn!(path = %dst_path.display(), "copy corrupted");
+    ctx.stats.add_file_rotted();
+    Some(expected)
+}
+
+/// Log a file copied with the unreadable `ranges` written as zeros and add
+/// it to the damage report
+fn report_damage(src_path: &Path, dst_path: &Path, ranges: &[Range<u64>], ctx: &CopyContext) {
+    let msg = format!(
+        "Warning: Salvaged {} with {} unreadable bytes written as zeros at {}",
+        src_path.display(),
+        salvage::damaged_bytes(ranges),
+        salvage::describe(ranges)
+    );
+    ctx.progress.on_log(&msg);
+    ctx.logger.log(&msg);
+    tracing::warn!(ranges = ranges.len(), "file salvaged");
+    ctx.stats.add_file_salvaged();
+
+    if let Some(report) = ctx.damage {
+        if let Err(e) = report.record(src_path, dst_path, ranges) {
+            let msg = format!(
+                "Warning: Could not write damage report {}: {}",
+                report.path().display(),
+                e
+            );
+            ctx.progress.on_log(&msg);
+            ctx.logger.log(&msg);
+        }
+    }
+}
+
+/// Copy files deferred by an earlier pass. Failures are recorded in the
+/// statistics and do not stop the other files.
+pub fn copy_deferred(files: &[DeferredFile], ctx: &CopyContext) {
+    let process_deferred = |(src, dst): &DeferredFile| {
+        let _ = copy_file(src, dst, ctx);
+    };
+
+    if ctx.options.parallel() {
+        files.par_iter().for_each(process_deferred);
+    } else {
+        files.iter().for_each(process_deferred);
+    }
+}
 
-            copy_directory(&path, &dst_subdir, options, logger, stats, progress)?;
+/// Visit the entries of a directory in bounded batches.
+///
+/// At most `options.entry_batch_size()` entries are held in memory at a
+/// time, so huge directories are streamed instead of collected up-front.
+/// Each batch is processed in parallel when multiple threads are enabled.
+/// In ordered mode the whole directory is visited one entry at a time, in
+/// alphabetical order.
+pub(crate) fn for_each_entry<F>(dir: &Path, ctx: &CopyContext, f: F) -> io::Result<()>
+where
+    F: Fn(&fs::DirEntry) -> io::Result<()> + Sync + Send,
+{
+    let options = ctx.options;
+    let batch_size = options.entry_batch_size();
+    let mut entries = retry_meta("reading directory", dir, ctx, || fs::read_dir(dir))?;
+    let mut batch = Vec::new();
 
-            // Move (delete source dir) if requested
-            if options.move_dirs && !options.list_only {
-                let is_empty = path.read_dir()?.next().is_none();
-                if is_empty {
-                    let _ = fs::remove_dir(&path);
-                }
-            }
+    loop {
+        batch.clear();
+        for entry in entries.by_ref().take(batch_size) {
+            batch.push(entry.context("reading directory", dir)?);
         }
-        Ok(())
-    };
 
-    if options.threads > 1 {
-        entries.par_iter().try_for_each(process_entry)?;
-    } else {
-        entries.iter().try_for_each(process_entry)?;
+        if options.parallel() {
+            batch.par_iter().try_for_each(&f)?;
+        } else if options.ordered {
+            batch.sort_by(|a, b| {
+                compare_names(
+                    &a.file_name().to_string_lossy(),
+                    &b.file_name().to_string_lossy(),
+                )
+            });
+            batch.iter().try_for_each(&f)?;
+        } else {
+            batch.iter().try_for_each(&f)?;
+        }
+
+        if batch.len() < batch_size {
+            return Ok(());
+        }
     }
+}
 
-    // Purge files/directories in destination that don't exist in source
-    if (options.purge || options.mirror) && !options.list_only {
-        if let Ok(dst_entries) = fs::read_dir(dst_path) {
-            let dst_entries: Vec<_> = dst_entries.collect::<Result<Vec<_>, io::Error>>()?;
+/// Alphabetical order of names, ignoring case first
+fn compare_names(a: &str, b: &str) -> std::cmp::Ordering {
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| a.cmp(b))
+}
 
-            let process_purge = |entry: &fs::DirEntry| -> io::Result<()> {
-                if progress.is_cancelled() {
-                    return Ok(());
-                }
+/// The change copying over the destination with `dst_meta` makes
+fn copy_action(dst_meta: Option<&Metadata>) -> ActionKind {
+    if dst_meta.is_some() {
+        ActionKind::ReplaceFile
+    } else {
+        ActionKind::NewFile
+    }
+}
 
-                let path = entry.path();
-                let file_name = path.file_name().unwrap().to_string_lossy().to_string();
-
-                if !src_names.contains(&file_name) {
-                    if path.is_file() {
-                        if options.shred_files {
-                            let msg = format!("Securely removing file: {}", path.display());
-                            progress.on_log(&msg);
-                            logger.log(&msg);
-                            securely_delete_file(&path, logger)?;
-                        } else {
-                            let msg = format!("Removing file: {}", path.display());
-                            progress.on_log(&msg);
-                            logger.log(&msg);
-                            fs::remove_file(&path)?;
-                        }
-                        stats.add_file_removed();
-                    } else if path.is_dir() {
-                        if options.shred_files {
-                            let msg = format!("Securely removing directory: {}", path.display());
-                            progress.on_log(&msg);
-                            logger.log(&msg);
-                            secure_remove_dir_all(&path, logger)?;
-                        } else {
-                            let msg = format!("Removing directory: {}", path.display());
-                            progress.on_log(&msg);
-                            logger.log(&msg);
-                            fs::remove_dir_all(&path)?;
-                        }
-                        stats.add_dir_removed();
-                    }
+/// What copying the source at `src_path` with `src_meta` over the
+/// destination at `dst_path` with `dst_meta` changes. `/COMPARE:HASH`
+/// reads both files when their sizes match, except cloud placeholders,
+/// which are compared by size and time; one that cannot be read is
+/// copied.
+fn compare_file(
+    src_path: &Path,
+    src_meta: &Metadata,
+    dst_path: &Path,
+    dst_meta: Option<&Metadata>,
+    options: &CopyOptions,
+) -> FileChange {
+    let state = |meta: &Metadata| FileState {
+        size: meta.len(),
+        mtime: mtime_nanos(meta),
+        attributes: attributes::of(meta),
+        hash: None,
+    };
+    let comparison = options.comparison();
+    let mut src = state(src_meta);
+    let mut dst = dst_meta.map(state);
+    if let Some(dst) = &mut dst {
+        if comparison.strategy == CompareStrategy::Hash
+            && !comparison.force_overwrite
+            && src.size == dst.size
+            && !cloud::is_placeholder(src_meta)
+        {
+            match (verify::hash_file(src_path), verify::hash_file(dst_path)) {
+                (Ok(src_hash), Ok(dst_hash)) => {
+                    src.hash = Some(*src_hash.as_bytes());
+                    dst.hash = Some(*dst_hash.as_bytes());
                 }
-                Ok(())
-            };
-
-            if options.threads > 1 {
-                dst_entries.par_iter().try_for_each(process_purge)?;
-            } else {
-                dst_entries.iter().try_for_each(process_purge)?;
+                _ => return FileChange::Contents,
             }
         }
     }
+    compare(&src, dst.as_ref(), &comparison)
+}
+
+/// Copy one file. A panic while at it fails the file instead of taking
+/// the whole job down, and the other files go on.
+fn copy_file(src_path: &Path, dst_path: &Path, ctx: &CopyContext) -> io::Result<()> {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(|| {
+        copy_file_unguarded(src_path, dst_path, ctx)
+    })) {
+        Ok(result) => return result,
+        Err(payload) => payload,
+    };
 
+    let reason = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    let msg = format!(
+        "Failed to copy: {} -> {}, Error: internal error ({})",
+        src_path.display(),
+        dst_path.display(),
+        reason
+    );
+    ctx.progress.on_log(&msg);
+    ctx.logger.log(&msg);
+    tracing::error!(reason, "file panicked");
+    ctx.stats
+        .add_failure(src_path, format!("internal error ({})", reason));
     Ok(())
 }
 
-fn should_copy_file(
-    src_meta: &Metadata,
-    dst_meta: Option<&Metadata>,
-    force_overwrite: bool,
-) -> bool {
-    if force_overwrite {
-        return true;
+fn copy_file_unguarded(src_path: &Path, dst_path: &Path, ctx: &CopyContext) -> io::Result<()> {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        checkpoint,
+        ..
+    } = *ctx;
+
+    let span = tracing::debug_span!(
+        "file",
+        path = %src_path.display(),
+        bytes = tracing::field::Empty,
+        duration_ms = tracing::field::Empty
+    );
+    let _guard = span.enter();
+
+    if progress.is_cancelled() {
+        return Ok(());
     }
+    progress.wait_if_paused();
+    let _permit = ctx.limit.map(ConcurrencyLimit::acquire);
+    // Given up before waiting for a data slot, so metadata calls of other
+    // files go on meanwhile
+    let meta = ctx.meta_permit();
 
-    if dst_meta.is_none() {
-        return true;
+    // Another source's copy goes to the destination
+    if ctx.merge.is_some_and(|plan| plan.is_overridden(src_path)) {
+        stats.add_file_skipped();
+        return Ok(());
     }
 
-    let dst_meta = dst_meta.unwrap();
-    let src_modified = src_meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-    let dst_modified = dst_meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    // Finished by a previous attempt of this job
+    if checkpoint.is_some_and(|c| c.is_completed(src_path)) {
+        stats.add_file_skipped();
+        return Ok(());
+    }
 
-    if src_modified > dst_modified {
-        return true;
+    // Failed too often to be worth its retries
+    if ctx.quarantine.is_some_and(|q| q.contains(src_path)) {
+        let msg = tr!("quarantine-skipped", path = src_path.display().to_string());
+        if options.log_file_names {
+            progress.on_log(&msg);
+        }
+        logger.log(&msg);
+        stats.add_file_quarantined();
+        return Ok(());
     }
 
-    if src_modified == dst_modified && src_meta.len() != dst_meta.len() {
-        return true;
+    // Parts are copied through their manifest
+    if options.rejoin {
+        if split::is_manifest(src_path) {
+            drop(meta);
+            let _data = ctx.data_permit();
+            return rejoin_file(src_path, &dst_path.with_extension(""), ctx);
+        }
+        if split::is_part(src_path) {
+            return Ok(());
+        }
     }
 
-    false
-}
+    let src_meta = match retry_meta("reading metadata of", src_path, ctx, || {
+        fs::metadata(src_path)
+    }) {
+        Ok(meta) => meta,
+        Err(e) if ignore_error(&e, src_path, ctx) => return Ok(()),
+        Err(e) => return Err(e),
+    };
 
-fn copy_file(
-    src_path: &Path,
-    dst_path: &Path,
-    options: &CopyOptions,
-    logger: &Logger,
-    stats: &Statistics,
-    progress: &dyn ProgressCallback,
-) -> io::Result<()> {
-    if progress.is_cancelled() {
+    if let Some(reason) = ctx
+        .skip_list
+        .and_then(|list| list.skips(src_path, &src_meta))
+    {
+        let path = src_path.display().to_string();
+        let msg = if reason.is_empty() {
+            tr!("skip-list-skipped", path = path)
+        } else {
+            tr!("skip-list-skipped-reason", path = path, reason = reason)
+        };
+        if options.log_file_names {
+            progress.on_log(&msg);
+        }
+        logger.log(&msg);
+        stats.add_file_skip_listed();
         return Ok(());
     }
-    progress.wait_if_paused();
 
-    let src_meta = fs::metadata(src_path)?;
-    let dst_meta = fs::metadata(dst_path).ok();
+    // Another name of a file copied already, unless hard links are followed
+    if let Some(links) = ctx.links {
+        if let Some(copy) = links.copy_of(src_path, &src_meta) {
+            copy_link(src_path, dst_path, LinkKind::HardLink, &copy, links, ctx);
+            return Ok(());
+        }
+    }
 
-    if !should_copy_file(&src_meta, dst_meta.as_ref(), options.force_overwrite) {
-        stats.add_file_skipped();
+    let dst_meta = retry_meta("reading metadata of", dst_path, ctx, || {
+        fs::metadata(dst_path)
+    })
+    .ok();
+
+    // Files over the part size are written as parts and a manifest, unless
+    // only empty files are created
+    let part_size = options.split_mb.saturating_mul(1024 * 1024);
+    let split = part_size > 0 && src_meta.len() > part_size && !options.empty_files;
+
+    // A file that was in progress when the job was interrupted must be
+    // copied again even though the partial destination looks newer
+    let interrupted = checkpoint.is_some_and(|c| c.was_interrupted(src_path));
+
+    let change = if split {
+        let current = split::is_current(
+            dst_path,
+            src_meta.len(),
+            src_meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        );
+        if options.force_overwrite || !current {
+            FileChange::Contents
+        } else {
+            FileChange::Unchanged
+        }
+    } else {
+        compare_file(src_path, &src_meta, dst_path, dst_meta.as_ref(), options)
+    };
+    let needed = change == FileChange::Contents;
+    // A choice made in the conflict table wins over the comparison
+    let needed = match (options.conflict_actions.get(src_path), &dst_meta) {
+        (Some(action), Some(_)) => *action == ConflictAction::Overwrite,
+        _ => needed,
+    };
+    // A copy about to be skipped is hashed to catch contents changed
+    // behind an unchanged size and time
+    let rotted = match (ctx.hashes, &dst_meta) {
+        (Some(hashes), Some(dst_meta))
+            if options.check_bitrot && !needed && !interrupted && !split =>
+        {
+            check_rot(dst_path, dst_meta, hashes, ctx)
+        }
+        _ => None,
+    };
+    let recopy = rotted.is_some() && options.bitrot_recopy;
+
+    // The copy its other names link to, once there is one
+    let add_copy = || {
+        if let (Some(links), false) = (ctx.links, split) {
+            links.add_copy(src_path, &src_meta, dst_path);
+        }
+    };
+
+    if !interrupted && !needed && !recopy {
+        add_copy();
+        // Only the attributes of the source changed
+        if change == FileChange::Attributes {
+            update_attributes(src_path, &src_meta, dst_path, ctx);
+        } else {
+            stats.add_file_skipped();
+        }
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.mark_completed(src_path);
+        }
         return Ok(());
     }
 
-    if options.list_only {
-        let msg = format!(
-            "Would copy file: {} -> {}",
-            src_path.display(),
-            dst_path.display()
-        );
-        progress.on_log(&msg);
+    // Reading a placeholder downloads it, which only /CLOUD:HYDRATE wants
+    if cloud::is_placeholder(&src_meta) {
+        let policy = options.cloud_placeholders;
+        let path = src_path.display().to_string();
+        let msg = match policy {
+            PlaceholderPolicy::Skip => tr!("cloud-skipped", path = path),
+            PlaceholderPolicy::Metadata => tr!("cloud-listed", path = path),
+            PlaceholderPolicy::Hydrate => tr!("cloud-hydrating", path = path),
+        };
+        if options.log_file_names {
+            progress.on_log(&msg);
+        }
         logger.log(&msg);
-        stats.add_file_copied(src_meta.len());
+        match policy {
+            PlaceholderPolicy::Hydrate => stats.add_file_hydrated(),
+            _ => {
+                if let (PlaceholderPolicy::Metadata, Some(list), false) =
+                    (policy, ctx.placeholders, options.list_only)
+                {
+                    list.record(dst_path, &src_meta);
+                }
+                stats.add_file_placeholder();
+                return Ok(());
+            }
+        }
+    }
+
+    if options.list_only {
+        if options.log_file_names {
+            let msg = format!(
+                "Would copy file: {} -> {}",
+                src_path.display(),
+                dst_path.display()
+            );
+            progress.on_log(&msg);
+            logger.log(&msg);
+        }
+        add_copy();
+        stats.add_file_copied(src_path, src_meta.len());
+        ctx.record_action(copy_action(dst_meta.as_ref()), dst_path, src_meta.len());
         return Ok(());
     }
 
+    drop(meta);
+    let _data = ctx.data_permit();
+
+    if let Some(filter) = ctx.content_filter {
+        // A file the scanner could not check does not get through
+        let verdict = filter.check(src_path).inspect_err(|e| {
+            let msg = format!(
+                "Failed to copy: {} -> {}, Error: {}",
+                src_path.display(),
+                dst_path.display(),
+                e
+            );
+            progress.on_log(&msg);
+            logger.log(&msg);
+            stats.add_io_failure(src_path, e, e);
+        })?;
+        if let Verdict::Block(reason) = verdict {
+            let msg = tr!(
+                "content-blocked",
+                path = src_path.display().to_string(),
+                reason = reason.as_str()
+            );
+            progress.on_log(&msg);
+            logger.log(&msg);
+            tracing::warn!(reason, "file blocked");
+            stats.add_file_blocked();
+            return Ok(());
+        }
+    }
+
+    // Overwriting a file only needs room for the difference, which stays
+    // claimed until the file is done. Swap mode keeps the old file.
+    let replaced = dst_meta
+        .as_ref()
+        .filter(|_| !options.swap_dest)
+        .map_or(0, Metadata::len);
+    let growth = src_meta.len().saturating_sub(replaced);
+    let _claim = match ctx.reserve.map(|reserve| reserve.claim(src_path, growth)) {
+        Some(None) => return Ok(()),
+        claim => claim,
+    };
+
     if options.log_file_names {
-        let msg = format!(
-            "Copying file: {} -> {}",
-            src_path.display(),
-            dst_path.display()
-        );
+        let msg = if split {
+            format!(
+                "Splitting file: {} -> {}.part*",
+                src_path.display(),
+                dst_path.display()
+            )
+        } else {
+            format!(
+                "Copying file: {} -> {}",
+                src_path.display(),
+                dst_path.display()
+            )
+        };
         progress.on_log(&msg);
         logger.log(&msg);
     }
 
+    if let Some(checkpoint) = checkpoint {
+        if !interrupted {
+            checkpoint.mark_partial(src_path, 0, &src_meta);
+        }
+    }
+
+    let started = Instant::now();
     let mut retry_count = 0;
     loop {
         if progress.is_cancelled() {
             return Ok(());
         }
 
-        match copy_file_content(src_path, dst_path, src_meta.len(), options, progress) {
-            Ok(_) => {
-                // Preserve timestamps
+        let copied = copy_file_content(
+            src_path,
+            dst_path,
+            src_meta.len(),
+            split.then_some(part_size),
+            ctx,
+        );
+        match copied {
+            Ok(copied) => {
+                if !copied.damage.is_empty() {
+                    report_damage(src_path, dst_path, &copied.damage, ctx);
+                }
+                if copied.sampled {
+                    stats.add_file_sampled();
+                }
+
+                // Preserve timestamps and owners, on the manifest of a
+                // split file
+                let written = if split {
+                    split::manifest_path(dst_path)
+                } else {
+                    dst_path.to_path_buf()
+                };
                 if let Ok(src_time) = src_meta.modified() {
                     let _ = filetime::set_file_mtime(
-                        dst_path,
+                        &written,
                         filetime::FileTime::from_system_time(src_time),
                     );
                 }
+                if let Some(owners) = ctx.owners {
+                    owners.apply(src_path, &src_meta, &written);
+                }
+                add_copy();
+                if let Some(list) = ctx.placeholders {
+                    list.remove(dst_path);
+                }
+                if !options.ignore_attributes && !split {
+                    let _ = attributes::apply(dst_path, attributes::of(&src_meta));
+                }
+
+                // Kept so later runs can tell when the copy changes on its own
+                if let (Some(hashes), Some(hash)) = (ctx.hashes, copied.hash) {
+                    if let Ok(meta) = fs::metadata(dst_path) {
+                        hashes.record(dst_path, meta.len(), mtime_nanos(&meta), hash);
+                    }
+                    if rotted.is_some_and(|expected| expected != hash) {
+                        let msg = format!(
+                            "Warning: Source {} does not match the recorded hash either",
+                            src_path.display()
+                        );
+                        progress.on_log(&msg);
+                        logger.log(&msg);
+                    }
+                }
 
                 // Handle attributes (Windows only)
                 #[cfg(windows)]
@@ -329,19 +1603,61 @@ fn copy_file(
                     }
                 }
 
+                // The copy must be on disk before the source may go
+                sync_parent(dst_path, options)?;
+
                 // Move/Delete source
                 if options.move_files {
                     if options.shred_files {
-                        securely_delete_file(src_path, logger)?;
+                        securely_delete_file(src_path, logger)
+                            .context("removing file", src_path)?;
                     } else {
                         let _ = fs::remove_file(src_path);
                     }
                 }
 
-                stats.add_file_copied(src_meta.len());
+                span.record("bytes", src_meta.len());
+                span.record("duration_ms", started.elapsed().as_millis() as u64);
+                tracing::debug!("file copied");
+
+                stats.add_file_copied(src_path, src_meta.len());
+                ctx.record_action(copy_action(dst_meta.as_ref()), dst_path, src_meta.len());
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.mark_completed(src_path);
+                }
+                if options.print0 {
+                    print_null_separated(dst_path);
+                }
                 break;
             }
             Err(e) => {
+                // Interrupted by cancellation, not worth retrying
+                if progress.is_cancelled() {
+                    return Ok(());
+                }
+
+                if ignore_error(&e, src_path, ctx) {
+                    return Ok(());
+                }
+
+                let description = describe_error(&e, src_path, dst_path);
+
+                // Files in use are tried again at the end of the job
+                if let Some(deferred) = ctx.deferred.filter(|_| is_locked_error(&e)) {
+                    let msg = format!(
+                        "Deferring locked file: {}, Error: {}",
+                        src_path.display(),
+                        description
+                    );
+                    progress.on_log(&msg);
+                    logger.log(&msg);
+                    deferred
+                        .lock()
+                        .unwrap()
+                        .push((src_path.to_path_buf(), dst_path.to_path_buf()));
+                    return Ok(());
+                }
+
                 retry_count += 1;
                 if retry_count >= options.retries {
                     logger.log(&format!(
@@ -349,19 +1665,24 @@ fn copy_file(
                         options.retries,
                         src_path.display(),
                         dst_path.display(),
-                        e
+                        description
                     ));
-                    stats.add_file_failed();
+                    tracing::warn!(error = %description, retries = options.retries, "file failed");
+                    if e.kind() == io::ErrorKind::PermissionDenied {
+                        stats.add_file_denied();
+                    }
+                    stats.add_io_failure(src_path, &e, description);
                     return Err(e);
                 }
 
+                stats.add_retry();
                 logger.log(&format!(
                     "Retry {} of {}: {} -> {}, Error: {}",
                     retry_count,
                     options.retries,
                     src_path.display(),
                     dst_path.display(),
-                    e
+                    description
                 ));
 
                 thread::sleep(Duration::from_secs(options.wait_time));
@@ -372,25 +1693,304 @@ fn copy_file(
     Ok(())
 }
 
-fn copy_file_content(
+/// Restore at `dst_path` the file split into the parts described by the
+/// manifest at `src_path`
+fn rejoin_file(src_path: &Path, dst_path: &Path, ctx: &CopyContext) -> io::Result<()> {
+    let CopyContext {
+        options,
+        logger,
+        stats,
+        progress,
+        ..
+    } = *ctx;
+    let original = src_path.with_extension("");
+
+    let rejoined = SplitManifest::load(src_path).and_then(|manifest| {
+        let modified = fs::metadata(src_path)
+            .context("reading metadata of", src_path)?
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let current = fs::metadata(dst_path).is_ok_and(|meta| {
+            meta.len() == manifest.size && meta.modified().is_ok_and(|time| time >= modified)
+        });
+        if current && !options.force_overwrite {
+            stats.add_file_skipped();
+            return Ok(());
+        }
+
+        if options.log_file_names {
+            let verb = if options.list_only {
+                "Would rejoin file"
+            } else {
+                "Rejoining file"
+            };
+            let msg = format!(
+                "{}: {} ({} parts) -> {}",
+                verb,
+                original.display(),
+                manifest.parts,
+                dst_path.display()
+            );
+            progress.on_log(&msg);
+            logger.log(&msg);
+        }
+        if !options.list_only {
+            split::rejoin(
+                src_path,
+                &manifest,
+                dst_path,
+                options.durability(),
+                progress,
+            )?;
+            let _ =
+                filetime::set_file_mtime(dst_path, filetime::FileTime::from_system_time(modified));
+            sync_parent(dst_path, options)?;
+        }
+        stats.add_file_copied(&original, manifest.size);
+        Ok(())
+    });
+
+    match rejoined {
+        Err(_) if progress.is_cancelled() => Ok(()),
+        Err(e) => {
+            let description = describe_error(&e, src_path, dst_path);
+            logger.log(&format!(
+                "Failed to rejoin: {} -> {}, Error: {}",
+                original.display(),
+                dst_path.display(),
+                description
+            ));
+            stats.add_io_failure(&original, &e, description);
+            Err(e)
+        }
+        ok => ok,
+    }
+}
+
+/// Sync the directory holding `path` if the durability level asks for it
+fn sync_parent(path: &Path, options: &CopyOptions) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if options.durability() == Durability::Directory => {
+            durability::sync_dir(parent).context("syncing directory", parent)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Create or truncate the destination file. The staging tree of swap mode
+/// shares files with the live tree, so there the link is replaced instead of
+/// written through.
+fn create_destination(dst_path: &Path, options: &CopyOptions) -> io::Result<File> {
+    if options.swap_dest {
+        match fs::remove_file(dst_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    } else {
+        attributes::clear_read_only(dst_path)?;
+    }
+    backup_semantics(
+        OpenOptions::new().write(true).create(true).truncate(true),
+        options,
+    )
+    .open(dst_path)
+}
+
+/// Open options that bypass file permissions with the backup and restore
+/// privileges when `/B` is set. Only Windows has such a mode.
+fn backup_semantics<'o>(open: &'o mut OpenOptions, options: &CopyOptions) -> &'o mut OpenOptions {
+    #[cfg(windows)]
+    if options.backup_mode {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        open.custom_flags(FILE_FLAG_BACKUP_SEMANTICS);
+    }
+    #[cfg(not(windows))]
+    let _ = options;
+    open
+}
+
+/// Where the contents of a file are written: the file itself, or with
+/// /SPLIT its parts
+enum Destination<'a> {
+    File(File),
+    Parts(split::PartWriter<'a>),
+}
+
+impl Destination<'_> {
+    /// Write out what the system caches of the destination, as
+    /// `durability` asks
+    fn sync(&mut self, durability: Durability) -> io::Result<()> {
+        match self {
+            Destination::File(file) => durability::sync_file(file, durability),
+            Destination::Parts(parts) => parts.sync(durability),
+        }
+    }
+}
+
+impl Write for Destination<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Destination::File(file) => file.write(buf),
+            Destination::Parts(parts) => parts.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Destination::File(file) => file.flush(),
+            Destination::Parts(parts) => parts.flush(),
+        }
+    }
+}
+
+/// Open the source and destination of a file to copy from `offset` on,
+/// keeping the first `offset` bytes of the destination. With a part size
+/// the destination is written as parts of that size.
+fn open_at<'a>(
     src_path: &Path,
     dst_path: &Path,
+    offset: u64,
+    part_size: Option<u64>,
+    ctx: &CopyContext<'a>,
+) -> io::Result<(File, Destination<'a>)> {
+    let options = ctx.options;
+    #[cfg(feature = "testing")]
+    ctx.inject(FaultOp::Open, src_path)
+        .context("opening", src_path)?;
+    let mut src = backup_semantics(OpenOptions::new().read(true), options)
+        .open(src_path)
+        .context("opening", src_path)?;
+    if offset > 0 {
+        src.seek(SeekFrom::Start(offset))
+            .context("seeking in", src_path)?;
+    }
+    let dst = match part_size {
+        Some(part_size) => Destination::Parts(split::PartWriter::open(
+            dst_path,
+            part_size,
+            offset,
+            options.durability(),
+            Box::new(move |path, keep| open_destination(path, keep, options)),
+        )?),
+        None => Destination::File(open_destination(dst_path, offset, options)?),
+    };
+    Ok((src, dst))
+}
+
+/// Open the destination file keeping its first `offset` bytes, or create it
+/// anew when `offset` is 0
+fn open_destination(dst_path: &Path, offset: u64, options: &CopyOptions) -> io::Result<File> {
+    if offset == 0 {
+        return create_destination(dst_path, options).context("creating", dst_path);
+    }
+    let mut dst = backup_semantics(OpenOptions::new().write(true), options)
+        .open(dst_path)
+        .context("opening", dst_path)?;
+    dst.set_len(offset).context("truncating", dst_path)?;
+    dst.seek(SeekFrom::Start(offset))
+        .context("seeking in", dst_path)?;
+    Ok(dst)
+}
+
+/// Buffered reader of `src`, positioned at `offset`. A read hanging on a
+/// failing disk fails the attempt after the timeout. Salvaging reads past
+/// the errors instead, adding the ranges written as zeros to `damage`.
+fn source_reader<'a>(
+    src: File,
+    offset: u64,
     total_size: u64,
     options: &CopyOptions,
-    progress: &dyn ProgressCallback,
-) -> io::Result<()> {
+    damage: &'a mut Vec<Range<u64>>,
+) -> io::BufReader<Box<dyn Read + 'a>> {
+    let src: Box<dyn Read + 'a> = if options.salvage {
+        Box::new(SalvageReader::new(src, offset, total_size, damage))
+    } else {
+        match options.read_timeout() {
+            Some(timeout) => Box::new(TimedReader::new(src, timeout)),
+            None => Box::new(src),
+        }
+    };
+    io::BufReader::with_capacity(options.buffer_size(), src)
+}
+
+/// What copying the contents of a file found out
+#[derive(Default)]
+struct Copied {
+    /// Hash of the source as it was copied, if it was hashed
+    hash: Option<Hash>,
+    /// Ranges of the source written as zeros, with /SALVAGE
+    damage: Vec<Range<u64>>,
+    /// Whether /SAMPLE checked the copy
+    sampled: bool,
+}
+
+/// Copy the contents of the file at `src_path` to `dst_path`, or with a
+/// part size to parts of that size next to it
+fn copy_file_content(
+    src_path: &Path,
+    dst_path: &Path,
+    total_size: u64,
+    part_size: Option<u64>,
+    ctx: &CopyContext,
+) -> io::Result<Copied> {
+    let CopyContext {
+        options,
+        progress,
+        checkpoint,
+        throttle,
+        ..
+    } = *ctx;
     if options.empty_files {
-        let mut dst_file = File::create(dst_path)?;
-        dst_file.flush()?;
-        return Ok(());
+        let dst_file = create_destination(dst_path, options).context("creating", dst_path)?;
+        durability::sync_file(&dst_file, options.durability()).context("syncing", dst_path)?;
+        return Ok(Copied::default());
     }
 
-    const BUFFER_SIZE: usize = 1024 * 1024; // 1MB buffer for better performance, especially on networks
-    let mut src_file = io::BufReader::with_capacity(BUFFER_SIZE, File::open(src_path)?);
-    let mut dst_file = io::BufWriter::with_capacity(BUFFER_SIZE, File::create(dst_path)?);
+    // Continue from the offset recorded by an interrupted attempt, as long
+    // as the source is unchanged since and the partial destination still
+    // covers it
+    let src_meta = fs::metadata(src_path).context("reading metadata of", src_path)?;
+    let written = match part_size {
+        Some(_) => split::written_len(dst_path),
+        None => fs::metadata(dst_path).map_or(0, |m| m.len()),
+    };
+    let resume_from = checkpoint
+        .and_then(|c| c.resume_offset(src_path, &src_meta))
+        .filter(|&offset| written >= offset)
+        .unwrap_or(0);
+
+    let (src, dst) = open_at(src_path, dst_path, resume_from, part_size, ctx)?;
+    // What the source was when opened, to tell whether it changed during
+    // a /DEEPPAUSE
+    let opened = options.deep_pause.then(|| FileState {
+        size: src_meta.len(),
+        mtime: mtime_nanos(&src_meta),
+        ..Default::default()
+    });
+
+    let mut damage = Vec::new();
+    let buffer_size = options.buffer_size();
+    let mut src_file = source_reader(src, resume_from, total_size, options, &mut damage);
+    let mut dst_file = io::BufWriter::with_capacity(buffer_size, dst);
+
+    let mut buffer = vec![0; buffer_size];
+    let mut bytes_copied: u64 = resume_from;
+    let mut last_checkpoint = resume_from;
 
-    let mut buffer = vec![0; BUFFER_SIZE];
-    let mut bytes_copied: u64 = 0;
+    // Copies picked by /SAMPLE are checked, in full like /VERIFY checks
+    // them all or only at their ends. The ends of parts are not those of
+    // the file, so parts are checked in full.
+    let sample = options
+        .sample
+        .filter(|sample| !options.verify && sample.picks());
+    let verifies = options.verify
+        || matches!(sample, Some(Sampling::Percent(_)))
+        || (part_size.is_some() && sample.is_some());
+
+    // The source is hashed as it is copied, unless part of it was copied
+    // by an earlier attempt
+    let mut hasher = ((options.keeps_hashes() || verifies) && resume_from == 0).then(Hasher::new);
 
     // Create a local progress info to update
     let mut progress_info = ProgressInfo {
@@ -404,26 +2004,154 @@ fn copy_file_content(
         if progress.is_cancelled() {
             return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
         }
+        if let Some(opened) = opened.filter(|_| progress.is_paused()) {
+            // Close both files between chunks, so nothing stays locked
+            // while paused, and carry on from here as a resumed attempt
+            // would
+            dst_file.flush().context("writing", dst_path)?;
+            if checkpoint.is_some() {
+                // On disk before the journal says it was copied
+                dst_file
+                    .get_mut()
+                    .sync(Durability::File)
+                    .context("syncing", dst_path)?;
+            }
+            drop(src_file);
+            drop(dst_file);
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.mark_partial(src_path, bytes_copied, &src_meta);
+                last_checkpoint = bytes_copied;
+            }
+            progress.wait_if_paused();
+            if progress.is_cancelled() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+            }
+
+            let meta = fs::metadata(src_path).context("reading metadata of", src_path)?;
+            if meta.len() != opened.size || mtime_nanos(&meta) != opened.mtime {
+                // The retry starts the file over
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.mark_partial(src_path, 0, &meta);
+                }
+                return Err(io::Error::other("Source changed while paused"));
+            }
+            let (src, dst) = open_at(src_path, dst_path, bytes_copied, part_size, ctx)?;
+            src_file = source_reader(src, bytes_copied, total_size, options, &mut damage);
+            dst_file = io::BufWriter::with_capacity(buffer_size, dst);
+        }
         progress.wait_if_paused();
 
-        let bytes_read = src_file.read(&mut buffer)?;
+        #[cfg(feature = "testing")]
+        ctx.inject(FaultOp::Read, src_path)
+            .context("reading", src_path)?;
+        let bytes_read = src_file.read(&mut buffer).context("reading", src_path)?;
         if bytes_read == 0 {
             break;
         }
 
-        dst_file.write_all(&buffer[..bytes_read])?;
+        if let Some(throttle) = throttle {
+            throttle.take(bytes_read as u64, progress);
+        }
+        #[cfg(feature = "testing")]
+        ctx.inject(FaultOp::Write, dst_path)
+            .context("writing", dst_path)?;
+        dst_file
+            .write_all(&buffer[..bytes_read])
+            .context("writing", dst_path)?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&buffer[..bytes_read]);
+        }
 
         if options.restartable {
-            dst_file.flush()?;
+            dst_file.flush().context("writing", dst_path)?;
         }
 
         bytes_copied += bytes_read as u64;
 
-        // Update progress
+        if let Some(checkpoint) = checkpoint {
+            if bytes_copied - last_checkpoint >= CHECKPOINT_INTERVAL {
+                dst_file.flush().context("writing", dst_path)?;
+                // On disk before the journal says it was copied
+                dst_file
+                    .get_mut()
+                    .sync(Durability::File)
+                    .context("syncing", dst_path)?;
+                checkpoint.mark_partial(src_path, bytes_copied, &src_meta);
+                last_checkpoint = bytes_copied;
+            }
+        }
+
+        // Bytes still in the write buffer are not counted as done yet
+        progress_info.current_file_bytes_done = bytes_copied - dst_file.buffer().len() as u64;
+        progress.on_progress(&progress_info);
+    }
+
+    // A sync writes out everything the system still caches of the file
+    let syncs = options.durability() >= Durability::File;
+    let pending = if syncs {
+        bytes_copied - resume_from
+    } else {
+        dst_file.buffer().len() as u64
+    };
+    let finalizing = pending >= FINALIZE_REPORT_BYTES;
+    if finalizing {
+        progress_info.state = ProgressState::Finalizing;
+        progress.on_progress(&progress_info);
+    }
+    dst_file.flush().context("writing", dst_path)?;
+    dst_file
+        .get_mut()
+        .sync(options.durability())
+        .context("syncing", dst_path)?;
+    if finalizing || progress_info.current_file_bytes_done < bytes_copied {
+        progress_info.state = ProgressState::Copying;
         progress_info.current_file_bytes_done = bytes_copied;
         progress.on_progress(&progress_info);
     }
+    let parts = match dst_file.get_ref() {
+        Destination::Parts(parts) => Some(parts.parts()),
+        Destination::File(_) => None,
+    };
+    drop(dst_file);
+    drop(src_file);
 
-    dst_file.flush()?;
-    Ok(())
+    let hash = hasher.map(|hasher| hasher.finalize());
+    if verifies {
+        let expected = match hash {
+            Some(hash) => hash,
+            None => verify::hash_file(src_path).context("hashing", src_path)?,
+        };
+        match parts {
+            Some(parts) => verify::verify_parts(
+                expected,
+                (1..=parts).map(|number| split::part_path(dst_path, number)),
+            ),
+            None => verify::verify_copy(expected, dst_path),
+        }
+        .context("checking", dst_path)?;
+    } else if sample == Some(Sampling::Ends) {
+        verify::verify_ends(src_path, dst_path).context("checking", dst_path)?;
+    }
+
+    // The manifest is written last, once the parts are complete
+    if let (Some(part_size), Some(parts)) = (part_size, parts) {
+        let manifest = SplitManifest {
+            name: dst_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            size: bytes_copied,
+            part_size,
+            parts,
+        };
+        let path = split::manifest_path(dst_path);
+        let file = create_destination(&path, options).context("creating", &path)?;
+        split::finish(dst_path, &manifest, file, options.durability()).context("writing", &path)?;
+    }
+    Ok(Copied {
+        hash,
+        damage,
+        sampled: sample.is_some(),
+    })
 }
@@ -0,0 +1,102 @@
+//! Breaking cycles made by links inside the source.
+//!
+//! A symlink or junction inside the source pointing at one of the folders
+//! above it, or at the destination, makes the walk go round for ever: each
+//! pass copies the tree into itself once more. Comparing paths does not
+//! catch it, as the link has a path of its own. The walk therefore
+//! remembers the identity of each directory above the current one (device
+//! and inode on Unix, the path with links resolved elsewhere) and leaves
+//! out a directory met again.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+#[cfg(not(unix))]
+use std::path::PathBuf;
+
+#[cfg(unix)]
+type Key = (u64, u64);
+#[cfg(not(unix))]
+type Key = PathBuf;
+
+/// Identity of a directory, the same whatever link it is reached through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirId(Key);
+
+impl DirId {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let meta = fs::metadata(path)?;
+            Ok(DirId((meta.dev(), meta.ino())))
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(DirId(fs::canonicalize(path)?))
+        }
+    }
+}
+
+/// Why a directory is left out of the walk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Revisit {
+    /// It is one of the directories above it
+    Ancestor,
+    /// It is the destination
+    Destination,
+}
+
+/// A directory being walked, linked to the one above it
+#[derive(Debug)]
+pub struct Ancestors<'a> {
+    id: DirId,
+    parent: Option<&'a Ancestors<'a>>,
+}
+
+impl<'a> Ancestors<'a> {
+    /// Enter the directory at `path` below `parent`, failing if the walk
+    /// has been there. `None` if its identity cannot be read, in which case
+    /// it is walked unchecked.
+    pub fn enter(
+        path: &Path,
+        parent: Option<&'a Ancestors<'a>>,
+        destination: Option<&DirId>,
+    ) -> Result<Option<Self>, Revisit> {
+        let Ok(id) = DirId::of(path) else {
+            return Ok(None);
+        };
+        if destination == Some(&id) {
+            return Err(Revisit::Destination);
+        }
+        if parent.is_some_and(|parent| parent.contains(&id)) {
+            return Err(Revisit::Ancestor);
+        }
+        Ok(Some(Ancestors { id, parent }))
+    }
+
+    fn contains(&self, id: &DirId) -> bool {
+        let mut current = Some(self);
+        while let Some(dir) = current {
+            if dir.id == *id {
+                return true;
+            }
+            current = dir.parent;
+        }
+        false
+    }
+}
+
+/// Warning logged for a directory left out of the walk
+pub fn describe(path: &Path, revisit: Revisit) -> String {
+    match revisit {
+        Revisit::Ancestor => format!(
+            "Warning: Skipping {}, a link to a directory above it",
+            path.display()
+        ),
+        Revisit::Destination => format!(
+            "Warning: Skipping {}, a link to the destination",
+            path.display()
+        ),
+    }
+}
@@ -0,0 +1,153 @@
+//! Time limits on jobs and on reads of the source.
+//!
+//! A nightly job started with `/DEADLINE` stops when its time is up, so it
+//! does not run into office hours. The files it did not get to are copied
+//! by the next run; the run is recorded as partial rather than completed.
+//!
+//! A dying disk can take minutes to give up on a bad sector, and a hung
+//! network share never does, which stalls a thread for good. With
+//! `/IOTIMEOUT` the source is read on a helper thread, and a read taking
+//! longer than the timeout fails that attempt of the file so it is retried
+//! with a fresh handle. The helper stays blocked in the read until the
+//! system gives up on it; nothing else waits for it.
+
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// When a job has to stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deadline {
+    /// This many minutes after the job starts
+    After { minutes: u64 },
+    /// The next time the clock shows this time of day, in minutes after
+    /// midnight
+    At { minute_of_day: u32 },
+}
+
+impl Deadline {
+    /// Parse a number of minutes, or a time of day as `HH:MM`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+            return Ok(Deadline::At {
+                minute_of_day: time.hour() * 60 + time.minute(),
+            });
+        }
+        value
+            .parse::<u64>()
+            .map(|minutes| Deadline::After { minutes })
+            .map_err(|_| format!("Invalid deadline: {} (expected minutes or HH:MM)", value))
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Deadline::After { minutes } => minutes.to_string(),
+            Deadline::At { minute_of_day } => {
+                format!("{:02}:{:02}", minute_of_day / 60, minute_of_day % 60)
+            }
+        }
+    }
+
+    /// Time left from now until the deadline
+    pub fn remaining(self) -> Duration {
+        match self {
+            Deadline::After { minutes } => Duration::from_secs(minutes * 60),
+            Deadline::At { minute_of_day } => {
+                let now = Local::now();
+                let time = NaiveTime::from_num_seconds_from_midnight_opt(minute_of_day * 60, 0)
+                    .unwrap_or_default();
+                let today = Local
+                    .from_local_datetime(&now.date_naive().and_time(time))
+                    .earliest();
+                let next = match today {
+                    Some(today) if today > now => today,
+                    _ => {
+                        let tomorrow = now.date_naive().succ_opt().unwrap_or(now.date_naive());
+                        Local
+                            .from_local_datetime(&tomorrow.and_time(time))
+                            .earliest()
+                            .unwrap_or(now)
+                    }
+                };
+                (next - now).to_std().unwrap_or_default()
+            }
+        }
+    }
+
+    /// The moment the deadline passes, for a job starting now
+    pub fn instant(self) -> Instant {
+        Instant::now() + self.remaining()
+    }
+}
+
+/// Reads through a helper thread, failing reads that take too long
+pub struct TimedReader {
+    requests: Sender<Vec<u8>>,
+    replies: Receiver<(Vec<u8>, io::Result<usize>)>,
+    /// Buffer handed to the helper for the next read
+    spare: Vec<u8>,
+    timeout: Duration,
+    timed_out: bool,
+}
+
+impl TimedReader {
+    pub fn new<R: Read + Send + 'static>(mut reader: R, timeout: Duration) -> Self {
+        let (requests, wanted) = mpsc::channel::<Vec<u8>>();
+        let (reply, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for mut buffer in wanted {
+                let result = reader.read(&mut buffer);
+                if reply.send((buffer, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        TimedReader {
+            requests,
+            replies,
+            spare: Vec::new(),
+            timeout,
+            timed_out: false,
+        }
+    }
+
+    fn timed_out_error(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Read timed out after {} s", self.timeout.as_secs()),
+        )
+    }
+}
+
+impl Read for TimedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The helper may still be stuck in the read that timed out
+        if self.timed_out {
+            return Err(self.timed_out_error());
+        }
+
+        let mut buffer = std::mem::take(&mut self.spare);
+        buffer.resize(buf.len(), 0);
+        self.requests
+            .send(buffer)
+            .map_err(|_| io::Error::other("Reader thread stopped"))?;
+        match self.replies.recv_timeout(self.timeout) {
+            Ok((buffer, result)) => {
+                let read = result?;
+                buf[..read].copy_from_slice(&buffer[..read]);
+                self.spare = buffer;
+                Ok(read)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.timed_out = true;
+                Err(self.timed_out_error())
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("Reader thread stopped")),
+        }
+    }
+}
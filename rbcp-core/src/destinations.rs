@@ -0,0 +1,157 @@
+//! Settings tuned for each kind of destination.
+//!
+//! A NAS wants many threads and a bandwidth cap during office hours, a USB
+//! hard disk one thread and a file synced before the next, a folder synced
+//! to the cloud a throttle so the sync client keeps up. Destination profiles
+//! are kept in `destinations.json` in the data directory, each naming the
+//! destinations it is for by path prefix or by volume, so a USB disk is
+//! recognised whatever drive letter it gets. When a job starts, the profile
+//! of its destination fills in the job's threads, buffer size, sync level
+//! and throttle, for the settings it has and the job leaves at their
+//! defaults.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::durability::Durability;
+use crate::utils::{data_dir, volume_id};
+
+/// Settings for the destinations of one kind
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DestinationProfile {
+    pub name: String,
+    /// Destinations at or below one of these paths use the profile
+    pub paths: Vec<String>,
+    /// Destinations on one of these volumes use the profile, by serial
+    /// number on Windows (as shown by `vol`) or file system UUID on Linux
+    /// (as shown by `lsblk -f`)
+    pub volumes: Vec<String>,
+    pub threads: Option<usize>,
+    pub buffer_kb: Option<usize>,
+    pub sync: Option<Durability>,
+    /// Bandwidth cap in MB/s
+    pub throttle_mbps: Option<f64>,
+}
+
+impl DestinationProfile {
+    /// Fill in the settings of `options` the profile has, where they are
+    /// still at their defaults. Settings given for the job win.
+    pub fn apply(&self, options: &mut CopyOptions) {
+        let defaults = CopyOptions::default();
+        let threads_given =
+            options.threads != defaults.threads || options.auto_threads != defaults.auto_threads;
+        if let Some(threads) = self.threads.filter(|_| !threads_given) {
+            options.threads = threads.max(1);
+        }
+        if let Some(buffer_kb) = self
+            .buffer_kb
+            .filter(|_| options.buffer_kb == defaults.buffer_kb)
+        {
+            options.buffer_kb = buffer_kb;
+        }
+        if let Some(sync) = self.sync.filter(|_| options.sync == defaults.sync) {
+            options.sync = sync;
+        }
+        if let Some(mbps) = self
+            .throttle_mbps
+            .filter(|_| options.throttle_mbps == defaults.throttle_mbps)
+        {
+            options.throttle_mbps = mbps;
+        }
+    }
+
+    /// Length of the longest of the profile's paths `destination` is at or
+    /// below, if any
+    fn path_match(&self, destination: &Path) -> Option<usize> {
+        self.paths
+            .iter()
+            .filter(|prefix| is_below(destination, &absolute(Path::new(prefix))))
+            .map(String::len)
+            .max()
+    }
+}
+
+/// `path` with links resolved, or made absolute if it does not exist yet
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether `path` is `dir` or below it, ignoring case where names do
+fn is_below(path: &Path, dir: &Path) -> bool {
+    if cfg!(windows) {
+        let lower = |path: &Path| PathBuf::from(path.to_string_lossy().to_lowercase());
+        lower(path).starts_with(lower(dir))
+    } else {
+        path.starts_with(dir)
+    }
+}
+
+/// The destination profiles of this machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DestinationProfiles {
+    pub profiles: Vec<DestinationProfile>,
+}
+
+impl DestinationProfiles {
+    /// Default location of the profiles
+    pub fn default_path() -> PathBuf {
+        data_dir().join("destinations.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DestinationProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Profile for `destination`: the one with the longest path it is at
+    /// or below, else the first one naming its volume
+    pub fn find(&self, destination: &Path) -> Option<&DestinationProfile> {
+        let destination = absolute(destination);
+        if let Some((_, profile)) = self
+            .profiles
+            .iter()
+            .filter_map(|profile| Some((profile.path_match(&destination)?, profile)))
+            .max_by_key(|(len, _)| *len)
+        {
+            return Some(profile);
+        }
+
+        if self
+            .profiles
+            .iter()
+            .all(|profile| profile.volumes.is_empty())
+        {
+            return None;
+        }
+        let volume = volume_id(&destination)?;
+        self.profiles.iter().find(|profile| {
+            profile
+                .volumes
+                .iter()
+                .any(|id| id.eq_ignore_ascii_case(&volume))
+        })
+    }
+}
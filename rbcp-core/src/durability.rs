@@ -0,0 +1,72 @@
+//! How sure a copy is to survive a power cut.
+//!
+//! Written data normally sits in the operating system's cache for a while
+//! before it reaches the disk, and a file whose data has been synced can
+//! still vanish if the directory entry naming it has not. When the source is
+//! deleted or wiped after copying, the copy must be on stable storage first.
+//! The durability level says how much syncing a copy does before it counts
+//! as done, trading speed for safety.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// What is synced to stable storage before a file counts as copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    /// Leave it to the operating system
+    #[default]
+    None,
+    /// Sync each file's data and metadata
+    File,
+    /// Also sync the directory holding each file, so its entry is on disk
+    Directory,
+}
+
+impl Durability {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_uppercase().as_str() {
+            "NONE" => Ok(Durability::None),
+            "FILE" => Ok(Durability::File),
+            "DIR" => Ok(Durability::Directory),
+            _ => Err(format!(
+                "Unknown durability: {} (expected none, file or dir)",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Durability::None => "NONE",
+            Durability::File => "FILE",
+            Durability::Directory => "DIR",
+        }
+    }
+}
+
+/// Sync the entries of directory `dir` to stable storage. Windows commits
+/// directory changes through the NTFS journal and cannot sync a directory
+/// handle, so there this does nothing.
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        File::open(dir)?.sync_all()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(())
+    }
+}
+
+/// Sync `file` if `durability` asks for it
+pub fn sync_file(file: &File, durability: Durability) -> io::Result<()> {
+    if durability >= Durability::File {
+        file.sync_all()?;
+    }
+    Ok(())
+}
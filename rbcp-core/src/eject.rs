@@ -0,0 +1,280 @@
+//! Ejecting the destination drive after a job.
+//!
+//! Pulling a USB disk right after a copy loses whatever is still in the
+//! write cache. Ejecting flushes the cache, unmounts the volume and, where
+//! the platform allows, powers the device down, so it is safe to unplug as
+//! soon as the job reports it. Linux uses udisks, falling back to `umount`
+//! and `eject`; macOS uses `diskutil`; Windows locks, dismounts and ejects
+//! the volume itself.
+
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+
+/// Flush and eject the removable drive holding `path`, returning the mount
+/// point or volume that was ejected
+pub fn eject(path: &Path) -> io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+
+    #[cfg(windows)]
+    {
+        windows::eject(&path)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux::eject(&path)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mount_point = macos::mount_point(&path)?;
+        run(Command::new("diskutil").arg("eject").arg(&mount_point))?;
+        Ok(mount_point)
+    }
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ejecting drives is not supported on this platform",
+        ))
+    }
+}
+
+/// Run an external command, failing with its error output if it fails
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(command: &mut Command) -> io::Result<()> {
+    let output = command.output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(io::Error::other(format!(
+        "{:?} failed: {}",
+        command.get_program(),
+        stderr.trim()
+    )))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use super::run;
+
+    /// Device and mount point of the filesystem holding `path`
+    fn mount_of(path: &Path) -> io::Result<(String, PathBuf)> {
+        let mounts = fs::read_to_string("/proc/self/mounts")?;
+        mounts
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let device = unescape(fields.next()?);
+                let mount_point = PathBuf::from(unescape(fields.next()?));
+                path.starts_with(&mount_point)
+                    .then_some((device, mount_point))
+            })
+            .max_by_key(|(_, mount_point)| mount_point.as_os_str().len())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no mount point found"))
+    }
+
+    /// Undo the octal escapes of spaces and tabs in /proc/self/mounts
+    fn unescape(field: &str) -> String {
+        field
+            .replace("\\040", " ")
+            .replace("\\011", "\t")
+            .replace("\\134", "\\")
+    }
+
+    pub fn eject(path: &Path) -> io::Result<PathBuf> {
+        let (device, mount_point) = mount_of(path)?;
+        if !device.starts_with("/dev/") || mount_point == Path::new("/") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not on an ejectable drive", path.display()),
+            ));
+        }
+
+        unsafe { libc::sync() };
+
+        // udisks lets desktop users eject without root
+        let unmounted = run(Command::new("udisksctl")
+            .args(["unmount", "--no-user-interaction", "-b"])
+            .arg(&device));
+        match unmounted {
+            Ok(()) => {
+                run(Command::new("udisksctl")
+                    .args(["power-off", "--no-user-interaction", "-b"])
+                    .arg(&device))?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                run(Command::new("umount").arg(&mount_point))?;
+                run(Command::new("eject").arg(&device))?;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(mount_point)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    pub fn mount_point(path: &Path) -> io::Result<PathBuf> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mount_point = unsafe { CStr::from_ptr(stat.f_mntonname.as_ptr()) };
+        Ok(PathBuf::from(mount_point.to_string_lossy().into_owned()))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Path, PathBuf, Prefix};
+    use std::ptr::null_mut;
+    use std::time::Duration;
+
+    type Handle = *mut c_void;
+
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const GENERIC_WRITE: u32 = 0x4000_0000;
+    const FILE_SHARE_READ: u32 = 0x0000_0001;
+    const FILE_SHARE_WRITE: u32 = 0x0000_0002;
+    const OPEN_EXISTING: u32 = 3;
+    const FSCTL_LOCK_VOLUME: u32 = 0x0009_0018;
+    const FSCTL_DISMOUNT_VOLUME: u32 = 0x0009_0020;
+    const IOCTL_STORAGE_MEDIA_REMOVAL: u32 = 0x002D_4804;
+    const IOCTL_STORAGE_EJECT_MEDIA: u32 = 0x002D_4808;
+    const LOCK_ATTEMPTS: usize = 10;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileW(
+            name: *const u16,
+            access: u32,
+            share: u32,
+            security: *mut c_void,
+            disposition: u32,
+            flags: u32,
+            template: Handle,
+        ) -> Handle;
+        fn DeviceIoControl(
+            device: Handle,
+            code: u32,
+            input: *const c_void,
+            input_size: u32,
+            output: *mut c_void,
+            output_size: u32,
+            returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+        fn FlushFileBuffers(file: Handle) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        std::ffi::OsStr::new(text)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    /// Open volume, closed when dropped
+    struct Volume(Handle);
+
+    impl Volume {
+        fn control(&self, code: u32, input: &[u8]) -> io::Result<()> {
+            let mut returned = 0;
+            let ok = unsafe {
+                DeviceIoControl(
+                    self.0,
+                    code,
+                    input.as_ptr() as *const c_void,
+                    input.len() as u32,
+                    null_mut(),
+                    0,
+                    &mut returned,
+                    null_mut(),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Volume {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    pub fn eject(path: &Path) -> io::Result<PathBuf> {
+        let letter = match path.components().next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter as char,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is not on a drive letter", path.display()),
+                    ))
+                }
+            },
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        };
+
+        let root = format!("{}:\\", letter);
+        let name = wide(&format!("\\\\.\\{}:", letter));
+        let handle = unsafe {
+            CreateFileW(
+                name.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                null_mut(),
+                OPEN_EXISTING,
+                0,
+                null_mut(),
+            )
+        };
+        if handle as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let volume = Volume(handle);
+
+        if unsafe { FlushFileBuffers(volume.0) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Explorer or an antivirus may hold the volume for a moment
+        let mut attempt = 1;
+        while let Err(e) = volume.control(FSCTL_LOCK_VOLUME, &[]) {
+            if attempt == LOCK_ATTEMPTS {
+                return Err(e);
+            }
+            attempt += 1;
+            std::thread::sleep(Duration::from_millis(500));
+        }
+
+        volume.control(FSCTL_DISMOUNT_VOLUME, &[])?;
+        // PREVENT_MEDIA_REMOVAL with PreventMediaRemoval = FALSE
+        volume.control(IOCTL_STORAGE_MEDIA_REMOVAL, &[0])?;
+        volume.control(IOCTL_STORAGE_EJECT_MEDIA, &[])?;
+        Ok(PathBuf::from(root))
+    }
+}
@@ -0,0 +1,226 @@
+//! Administrator rights and backup privileges.
+//!
+//! Copying Program Files or other users' profiles on Windows needs an
+//! elevated process, and reading files regardless of their ACLs needs the
+//! backup and restore privileges that `/B` asks for. This module tells
+//! whether the process is elevated, enables those privileges and relaunches
+//! the process elevated. Other platforms treat root as elevated and have no
+//! privileges to enable.
+
+use std::io;
+
+/// Whether the process runs with administrator rights
+pub fn is_elevated() -> bool {
+    #[cfg(windows)]
+    {
+        windows::is_elevated()
+    }
+    #[cfg(unix)]
+    {
+        unsafe { libc::geteuid() == 0 }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        false
+    }
+}
+
+/// Enable the privileges to read and write files regardless of their
+/// permissions. Fails unless the process is elevated.
+pub fn enable_backup_privileges() -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        windows::enable_privileges(&["SeBackupPrivilege", "SeRestorePrivilege"])
+    }
+    #[cfg(not(windows))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backup privileges are only available on Windows",
+        ))
+    }
+}
+
+/// Start the current executable again with the same arguments, asking the
+/// user for administrator rights. The caller is expected to exit afterwards.
+pub fn relaunch_elevated() -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        windows::relaunch_elevated()
+    }
+    #[cfg(not(windows))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "relaunching elevated is only supported on Windows",
+        ))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+
+    type Handle = *mut c_void;
+
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_ADJUST_PRIVILEGES: u32 = 0x0020;
+    const TOKEN_ELEVATION: u32 = 20;
+    const SE_PRIVILEGE_ENABLED: u32 = 0x0002;
+    const ERROR_NOT_ALL_ASSIGNED: i32 = 1300;
+    const SW_SHOWNORMAL: i32 = 1;
+
+    #[repr(C)]
+    struct Luid {
+        low_part: u32,
+        high_part: i32,
+    }
+
+    #[repr(C)]
+    struct TokenPrivileges {
+        count: u32,
+        luid: Luid,
+        attributes: u32,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: Handle, access: u32, token: *mut Handle) -> i32;
+        fn GetTokenInformation(
+            token: Handle,
+            class: u32,
+            info: *mut c_void,
+            length: u32,
+            returned: *mut u32,
+        ) -> i32;
+        fn LookupPrivilegeValueW(system: *const u16, name: *const u16, luid: *mut Luid) -> i32;
+        fn AdjustTokenPrivileges(
+            token: Handle,
+            disable_all: i32,
+            new_state: *const TokenPrivileges,
+            length: u32,
+            previous: *mut TokenPrivileges,
+            returned: *mut u32,
+        ) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> Handle;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    #[link(name = "shell32")]
+    extern "system" {
+        fn ShellExecuteW(
+            window: Handle,
+            operation: *const u16,
+            file: *const u16,
+            parameters: *const u16,
+            directory: *const u16,
+            show: i32,
+        ) -> Handle;
+    }
+
+    fn wide(text: &std::ffi::OsStr) -> Vec<u16> {
+        text.encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    /// Access token of the current process, closed when dropped
+    struct Token(Handle);
+
+    impl Token {
+        fn open(access: u32) -> io::Result<Self> {
+            let mut handle = null_mut();
+            if unsafe { OpenProcessToken(GetCurrentProcess(), access, &mut handle) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Token(handle))
+        }
+    }
+
+    impl Drop for Token {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    pub fn is_elevated() -> bool {
+        let Ok(token) = Token::open(TOKEN_QUERY) else {
+            return false;
+        };
+        let mut elevated: u32 = 0;
+        let mut returned = 0;
+        let ok = unsafe {
+            GetTokenInformation(
+                token.0,
+                TOKEN_ELEVATION,
+                &mut elevated as *mut u32 as *mut c_void,
+                std::mem::size_of::<u32>() as u32,
+                &mut returned,
+            )
+        };
+        ok != 0 && elevated != 0
+    }
+
+    pub fn enable_privileges(names: &[&str]) -> io::Result<()> {
+        let token = Token::open(TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY)?;
+        for name in names {
+            let mut privileges = TokenPrivileges {
+                count: 1,
+                luid: Luid {
+                    low_part: 0,
+                    high_part: 0,
+                },
+                attributes: SE_PRIVILEGE_ENABLED,
+            };
+            let name = wide(name.as_ref());
+            if unsafe { LookupPrivilegeValueW(null(), name.as_ptr(), &mut privileges.luid) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let ok = unsafe {
+                AdjustTokenPrivileges(token.0, 0, &privileges, 0, null_mut(), null_mut())
+            };
+            // Succeeds without enabling anything if the token lacks the
+            // privilege, which only shows in the last error
+            let error = io::Error::last_os_error();
+            if ok == 0 || error.raw_os_error() == Some(ERROR_NOT_ALL_ASSIGNED) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn relaunch_elevated() -> io::Result<()> {
+        let exe = std::env::current_exe()?;
+        let arguments = std::env::args()
+            .skip(1)
+            .map(|arg| format!("\"{}\"", arg.replace('"', "\\\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let operation = wide("runas".as_ref());
+        let file = wide(exe.as_os_str());
+        let parameters = wide(arguments.as_ref());
+        let result = unsafe {
+            ShellExecuteW(
+                null_mut(),
+                operation.as_ptr(),
+                file.as_ptr(),
+                parameters.as_ptr(),
+                null(),
+                SW_SHOWNORMAL,
+            )
+        };
+        // Values up to 32 are error codes, e.g. when the user declined
+        if result as usize <= 32 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
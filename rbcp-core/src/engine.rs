@@ -1,30 +1,320 @@
 use rayon::ThreadPoolBuilder;
 use std::fs::{self, File};
-use std::path::Path;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::abort::{AbortMonitor, FreeSpaceReserve};
 use crate::args::CopyOptions;
+use crate::artifacts::Artifacts;
+use crate::bench::TuningStore;
+use crate::bitrot::HashManifest;
+use crate::cancel::CancellationToken;
+use crate::checkpoint::Checkpoint;
+use crate::cloud::{PlaceholderList, PlaceholderPolicy, PLACEHOLDER_MANIFEST_NAME};
+use crate::congestion::{ConcurrencyLimit, ThreadController, ADJUST_INTERVAL};
+use crate::content::{CommandFilter, ContentFilter};
+use crate::controls::JobControls;
+use crate::copy::{copy_deferred, copy_directory, copy_listed_files, CopyContext, DeferredFile};
+use crate::cycles::{Ancestors, DirId};
+use crate::destinations::DestinationProfiles;
+use crate::eject;
+use crate::elevation;
+use crate::estimate::Estimate;
+use crate::history::{job_key, RunComparison, RunHistory, RunOutcome, RunRecord};
+use crate::index::ScanIndex;
+use crate::limits::LimitGuard;
+use crate::links::{LinkPolicies, Links};
+use crate::merge::{MergePlan, Precedence};
+use crate::network;
+use crate::notify::{send_notifications, JobReport};
+use crate::owners::{parse_owner_map, OwnerCopier};
+use crate::power;
 use crate::progress::{ProgressCallback, ProgressInfo, ProgressState};
-use crate::stats::Statistics;
-use crate::utils::{format_time, Logger};
+use crate::quarantine::{FailingFile, Quarantine, QuarantineSet};
+use crate::remap::{self, PathMapper};
+use crate::report::{self, ActionLog};
+use crate::retry::FailureReport;
+use crate::roots::{self, Root};
+use crate::salvage::DamageReport;
+use crate::skiplist::{SkipList, SkipMatcher};
+use crate::stall::{StallChange, StallWatch};
+use crate::stats::{Statistics, TopSizes};
+use crate::status::StatusFile;
+use crate::swap;
+#[cfg(feature = "testing")]
+use crate::testing::FaultPlan;
+use crate::tr;
+use crate::utils::{
+    check_writable, format_elapsed, format_time, is_read_only_error, read_file_list, Logger,
+    PathContext,
+};
+
+/// Pause before each pass over deferred locked files
+const DEFER_PASS_DELAY: Duration = Duration::from_secs(2);
+
+/// Time between checks of the abort policy
+const ABORT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between checks of the network policy
+const NETWORK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time between checks of the power source
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Shortest time between progress reports while scanning
+const SCAN_REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Running totals of the pre-scan
+struct Scan {
+    info: ProgressInfo,
+    /// Identity of the destination if it exists, which the scan must not
+    /// enter
+    destination: Option<DirId>,
+    top_sizes: TopSizes,
+    last_report: Instant,
+}
+
+impl Scan {
+    fn enter_dir(&mut self, path: &Path) {
+        self.info.dirs_scanned += 1;
+        self.info.scan_dir = path.to_string_lossy().to_string();
+    }
+
+    fn add_file(&mut self, path: &Path, size: u64) {
+        self.info.files_total += 1;
+        self.info.bytes_total += size;
+        self.top_sizes.files.offer(size, path);
+    }
+}
+
+/// Progress callback of a job that also stops when its token is cancelled
+/// or its deadline passes, and keeps its status file
+struct CancellableProgress {
+    inner: Arc<dyn ProgressCallback>,
+    cancel: CancellationToken,
+    deadline: CancellationToken,
+    status: Option<StatusFile>,
+    /// Number of policies holding the job paused
+    holds: Arc<AtomicUsize>,
+}
+
+impl ProgressCallback for CancellableProgress {
+    fn on_progress(&self, info: &ProgressInfo) {
+        self.inner.on_progress(info);
+        if let Some(error) = self.status.as_ref().and_then(|status| status.update(info)) {
+            self.inner
+                .on_log(&format!("Warning: Could not write status file: {}", error));
+        }
+    }
+    fn on_log(&self, message: &str) {
+        self.inner.on_log(message);
+    }
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled() || self.deadline.is_cancelled() || self.inner.is_cancelled()
+    }
+    fn is_paused(&self) -> bool {
+        self.holds.load(Ordering::Relaxed) > 0 || self.inner.is_paused()
+    }
+}
 
 pub struct CopyEngine {
     options: CopyOptions,
     stats: Arc<Statistics>,
     progress: Arc<dyn ProgressCallback>,
+    /// Cancels the job at `options.deadline`, kept apart from the caller's
+    /// token so a deadline is told from a cancel request
+    deadline: CancellationToken,
+    /// Number of policies, such as the network and battery ones, holding
+    /// the job paused
+    holds: Arc<AtomicUsize>,
+    /// Bandwidth cap and files at once, which may change while it runs
+    controls: Arc<JobControls>,
+    /// Checks each file before it is copied, if set. An error when the
+    /// scanner of the options is not a command that can be run.
+    content_filter: Result<Option<Arc<dyn ContentFilter>>, String>,
+    /// Faults injected into the copies, if set
+    #[cfg(feature = "testing")]
+    faults: Option<Arc<FaultPlan>>,
 }
 
 impl CopyEngine {
-    pub fn new(options: CopyOptions, progress: Arc<dyn ProgressCallback>) -> Self {
+    /// Engine for a job reporting to `progress`, stopped when either
+    /// `progress` or `cancel` says so, using the destination profiles of
+    /// this machine
+    pub fn new(
+        options: CopyOptions,
+        progress: Arc<dyn ProgressCallback>,
+        cancel: CancellationToken,
+    ) -> Self {
+        let profiles = DestinationProfiles::load(&DestinationProfiles::default_path());
+        Self::with_profiles(options, &profiles, progress, cancel)
+    }
+
+    /// Engine like [`CopyEngine::new`] picking the destination profile from
+    /// `profiles`. `DestinationProfiles::default()` picks none.
+    pub fn with_profiles(
+        mut options: CopyOptions,
+        profiles: &DestinationProfiles,
+        progress: Arc<dyn ProgressCallback>,
+        cancel: CancellationToken,
+    ) -> Self {
+        // Options loaded from profiles may leave out the default pattern
+        if options.patterns.is_empty() {
+            options.patterns.push("*.*".to_string());
+        }
+
+        // The profile of the destination fills in the settings the job
+        // leaves at their defaults
+        let profile = match options.destination_profile.as_deref() {
+            Some(name) if name.eq_ignore_ascii_case("NONE") => None,
+            Some(name) => {
+                let profile = profiles.get(name);
+                if profile.is_none() {
+                    progress.on_log(&tr!("destination-profile-missing", name = name));
+                }
+                profile
+            }
+            None => profiles.find(Path::new(&options.destination)),
+        };
+        if let Some(profile) = profile {
+            progress.on_log(&tr!("destination-profile", name = profile.name.as_str()));
+            profile.apply(&mut options);
+        }
+
+        // Settings found by `rbcp bench` replace the given ones
+        if options.tuned {
+            let store = TuningStore::load(&TuningStore::default_path());
+            if let Some(tuning) = store.find(Path::new(&options.destination)) {
+                options.threads = tuning.threads;
+                options.buffer_kb = tuning.buffer_kb;
+                options.auto_threads = false;
+            }
+        }
+
+        // An estimate is a quiet list-only run that leaves no trace
+        if options.estimate_only {
+            options.list_only = true;
+            options.log_file_names = false;
+            options.record_history = false;
+            options.notify = None;
+            options.job_name = None;
+        }
+
+        let deadline = CancellationToken::new();
+        let holds = Arc::new(AtomicUsize::new(0));
+        let controls = Arc::new(JobControls::new(&options));
+        let mut stats = Statistics::new().with_size_format(options.size_format);
+        if options.failure_report.is_some() {
+            stats = stats.with_failure_report();
+        }
+        let stats = Arc::new(stats);
+        let content_filter = options
+            .scan_command
+            .as_deref()
+            .map(CommandFilter::new)
+            .transpose()
+            .map(|filter| filter.map(|filter| Arc::new(filter) as Arc<dyn ContentFilter>));
+        let status = options
+            .status_file
+            .as_ref()
+            .map(|path| StatusFile::new(PathBuf::from(path), stats.clone()));
         Self {
             options,
-            stats: Arc::new(Statistics::new()),
-            progress,
+            stats,
+            progress: Arc::new(CancellableProgress {
+                inner: progress,
+                cancel,
+                deadline: deadline.clone(),
+                status,
+                holds: holds.clone(),
+            }),
+            deadline,
+            holds,
+            controls,
+            content_filter,
+            #[cfg(feature = "testing")]
+            faults: None,
         }
     }
 
+    /// Check each file with `filter` before copying it, instead of the
+    /// scanner of /SCANCMD. Files it blocks are not copied.
+    pub fn with_content_filter(mut self, filter: Arc<dyn ContentFilter>) -> Self {
+        self.content_filter = Ok(Some(filter));
+        self
+    }
+
+    /// Inject the faults of `plan` into the copies of the job, which counts
+    /// them in the plan
+    #[cfg(feature = "testing")]
+    pub fn with_faults(mut self, plan: Arc<FaultPlan>) -> Self {
+        self.faults = Some(plan);
+        self
+    }
+
+    /// Statistics of the job so far
+    pub fn stats(&self) -> Arc<Statistics> {
+        self.stats.clone()
+    }
+
+    /// Controls to change the speed of the job while it runs
+    pub fn controls(&self) -> Arc<JobControls> {
+        self.controls.clone()
+    }
+
     pub fn run(&self) -> std::io::Result<Arc<Statistics>> {
+        let started = SystemTime::now();
+        let span = tracing::info_span!(
+            "job",
+            sources = ?self.options.sources,
+            destination = %self.options.destination
+        );
+        let result = span.in_scope(|| self.run_job());
+        if let Err(e) = &result {
+            tracing::error!(parent: &span, error = %e, "job failed");
+            self.progress.on_progress(&ProgressInfo {
+                state: ProgressState::Failed,
+                ..Default::default()
+            });
+        }
+
+        // Successful runs are recorded by run_job so the comparison is logged
+        if result.is_err() && self.options.record_history {
+            self.record_run(started, RunOutcome::Failed);
+        }
+
+        if let Some(notify) = &self.options.notify {
+            let mut summary = String::new();
+            if let Err(e) = &result {
+                summary.push_str(&format!("Error: {}\n", e));
+            }
+            summary.push_str(&self.stats.to_string());
+
+            let report = JobReport {
+                success: result.is_ok() && !self.progress.is_cancelled(),
+                sources: self.options.sources.clone(),
+                destination: self.options.destination.clone(),
+                summary,
+                failures: self
+                    .stats
+                    .failures
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            };
+            for error in send_notifications(notify, &report) {
+                self.progress.on_log(&error);
+            }
+        }
+
+        result
+    }
+
+    fn run_job(&self) -> std::io::Result<Arc<Statistics>> {
         let dest_dir = &self.options.destination;
         let dest_path = Path::new(dest_dir);
 
@@ -54,58 +344,290 @@ impl CopyEngine {
             }
         }
 
-        // Configure thread pool if needed
-        if self.options.threads > 1 {
-            let _ = ThreadPoolBuilder::new()
-                .num_threads(self.options.threads)
-                .build_global(); // Ignore error if already initialized
+        // Sources must stay inside the destination
+        for (source_dir, subdir) in &self.options.source_subdirs {
+            let inside = Path::new(subdir)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !inside {
+                let msg = format!(
+                    "ERROR: Destination subdirectory must be a relative path inside the destination: {} > {}",
+                    source_dir, subdir
+                );
+                self.progress.on_log(&msg);
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+            }
+        }
+
+        // Copying unscanned is not a way to fail safe
+        if let Err(e) = &self.content_filter {
+            let msg = format!("ERROR: {}", e);
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+
+        // Shredding a purged file in the staging tree would also destroy
+        // the live copy it is linked to
+        if self.options.swap_dest && self.options.shred_files {
+            let msg = "ERROR: /SWAP cannot be combined with /SHRED".to_string();
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
         }
 
+        // Parts and rejoined files are written in place, which in the
+        // staging tree would write through to the live files
+        if self.options.swap_dest && (self.options.split_mb != 0 || self.options.rejoin) {
+            let msg = "ERROR: /SWAP cannot be combined with /SPLIT or /REJOIN".to_string();
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+
+        // Purging compares the destination with the source name by name,
+        // which no longer holds once paths are rewritten
+        if self.options.rewrites_paths() && (self.options.purge || self.options.mirror) {
+            let msg =
+                "ERROR: /REMAP, /ROUTE, /FLAT and /CASE cannot be combined with /PURGE or /MIR"
+                    .to_string();
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+
+        // Parts and rejoined files have no namesake on the other side, so
+        // purging would delete them
+        if (self.options.split_mb > 0 || self.options.rejoin)
+            && (self.options.purge || self.options.mirror)
+        {
+            let msg =
+                "ERROR: /SPLIT and /REJOIN cannot be combined with /PURGE or /MIR".to_string();
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+
+        // Overlaid sources share the destination, so purging for one would
+        // delete the files only the others have
+        if self.options.merge.is_some() && (self.options.purge || self.options.mirror) {
+            let msg = "ERROR: /MERGE cannot be combined with /PURGE or /MIR".to_string();
+            self.progress.on_log(&msg);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+
+        // The job's own pool, so jobs running side by side in one process
+        // each get the threads they were given
+        let pool = if self.options.threads > 1 {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(self.options.threads)
+                .thread_name(|index| format!("rbcp-worker-{}", index))
+                .build()
+                .map_err(std::io::Error::other)?;
+            Some(pool)
+        } else {
+            None
+        };
+
         // Initialize logger
         let log_file = if let Some(log_path) = &self.options.log_file {
-            Some(File::create(log_path)?)
+            Some(File::create(log_path).context("creating log file", Path::new(log_path))?)
         } else {
             None
         };
-        let logger = Logger::new(log_file);
+        let mut logger = Logger::new(log_file);
+        if self.options.print0 || self.options.json_progress {
+            logger = logger.to_stderr();
+        }
+        if self.options.quiet {
+            logger = logger.quiet();
+        }
+
+        // Load the scan index of the previous run, if enabled
+        let mut index = self
+            .options
+            .index_file
+            .as_ref()
+            .map(|path| ScanIndex::load(Path::new(path)));
+
+        if let (Some(index), true) = (&mut index, self.options.fast_skip > 0) {
+            let msg = if index.start_fast_skip(self.options.fast_skip) {
+                "Fast skip: files of unchanged directories are not compared".to_string()
+            } else {
+                "Fast skip: comparing every file in this run".to_string()
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        // Only NTFS keeps a journal of changes a later run can read
+        if let (Some(_), true) = (&index, self.options.use_journal && !cfg!(windows)) {
+            let msg = "Change journals are only read on Windows, using directory timestamps";
+            self.progress.on_log(msg);
+            logger.log(msg);
+        } else if let (Some(index), true) = (&mut index, self.options.use_journal) {
+            for source_dir in &self.options.sources {
+                let msg = if index.attach_journal(Path::new(source_dir)) {
+                    format!("Using change journal for {}", source_dir)
+                } else {
+                    format!(
+                        "Change journal unavailable for {}, using directory timestamps",
+                        source_dir
+                    )
+                };
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+
+        // Open the restart journal of a named job
+        let checkpoint = match &self.options.job_name {
+            Some(job) if self.options.resume => {
+                let msg = format!("Resuming job: {}", job);
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+                Some(Checkpoint::resume(job)?)
+            }
+            Some(job) => Some(Checkpoint::create(job, &self.options)?),
+            None => None,
+        };
 
         // Log start message
         let start_time = SystemTime::now();
         let start_msg = format!(
-            "RBCP - Started: {}\n\
-             Sources: {}\n\
-             Destination: {}\n\
-             Patterns: {}\n\
-             Options: {}\n",
-            format_time(start_time),
-            self.options.sources.join(", "),
-            dest_dir,
-            self.options.patterns.join(" "),
-            self.options.to_string_flags()
+            "{}\n",
+            tr!(
+                "job-started",
+                time = format_time(start_time),
+                sources = self.options.sources.join(", "),
+                destination = dest_dir.as_str(),
+                patterns = self.options.patterns.join(" "),
+                options = self.options.to_string_flags()
+            )
         );
         self.progress.on_log(&start_msg);
         logger.log(&start_msg);
 
-        // Scan source for progress info
-        let mut total_files = 0;
-        let mut total_bytes = 0;
+        if let Some(deadline) = self.options.deadline {
+            let remaining = deadline.remaining();
+            self.deadline.set_deadline(Some(Instant::now() + remaining));
+            let msg = tr!(
+                "deadline-set",
+                time = format_time(SystemTime::now() + remaining)
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
 
-        if self.options.show_progress {
-            let mut info = ProgressInfo::default();
-            info.state = ProgressState::Scanning;
-            self.progress.on_progress(&info);
+        // The log file exists by now, so it is found wherever it is linked
+        let artifacts = Artifacts::new(&self.options);
+        let msg = tr!(
+            "artifacts-excluded",
+            artifacts = artifacts.describe().join(", ")
+        );
+        self.progress.on_log(&msg);
+        logger.log(&msg);
+
+        // /B reads and writes files regardless of their permissions
+        if self.options.backup_mode {
+            let msg = match elevation::enable_backup_privileges() {
+                Ok(()) => "Backup and restore privileges enabled".to_string(),
+                Err(e) => format!("Warning: Could not enable backup privileges: {}", e),
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        // Explicit list of files to copy instead of walking the source,
+        // the same for every source or, retrying failed files, its own
+        let file_list = match &self.options.files_from {
+            Some(list) => Some(read_file_list(list)?),
+            None => None,
+        };
+        if !self.options.retry_files.is_empty() {
+            let count: usize = self.options.retry_files.values().map(Vec::len).sum();
+            let msg = tr!("retry-files", count = count);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+        let listed = |source: &str| self.options.retry_files.get(source).or(file_list.as_ref());
+
+        let skip_list = self.load_skip_list(&logger);
+        let owners = self.owner_copier()?;
+        let quarantine = (self.options.quarantine_runs > 0)
+            .then(|| {
+                let quarantine = Quarantine::load(&Quarantine::default_path());
+                QuarantineSet::new(&quarantine, &job_key(&self.options))
+            })
+            .filter(|quarantine| !quarantine.is_empty());
+
+        // Scan source for progress info. The size report needs the scan
+        // even when progress is off.
+        let mut scan = Scan {
+            info: ProgressInfo {
+                state: ProgressState::Scanning,
+                ..Default::default()
+            },
+            destination: DirId::of(dest_path).ok(),
+            top_sizes: TopSizes::new(self.options.top_sizes, self.options.size_format),
+            last_report: Instant::now(),
+        };
+
+        let limits = self
+            .options
+            .limits
+            .is_active()
+            .then(|| LimitGuard::new(self.options.limits));
+        if (self.options.show_progress && !self.options.no_prescan) || self.options.top_sizes > 0 {
+            self.progress.on_progress(&scan.info);
+            let scan_started = Instant::now();
 
             for source_dir in &self.options.sources {
                 let source_path = Path::new(source_dir);
-                if let Ok((files, bytes)) = self.scan_source(source_path) {
-                    total_files += files;
-                    total_bytes += bytes;
+                if let Some(file_list) = listed(source_dir) {
+                    for relative in file_list {
+                        let path = source_path.join(relative);
+                        if let Ok(metadata) = fs::metadata(&path) {
+                            scan.add_file(&path, metadata.len());
+                            self.report_scan(&mut scan);
+                        }
+                    }
+                } else {
+                    let _ = self.scan_source(
+                        source_path,
+                        0,
+                        None,
+                        &artifacts,
+                        index.as_ref(),
+                        &mut scan,
+                    );
                 }
             }
-            info.files_total = total_files;
-            info.bytes_total = total_bytes;
-            self.progress.on_progress(&info);
+            self.stats.add_scan_time(scan_started.elapsed());
+            scan.info.scan_dir.clear();
+            scan.info.phases = self.stats.phase_times();
+            self.progress.on_progress(&scan.info);
+
+            // Too many files are warned about before copying any of them
+            let warning = limits
+                .as_ref()
+                .and_then(|limits| limits.scanned(scan.info.files_total));
+            if let Some(warning) = warning {
+                let msg = warning.to_string();
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
         }
+        let total_files = scan.info.files_total;
+        let total_bytes = scan.info.bytes_total;
+        let top_sizes = scan.top_sizes;
+
+        // In swap mode the job writes into a staging copy of the destination
+        let live_path = dest_path;
+        let staging = (self.options.swap_dest && !self.options.list_only)
+            .then(|| swap::staging_path(live_path));
+        if let Some(staging) = &staging {
+            let msg = tr!("swap-staging", staging = staging.display().to_string());
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            swap::prepare_staging(live_path, staging, self.options.resume)?;
+        }
+        let dest_path = staging.as_deref().unwrap_or(live_path);
 
         // Create destination directory if it doesn't exist
         if !dest_path.exists() {
@@ -113,7 +635,8 @@ impl CopyEngine {
                 let msg = format!("Creating destination directory: {}", dest_dir);
                 self.progress.on_log(&msg);
                 logger.log(&msg);
-                fs::create_dir_all(dest_path)?;
+                fs::create_dir_all(dest_path).context("creating directory", dest_path)?;
+                self.stats.add_root_dir_created();
             } else {
                 let msg = format!("Would create destination directory: {}", dest_dir);
                 self.progress.on_log(&msg);
@@ -121,6 +644,27 @@ impl CopyEngine {
             }
         }
 
+        // Fail up front rather than with an error for every file
+        if !self.options.list_only {
+            if let Err(e) = check_writable(dest_path) {
+                let reason = if is_read_only_error(&e) {
+                    "the volume is read-only".to_string()
+                } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    "permission denied".to_string()
+                } else {
+                    e.to_string()
+                };
+                let msg = format!(
+                    "ERROR: Cannot write to destination {}: {}",
+                    dest_path.display(),
+                    reason
+                );
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+                return Err(std::io::Error::new(e.kind(), msg));
+            }
+        }
+
         // Perform the copy operation
         let mut info = ProgressInfo {
             state: ProgressState::Copying,
@@ -137,12 +681,16 @@ impl CopyEngine {
             total_files: u64,
             total_bytes: u64,
             start_time: SystemTime,
+            aborted: &'a AtomicBool,
+            /// Progress reports so far, which move with each chunk copied
+            ticks: &'a AtomicU64,
         }
 
         impl<'a> ProgressCallback for ProgressWrapper<'a> {
             fn on_progress(&self, info: &ProgressInfo) {
+                self.ticks.fetch_add(1, Ordering::Relaxed);
+
                 // Merge local file progress with global stats
-                use std::sync::atomic::Ordering;
                 let files_done = self.stats.files_copied.load(Ordering::Relaxed) as u64;
                 let bytes_done = self.stats.bytes_copied.load(Ordering::Relaxed);
 
@@ -155,6 +703,7 @@ impl CopyEngine {
 
                 new_info.files_total = self.total_files;
                 new_info.bytes_total = self.total_bytes;
+                new_info.phases = self.stats.phase_times();
 
                 // Calculate speed
                 if let Ok(duration) = SystemTime::now().duration_since(self.start_time) {
@@ -171,134 +720,1040 @@ impl CopyEngine {
                 self.inner.on_log(message);
             }
             fn is_cancelled(&self) -> bool {
-                self.inner.is_cancelled()
+                self.inner.is_cancelled() || self.aborted.load(Ordering::Relaxed)
             }
             fn is_paused(&self) -> bool {
                 self.inner.is_paused()
             }
         }
 
+        let abort_flag = AtomicBool::new(false);
+        let ticks = AtomicU64::new(0);
         let wrapper = ProgressWrapper {
             inner: self.progress.as_ref(),
             stats: &self.stats,
             total_files,
             total_bytes,
             start_time,
+            aborted: &abort_flag,
+            ticks: &ticks,
         };
 
-        // Handle child-only mode
-        if self.options.child_only {
-            for source_dir in &self.options.sources {
-                let source_path = Path::new(source_dir);
-                if source_path.is_dir() {
-                    if let Ok(entries) = fs::read_dir(source_path) {
-                        let entries: Vec<_> = entries.collect::<Result<Vec<_>, _>>()?;
-
-                        use rayon::prelude::*;
-
-                        let process_child = |entry: &fs::DirEntry| -> std::io::Result<()> {
-                            let child_path = entry.path();
-                            if child_path.is_dir() {
-                                let child_name = child_path
-                                    .file_name()
-                                    .unwrap_or_default()
-                                    .to_string_lossy()
-                                    .to_string();
-                                let child_dest = dest_path.join(&child_name);
-
-                                let msg = format!("\nProcessing child directory: {}", child_name);
-                                self.progress.on_log(&msg);
-                                logger.log(&msg);
-
-                                crate::copy::copy_directory(
-                                    &child_path,
-                                    &child_dest,
-                                    &self.options,
-                                    &logger,
-                                    &self.stats,
-                                    &wrapper,
-                                )?;
-                            }
-                            Ok(())
-                        };
-
-                        if self.options.threads > 1 {
-                            entries.par_iter().try_for_each(process_child)?;
-                        } else {
-                            entries.iter().try_for_each(process_child)?;
-                        }
-                    }
+        let paths = PathMapper::new(dest_path, &self.options)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let trees = self.roots(&paths, file_list.is_some())?;
+
+        // Flattened names are given out in sorted order before the walk,
+        // so every run renames colliding files alike
+        if self.options.flatten.is_some() {
+            let mut files = Vec::new();
+            for root in &trees {
+                match listed(&root.source.to_string_lossy()) {
+                    Some(file_list) if root.child.is_none() => files.extend(
+                        file_list
+                            .iter()
+                            .map(|relative| root.destination.join(relative)),
+                    ),
+                    _ if root.path.is_file() => files.push(if root.destination.is_dir() {
+                        root.destination
+                            .join(root.path.file_name().unwrap_or_default())
+                    } else {
+                        root.destination.clone()
+                    }),
+                    _ => remap::list_files(
+                        &root.path,
+                        &root.destination,
+                        usize::from(root.child.is_some()),
+                        &self.options,
+                        None,
+                        &mut files,
+                    ),
                 }
             }
-        } else {
-            for source_dir in &self.options.sources {
-                let source_path = Path::new(source_dir);
-                let actual_dest_path = if self.options.preserve_root && source_path.is_dir() {
-                    let dir_name = source_path.file_name().unwrap_or_default();
-                    dest_path.join(dir_name)
-                } else {
-                    dest_path.to_path_buf()
+            paths.claim_in_order(files);
+        }
+
+        // Sources overlaid on each other are listed first to settle which
+        // copy of each path wins
+        let merge = match self.options.merge {
+            Some(precedence) => {
+                let roots: Vec<_> = self
+                    .options
+                    .sources
+                    .iter()
+                    .map(|source| {
+                        let source_path = Path::new(source);
+                        (source_path.to_path_buf(), paths.source_root(source_path))
+                    })
+                    .collect();
+                let plan = MergePlan::build(&roots, precedence, &self.options)?;
+                self.report_merge(&plan, precedence, &logger);
+                Some(plan)
+            }
+            None => None,
+        };
+
+        let reserve = FreeSpaceReserve::new(&self.options.abort, live_path, &abort_flag);
+
+        // Files wait for a slot below the limit /MT:AUTO moves, or the
+        // user sets while the job runs
+        let limit =
+            (self.options.auto_threads || self.options.parallel()).then(|| self.controls.limit());
+        if self.options.auto_threads {
+            self.stats.add_thread_limit(self.controls.threads());
+        }
+
+        // Fixed limits only matter below the size of the pool
+        let fixed_limit = |threads: usize| {
+            (threads > 0 && threads < self.options.threads && self.options.parallel())
+                .then(|| ConcurrencyLimit::new(threads))
+        };
+        let data_limit = fixed_limit(self.options.data_threads);
+        let meta_limit = fixed_limit(self.options.meta_threads);
+
+        let destination_id = DirId::of(dest_path).ok();
+
+        let deferred = Mutex::new(Vec::new());
+        let damage = self
+            .options
+            .salvage
+            .then(|| DamageReport::new(self.options.damage_report_path(dest_path)));
+
+        let hashes = self
+            .options
+            .keeps_hashes()
+            .then(|| HashManifest::load(dest_path));
+        let links = (self.options.links != LinkPolicies::default())
+            .then(|| Links::new(&self.options.links, dest_path));
+        // Kept up to date once there is one, as copies replace listings
+        let placeholders = (self.options.cloud_placeholders == PlaceholderPolicy::Metadata
+            || dest_path.join(PLACEHOLDER_MANIFEST_NAME).exists())
+        .then(|| PlaceholderList::load(dest_path));
+        let actions = self.options.html_report.is_some().then(ActionLog::new);
+        let ctx = CopyContext {
+            options: &self.options,
+            logger: &logger,
+            stats: &self.stats,
+            progress: &wrapper,
+            index: index.as_ref(),
+            checkpoint: checkpoint.as_ref(),
+            deferred: (self.options.defer_locked > 0).then_some(&deferred),
+            paths: &paths,
+            reserve: reserve.as_ref(),
+            limit,
+            data_limit: data_limit.as_ref(),
+            meta_limit: meta_limit.as_ref(),
+            merge: merge.as_ref(),
+            depth: 0,
+            root: true,
+            artifacts: &artifacts,
+            ancestors: None,
+            destination_id: destination_id.as_ref(),
+            damage: damage.as_ref(),
+            hashes: hashes.as_ref(),
+            actions: actions.as_ref(),
+            throttle: Some(self.controls.throttle()),
+            skip_list: skip_list.as_ref(),
+            quarantine: quarantine.as_ref(),
+            content_filter: self.content_filter.as_ref().ok().and_then(Option::as_deref),
+            owners: owners.as_ref(),
+            links: links.as_ref(),
+            placeholders: placeholders.as_ref(),
+            limits: limits.as_ref(),
+            #[cfg(feature = "testing")]
+            faults: self.faults.as_deref(),
+        };
+        let copy_started = Instant::now();
+
+        let copy_root = |root: &Root| -> std::io::Result<()> {
+            let Some(child_name) = &root.child else {
+                // A source retried without a list is a file of its own
+                return match listed(&root.source.to_string_lossy()) {
+                    Some(file_list) => {
+                        copy_listed_files(&root.path, &root.destination, file_list, &ctx)
+                    }
+                    None => copy_directory(&root.path, &root.destination, &ctx),
                 };
-                crate::copy::copy_directory(
-                    source_path,
-                    &actual_dest_path,
-                    &self.options,
-                    &logger,
-                    &self.stats,
-                    &wrapper,
-                )?;
+            };
+            let msg = format!("\nProcessing child directory: {}", child_name);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+
+            let child_ctx = CopyContext { depth: 1, ..ctx };
+            let started = Instant::now();
+            self.stats.start_child(&root.path, child_name);
+            let result = copy_directory(&root.path, &root.destination, &child_ctx);
+            self.stats.finish_child(&root.path, started.elapsed());
+            result
+        };
+
+        let copy_roots = || -> std::io::Result<()> {
+            let groups = roots::group(trees);
+            roots::copy_all(&groups, self.options.parallel(), copy_root)?;
+            self.copy_deferred_files(&deferred, &ctx, &logger);
+            Ok(())
+        };
+        let walk = || match &pool {
+            Some(pool) => pool.install(copy_roots),
+            None => copy_roots(),
+        };
+
+        // The abort policy, the throughput, stalls, the network and the
+        // battery are watched from other threads while copying
+        let network_policy = self.options.network.is_active()
+            && network::is_network_path(Path::new(&self.options.destination));
+        let watched = self.options.abort.is_active()
+            || self.options.auto_threads
+            || self.options.stall_secs > 0
+            || network_policy
+            || self.options.battery.is_active();
+        let (result, abort_reason) = if watched {
+            let done = AtomicBool::new(false);
+            std::thread::scope(|scope| {
+                let monitor = self
+                    .options
+                    .abort
+                    .is_active()
+                    .then(|| scope.spawn(|| self.watch_abort_policy(&done, &abort_flag)));
+                if self.options.auto_threads {
+                    let done = &done;
+                    let logger = &logger;
+                    scope.spawn(move || self.adjust_threads(done, logger));
+                }
+                if self.options.stall_secs > 0 {
+                    let (done, ticks, info, logger) = (&done, &ticks, &info, &logger);
+                    scope.spawn(move || self.watch_stall(done, ticks, info, logger));
+                }
+                if network_policy {
+                    let (done, info, logger) = (&done, &info, &logger);
+                    scope.spawn(move || self.watch_network(done, info, logger));
+                }
+                if self.options.battery.is_active() {
+                    let (done, info, logger) = (&done, &info, &logger);
+                    scope.spawn(move || self.watch_battery(done, info, logger));
+                }
+                let result = walk();
+                done.store(true, Ordering::Relaxed);
+                (result, monitor.and_then(|m| m.join().unwrap_or(None)))
+            })
+        } else {
+            (walk(), None)
+        };
+        let abort_reason = abort_reason.or_else(|| reserve.as_ref()?.reason());
+
+        // Hashes of the files copied so far hold even if the job stopped
+        if let (Some(hashes), false) = (&hashes, self.options.list_only) {
+            if let Err(e) = hashes.save() {
+                let msg = format!("Warning: Could not save hash manifest: {}", e);
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+        if let (Some(placeholders), false) = (&placeholders, self.options.list_only) {
+            if let Err(e) = placeholders.save() {
+                let msg = format!("Warning: Could not save list of cloud placeholders: {}", e);
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+        if let (Some(links), false) = (&links, self.options.list_only) {
+            if let Err(e) = links.save() {
+                let msg = format!("Warning: Could not save link manifest: {}", e);
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+        // Files that failed are worth retrying however the job ended
+        if let Some(path) = &self.options.failure_report {
+            let reported = self.stats.reported_failures.as_ref();
+            let failures =
+                reported.map(|failures| failures.lock().unwrap_or_else(|e| e.into_inner()).clone());
+            let report = FailureReport::new(&self.options, &failures.unwrap_or_default());
+            let msg = match report.save(Path::new(path)) {
+                Ok(()) if report.failures.is_empty() => {
+                    tr!("failure-report-clean", path = path.as_str())
+                }
+                Ok(()) => tr!(
+                    "failure-report-written",
+                    count = report.failures.len(),
+                    path = path.as_str()
+                ),
+                Err(e) => format!("Warning: Could not write failure report {}: {}", path, e),
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        let completed = abort_reason.is_none() && result.is_ok() && !self.progress.is_cancelled();
+        let quarantined = self.update_quarantine(completed, &logger);
+        let owned = owners.as_ref().and_then(OwnerCopier::report);
+        // A job that stops here has no summary to list them in
+        let stops = abort_reason.is_some() || result.is_err();
+        if stops {
+            for section in quarantined.iter().chain(&owned) {
+                self.progress.on_log(section);
+                logger.log(section);
             }
         }
+        if let (Some(staging), false) = (&staging, completed) {
+            let msg = tr!(
+                "swap-kept",
+                destination = dest_dir.as_str(),
+                staging = staging.display().to_string()
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+        if let Some(reason) = abort_reason {
+            let msg = tr!("job-aborted", reason = reason);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            return Err(std::io::Error::other(msg));
+        }
+        result?;
+
+        if let Some(damage) = damage.as_ref().filter(|damage| damage.files() > 0) {
+            let msg = tr!(
+                "damage-report",
+                files = damage.files(),
+                report = damage.path().display().to_string()
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        // Files left are copied by the next run, like after a cancel
+        let partial = self.deadline.is_past_deadline();
+        if partial {
+            let handled = self.stats.files_copied.load(Ordering::Relaxed)
+                + self.stats.files_skipped.load(Ordering::Relaxed)
+                + self.stats.files_failed.load(Ordering::Relaxed)
+                + self.stats.files_blocked.load(Ordering::Relaxed);
+            let msg = tr!(
+                "deadline-reached",
+                files = total_files.saturating_sub(handled as u64)
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        if let (Some(staging), true) = (&staging, completed) {
+            swap::swap_into_place(staging, live_path)?;
+            let msg = tr!("swap-done", destination = dest_dir.as_str());
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        // Purging is timed separately as it happens
+        let purge_time = Duration::from_millis(self.stats.purge_ms.load(Ordering::Relaxed));
+        self.stats
+            .add_copy_time(copy_started.elapsed().saturating_sub(purge_time));
+
+        // A list-only run copied nothing, so the index must not claim the
+        // destination is up to date
+        if let (Some(index), false) = (&index, self.options.list_only) {
+            if let Err(e) = index.save() {
+                let msg = format!("Warning: Could not save scan index: {}", e);
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+
+        // The job is complete unless it was cancelled part way
+        if let Some(checkpoint) = checkpoint {
+            if !self.progress.is_cancelled() {
+                let _ = checkpoint.finish();
+            }
+        }
+
+        if self.options.estimate_only {
+            let msg = Estimate::new(&self.options, &self.stats).summary(self.options.size_format);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            self.log_top_sizes(&top_sizes, &logger);
+
+            info.state = ProgressState::Completed;
+            info.files_done = self.stats.files_copied.load(Ordering::Relaxed) as u64;
+            info.bytes_done = self.stats.bytes_copied.load(Ordering::Relaxed);
+            info.categories = self.stats.categories();
+            info.phases = self.stats.phase_times();
+            self.progress.on_progress(&info);
+            return Ok(self.stats.clone());
+        }
 
         // Log completion
         let end_time = SystemTime::now();
         let elapsed = end_time
             .duration_since(start_time)
             .unwrap_or(Duration::from_secs(0));
+        self.stats
+            .elapsed_ms
+            .store(elapsed.as_millis() as u64, Ordering::Relaxed);
 
-        use std::sync::atomic::Ordering;
+        let bytes_copied = self.stats.bytes_copied.load(Ordering::Relaxed);
+        let average_speed = if elapsed.is_zero() {
+            0
+        } else {
+            (bytes_copied as f64 / elapsed.as_secs_f64()) as u64
+        };
         let summary = format!(
-            "RBCP - Finished: {}\n\
-             Sources: {}\n\
-             Destination: {}\n\n\
-             Statistics:\n\
-                 Directories: {}\n\
-                 Files: {}\n\
-                 Bytes: {}\n\
-                 Directories skipped: {}\n\
-                 Files skipped: {}\n\
-                 Files failed: {}\n\
-                 Directories removed: {}\n\
-                 Files removed: {}\n\n\
-             Elapsed time: {} seconds\n",
-            format_time(end_time),
-            self.options.sources.join(", "),
-            dest_dir,
-            self.stats.dirs_created.load(Ordering::Relaxed),
-            self.stats.files_copied.load(Ordering::Relaxed),
-            self.stats.bytes_copied.load(Ordering::Relaxed),
-            self.stats.dirs_skipped.load(Ordering::Relaxed),
-            self.stats.files_skipped.load(Ordering::Relaxed),
-            self.stats.files_failed.load(Ordering::Relaxed),
-            self.stats.dirs_removed.load(Ordering::Relaxed),
-            self.stats.files_removed.load(Ordering::Relaxed),
-            elapsed.as_secs()
+            "{}\n\n{}\n{}\n{}\n",
+            tr!(
+                "job-finished",
+                time = format_time(end_time),
+                sources = self.options.sources.join(", "),
+                destination = dest_dir.as_str()
+            ),
+            self.stats,
+            tr!("job-elapsed", time = format_elapsed(elapsed)),
+            tr!(
+                "job-speed",
+                speed = self.options.size_format.format(average_speed)
+            )
         );
 
         self.progress.on_log(&summary);
         logger.log(&summary);
+        for section in quarantined.iter().chain(&owned) {
+            self.progress.on_log(section);
+            logger.log(section);
+        }
+        self.log_top_sizes(&top_sizes, &logger);
+
+        // Access denied without admin rights is usually fixed by elevating
+        let denied = self.stats.files_denied.load(Ordering::Relaxed) as u64;
+        let needs_elevation = denied > 0 && !elevation::is_elevated();
+        if needs_elevation {
+            let msg = tr!("elevation-hint", files = denied);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        tracing::info!(
+            files = self.stats.files_copied.load(Ordering::Relaxed),
+            bytes = self.stats.bytes_copied.load(Ordering::Relaxed),
+            failed = self.stats.files_failed.load(Ordering::Relaxed),
+            duration_ms = elapsed.as_millis() as u64,
+            "job finished"
+        );
 
-        info.state = ProgressState::Completed;
+        let outcome = if partial {
+            RunOutcome::Partial
+        } else if self.progress.is_cancelled() {
+            RunOutcome::Cancelled
+        } else {
+            RunOutcome::Completed
+        };
+        if let (Some(path), Some(actions)) = (&self.options.html_report, &actions) {
+            let msg = match report::write_html(
+                Path::new(path),
+                &self.options,
+                &self.stats,
+                actions,
+                start_time,
+                elapsed,
+                outcome,
+            ) {
+                Ok(()) => tr!("html-report-written", path = path.as_str()),
+                Err(e) => format!("Warning: Could not write report {}: {}", path, e),
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        if self.options.record_history {
+            if let Some(comparison) = self.record_run(start_time, outcome) {
+                let msg = comparison.summary();
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+            }
+        }
+
+        if self.options.eject && !self.options.list_only && !self.progress.is_cancelled() {
+            // A log file on the drive would keep it busy
+            drop(logger);
+            let msg = match eject::eject(live_path) {
+                Ok(drive) => tr!("eject-done", drive = drive.display().to_string()),
+                Err(e) => tr!("eject-failed", error = e.to_string()),
+            };
+            self.progress.on_log(&msg);
+        }
+
+        info.state = if partial {
+            ProgressState::Partial
+        } else {
+            ProgressState::Completed
+        };
+        info.files_done = self.stats.files_copied.load(Ordering::Relaxed) as u64;
+        info.bytes_done = self.stats.bytes_copied.load(Ordering::Relaxed);
+        info.categories = self.stats.categories();
+        info.phases = self.stats.phase_times();
+        info.needs_elevation = needs_elevation;
         self.progress.on_progress(&info);
 
         Ok(self.stats.clone())
     }
 
-    fn scan_source(&self, path: &Path) -> std::io::Result<(u64, u64)> {
+    /// The trees the job copies: its sources, or with /CHILDONLY the
+    /// selected child folders of each, unless the files are listed
+    fn roots(&self, paths: &PathMapper, files_listed: bool) -> std::io::Result<Vec<Root>> {
+        let listed = files_listed || !self.options.retry_files.is_empty();
+        let mut roots = Vec::new();
+        for source_dir in &self.options.sources {
+            let source_path = Path::new(source_dir);
+            if listed || !self.options.child_only {
+                roots.push(Root::source(source_path, paths.source_root(source_path)));
+                continue;
+            }
+            if !source_path.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(source_path).context("reading directory", source_path)? {
+                let entry = entry.context("reading directory", source_path)?;
+                let child_path = entry.path();
+                let child_name = entry.file_name().to_string_lossy().to_string();
+                if !child_path.is_dir()
+                    || !self.options.child_selected(&child_name)
+                    || self.options.excludes_dir(&child_path)
+                {
+                    continue;
+                }
+                // Children are the second level of the tree
+                if self.options.max_depth == 1 {
+                    self.stats.add_dir_skipped();
+                    continue;
+                }
+                let child_dest = paths.child_root(source_path, &entry.file_name());
+                roots.push(Root::child(source_path, child_path, child_name, child_dest));
+            }
+        }
+        Ok(roots)
+    }
+
+    /// Append this run to the run history and compare it with the previous
+    /// run of the same job
+    fn record_run(&self, started: SystemTime, outcome: RunOutcome) -> Option<RunComparison> {
+        let history = RunHistory::new(&RunHistory::default_path());
+        let record = RunRecord::new(&self.options, &self.stats, started, outcome);
+        let previous = history.runs(&record.job).pop();
+
+        if let Err(e) = history.append(&record) {
+            self.progress
+                .on_log(&format!("Warning: Could not save run history: {}", e));
+        }
+
+        previous.map(|previous| RunComparison::new(previous, record))
+    }
+
+    /// Count the files that failed in this run towards their quarantine,
+    /// returning the summary section listing the job's quarantined files
+    /// if it has any
+    fn update_quarantine(&self, completed: bool, logger: &Logger) -> Option<String> {
+        let runs = self.options.quarantine_runs;
+        if runs == 0 || self.options.list_only {
+            return None;
+        }
+        let path = Quarantine::default_path();
+        let mut quarantine = Quarantine::load(&path);
+        let job = job_key(&self.options);
+        let failures = self
+            .stats
+            .failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let new = quarantine.record_run(&job, &failures, completed, runs);
+        if let Err(e) = quarantine.save(&path) {
+            let msg = tr!(
+                "quarantine-unsaved",
+                path = path.display().to_string(),
+                error = e.to_string()
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+
+        let files = quarantine.quarantined(&job);
+        if self.options.quarantine_skip_list && !new.is_empty() {
+            self.skip_list_quarantined(&files, &new, logger);
+        }
+        if files.is_empty() {
+            return None;
+        }
+        let mut section = tr!("quarantine-title", runs = runs);
+        for (path, file) in files {
+            let id = if new.iter().any(|new| new == path) {
+                "quarantine-new"
+            } else {
+                "quarantine-file"
+            };
+            section.push_str("\n    ");
+            section.push_str(&tr!(
+                id,
+                path = path.display().to_string(),
+                reason = file.reason.as_str()
+            ));
+        }
+        section.push('\n');
+        Some(section)
+    }
+
+    /// Put the files quarantined by this run on the skip-list
+    fn skip_list_quarantined(
+        &self,
+        files: &[(&Path, &FailingFile)],
+        new: &[PathBuf],
+        logger: &Logger,
+    ) {
+        let path = self
+            .options
+            .skip_list
+            .as_ref()
+            .map_or_else(SkipList::default_path, PathBuf::from);
+        let result = SkipList::load(&path).and_then(|mut list| {
+            for (file, failing) in files
+                .iter()
+                .filter(|(file, _)| new.iter().any(|new| new == file))
+            {
+                let reason = tr!(
+                    "quarantine-skip-reason",
+                    runs = failing.runs,
+                    reason = failing.reason.as_str()
+                );
+                list.add(file, false, &reason)?;
+            }
+            list.save(&path)
+        });
+        if let Err(e) = result {
+            let msg = tr!(
+                "skip-list-unsaved",
+                path = path.display().to_string(),
+                error = e.to_string()
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+    }
+
+    fn log_top_sizes(&self, top_sizes: &TopSizes, logger: &Logger) {
+        if self.options.top_sizes > 0 {
+            let msg = top_sizes.to_string();
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+    }
+
+    /// Retry the files deferred because they were locked, in up to
+    /// `defer_locked` passes. Files still locked in the last pass are
+    /// retried and failed like any other file.
+    fn copy_deferred_files(
+        &self,
+        deferred: &Mutex<Vec<DeferredFile>>,
+        ctx: &CopyContext,
+        logger: &Logger,
+    ) {
+        let passes = self.options.defer_locked;
+        for pass in 1..=passes {
+            let files = std::mem::take(&mut *deferred.lock().unwrap());
+            if files.is_empty() || ctx.progress.is_cancelled() {
+                break;
+            }
+
+            let msg = tr!(
+                "defer-pass",
+                files = files.len(),
+                pass = pass,
+                passes = passes
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+
+            // Give the other process a moment to let go of the files
+            std::thread::sleep(DEFER_PASS_DELAY);
+
+            let pass_ctx = CopyContext {
+                deferred: ctx.deferred.filter(|_| pass < passes),
+                ..*ctx
+            };
+            copy_deferred(&files, &pass_ctx);
+        }
+    }
+
+    /// Check the abort policy until `done` is set, returning the reason the
+    /// job must stop. `aborted` is set when it must, so workers stop.
+    fn watch_abort_policy(&self, done: &AtomicBool, aborted: &AtomicBool) -> Option<String> {
+        let mut monitor =
+            AbortMonitor::new(&self.options.abort, Path::new(&self.options.destination));
+        let mut last_check = Instant::now();
+
+        while !done.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+            if last_check.elapsed() < ABORT_CHECK_INTERVAL {
+                continue;
+            }
+            last_check = Instant::now();
+
+            // Time spent paused says nothing about throughput
+            if self.progress.is_paused() {
+                monitor.reset_throughput();
+                continue;
+            }
+            if let Some(reason) = monitor.check(&self.stats) {
+                aborted.store(true, Ordering::Relaxed);
+                tracing::warn!(reason = %reason, "job aborted by policy");
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    /// Report the job stalled while neither data nor files move, and
+    /// copying again once they do
+    fn watch_stall(
+        &self,
+        done: &AtomicBool,
+        ticks: &AtomicU64,
+        info: &ProgressInfo,
+        logger: &Logger,
+    ) {
+        let mut watch = StallWatch::new(Duration::from_secs(self.options.stall_secs));
+        while !done.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+            if self.progress.is_paused() {
+                watch.reset();
+                continue;
+            }
+
+            let files = self.stats.files_copied.load(Ordering::Relaxed)
+                + self.stats.files_skipped.load(Ordering::Relaxed)
+                + self.stats.files_failed.load(Ordering::Relaxed);
+            let idle = watch.idle().as_secs();
+            let (msg, state) = match watch.check(ticks.load(Ordering::Relaxed) + files as u64) {
+                Some(StallChange::Stalled) => {
+                    tracing::warn!(seconds = idle, "job stalled");
+                    (
+                        tr!("stall-detected", seconds = idle),
+                        ProgressState::Stalled,
+                    )
+                }
+                Some(StallChange::Resumed) => {
+                    (tr!("stall-resumed", seconds = idle), ProgressState::Copying)
+                }
+                None => continue,
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            self.progress.on_progress(&ProgressInfo {
+                state,
+                files_done: self.stats.files_copied.load(Ordering::Relaxed) as u64,
+                bytes_done: self.stats.bytes_copied.load(Ordering::Relaxed),
+                ..info.clone()
+            });
+        }
+    }
+
+    /// Hold the job while the network policy says so, checking the
+    /// connection every few seconds
+    fn watch_network(&self, done: &AtomicBool, info: &ProgressInfo, logger: &Logger) {
+        let mut last_check: Option<Instant> = None;
+        let mut held = false;
+        while !done.load(Ordering::Relaxed) {
+            if last_check.is_some_and(|last| last.elapsed() < NETWORK_CHECK_INTERVAL) {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            last_check = Some(Instant::now());
+
+            let reason = self.options.network.hold_reason();
+            let (msg, state) = match reason {
+                Some(reason) if !held => {
+                    tracing::info!(reason = %reason, "job held for the network");
+                    (
+                        tr!("network-paused", reason = reason),
+                        ProgressState::NetworkPaused,
+                    )
+                }
+                None if held => (tr!("network-resumed"), ProgressState::Copying),
+                _ => continue,
+            };
+            held = !held;
+            self.set_held(held);
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            self.report_state(state, info);
+        }
+        if held {
+            self.set_held(false);
+        }
+    }
+
+    /// Pause the job while the machine is low on battery, or slow it down,
+    /// checking the power source every few seconds
+    fn watch_battery(&self, done: &AtomicBool, info: &ProgressInfo, logger: &Logger) {
+        let policy = &self.options.battery;
+        let mut last_check: Option<Instant> = None;
+        let mut low = false;
+        while !done.load(Ordering::Relaxed) {
+            if last_check.is_some_and(|last| last.elapsed() < BATTERY_CHECK_INTERVAL) {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            last_check = Some(Instant::now());
+
+            let Some(status) = power::power_status() else {
+                continue;
+            };
+            // Once low, the job stays slowed until the machine is plugged in
+            let msg = if !low && policy.is_low(&status) {
+                low = true;
+                let percent = status
+                    .percent
+                    .map_or_else(|| "?".to_string(), |percent| percent.to_string());
+                tracing::info!(percent = %percent, "job held for the battery");
+                if policy.throttle_mbps > 0.0 {
+                    let throttle = self.controls.throttle();
+                    throttle.set_limit(policy.throttle_mbps);
+                    tr!(
+                        "battery-throttled",
+                        percent = percent,
+                        mbps = throttle.rate()
+                    )
+                } else {
+                    self.set_held(true);
+                    self.report_state(ProgressState::BatteryPaused, info);
+                    tr!("battery-paused", percent = percent)
+                }
+            } else if low && !status.on_battery {
+                low = false;
+                if policy.throttle_mbps > 0.0 {
+                    self.controls.throttle().set_limit(0.0);
+                } else {
+                    self.set_held(false);
+                    self.report_state(ProgressState::Copying, info);
+                }
+                tr!("battery-resumed")
+            } else {
+                continue;
+            };
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+        if low && policy.throttle_mbps <= 0.0 {
+            self.set_held(false);
+        }
+    }
+
+    /// Add or remove a hold of a policy pausing the job
+    fn set_held(&self, held: bool) {
+        if held {
+            self.holds.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.holds.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Report the job in `state`, with the totals copied so far
+    fn report_state(&self, state: ProgressState, info: &ProgressInfo) {
+        self.progress.on_progress(&ProgressInfo {
+            state,
+            files_done: self.stats.files_copied.load(Ordering::Relaxed) as u64,
+            bytes_done: self.stats.bytes_copied.load(Ordering::Relaxed),
+            ..info.clone()
+        });
+    }
+
+    /// What gives the copies the owners of their source, with /OWNER and
+    /// the mapping of the job and its mapping file
+    fn owner_copier(&self) -> std::io::Result<Option<OwnerCopier>> {
+        if !self.options.copy_owner || self.options.list_only {
+            return Ok(None);
+        }
+        let mut map = self.options.owner_map.clone();
+        if let Some(path) = &self.options.owner_map_file {
+            let read = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| parse_owner_map(&text));
+            match read {
+                Ok(more) => map.extend(more),
+                Err(e) => {
+                    let msg = tr!("owner-map-unreadable", path = path.as_str(), error = e);
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+                }
+            }
+        }
+        Ok(Some(OwnerCopier::new(&map)))
+    }
+
+    /// The skip-list of the job, unless /NOSKIPLIST or it lists nothing
+    fn load_skip_list(&self, logger: &Logger) -> Option<SkipMatcher> {
+        if self.options.no_skip_list {
+            return None;
+        }
+        let path = self
+            .options
+            .skip_list
+            .as_ref()
+            .map_or_else(SkipList::default_path, PathBuf::from);
+        let list = match SkipList::load(&path) {
+            Ok(list) => list,
+            Err(e) => {
+                let msg = tr!(
+                    "skip-list-unreadable",
+                    path = path.display().to_string(),
+                    error = e.to_string()
+                );
+                self.progress.on_log(&msg);
+                logger.log(&msg);
+                return None;
+            }
+        };
+        let matcher = SkipMatcher::new(&list);
+        if matcher.is_empty() {
+            return None;
+        }
+        let msg = tr!("skip-list-loaded", count = matcher.len());
+        self.progress.on_log(&msg);
+        logger.log(&msg);
+        Some(matcher)
+    }
+
+    /// Log the paths found in more than one source and the copy taken
+    fn report_merge(&self, plan: &MergePlan, precedence: Precedence, logger: &Logger) {
+        if plan.collisions().is_empty() {
+            return;
+        }
+        let msg = tr!(
+            "merge-plan",
+            collisions = plan.collisions().len(),
+            precedence = precedence.name().to_lowercase()
+        );
+        self.progress.on_log(&msg);
+        logger.log(&msg);
+
+        for collision in plan.collisions() {
+            let losers: Vec<String> = collision
+                .losers
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            let msg = format!(
+                "Collision: {} <- {} (over {})",
+                collision.destination.display(),
+                collision.winner.display(),
+                losers.join(", ")
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+        }
+    }
+
+    /// Move the limit of files handled at once towards the one copying
+    /// fastest until `done` is set, logging each change
+    fn adjust_threads(&self, done: &AtomicBool, logger: &Logger) {
+        let limit = self.controls.limit();
+        let mut controller = ThreadController::new(self.options.threads, &self.stats);
+        let mut last_check = Instant::now();
+        let mut last_bytes = self.stats.bytes_copied.load(Ordering::Relaxed);
+
+        while !done.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(100));
+            if last_check.elapsed() < ADJUST_INTERVAL {
+                continue;
+            }
+            let elapsed = last_check.elapsed();
+            last_check = Instant::now();
+            let bytes = self.stats.bytes_copied.load(Ordering::Relaxed);
+            let copied = bytes - last_bytes;
+            last_bytes = bytes;
+
+            // The user set the files at once by hand
+            if !self.controls.is_auto() {
+                return;
+            }
+            if self.progress.is_paused() {
+                controller.reset(&self.stats);
+                continue;
+            }
+            let current = limit.limit();
+            let next = controller.adjust(current, &self.stats);
+            if next == current {
+                continue;
+            }
+
+            limit.set_limit(next);
+            self.stats.add_thread_limit(next);
+            let msg = tr!(
+                "auto-threads",
+                from = current,
+                to = next,
+                speed = format!("{:.1}", copied as f64 / elapsed.as_secs_f64() / 1_000_000.0)
+            );
+            self.progress.on_log(&msg);
+            logger.log(&msg);
+            tracing::info!(from = current, to = next, "thread limit changed");
+        }
+    }
+
+    /// Report scan progress, at most every `SCAN_REPORT_INTERVAL`
+    fn report_scan(&self, scan: &mut Scan) {
+        if scan.last_report.elapsed() >= SCAN_REPORT_INTERVAL {
+            scan.last_report = Instant::now();
+            self.progress.on_progress(&scan.info);
+        }
+    }
+
+    /// Count the matching files and bytes below `path`, adding them to
+    /// `scan` as they are found
+    fn scan_source(
+        &self,
+        path: &Path,
+        depth: usize,
+        parent: Option<&Ancestors>,
+        artifacts: &Artifacts,
+        index: Option<&ScanIndex>,
+        scan: &mut Scan,
+    ) -> std::io::Result<(u64, u64)> {
         let mut files = 0;
         let mut bytes = 0;
 
+        // The copy reports link cycles, the scan just stops at them
+        let Ok(here) = Ancestors::enter(path, parent, scan.destination.as_ref()) else {
+            return Ok((0, 0));
+        };
+        let parent = here.as_ref().or(parent);
+
+        // Unchanged directories are counted from the index
+        if let Some(record) = index.and_then(|index| index.unchanged_dir(path)) {
+            scan.enter_dir(path);
+            for file in &record.files {
+                if self.options.selects_file(&file.name) {
+                    files += 1;
+                    bytes += file.size;
+                    scan.add_file(&path.join(&file.name), file.size);
+                }
+            }
+            self.report_scan(scan);
+            if self.options.descends_below(depth) {
+                for dir in &record.dirs {
+                    let dir = path.join(dir);
+                    if self.options.excludes_dir(&dir) {
+                        continue;
+                    }
+                    if let Ok((f, b)) =
+                        self.scan_source(&dir, depth + 1, parent, artifacts, index, scan)
+                    {
+                        files += f;
+                        bytes += b;
+                        scan.top_sizes.dirs.offer(b, &dir);
+                    }
+                }
+            }
+            return Ok((files, bytes));
+        }
+
         if path.is_dir() {
+            scan.enter_dir(path);
             let entries = match fs::read_dir(path) {
                 Ok(e) => e,
                 Err(e) => {
@@ -313,25 +1768,27 @@ impl CopyEngine {
 
             for entry in entries.flatten() {
                 let path = entry.path();
+                if artifacts.contains(&path) {
+                    continue;
+                }
                 if path.is_dir() {
-                    if self.options.recursive {
-                        if let Ok((f, b)) = self.scan_source(&path) {
+                    if self.options.descends_below(depth) && !self.options.excludes_dir(&path) {
+                        if let Ok((f, b)) =
+                            self.scan_source(&path, depth + 1, parent, artifacts, index, scan)
+                        {
                             files += f;
                             bytes += b;
+                            scan.top_sizes.dirs.offer(b, &path);
                         }
                     }
                 } else {
                     let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-                    let matches = self
-                        .options
-                        .patterns
-                        .iter()
-                        .any(|p| crate::utils::matches_pattern(&file_name, p));
-                    if matches {
+                    if self.options.selects_file(&file_name) {
                         files += 1;
-                        if let Ok(metadata) = fs::metadata(&path) {
-                            bytes += metadata.len();
-                        }
+                        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                        bytes += size;
+                        scan.add_file(&path, size);
+                        self.report_scan(scan);
                     }
                 }
             }
@@ -340,14 +1797,13 @@ impl CopyEngine {
             // The app assumes source is dir usually.
             // But let's handle it safely.
             let file_name = path.file_name().unwrap().to_string_lossy();
-            let matches = self
-                .options
-                .patterns
-                .iter()
-                .any(|p| crate::utils::matches_pattern(&file_name, p));
-            if matches {
+            if self.options.selects_file(&file_name) {
+                let size = fs::metadata(path)
+                    .context("reading metadata of", path)?
+                    .len();
                 files += 1;
-                bytes += fs::metadata(&path)?.len();
+                bytes += size;
+                scan.add_file(path, size);
             }
         }
         Ok((files, bytes))
@@ -0,0 +1,243 @@
+//! Predicting how long a copy will take without copying.
+//!
+//! An estimate walks the sources like a list-only run, so filters and the
+//! skip logic decide what would be transferred exactly as in a real run.
+//! The duration is predicted from the throughput of previous runs of the
+//! same job, or from a short bandwidth probe when there are none.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::args::CopyOptions;
+use crate::history::{job_key, RunHistory, RunOutcome};
+use crate::stats::Statistics;
+use crate::tr;
+use crate::utils::{format_duration, SizeFormat};
+
+/// Most recent runs averaged for the historical throughput
+const HISTORY_RUNS: usize = 5;
+/// Runs copying less than this say little about throughput
+const HISTORY_MIN_BYTES: u64 = 1024 * 1024;
+/// Bytes copied by the bandwidth probe
+const PROBE_BYTES: u64 = 16 * 1024 * 1024;
+/// Source entries looked at when choosing the file to probe with
+const PROBE_SCAN_LIMIT: usize = 10_000;
+
+/// Where a throughput figure comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ThroughputSource {
+    /// Average of previous runs of the same job
+    History { runs: usize },
+    /// Timed copy of part of a source file to the destination
+    Probe,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Throughput {
+    pub bytes_per_sec: f64,
+    pub source: ThroughputSource,
+}
+
+/// What a copy would transfer and how long it would take
+#[derive(Debug, Clone, Serialize)]
+pub struct Estimate {
+    /// Files matching the filters
+    pub files_total: u64,
+    /// Files that would be copied rather than skipped
+    pub files_to_copy: u64,
+    pub bytes_to_copy: u64,
+    pub throughput: Option<Throughput>,
+    pub predicted_ms: Option<u64>,
+}
+
+impl Estimate {
+    /// Estimate from the statistics of a list-only walk with `options`
+    pub fn new(options: &CopyOptions, stats: &Statistics) -> Self {
+        let files_to_copy = stats.files_copied.load(Ordering::Relaxed) as u64;
+        let bytes_to_copy = stats.bytes_copied.load(Ordering::Relaxed);
+        let files_total = files_to_copy + stats.files_skipped.load(Ordering::Relaxed) as u64;
+
+        // Nothing to measure if nothing would be copied
+        let throughput = if bytes_to_copy == 0 {
+            None
+        } else {
+            historical_throughput(options).or_else(|| probe_throughput(options))
+        };
+        let predicted_ms = if bytes_to_copy == 0 {
+            Some(0)
+        } else {
+            throughput.map(|t| (bytes_to_copy as f64 / t.bytes_per_sec * 1000.0) as u64)
+        };
+
+        Estimate {
+            files_total,
+            files_to_copy,
+            bytes_to_copy,
+            throughput,
+            predicted_ms,
+        }
+    }
+
+    /// Localized multi-line description for logs, with sizes in
+    /// `size_format`
+    pub fn summary(&self, size_format: SizeFormat) -> String {
+        let mut lines = vec![
+            tr!("estimate-title"),
+            format!(
+                "    {}",
+                tr!(
+                    "estimate-files",
+                    files = self.files_to_copy,
+                    total = self.files_total
+                )
+            ),
+            format!(
+                "    {}",
+                tr!(
+                    "estimate-bytes",
+                    size = size_format.format(self.bytes_to_copy)
+                )
+            ),
+        ];
+
+        let duration = self
+            .predicted_ms
+            .map(|ms| format_duration(Duration::from_millis(ms)));
+        match (duration, &self.throughput) {
+            (Some(duration), Some(throughput)) => {
+                let basis = match throughput.source {
+                    ThroughputSource::History { runs } => {
+                        tr!("estimate-basis-history", runs = runs)
+                    }
+                    ThroughputSource::Probe => tr!("estimate-basis-probe"),
+                };
+                lines.push(format!(
+                    "    {}",
+                    tr!(
+                        "estimate-duration",
+                        duration = duration,
+                        speed = size_format.format(throughput.bytes_per_sec as u64),
+                        basis = basis
+                    )
+                ));
+            }
+            (Some(duration), None) => lines.push(format!(
+                "    {}",
+                tr!("estimate-duration-none", duration = duration)
+            )),
+            (None, _) => lines.push(format!("    {}", tr!("estimate-duration-unknown"))),
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Average throughput of the most recent completed runs of the job
+pub fn historical_throughput(options: &CopyOptions) -> Option<Throughput> {
+    let runs: Vec<_> = RunHistory::new(&RunHistory::default_path())
+        .runs(&job_key(options))
+        .into_iter()
+        .filter(|run| {
+            run.outcome == RunOutcome::Completed
+                && run.bytes_copied >= HISTORY_MIN_BYTES
+                && run.duration_ms > 0
+        })
+        .collect();
+    let recent = &runs[runs.len().saturating_sub(HISTORY_RUNS)..];
+    if recent.is_empty() {
+        return None;
+    }
+
+    let bytes: u64 = recent.iter().map(|run| run.bytes_copied).sum();
+    let duration_ms: u64 = recent.iter().map(|run| run.duration_ms).sum();
+    Some(Throughput {
+        bytes_per_sec: bytes as f64 * 1000.0 / duration_ms as f64,
+        source: ThroughputSource::History { runs: recent.len() },
+    })
+}
+
+/// Time copying up to `PROBE_BYTES` of a source file into the destination.
+/// The probe file is removed afterwards.
+pub fn probe_throughput(options: &CopyOptions) -> Option<Throughput> {
+    let dest_dir = Path::new(&options.destination);
+    if !dest_dir.is_dir() {
+        return None;
+    }
+    let source = find_probe_file(options)?;
+    let probe_path = dest_dir.join(format!(".rbcp-probe-{}.tmp", std::process::id()));
+
+    let result = probe_copy(&source, &probe_path);
+    let _ = fs::remove_file(&probe_path);
+    let (bytes, elapsed) = result.ok()?;
+
+    if bytes == 0 || elapsed.is_zero() {
+        return None;
+    }
+    Some(Throughput {
+        bytes_per_sec: bytes as f64 / elapsed.as_secs_f64(),
+        source: ThroughputSource::Probe,
+    })
+}
+
+fn probe_copy(source: &Path, target: &Path) -> io::Result<(u64, Duration)> {
+    let started = Instant::now();
+    let mut reader = File::open(source)?.take(PROBE_BYTES);
+    let mut writer = File::create(target)?;
+    let bytes = io::copy(&mut reader, &mut writer)?;
+    // Include the time to reach the disk, not just the page cache
+    writer.flush()?;
+    writer.sync_all()?;
+    Ok((bytes, started.elapsed()))
+}
+
+/// First source file of at least `PROBE_BYTES`, or the largest file seen
+/// within `PROBE_SCAN_LIMIT` entries
+fn find_probe_file(options: &CopyOptions) -> Option<PathBuf> {
+    let mut pending: Vec<PathBuf> = options.sources.iter().map(PathBuf::from).collect();
+    let mut largest: Option<(u64, PathBuf)> = None;
+    let mut seen = 0;
+
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if options.excludes_dir(&path) {
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&path) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                seen += 1;
+                if seen > PROBE_SCAN_LIMIT {
+                    break;
+                }
+                pending.push(entry.path());
+            }
+            continue;
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !options.selects_file(&name) {
+            continue;
+        }
+        if metadata.len() >= PROBE_BYTES {
+            return Some(path);
+        }
+        if largest
+            .as_ref()
+            .is_none_or(|(size, _)| metadata.len() > *size)
+        {
+            largest = Some((metadata.len(), path));
+        }
+    }
+
+    largest.filter(|(size, _)| *size > 0).map(|(_, path)| path)
+}
@@ -0,0 +1,257 @@
+//! C interface for embedding the engine in other languages.
+//!
+//! Automation written in Python or PowerShell would otherwise run the CLI
+//! and scrape its output. Built with the `ffi` feature, the library exports
+//! a few C functions around [`CopyEngine`] that such scripts can load with
+//! `ctypes` or P/Invoke. Options are the CLI arguments, and progress and
+//! results are JSON, so nothing but strings and pointers crosses the
+//! boundary; `include/rbcp.h` declares the functions.
+//!
+//! A job is created with `rbcp_job_new`, run to the end with the blocking
+//! `rbcp_job_run`, and can be paused or cancelled meanwhile from another
+//! thread. Strings returned by the library are freed with
+//! `rbcp_string_free` and jobs with `rbcp_job_free`. A panic inside the
+//! library is reported as an error of the call instead of unwinding into
+//! the caller, which would abort its process.
+
+use std::any::Any;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::args::CopyOptions;
+use crate::cancel::CancellationToken;
+use crate::engine::CopyEngine;
+use crate::progress::{ProgressCallback, ProgressInfo};
+
+/// Receives each event of a job as a JSON object, with the `user_data`
+/// given to `rbcp_job_new`. The string is only valid during the call.
+pub type RbcpEventCallback = extern "C" fn(event: *const c_char, user_data: *mut c_void);
+
+/// A job created by `rbcp_job_new`
+pub struct RbcpJob {
+    engine: CopyEngine,
+    cancel: CancellationToken,
+    paused: Arc<AtomicBool>,
+}
+
+/// Passes events to the callback of the caller
+struct ForeignProgress {
+    callback: Option<RbcpEventCallback>,
+    user_data: *mut c_void,
+    paused: Arc<AtomicBool>,
+}
+
+// The caller promises its callback and `user_data` may be used from the
+// threads copying, as documented in the header
+unsafe impl Send for ForeignProgress {}
+unsafe impl Sync for ForeignProgress {}
+
+#[derive(Serialize)]
+struct ProgressEvent<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    info: &'a ProgressInfo,
+    percentage: f32,
+}
+
+impl ForeignProgress {
+    fn send(&self, event: &impl Serialize) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        if let Ok(Ok(json)) = serde_json::to_string(event).map(CString::new) {
+            callback(json.as_ptr(), self.user_data);
+        }
+    }
+}
+
+impl ProgressCallback for ForeignProgress {
+    fn on_progress(&self, info: &ProgressInfo) {
+        self.send(&ProgressEvent {
+            event: "progress",
+            info,
+            percentage: info.percentage(),
+        });
+    }
+    fn on_log(&self, message: &str) {
+        self.send(&json!({ "event": "log", "message": message }));
+    }
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// `value` as a string owned by the caller
+fn into_c_string(value: String) -> *mut c_char {
+    // Messages never contain NUL, but a path could
+    CString::new(value.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// The error reported for a panic with `payload`
+fn panic_error(payload: Box<dyn Any + Send>) -> String {
+    let reason = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("internal error ({})", reason)
+}
+
+/// Create a job from `args_json`, a JSON array of the arguments of the CLI
+/// such as `["C:\\Source", "D:\\Backup", "/MIR"]`. Events go to `callback`,
+/// which may be NULL. Returns NULL when the arguments are invalid, with the
+/// reason in `*error` if `error` is not NULL.
+///
+/// # Safety
+///
+/// `args_json` must be a valid NUL-terminated string and `error` NULL or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_job_new(
+    args_json: *const c_char,
+    callback: Option<RbcpEventCallback>,
+    user_data: *mut c_void,
+    error: *mut *mut c_char,
+) -> *mut RbcpJob {
+    let job = panic::catch_unwind(AssertUnwindSafe(|| new_job(args_json, callback, user_data)))
+        .unwrap_or_else(|payload| Err(panic_error(payload)));
+    match job {
+        Ok(job) => Box::into_raw(Box::new(job)),
+        Err(e) => {
+            if !error.is_null() {
+                *error = into_c_string(e);
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The job `rbcp_job_new` creates, or why it cannot
+unsafe fn new_job(
+    args_json: *const c_char,
+    callback: Option<RbcpEventCallback>,
+    user_data: *mut c_void,
+) -> Result<RbcpJob, String> {
+    let mut options = if args_json.is_null() {
+        Err("No arguments given".to_string())
+    } else {
+        CStr::from_ptr(args_json)
+            .to_str()
+            .map_err(|e| e.to_string())
+            .and_then(|args| serde_json::from_str(args).map_err(|e| e.to_string()))
+            .and_then(CopyOptions::parse_args)
+    }?;
+
+    // The log reaches the caller as events
+    options.quiet = true;
+
+    let paused = Arc::new(AtomicBool::new(false));
+    let progress = ForeignProgress {
+        callback,
+        user_data,
+        paused: paused.clone(),
+    };
+    let cancel = CancellationToken::new();
+    Ok(RbcpJob {
+        engine: CopyEngine::new(options, Arc::new(progress), cancel.clone()),
+        cancel,
+        paused,
+    })
+}
+
+/// Run `job` to the end, returning a JSON object with `ok`, the `stats` of
+/// the run, the totals of each of its `children` with /CHILDONLY and, if
+/// it failed, the `error`. A panic ends the run with `ok` false and the
+/// panic as the `error`. Free the result with `rbcp_string_free`.
+///
+/// # Safety
+///
+/// `job` must come from `rbcp_job_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_job_run(job: *const RbcpJob) -> *mut c_char {
+    let Some(job) = job.as_ref() else {
+        return ptr::null_mut();
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match job.engine.run() {
+        Ok(stats) => json!({
+            "ok": true,
+            "stats": stats.snapshot(),
+            "children": stats.children(),
+        }),
+        Err(e) => json!({
+            "ok": false,
+            "error": e.to_string(),
+            "stats": job.engine.stats().snapshot(),
+            "children": job.engine.stats().children(),
+        }),
+    }))
+    .unwrap_or_else(|payload| json!({ "ok": false, "error": panic_error(payload) }));
+    into_c_string(result.to_string())
+}
+
+/// Ask `job` to stop as soon as possible. Safe to call from any thread
+/// while the job runs.
+///
+/// # Safety
+///
+/// `job` must come from `rbcp_job_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_job_cancel(job: *const RbcpJob) {
+    if let Some(job) = job.as_ref() {
+        job.cancel.cancel();
+    }
+}
+
+/// Pause `job` if `paused` is not 0, or resume it. Safe to call from any
+/// thread while the job runs.
+///
+/// # Safety
+///
+/// `job` must come from `rbcp_job_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_job_pause(job: *const RbcpJob, paused: c_int) {
+    if let Some(job) = job.as_ref() {
+        job.paused.store(paused != 0, Ordering::Relaxed);
+    }
+}
+
+/// Free a job once it is no longer running.
+///
+/// # Safety
+///
+/// `job` must be NULL or come from `rbcp_job_new`, and not be in use.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_job_free(job: *mut RbcpJob) {
+    if !job.is_null() {
+        drop(Box::from_raw(job));
+    }
+}
+
+/// Free a string returned by the library.
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned by the library, freed once.
+#[no_mangle]
+pub unsafe extern "C" fn rbcp_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// Version of the library, owned by the library.
+#[no_mangle]
+pub extern "C" fn rbcp_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
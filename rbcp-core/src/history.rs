@@ -0,0 +1,232 @@
+//! Statistics of past runs.
+//!
+//! Every run of a job with history enabled appends a record to the history
+//! file. Comparing the latest run with the previous run of the same job
+//! shows trends a single summary hides, such as a mirror that slowly starts
+//! failing more files or taking much longer.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::stats::{ChildTotals, Statistics};
+use crate::tr;
+use crate::utils::data_dir;
+
+/// Runs kept in the history file, across all jobs
+const MAX_RUNS: usize = 5000;
+
+/// A run this much slower than the previous one is a regression...
+const REGRESSION_FACTOR: f64 = 1.5;
+/// ...if it is also at least this much slower
+const REGRESSION_MIN_MS: u64 = 10_000;
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Completed,
+    /// Stopped at its deadline with files left to copy
+    Partial,
+    Cancelled,
+    Failed,
+}
+
+/// Statistics of one run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Identifies runs of the same job, see [`job_key`]
+    pub job: String,
+    /// Unix timestamp of the start of the run
+    pub started_at: i64,
+    pub duration_ms: u64,
+    pub outcome: RunOutcome,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    pub files_skipped: u64,
+    pub files_failed: u64,
+    /// Totals of each child folder, with /CHILDONLY
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ChildTotals>,
+}
+
+impl RunRecord {
+    pub fn new(
+        options: &CopyOptions,
+        stats: &Statistics,
+        started: SystemTime,
+        outcome: RunOutcome,
+    ) -> Self {
+        RunRecord {
+            job: job_key(options),
+            started_at: started
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+            duration_ms: started.elapsed().map(|d| d.as_millis() as u64).unwrap_or(0),
+            outcome,
+            files_copied: stats.files_copied.load(Ordering::Relaxed) as u64,
+            bytes_copied: stats.bytes_copied.load(Ordering::Relaxed),
+            files_skipped: stats.files_skipped.load(Ordering::Relaxed) as u64,
+            files_failed: stats.files_failed.load(Ordering::Relaxed) as u64,
+            children: stats.children(),
+        }
+    }
+}
+
+/// Key grouping the runs of one job: its sources and destination
+pub fn job_key(options: &CopyOptions) -> String {
+    format!("{} -> {}", options.sources.join(";"), options.destination)
+}
+
+/// History file holding the records of past runs
+pub struct RunHistory {
+    path: PathBuf,
+}
+
+impl RunHistory {
+    /// Default location of the history file
+    pub fn default_path() -> PathBuf {
+        data_dir().join("history.jsonl")
+    }
+
+    pub fn new(path: &Path) -> Self {
+        RunHistory {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// All recorded runs, oldest first. Unreadable lines are skipped.
+    pub fn all_runs(&self) -> Vec<RunRecord> {
+        let Ok(file) = File::open(&self.path) else {
+            return Vec::new();
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    /// Recorded runs of `job`, oldest first
+    pub fn runs(&self, job: &str) -> Vec<RunRecord> {
+        self.all_runs()
+            .into_iter()
+            .filter(|run| run.job == job)
+            .collect()
+    }
+
+    /// Add a run, dropping the oldest runs beyond `MAX_RUNS`
+    pub fn append(&self, record: &RunRecord) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let line = serde_json::to_string(record).map_err(io::Error::other)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        drop(file);
+
+        let runs = self.all_runs();
+        if runs.len() > MAX_RUNS {
+            let mut file = File::create(&self.path)?;
+            for run in &runs[runs.len() - MAX_RUNS..] {
+                writeln!(
+                    file,
+                    "{}",
+                    serde_json::to_string(run).map_err(io::Error::other)?
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Differences between a run and the previous run of the same job
+#[derive(Debug, Clone, Serialize)]
+pub struct RunComparison {
+    pub previous: RunRecord,
+    pub latest: RunRecord,
+    pub files_copied_delta: i64,
+    pub files_failed_delta: i64,
+    pub duration_delta_ms: i64,
+    /// Files failed although none did in the previous run
+    pub failures_introduced: bool,
+    /// The run took markedly longer than the previous one
+    pub duration_regressed: bool,
+}
+
+impl RunComparison {
+    pub fn new(previous: RunRecord, latest: RunRecord) -> Self {
+        let duration_regressed = latest.duration_ms >= previous.duration_ms + REGRESSION_MIN_MS
+            && latest.duration_ms as f64 >= previous.duration_ms as f64 * REGRESSION_FACTOR;
+
+        RunComparison {
+            files_copied_delta: latest.files_copied as i64 - previous.files_copied as i64,
+            files_failed_delta: latest.files_failed as i64 - previous.files_failed as i64,
+            duration_delta_ms: latest.duration_ms as i64 - previous.duration_ms as i64,
+            failures_introduced: previous.files_failed == 0 && latest.files_failed > 0,
+            duration_regressed,
+            previous,
+            latest,
+        }
+    }
+
+    /// Localized multi-line description for logs
+    pub fn summary(&self) -> String {
+        let previous_time = chrono::DateTime::from_timestamp(self.previous.started_at, 0)
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        let mut lines = vec![
+            tr!("history-compare-title", time = previous_time),
+            format!(
+                "    {}",
+                tr!(
+                    "history-files-copied",
+                    value = self.latest.files_copied,
+                    delta = format!("{:+}", self.files_copied_delta)
+                )
+            ),
+            format!(
+                "    {}",
+                tr!(
+                    "history-files-failed",
+                    value = self.latest.files_failed,
+                    delta = format!("{:+}", self.files_failed_delta)
+                )
+            ),
+            format!(
+                "    {}",
+                tr!(
+                    "history-duration",
+                    value = self.latest.duration_ms / 1000,
+                    delta = format!("{:+}", self.duration_delta_ms / 1000)
+                )
+            ),
+        ];
+
+        if self.failures_introduced {
+            lines.push(tr!("history-failures-introduced"));
+        } else if self.files_failed_delta > 0 {
+            lines.push(tr!("history-failures-increased"));
+        }
+        if self.duration_regressed {
+            lines.push(tr!("history-duration-regressed"));
+        }
+
+        lines.join("\n")
+    }
+}
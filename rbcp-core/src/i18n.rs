@@ -0,0 +1,199 @@
+//! Localization of user-facing strings.
+//!
+//! Messages are Fluent resources. English is built in and is the fallback
+//! for any message a translation lacks. Translations are either bundled in
+//! [`BUNDLED`] or placed as `<lang>.ftl` in [`locales_dir`], where they also
+//! override bundled ones. The locale comes from `RBCP_LANG` or the operating
+//! system and can be changed at runtime with [`set_locale`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use fluent_syntax::ast::Entry;
+use unic_langid::LanguageIdentifier;
+
+use crate::utils::data_dir;
+
+pub use fluent_bundle::FluentValue;
+
+/// Locale used when nothing better is available
+const FALLBACK_LOCALE: &str = "en";
+
+/// Translations compiled into the library
+const BUNDLED: &[(&str, &str)] = &[("en", include_str!("../locales/en.ftl"))];
+
+/// Format a localized message, e.g. `tr!("usage-header", program = name)`
+#[macro_export]
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::message($id, &[])
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message(
+            $id,
+            &[$((stringify!($name), $crate::i18n::FluentValue::from($value))),+],
+        )
+    };
+}
+
+struct Localizer {
+    locale: String,
+    /// Bundles in lookup order: the selected translation, then English
+    bundles: Vec<FluentBundle<FluentResource>>,
+    /// Ids of all messages across the bundles
+    ids: Vec<String>,
+}
+
+impl Localizer {
+    fn new(requested: &str) -> Self {
+        let mut localizer = Localizer {
+            locale: FALLBACK_LOCALE.to_string(),
+            bundles: Vec::new(),
+            ids: Vec::new(),
+        };
+
+        // "de_DE.UTF-8" -> "de-DE"; try the full tag, then the language
+        let tag = requested
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .replace('_', "-");
+        if let Ok(langid) = tag.parse::<LanguageIdentifier>() {
+            let full = langid.to_string();
+            let language = langid.language.to_string();
+            for candidate in [full, language] {
+                if candidate == FALLBACK_LOCALE {
+                    break;
+                }
+                if let Some(source) = load_source(&candidate) {
+                    localizer.add(&candidate, source);
+                    localizer.locale = candidate;
+                    break;
+                }
+            }
+        }
+
+        if let Some(source) = load_source(FALLBACK_LOCALE) {
+            localizer.add(FALLBACK_LOCALE, source);
+        }
+        localizer
+    }
+
+    fn add(&mut self, locale: &str, source: String) {
+        // Keep what parsed when a translation has syntax errors
+        let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, _)| resource);
+        for entry in resource.entries() {
+            if let Entry::Message(message) = entry {
+                if !self.ids.iter().any(|id| id == message.id.name) {
+                    self.ids.push(message.id.name.to_string());
+                }
+            }
+        }
+
+        let langid = locale.parse().unwrap_or_default();
+        let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+        bundle.set_use_isolating(false);
+        let _ = bundle.add_resource(resource);
+        self.bundles.push(bundle);
+    }
+
+    fn format(&self, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        self.bundles.iter().find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            Some(
+                bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned(),
+            )
+        })
+    }
+}
+
+fn localizer() -> &'static RwLock<Localizer> {
+    static LOCALIZER: OnceLock<RwLock<Localizer>> = OnceLock::new();
+    LOCALIZER.get_or_init(|| RwLock::new(Localizer::new(&system_locale())))
+}
+
+/// Source of the translation for `locale`: a user file, else a bundled one
+fn load_source(locale: &str) -> Option<String> {
+    fs::read_to_string(locales_dir().join(format!("{}.ftl", locale)))
+        .ok()
+        .or_else(|| {
+            BUNDLED
+                .iter()
+                .find(|(tag, _)| tag.eq_ignore_ascii_case(locale))
+                .map(|(_, source)| source.to_string())
+        })
+}
+
+/// Directory searched for additional `<lang>.ftl` translations
+pub fn locales_dir() -> PathBuf {
+    data_dir().join("locales")
+}
+
+/// Locale requested by `RBCP_LANG`, else the operating system's
+pub fn system_locale() -> String {
+    std::env::var("RBCP_LANG")
+        .ok()
+        .filter(|lang| !lang.is_empty())
+        .or_else(sys_locale::get_locale)
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string())
+}
+
+/// Switch to the translation best matching `locale`
+pub fn set_locale(locale: &str) {
+    *localizer().write().unwrap() = Localizer::new(locale);
+}
+
+/// Locale of the translation in use
+pub fn locale() -> String {
+    localizer().read().unwrap().locale.clone()
+}
+
+/// Locales with a translation, bundled or in [`locales_dir`]
+pub fn available_locales() -> Vec<String> {
+    let mut locales: Vec<String> = BUNDLED.iter().map(|(tag, _)| tag.to_string()).collect();
+    if let Ok(entries) = fs::read_dir(locales_dir()) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_some_and(|ext| ext == "ftl") {
+                if let Some(stem) = path.file_stem() {
+                    locales.push(stem.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    locales.sort();
+    locales.dedup();
+    locales
+}
+
+/// Format the message `id` with `args`. Unknown ids are returned as-is.
+pub fn message(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+
+    localizer()
+        .read()
+        .unwrap()
+        .format(id, Some(&fluent_args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// All messages whose id starts with `prefix`, for frontends that render
+/// strings themselves. Variables are left in place as `{$name}`.
+pub fn messages(prefix: &str) -> BTreeMap<String, String> {
+    let localizer = localizer().read().unwrap();
+    localizer
+        .ids
+        .iter()
+        .filter(|id| id.starts_with(prefix))
+        .filter_map(|id| Some((id.clone(), localizer.format(id, None)?)))
+        .collect()
+}
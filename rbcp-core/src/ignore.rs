@@ -0,0 +1,102 @@
+//! Errors the user has chosen to tolerate.
+//!
+//! Some failures are expected and say nothing about the copy, e.g. access
+//! denied on system folders nobody meant to copy, or dangling symlinks. An
+//! ignore rule names an error kind and optionally a path pattern. Files and
+//! directories failing with a matching error are skipped without counting
+//! as failed, and are counted as ignored instead.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{is_locked_error, matches_pattern};
+
+/// Kind of error an ignore rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnoredKind {
+    /// Access denied
+    Denied,
+    /// The file or directory does not exist, e.g. a dangling symlink
+    NotFound,
+    /// The file is open or locked by another process
+    Locked,
+    /// Any error
+    All,
+}
+
+impl IgnoredKind {
+    const NAMES: [(&'static str, IgnoredKind); 4] = [
+        ("denied", IgnoredKind::Denied),
+        ("notfound", IgnoredKind::NotFound),
+        ("locked", IgnoredKind::Locked),
+        ("all", IgnoredKind::All),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, kind)| *kind == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    fn matches(self, error: &io::Error) -> bool {
+        match self {
+            IgnoredKind::Denied => error.kind() == io::ErrorKind::PermissionDenied,
+            IgnoredKind::NotFound => error.kind() == io::ErrorKind::NotFound,
+            IgnoredKind::Locked => is_locked_error(error),
+            IgnoredKind::All => true,
+        }
+    }
+}
+
+/// An error kind to tolerate, optionally only for paths matching a pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreRule {
+    pub kind: IgnoredKind,
+    /// Pattern the full path must match, e.g. `*/System Volume Information*`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl IgnoreRule {
+    /// Parse `kind` or `kind:pattern`, e.g. `denied:*/lost+found*`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, path) = match spec.split_once(':') {
+            Some((name, path)) => (name, Some(path.to_string())),
+            None => (spec, None),
+        };
+        let kind = IgnoredKind::NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown error kind: {} (expected denied, notfound, locked or all)",
+                    name
+                )
+            })?;
+        Ok(IgnoreRule { kind, path })
+    }
+
+    pub fn matches(&self, error: &io::Error, path: &Path) -> bool {
+        self.kind.matches(error)
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|pattern| matches_pattern(&path.to_string_lossy(), pattern))
+    }
+}
+
+impl fmt::Display for IgnoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}", self.kind.name(), path),
+            None => write!(f, "{}", self.kind.name()),
+        }
+    }
+}
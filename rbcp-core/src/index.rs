@@ -0,0 +1,226 @@
+//! Persistent scan index of source trees.
+//!
+//! The index records the listing of every source directory visited during a
+//! run (file names, sizes and modification times). On the next run, a
+//! directory whose own modification time has not changed is known to have
+//! the same set of entries, so its listing can be taken from the index
+//! instead of reading the source directory again. Its files are still
+//! stat-ed, as one modified in place leaves the directory's timestamp
+//! alone, and compared with their copies only once they differ from the
+//! index.
+//!
+//! When a change journal is available for the source volume, directories
+//! it reports as changed are always re-read.
+//!
+//! With `/FASTSKIP` the files of such a directory are not compared at all
+//! when its number of entries is also unchanged and the run that listed it
+//! left none of them failed or waiting. A file changed in place without
+//! the journal noticing is then missed, so every n-th run reads every
+//! directory and compares every file again, leaving the index aside.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::changes::{self, JournalCursor};
+use crate::utils::replace_file;
+
+/// Runs that `/FASTSKIP` makes one full run in by default
+pub const DEFAULT_FAST_SKIP_RUNS: usize = 10;
+
+/// Directory modification times are only trusted when the directory was
+/// last changed at least this long before it was indexed. This covers
+/// filesystems with coarse timestamps (FAT has 2 second resolution).
+const MTIME_GRANULARITY_NANOS: u64 = 2_000_000_000;
+
+/// A file entry recorded in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub name: String,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime: u64,
+}
+
+/// The recorded listing of a single source directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirRecord {
+    /// Modification time of the directory itself
+    pub mtime: u64,
+    /// When the listing was taken
+    pub scanned_at: u64,
+    pub files: Vec<FileRecord>,
+    pub dirs: Vec<String>,
+    /// Number of entries of the directory, including those not listed
+    #[serde(default)]
+    pub entries: usize,
+    /// Whether the run that listed the directory left every file in it
+    /// copied or skipped, so `/FASTSKIP` may leave them alone
+    #[serde(default)]
+    pub complete: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct IndexFile {
+    dirs: HashMap<String, DirRecord>,
+    #[serde(default)]
+    cursors: HashMap<String, JournalCursor>,
+    /// Runs with `/FASTSKIP` since the last one that compared everything
+    #[serde(default)]
+    fast_runs: usize,
+}
+
+/// On-disk index of source directory listings, updated on every run.
+pub struct ScanIndex {
+    path: PathBuf,
+    previous: HashMap<String, DirRecord>,
+    current: Mutex<HashMap<String, DirRecord>>,
+    cursors: HashMap<String, JournalCursor>,
+    changed_dirs: HashSet<String>,
+    fast_runs: usize,
+    /// Whether unchanged directories are skipped in this run
+    fast_skip: bool,
+    /// Whether this is the run of `/FASTSKIP` that reads everything
+    full_run: bool,
+}
+
+impl ScanIndex {
+    /// Load the index from `path`. A missing or unreadable index starts empty.
+    pub fn load(path: &Path) -> Self {
+        let previous = File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader::<_, IndexFile>(BufReader::new(file)).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: path.to_path_buf(),
+            previous: previous.dirs,
+            current: Mutex::new(HashMap::new()),
+            cursors: previous.cursors,
+            changed_dirs: HashSet::new(),
+            fast_runs: previous.fast_runs,
+            fast_skip: false,
+            full_run: false,
+        }
+    }
+
+    /// Skip unchanged directories in this run, unless it is the one in
+    /// `every` that compares everything. Returns whether it skips them.
+    pub fn start_fast_skip(&mut self, every: usize) -> bool {
+        self.fast_skip = self.fast_runs + 1 < every;
+        self.full_run = !self.fast_skip;
+        self.fast_runs = if self.fast_skip {
+            self.fast_runs + 1
+        } else {
+            0
+        };
+        self.fast_skip
+    }
+
+    /// Whether the files of a directory unchanged since it was listed in
+    /// `record` need not be compared in this run
+    pub fn fast_skips(&self, record: &DirRecord) -> bool {
+        self.fast_skip && record.complete
+    }
+
+    /// Read the change journal of the volume containing `root` since the
+    /// previous run. Returns `false` if no journal is available, in which
+    /// case only directory timestamps are used to detect changes.
+    pub fn attach_journal(&mut self, root: &Path) -> bool {
+        let key = index_key(root);
+        let Some(current) = changes::current_cursor(root) else {
+            return false;
+        };
+
+        let changes = self
+            .cursors
+            .get(&key)
+            .and_then(|since| changes::changes_between(root, since, &current));
+        self.cursors.insert(key, current);
+
+        match changes {
+            Some(changes) => {
+                self.changed_dirs
+                    .extend(changes.dirs.iter().map(|dir| index_key(Path::new(dir))));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the recorded listing of `dir` if the directory is unchanged
+    /// since it was indexed, with `/FASTSKIP` down to its number of
+    /// entries.
+    pub fn unchanged_dir(&self, dir: &Path) -> Option<DirRecord> {
+        let key = index_key(dir);
+        if self.full_run || self.changed_dirs.contains(&key) {
+            return None;
+        }
+
+        let record = self.previous.get(&key)?;
+        let mtime = mtime_nanos(&fs::metadata(dir).ok()?);
+
+        let reliable = record.mtime + MTIME_GRANULARITY_NANOS < record.scanned_at;
+        // Entries are counted too before files are left alone
+        let same_entries = || {
+            !self.fast_skip
+                || fs::read_dir(dir).is_ok_and(|entries| entries.count() == record.entries)
+        };
+        if reliable && mtime == record.mtime && same_entries() {
+            Some(record.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Store the listing of `dir` for the next run.
+    pub fn record_dir(&self, dir: &Path, record: DirRecord) {
+        self.current.lock().unwrap().insert(index_key(dir), record);
+    }
+
+    /// Write the index back to disk. Directories that were not visited in
+    /// this run keep their previous listing.
+    pub fn save(&self) -> io::Result<()> {
+        let mut dirs = self.previous.clone();
+        dirs.extend(self.current.lock().unwrap().drain());
+
+        let index = IndexFile {
+            dirs,
+            cursors: self.cursors.clone(),
+            fast_runs: self.fast_runs,
+        };
+        replace_file(&self.path, |file| {
+            serde_json::to_writer(file, &index).map_err(io::Error::other)
+        })
+    }
+}
+
+/// Modification time of `meta` in nanoseconds since the Unix epoch
+pub fn mtime_nanos(meta: &Metadata) -> u64 {
+    meta.modified().map(system_time_nanos).unwrap_or(0)
+}
+
+pub(crate) fn system_time_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn index_key(dir: &Path) -> String {
+    let key = std::path::absolute(dir)
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .to_string_lossy()
+        .to_string();
+
+    // Windows paths are case-insensitive
+    if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
@@ -3,18 +3,78 @@
 //! This library provides the core copy engine that can be used by both
 //! CLI and GUI frontends.
 
+pub mod abort;
 pub mod args;
+pub mod artifacts;
+pub mod attributes;
+pub mod bench;
+pub mod bitrot;
+pub mod cancel;
+pub mod changes;
+pub mod checkpoint;
+pub mod cloud;
+pub mod conflicts;
+pub mod congestion;
+pub mod content;
+pub mod controls;
 pub mod copy;
+pub mod cycles;
+pub mod deadline;
+pub mod destinations;
+pub mod durability;
+pub mod eject;
+pub mod elevation;
+pub mod estimate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod history;
+pub mod i18n;
+pub mod ignore;
+pub mod index;
+pub mod limits;
+pub mod links;
+pub mod locks;
+pub mod merge;
+pub mod network;
+pub mod notify;
+pub mod owners;
+pub mod peek;
+pub mod power;
+pub mod profile;
+pub mod quarantine;
+pub mod queue;
+pub mod remap;
+pub mod report;
+pub mod retry;
+pub mod roots;
+pub mod salvage;
+pub mod scenario;
+pub mod selftest;
+pub mod service;
+pub mod settings;
+pub mod skiplist;
+pub mod special;
+pub mod split;
+pub mod stall;
 pub mod stats;
+pub mod status;
+pub mod swap;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod throttle;
 pub mod utils;
+pub mod verify;
 
 mod engine;
 mod progress;
 
 pub use args::CopyOptions;
+pub use cancel::CancellationToken;
 pub use engine::CopyEngine;
 pub use progress::{
-    CliProgress, NullProgress, ProgressCallback, ProgressInfo, ProgressState, SharedProgress,
+    CliProgress, JsonProgress, NullProgress, ProgressCallback, ProgressInfo, ProgressState,
+    SharedProgress, DEFAULT_LOG_LINES,
 };
 pub use stats::Statistics;
 pub use utils::Logger;
@@ -0,0 +1,219 @@
+//! Warnings about jobs far bigger than meant.
+//!
+//! A drive root picked instead of a folder on it starts a job over every
+//! file on the drive. Entry limits catch that early: `/MAXENTRIES:n` warns
+//! about each folder holding more than n entries, and `/MAXFILES:n` about a
+//! job over more than n files. A job warns when it gets there, or before
+//! copying when it scans its sources first, and goes on either way. The
+//! GUI checks the sources before a job starts, with [`EntryLimits::GUI`]
+//! unless the job sets limits of its own, and asks whether to go ahead.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::cycles::Ancestors;
+use crate::tr;
+
+/// Warnings a check collects before it stops looking
+const MAX_WARNINGS: usize = 20;
+
+/// Sizes past which a job is warned about. Zero disables a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntryLimits {
+    /// Warn about folders holding more than this many entries
+    pub max_dir_entries: u64,
+    /// Warn about a job over more than this many files
+    pub max_files: u64,
+}
+
+impl EntryLimits {
+    /// Limits the GUI checks jobs against when they set none
+    pub const GUI: EntryLimits = EntryLimits {
+        max_dir_entries: 100_000,
+        max_files: 1_000_000,
+    };
+
+    pub fn is_active(&self) -> bool {
+        self.max_dir_entries > 0 || self.max_files > 0
+    }
+
+    fn dir_warning(&self, path: &Path, entries: u64) -> Option<LimitWarning> {
+        (self.max_dir_entries > 0 && entries > self.max_dir_entries).then(|| {
+            LimitWarning::LargeDirectory {
+                path: path.to_path_buf(),
+                limit: self.max_dir_entries,
+            }
+        })
+    }
+}
+
+/// A limit a job went past
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LimitWarning {
+    /// A folder holding more entries than allowed
+    LargeDirectory { path: PathBuf, limit: u64 },
+    /// More files in the job than allowed
+    ManyFiles { limit: u64 },
+}
+
+impl fmt::Display for LimitWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            LimitWarning::LargeDirectory { path, limit } => tr!(
+                "limit-large-directory",
+                path = path.display().to_string(),
+                limit = *limit
+            ),
+            LimitWarning::ManyFiles { limit } => tr!("limit-many-files", limit = *limit),
+        };
+        f.write_str(&msg)
+    }
+}
+
+/// Walk the sources of `options` as the copy would, collecting the limits
+/// of `limits` they go past. Stops once past the file limit or after
+/// [`MAX_WARNINGS`] warnings.
+pub fn check(options: &CopyOptions, limits: &EntryLimits) -> Vec<LimitWarning> {
+    let mut walk = Walk {
+        options,
+        limits,
+        files: 0,
+        warnings: Vec::new(),
+    };
+    for source in &options.sources {
+        let source = Path::new(source);
+        if source.is_file() {
+            walk.add_file();
+        } else {
+            walk.dir(source, 0, None);
+        }
+        if walk.done() {
+            break;
+        }
+    }
+    walk.warnings
+}
+
+struct Walk<'a> {
+    options: &'a CopyOptions,
+    limits: &'a EntryLimits,
+    files: u64,
+    warnings: Vec<LimitWarning>,
+}
+
+impl Walk<'_> {
+    fn done(&self) -> bool {
+        self.warnings.len() >= MAX_WARNINGS
+            || self
+                .warnings
+                .iter()
+                .any(|warning| matches!(warning, LimitWarning::ManyFiles { .. }))
+    }
+
+    fn add_file(&mut self) {
+        self.files += 1;
+        if self.limits.max_files > 0 && self.files == self.limits.max_files + 1 {
+            self.warnings.push(LimitWarning::ManyFiles {
+                limit: self.limits.max_files,
+            });
+        }
+    }
+
+    fn dir(&mut self, dir: &Path, depth: usize, parent: Option<&Ancestors>) {
+        // The copy reports link cycles, the check just stops at them
+        let Ok(here) = Ancestors::enter(dir, parent, None) else {
+            return;
+        };
+        let parent = here.as_ref().or(parent);
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut count = 0;
+        let mut subdirs = Vec::new();
+        for entry in entries.flatten() {
+            count += 1;
+            let path = entry.path();
+            if path.is_dir() {
+                if self.options.descends_below(depth) && !self.options.excludes_dir(&path) {
+                    subdirs.push(path);
+                }
+            } else if path.is_file()
+                && self
+                    .options
+                    .selects_file(&entry.file_name().to_string_lossy())
+            {
+                self.add_file();
+            }
+        }
+        if let Some(warning) = self.limits.dir_warning(dir, count) {
+            self.warnings.push(warning);
+        }
+        for subdir in subdirs {
+            if self.done() {
+                return;
+            }
+            self.dir(&subdir, depth + 1, parent);
+        }
+    }
+}
+
+/// Warns a running job about the limits it goes past, the file limit once
+pub struct LimitGuard {
+    limits: EntryLimits,
+    files: AtomicU64,
+    warned_files: AtomicBool,
+}
+
+impl LimitGuard {
+    pub fn new(limits: EntryLimits) -> Self {
+        LimitGuard {
+            limits,
+            files: AtomicU64::new(0),
+            warned_files: AtomicBool::new(false),
+        }
+    }
+
+    /// The warning about the folder at `path` holding `entries` entries,
+    /// if that is too many
+    pub fn check_dir(&self, path: &Path, entries: u64) -> Option<LimitWarning> {
+        self.limits.dir_warning(path, entries)
+    }
+
+    /// The warning about the folder at `path` as it is listed, once the
+    /// entry counted as its `counted`th is the first past the limit
+    pub fn add_entry(&self, path: &Path, counted: u64) -> Option<LimitWarning> {
+        (counted == self.limits.max_dir_entries + 1)
+            .then(|| self.limits.dir_warning(path, counted))
+            .flatten()
+    }
+
+    /// Count a file of the job, with the warning if it is the first past
+    /// the limit
+    pub fn add_file(&self) -> Option<LimitWarning> {
+        let files = self.files.fetch_add(1, Ordering::Relaxed) + 1;
+        (self.limits.max_files > 0 && files > self.limits.max_files)
+            .then(|| self.warn_files())
+            .flatten()
+    }
+
+    /// The warning about the `files` files a scan of the sources found, if
+    /// that is too many, given before copying instead of on the way
+    pub fn scanned(&self, files: u64) -> Option<LimitWarning> {
+        (self.limits.max_files > 0 && files > self.limits.max_files)
+            .then(|| self.warn_files())
+            .flatten()
+    }
+
+    fn warn_files(&self) -> Option<LimitWarning> {
+        (!self.warned_files.swap(true, Ordering::Relaxed)).then_some(LimitWarning::ManyFiles {
+            limit: self.limits.max_files,
+        })
+    }
+}
@@ -0,0 +1,585 @@
+//! Junctions, directory symlinks and hard links.
+//!
+//! By default a walk follows a link to a directory and copies what is
+//! behind it, and copies each name of a hard-linked file as a file of its
+//! own. A migration meant to leave the destination like the source wants
+//! the links themselves instead. `/LINKS:[kind=]policy` sets what happens
+//! to each kind: `junction` (reparse tag `IO_REPARSE_TAG_MOUNT_POINT`),
+//! `dirsymlink` (`IO_REPARSE_TAG_SYMLINK` on a directory, or a symlink to a
+//! directory on Unix) and `hardlink`, the further names of a file already
+//! copied. `follow` keeps today's behaviour, `skip` leaves the link out,
+//! `recreate` makes the same link at the destination and `error` counts it
+//! as a failure. A recreated link pointing somewhere inside its source
+//! points at the same place inside the destination.
+//!
+//! Recreated links are listed with their targets in `.rbcp-links.json` at
+//! the root of the destination, and with `/VERIFY` each is read back and
+//! checked against its target.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::replace_file;
+
+/// Name of the manifest at the root of the destination
+pub const LINK_MANIFEST_NAME: &str = ".rbcp-links.json";
+
+/// A kind of link a walk can meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// NTFS mount point to a directory
+    Junction,
+    /// Symlink to a directory
+    DirSymlink,
+    /// Further name of a file copied already
+    HardLink,
+}
+
+impl LinkKind {
+    pub const ALL: [LinkKind; 3] = [LinkKind::Junction, LinkKind::DirSymlink, LinkKind::HardLink];
+
+    /// Name used in the `/LINKS` option
+    pub fn name(self) -> &'static str {
+        match self {
+            LinkKind::Junction => "junction",
+            LinkKind::DirSymlink => "dirsymlink",
+            LinkKind::HardLink => "hardlink",
+        }
+    }
+
+    /// Message ID of the localized plural label
+    pub fn message_id(self) -> &'static str {
+        match self {
+            LinkKind::Junction => "links-junction",
+            LinkKind::DirSymlink => "links-dir-symlink",
+            LinkKind::HardLink => "links-hard-link",
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Kind of the link to a directory at `path`, if it is one. Links of
+    /// other reparse tags are followed like directories.
+    pub fn of_dir(path: &Path) -> Option<LinkKind> {
+        let meta = fs::symlink_metadata(path).ok()?;
+        if !meta.file_type().is_symlink() || !fs::metadata(path).ok()?.is_dir() {
+            return None;
+        }
+        sys::dir_link_kind(path)
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What to do with a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkPolicy {
+    /// Copy what the link leads to
+    #[default]
+    Follow,
+    /// Leave it out of the copy
+    Skip,
+    /// Make the same link at the destination
+    Recreate,
+    /// Count it as a failed file
+    Error,
+}
+
+impl LinkPolicy {
+    pub const ALL: [LinkPolicy; 4] = [
+        LinkPolicy::Follow,
+        LinkPolicy::Skip,
+        LinkPolicy::Recreate,
+        LinkPolicy::Error,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LinkPolicy::Follow => "follow",
+            LinkPolicy::Skip => "skip",
+            LinkPolicy::Recreate => "recreate",
+            LinkPolicy::Error => "error",
+        }
+    }
+
+    /// Message ID of the localized outcome
+    pub fn message_id(self) -> &'static str {
+        match self {
+            LinkPolicy::Follow => "links-followed",
+            LinkPolicy::Skip => "special-skipped",
+            LinkPolicy::Recreate => "special-recreated",
+            LinkPolicy::Error => "special-failed",
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Policy for each kind of link
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkPolicies {
+    pub junctions: LinkPolicy,
+    pub dir_symlinks: LinkPolicy,
+    pub hard_links: LinkPolicy,
+}
+
+impl LinkPolicies {
+    pub fn policy(&self, kind: LinkKind) -> LinkPolicy {
+        match kind {
+            LinkKind::Junction => self.junctions,
+            LinkKind::DirSymlink => self.dir_symlinks,
+            LinkKind::HardLink => self.hard_links,
+        }
+    }
+
+    fn policy_mut(&mut self, kind: LinkKind) -> &mut LinkPolicy {
+        match kind {
+            LinkKind::Junction => &mut self.junctions,
+            LinkKind::DirSymlink => &mut self.dir_symlinks,
+            LinkKind::HardLink => &mut self.hard_links,
+        }
+    }
+
+    /// Whether links to directories are looked for while walking
+    pub fn checks_dirs(&self) -> bool {
+        self.junctions != LinkPolicy::Follow || self.dir_symlinks != LinkPolicy::Follow
+    }
+
+    /// Apply `policy` to all kinds, or `kind=policy` to one, e.g.
+    /// `junction=recreate`
+    pub fn parse_into(&mut self, spec: &str) -> Result<(), String> {
+        let parse_policy = |name: &str| {
+            LinkPolicy::ALL
+                .into_iter()
+                .find(|policy| policy.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown link policy: {} (expected follow, skip, recreate or error)",
+                        name
+                    )
+                })
+        };
+
+        match spec.split_once('=') {
+            Some((kind, policy)) => {
+                let kind = LinkKind::ALL
+                    .into_iter()
+                    .find(|k| k.name().eq_ignore_ascii_case(kind))
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown link kind: {} (expected junction, dirsymlink or hardlink)",
+                            kind
+                        )
+                    })?;
+                *self.policy_mut(kind) = parse_policy(policy)?;
+            }
+            None => {
+                let policy = parse_policy(spec)?;
+                for kind in LinkKind::ALL {
+                    *self.policy_mut(kind) = policy;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A recreated link, as listed in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LinkRecord {
+    kind: LinkKind,
+    /// Where the link points, or for a hard link the other name
+    target: PathBuf,
+}
+
+/// Links of a job: the copies of hard-linked files and the manifest of
+/// the links recreated below a destination
+pub struct Links {
+    policies: LinkPolicies,
+    /// First copy of each hard-linked source file
+    copies: Mutex<HashMap<sys::FileId, PathBuf>>,
+    root: PathBuf,
+    /// Records by path relative to the root
+    records: Mutex<BTreeMap<String, LinkRecord>>,
+}
+
+impl Links {
+    /// Links handled by `policies` below the destination at `root`, with
+    /// the manifest it has
+    pub fn new(policies: &LinkPolicies, root: &Path) -> Self {
+        let records = File::open(root.join(LINK_MANIFEST_NAME))
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        Links {
+            policies: policies.clone(),
+            copies: Mutex::new(HashMap::new()),
+            root: root.to_path_buf(),
+            records: Mutex::new(records),
+        }
+    }
+
+    pub fn policy(&self, kind: LinkKind) -> LinkPolicy {
+        self.policies.policy(kind)
+    }
+
+    /// The copy made of another name of the source file at `path`, if
+    /// hard links are not followed
+    pub fn copy_of(&self, path: &Path, meta: &Metadata) -> Option<PathBuf> {
+        if self.policies.hard_links == LinkPolicy::Follow {
+            return None;
+        }
+        let id = sys::hard_linked_id(path, meta)?;
+        self.copies.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Remember `copy` as the copy of the source file at `path`, for its
+    /// other names to link to
+    pub fn add_copy(&self, path: &Path, meta: &Metadata, copy: &Path) {
+        if self.policies.hard_links == LinkPolicy::Follow {
+            return;
+        }
+        if let Some(id) = sys::hard_linked_id(path, meta) {
+            self.copies
+                .lock()
+                .unwrap()
+                .entry(id)
+                .or_insert_with(|| copy.to_path_buf());
+        }
+    }
+
+    /// List the link recreated at `path` in the manifest
+    pub fn record(&self, path: &Path, kind: LinkKind, target: &Path) {
+        if let Ok(relative) = path.strip_prefix(&self.root) {
+            let key = relative.to_string_lossy().replace('\\', "/");
+            let record = LinkRecord {
+                kind,
+                target: target.to_path_buf(),
+            };
+            self.records.lock().unwrap().insert(key, record);
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let records = self.records.lock().unwrap();
+        let path = self.root.join(LINK_MANIFEST_NAME);
+        if records.is_empty() && !path.exists() {
+            return Ok(());
+        }
+        replace_file(&path, |file| {
+            serde_json::to_writer_pretty(file, &*records).map_err(io::Error::other)
+        })
+    }
+}
+
+/// Where the copy of the directory link at `path` points: its own target,
+/// moved below `dst_root` if it is inside `src_root`
+pub fn dir_link_target(path: &Path, src_root: &Path, dst_root: &Path) -> io::Result<PathBuf> {
+    let target = fs::read_link(path)?;
+    if target.is_relative() {
+        return Ok(target);
+    }
+    let Ok(src_root) = fs::canonicalize(src_root) else {
+        return Ok(target);
+    };
+    let inside = fs::canonicalize(&target)
+        .ok()
+        .and_then(|resolved| Some(resolved.strip_prefix(&src_root).ok()?.to_path_buf()));
+    Ok(match inside {
+        Some(relative) => std::path::absolute(dst_root.join(relative)).unwrap_or(target),
+        None => target,
+    })
+}
+
+/// Whether `path` already is a link of `kind` to `target`
+pub fn is_linked(path: &Path, kind: LinkKind, target: &Path) -> bool {
+    match kind {
+        LinkKind::HardLink => match (fs::metadata(path), fs::metadata(target)) {
+            (Ok(meta), Ok(target_meta)) => {
+                let id = sys::file_id(path, &meta);
+                id.is_some() && id == sys::file_id(target, &target_meta)
+            }
+            _ => false,
+        },
+        // The target need not exist yet, as the walk may not have got to it
+        _ => {
+            fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+                && sys::dir_link_kind(path) == Some(kind)
+                && fs::read_link(path).is_ok_and(|linked| linked == target)
+        }
+    }
+}
+
+/// Make `path` a link of `kind` to `target`, replacing a link, file or
+/// empty folder there
+pub fn recreate(path: &Path, kind: LinkKind, target: &Path) -> io::Result<()> {
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        // Links to directories are directories themselves on Windows
+        #[cfg(windows)]
+        let dir_link = std::os::windows::fs::FileTypeExt::is_symlink_dir(&meta.file_type());
+        #[cfg(not(windows))]
+        let dir_link = false;
+        if meta.is_dir() || dir_link {
+            fs::remove_dir(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+    match kind {
+        LinkKind::HardLink => fs::hard_link(target, path),
+        _ => sys::create_dir_link(path, kind, target),
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::fs::Metadata;
+    use std::io;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    use super::LinkKind;
+
+    /// Device and inode of a file
+    pub type FileId = (u64, u64);
+
+    pub fn dir_link_kind(_path: &Path) -> Option<LinkKind> {
+        Some(LinkKind::DirSymlink)
+    }
+
+    pub fn file_id(_path: &Path, meta: &Metadata) -> Option<FileId> {
+        Some((meta.dev(), meta.ino()))
+    }
+
+    /// Identity of the file at `path`, if it has more than one name
+    pub fn hard_linked_id(path: &Path, meta: &Metadata) -> Option<FileId> {
+        (meta.nlink() > 1).then(|| file_id(path, meta))?
+    }
+
+    pub fn create_dir_link(path: &Path, _kind: LinkKind, target: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, path)
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::{self, File, Metadata, OpenOptions};
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use std::path::Path;
+
+    use super::LinkKind;
+
+    /// Volume serial number and file index of a file
+    pub type FileId = (u32, u64);
+
+    const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+    const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const FSCTL_SET_REPARSE_POINT: u32 = 0x0009_00A4;
+    /// `FILE_INFO_BY_HANDLE_CLASS` of `FILE_ATTRIBUTE_TAG_INFO`
+    const FILE_ATTRIBUTE_TAG_INFO: u32 = 9;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct AttributeTagInfo {
+        attributes: u32,
+        reparse_tag: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileInformation {
+        attributes: u32,
+        times: [u32; 6],
+        volume_serial_number: u32,
+        size_high: u32,
+        size_low: u32,
+        number_of_links: u32,
+        index_high: u32,
+        index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(file: *mut c_void, info: *mut FileInformation) -> i32;
+        fn GetFileInformationByHandleEx(
+            file: *mut c_void,
+            class: u32,
+            info: *mut c_void,
+            size: u32,
+        ) -> i32;
+        fn DeviceIoControl(
+            device: *mut c_void,
+            code: u32,
+            input: *const c_void,
+            input_size: u32,
+            output: *mut c_void,
+            output_size: u32,
+            returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+    }
+
+    /// The link itself at `path`, not what it points to
+    fn open_link(path: &Path, write: bool) -> io::Result<File> {
+        OpenOptions::new()
+            .read(!write)
+            .write(write)
+            .custom_flags(FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)
+    }
+
+    pub fn dir_link_kind(path: &Path) -> Option<LinkKind> {
+        let file = open_link(path, false).ok()?;
+        let mut info = AttributeTagInfo::default();
+        let ok = unsafe {
+            GetFileInformationByHandleEx(
+                file.as_raw_handle(),
+                FILE_ATTRIBUTE_TAG_INFO,
+                &mut info as *mut AttributeTagInfo as *mut c_void,
+                std::mem::size_of::<AttributeTagInfo>() as u32,
+            )
+        };
+        match (ok != 0).then_some(info.reparse_tag)? {
+            IO_REPARSE_TAG_MOUNT_POINT => Some(LinkKind::Junction),
+            IO_REPARSE_TAG_SYMLINK => Some(LinkKind::DirSymlink),
+            _ => None,
+        }
+    }
+
+    fn information(path: &Path) -> Option<FileInformation> {
+        let file = File::open(path).ok()?;
+        let mut info = FileInformation::default();
+        let ok = unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) };
+        (ok != 0).then_some(info)
+    }
+
+    fn id_of(info: &FileInformation) -> FileId {
+        (
+            info.volume_serial_number,
+            ((info.index_high as u64) << 32) | info.index_low as u64,
+        )
+    }
+
+    pub fn file_id(path: &Path, _meta: &Metadata) -> Option<FileId> {
+        information(path).map(|info| id_of(&info))
+    }
+
+    /// Identity of the file at `path`, if it has more than one name
+    pub fn hard_linked_id(path: &Path, _meta: &Metadata) -> Option<FileId> {
+        let info = information(path)?;
+        (info.number_of_links > 1).then(|| id_of(&info))
+    }
+
+    pub fn create_dir_link(path: &Path, kind: LinkKind, target: &Path) -> io::Result<()> {
+        if kind == LinkKind::DirSymlink {
+            return std::os::windows::fs::symlink_dir(target, path);
+        }
+
+        // A junction is an empty directory with a mount point reparse
+        // point naming an absolute target
+        let target = std::path::absolute(path.parent().unwrap_or(path).join(target))?;
+        let print: Vec<u16> = target.as_os_str().encode_wide().collect();
+        let substitute: Vec<u16> = match target.to_str().and_then(|t| t.strip_prefix(r"\\?\")) {
+            Some(verbatim) => r"\??\"
+                .encode_utf16()
+                .chain(verbatim.encode_utf16())
+                .collect(),
+            None => r"\??\"
+                .encode_utf16()
+                .chain(print.iter().copied())
+                .collect(),
+        };
+        let mut names = substitute.clone();
+        names.push(0);
+        names.extend(&print);
+        names.push(0);
+
+        let data_length = 8 + names.len() * 2;
+        let mut buffer: Vec<u8> = Vec::with_capacity(8 + data_length);
+        buffer.extend(IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+        buffer.extend((data_length as u16).to_le_bytes());
+        buffer.extend(0u16.to_le_bytes());
+        // Offsets and lengths in bytes of the two names, without their NULs
+        let substitute_length = (substitute.len() * 2) as u16;
+        buffer.extend(0u16.to_le_bytes());
+        buffer.extend(substitute_length.to_le_bytes());
+        buffer.extend((substitute_length + 2).to_le_bytes());
+        buffer.extend(((print.len() * 2) as u16).to_le_bytes());
+        buffer.extend(names.iter().flat_map(|c| c.to_le_bytes()));
+
+        fs::create_dir(path)?;
+        let result = open_link(path, true).and_then(|dir| {
+            let mut returned = 0u32;
+            let ok = unsafe {
+                DeviceIoControl(
+                    dir.as_raw_handle(),
+                    FSCTL_SET_REPARSE_POINT,
+                    buffer.as_ptr() as *const c_void,
+                    buffer.len() as u32,
+                    std::ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = fs::remove_dir(path);
+        }
+        result
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::fs::Metadata;
+    use std::io;
+    use std::path::Path;
+
+    use super::LinkKind;
+
+    pub type FileId = ();
+
+    pub fn dir_link_kind(_path: &Path) -> Option<LinkKind> {
+        Some(LinkKind::DirSymlink)
+    }
+
+    pub fn file_id(_path: &Path, _meta: &Metadata) -> Option<FileId> {
+        None
+    }
+
+    pub fn hard_linked_id(_path: &Path, _meta: &Metadata) -> Option<FileId> {
+        None
+    }
+
+    pub fn create_dir_link(_path: &Path, _kind: LinkKind, _target: &Path) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
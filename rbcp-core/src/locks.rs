@@ -0,0 +1,224 @@
+//! Finding out which processes keep a file open.
+//!
+//! When a copy fails with a sharing violation, knowing the process holding
+//! the file tells an admin what to close. On Windows the Restart Manager
+//! lists the processes using a set of files. Other platforms report none.
+
+use std::fmt;
+use std::path::Path;
+
+/// A process that has a file open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    /// Executable name, or the application name if it cannot be read
+    pub name: String,
+    pub pid: u32,
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} pid {}", self.name, self.pid)
+    }
+}
+
+/// Processes that have any of `paths` open
+pub fn lock_holders(paths: &[&Path]) -> Vec<LockHolder> {
+    #[cfg(windows)]
+    {
+        restart_manager::lock_holders(paths).unwrap_or_default()
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = paths;
+        Vec::new()
+    }
+}
+
+#[cfg(windows)]
+mod restart_manager {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::null;
+
+    use super::LockHolder;
+
+    type Handle = *mut c_void;
+
+    const ERROR_SUCCESS: u32 = 0;
+    const ERROR_MORE_DATA: u32 = 234;
+    const CCH_RM_SESSION_KEY: usize = 32;
+    const CCH_RM_MAX_APP_NAME: usize = 255;
+    const CCH_RM_MAX_SVC_NAME: usize = 63;
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const MAX_PATH: usize = 260;
+
+    // Layouts of the Windows structures, not all fields are read
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    #[allow(dead_code)]
+    struct RmUniqueProcess {
+        process_id: u32,
+        start_time_low: u32,
+        start_time_high: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    #[allow(dead_code)]
+    struct RmProcessInfo {
+        process: RmUniqueProcess,
+        app_name: [u16; CCH_RM_MAX_APP_NAME + 1],
+        service_short_name: [u16; CCH_RM_MAX_SVC_NAME + 1],
+        application_type: i32,
+        app_status: u32,
+        ts_session_id: u32,
+        restartable: i32,
+    }
+
+    #[link(name = "rstrtmgr")]
+    extern "system" {
+        fn RmStartSession(session: *mut u32, flags: u32, key: *mut u16) -> u32;
+        fn RmRegisterResources(
+            session: u32,
+            file_count: u32,
+            files: *const *const u16,
+            app_count: u32,
+            apps: *const RmUniqueProcess,
+            service_count: u32,
+            services: *const *const u16,
+        ) -> u32;
+        fn RmGetList(
+            session: u32,
+            needed: *mut u32,
+            count: *mut u32,
+            infos: *mut RmProcessInfo,
+            reboot_reasons: *mut u32,
+        ) -> u32;
+        fn RmEndSession(session: u32) -> u32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> Handle;
+        fn QueryFullProcessImageNameW(
+            process: Handle,
+            flags: u32,
+            name: *mut u16,
+            size: *mut u32,
+        ) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    /// Ends the Restart Manager session when dropped
+    struct Session(u32);
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            unsafe {
+                RmEndSession(self.0);
+            }
+        }
+    }
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    fn from_wide(buffer: &[u16]) -> String {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    }
+
+    pub fn lock_holders(paths: &[&Path]) -> Option<Vec<LockHolder>> {
+        let mut handle = 0;
+        let mut key = [0u16; CCH_RM_SESSION_KEY + 1];
+        if unsafe { RmStartSession(&mut handle, 0, key.as_mut_ptr()) } != ERROR_SUCCESS {
+            return None;
+        }
+        let session = Session(handle);
+
+        let names: Vec<Vec<u16>> = paths.iter().map(|path| wide(path)).collect();
+        let pointers: Vec<*const u16> = names.iter().map(|name| name.as_ptr()).collect();
+        let registered = unsafe {
+            RmRegisterResources(
+                session.0,
+                pointers.len() as u32,
+                pointers.as_ptr(),
+                0,
+                null(),
+                0,
+                null(),
+            )
+        };
+        if registered != ERROR_SUCCESS {
+            return None;
+        }
+
+        // Ask for the count first, then fetch; the list can grow in between
+        let mut infos: Vec<RmProcessInfo> = Vec::new();
+        loop {
+            let mut needed = 0;
+            let mut count = infos.len() as u32;
+            let mut reasons = 0;
+            let result = unsafe {
+                RmGetList(
+                    session.0,
+                    &mut needed,
+                    &mut count,
+                    infos.as_mut_ptr(),
+                    &mut reasons,
+                )
+            };
+            match result {
+                ERROR_SUCCESS => {
+                    infos.truncate(count as usize);
+                    break;
+                }
+                ERROR_MORE_DATA => {
+                    // SAFETY: RmProcessInfo is plain data, all zeroes is valid
+                    infos = vec![unsafe { std::mem::zeroed() }; needed as usize];
+                }
+                _ => return None,
+            }
+        }
+
+        Some(
+            infos
+                .iter()
+                .map(|info| {
+                    let pid = info.process.process_id;
+                    LockHolder {
+                        name: image_name(pid).unwrap_or_else(|| from_wide(&info.app_name)),
+                        pid,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// File name of the executable of process `pid`, e.g. OUTLOOK.EXE
+    fn image_name(pid: u32) -> Option<String> {
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            return None;
+        }
+        let mut buffer = [0u16; MAX_PATH];
+        let mut size = buffer.len() as u32;
+        let ok = unsafe { QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut size) };
+        unsafe {
+            CloseHandle(process);
+        }
+        if ok == 0 {
+            return None;
+        }
+
+        let path = String::from_utf16_lossy(&buffer[..size as usize]);
+        Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    }
+}
@@ -0,0 +1,183 @@
+//! Overlaying several sources onto one destination.
+//!
+//! Sources are copied one after the other, so when two of them hold the
+//! same relative path the destination ends up with whichever copy the
+//! timestamp comparison lets through last. A precedence policy decides
+//! instead, before anything is copied: the sources are listed together, the
+//! copy that wins each collision is chosen, and the others are skipped. A
+//! base image overlaid with a per-customer override directory then gets
+//! exactly the override's files wherever both have one.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::cycles::Ancestors;
+
+/// Which source's copy of a path reaches the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precedence {
+    /// The first source listed that has the path
+    First,
+    /// The last source listed that has the path
+    Last,
+    /// The most recently modified copy, the first listed on a tie
+    Newest,
+}
+
+impl Precedence {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_uppercase().as_str() {
+            "FIRST" => Ok(Precedence::First),
+            "LAST" => Ok(Precedence::Last),
+            "NEWEST" => Ok(Precedence::Newest),
+            _ => Err(format!(
+                "Unknown precedence: {} (expected first, last or newest)",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precedence::First => "FIRST",
+            Precedence::Last => "LAST",
+            Precedence::Newest => "NEWEST",
+        }
+    }
+}
+
+/// A destination path more than one source has a file for
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub destination: PathBuf,
+    /// Source file copied to the destination
+    pub winner: PathBuf,
+    /// Source files skipped in favour of the winner, in source order
+    pub losers: Vec<PathBuf>,
+}
+
+/// Source files to skip so each destination path is copied from one source
+#[derive(Debug, Default)]
+pub struct MergePlan {
+    overridden: HashSet<PathBuf>,
+    collisions: Vec<Collision>,
+}
+
+impl MergePlan {
+    /// List the files of each source root and the destination root it is
+    /// copied to, and pick the winner of every path found more than once
+    pub fn build(
+        roots: &[(PathBuf, PathBuf)],
+        precedence: Precedence,
+        options: &CopyOptions,
+    ) -> io::Result<Self> {
+        let mut candidates: BTreeMap<PathBuf, Vec<(PathBuf, SystemTime)>> = BTreeMap::new();
+        for (source, destination) in roots {
+            if source.is_file() {
+                let name = source.file_name().unwrap_or_default();
+                add_candidate(&mut candidates, source, destination.join(name))?;
+            } else {
+                list_files(source, destination, options, 0, None, &mut candidates)?;
+            }
+        }
+
+        let mut plan = MergePlan::default();
+        for (destination, mut copies) in candidates {
+            if copies.len() < 2 {
+                continue;
+            }
+            let winner = match precedence {
+                Precedence::First => 0,
+                Precedence::Last => copies.len() - 1,
+                Precedence::Newest => {
+                    // max_by_key keeps the last of equal keys, so going
+                    // backwards keeps the first source listed
+                    copies
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .max_by_key(|(_, (_, modified))| *modified)
+                        .map_or(0, |(i, _)| i)
+                }
+            };
+            let (winner, _) = copies.remove(winner);
+            let losers: Vec<PathBuf> = copies.into_iter().map(|(path, _)| path).collect();
+            plan.overridden.extend(losers.iter().cloned());
+            plan.collisions.push(Collision {
+                destination,
+                winner,
+                losers,
+            });
+        }
+        Ok(plan)
+    }
+
+    /// Whether the source file at `path` loses to another source's copy
+    pub fn is_overridden(&self, path: &Path) -> bool {
+        self.overridden.contains(path)
+    }
+
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+}
+
+fn add_candidate(
+    candidates: &mut BTreeMap<PathBuf, Vec<(PathBuf, SystemTime)>>,
+    source: &Path,
+    destination: PathBuf,
+) -> io::Result<()> {
+    let modified = fs::metadata(source)?
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    candidates
+        .entry(destination)
+        .or_default()
+        .push((source.to_path_buf(), modified));
+    Ok(())
+}
+
+/// Add the files below `dir`, `depth` levels below its source root,
+/// matching the patterns, as they would be copied below `destination`
+fn list_files(
+    dir: &Path,
+    destination: &Path,
+    options: &CopyOptions,
+    depth: usize,
+    parent: Option<&Ancestors>,
+    candidates: &mut BTreeMap<PathBuf, Vec<(PathBuf, SystemTime)>>,
+) -> io::Result<()> {
+    // The walk reports link cycles, listing just stops at them
+    let Ok(here) = Ancestors::enter(dir, parent, None) else {
+        return Ok(());
+    };
+    let parent = here.as_ref().or(parent);
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        // Links are followed, as the copy follows them
+        if path.is_dir() {
+            if options.descends_below(depth) && !options.excludes_dir(&path) {
+                list_files(
+                    &path,
+                    &destination.join(&name),
+                    options,
+                    depth + 1,
+                    parent,
+                    candidates,
+                )?;
+            }
+        } else if path.is_file() && options.selects_file(&name.to_string_lossy()) {
+            add_candidate(candidates, &path, destination.join(&name))?;
+        }
+    }
+    Ok(())
+}
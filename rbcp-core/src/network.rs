@@ -0,0 +1,251 @@
+//! Holding back copies over costly or untrusted connections.
+//!
+//! A laptop copying to the office share should not burn a phone hotspot's
+//! data plan, nor send files over a café's network while the company VPN is
+//! down. With `/NETPAUSE:METERED` a job whose destination is on the network
+//! pauses while the active connection is metered, and with
+//! `/NETPAUSE:VPN=name` while the named VPN is not connected. The engine
+//! checks every few seconds and carries on by itself once the connection
+//! allows it, reporting the job as waiting for the network meanwhile.
+//!
+//! Metered connections are those Windows marks as such, or NetworkManager
+//! on Linux. A VPN is named as the network adapter or interface it brings
+//! up (e.g. `wg0`), or as the connection in the Windows, NetworkManager or
+//! macOS network settings.
+
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tr;
+
+/// When copying to the network pauses. Nothing set never pauses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkPolicy {
+    /// Pause while the active connection is metered
+    pub metered: bool,
+    /// Pause while the VPN with this name is not connected
+    pub vpn: Option<String>,
+}
+
+impl NetworkPolicy {
+    pub fn is_active(&self) -> bool {
+        self.metered || self.vpn.is_some()
+    }
+
+    /// Why copying has to wait for the network now, if it has to
+    pub fn hold_reason(&self) -> Option<String> {
+        if let Some(vpn) = self.vpn.as_deref().filter(|vpn| !is_connected(vpn)) {
+            return Some(tr!("network-vpn-down", name = vpn));
+        }
+        if self.metered && is_metered() {
+            return Some(tr!("network-metered"));
+        }
+        None
+    }
+
+    /// The policy as `/NETPAUSE` arguments
+    pub fn to_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.metered {
+            flags.push("/NETPAUSE:METERED".to_string());
+        }
+        if let Some(vpn) = &self.vpn {
+            flags.push(format!("/NETPAUSE:VPN={}", vpn));
+        }
+        flags
+    }
+}
+
+/// Whether `path` is on a network share, judged by its nearest existing
+/// ancestor
+pub fn is_network_path(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        windows::is_network_path(path)
+    }
+    #[cfg(unix)]
+    {
+        let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+            return false;
+        };
+        unix::is_network_fs(existing)
+    }
+    #[cfg(not(any(windows, unix)))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Whether the connection in use is metered, false if it cannot be told
+pub fn is_metered() -> bool {
+    #[cfg(windows)]
+    {
+        let cost = windows::powershell(
+            "[Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime] > $null; \
+             [Windows.Networking.Connectivity.NetworkInformation]::GetInternetConnectionProfile().GetConnectionCost().NetworkCostType",
+        );
+        matches!(cost.as_deref(), Some("Fixed" | "Variable"))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // NM_METERED_YES or NM_METERED_GUESS_YES
+        let metered = output(Command::new("busctl").args([
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ]));
+        matches!(metered.as_deref(), Some("u 1" | "u 3"))
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        false
+    }
+}
+
+/// Whether the VPN named `name` is connected
+pub fn is_connected(name: &str) -> bool {
+    #[cfg(windows)]
+    {
+        let name = name.replace('\'', "''");
+        windows::powershell(&format!(
+            "Get-NetIPInterface -InterfaceAlias '{}' -ConnectionState Connected -ErrorAction SilentlyContinue | Select-Object -First 1 -ExpandProperty InterfaceAlias",
+            name
+        ))
+        .is_some_and(|alias| !alias.is_empty())
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // Tunnels report their state as unknown while up
+        if let Ok(state) =
+            std::fs::read_to_string(Path::new("/sys/class/net").join(name).join("operstate"))
+        {
+            return state.trim() != "down";
+        }
+        output(Command::new("nmcli").args(["-t", "-g", "NAME", "connection", "show", "--active"]))
+            .is_some_and(|active| active.lines().any(|line| line == name))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let status = output(Command::new("scutil").args(["--nc", "status", name]));
+        status.is_some_and(|status| status.lines().next() == Some("Connected"))
+            || unix::has_interface(name)
+    }
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        let _ = name;
+        true
+    }
+}
+
+/// Trimmed standard output of a command that succeeded
+fn output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Whether the file system holding `path` is a network one
+    pub fn is_network_fs(path: &Path) -> bool {
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return false;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            const NETWORK_MAGICS: &[u32] = &[
+                0x6969,      // NFS
+                0x517B,      // SMB
+                0xFF53_4D42, // CIFS
+                0xFE53_4D42, // SMB2
+                0x5346_414F, // AFS
+                0x00C3_6400, // Ceph
+            ];
+            // The field type differs between platforms
+            #[allow(clippy::unnecessary_cast)]
+            NETWORK_MAGICS.contains(&(stat.f_type as u32))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let name = unsafe { std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+            matches!(
+                name.to_bytes(),
+                b"smbfs" | b"nfs" | b"afpfs" | b"webdav" | b"cifs"
+            )
+        }
+    }
+
+    /// Whether a network interface named `name` exists
+    #[cfg(target_os = "macos")]
+    pub fn has_interface(name: &str) -> bool {
+        CString::new(name).is_ok_and(|name| unsafe { libc::if_nametoindex(name.as_ptr()) } != 0)
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::process::CommandExt;
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::output;
+
+    const DRIVE_REMOTE: u32 = 4;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetVolumePathNameW(file_name: *const u16, volume_path: *mut u16, len: u32) -> i32;
+        fn GetDriveTypeW(root: *const u16) -> u32;
+    }
+
+    pub fn is_network_path(path: &Path) -> bool {
+        let text = path.to_string_lossy();
+        if let Some(rest) = text.strip_prefix(r"\\") {
+            // Device paths are local unless they name a share
+            return !(rest.starts_with(r"?\") || rest.starts_with(r".\"))
+                || rest[2..].to_ascii_uppercase().starts_with(r"UNC\");
+        }
+
+        let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+            return false;
+        };
+        let wide: Vec<u16> = existing
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut root = [0u16; 261];
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+            return false;
+        }
+        unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+    }
+
+    /// Output of a PowerShell command, run without a console window
+    pub fn powershell(script: &str) -> Option<String> {
+        output(
+            Command::new("powershell")
+                .args(["-NoProfile", "-NonInteractive", "-Command", script])
+                .creation_flags(CREATE_NO_WINDOW),
+        )
+    }
+}
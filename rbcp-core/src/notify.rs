@@ -0,0 +1,157 @@
+//! Notifications sent when a job finishes.
+//!
+//! A job can post its summary to a webhook (generic JSON or Slack-style
+//! `{"text": ...}` payload) and/or send it by email over SMTP. Delivery
+//! requires the `notify` feature.
+
+use serde::{Deserialize, Serialize};
+
+/// Payload format of the webhook request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// Structured JSON with the summary and the failure list
+    #[default]
+    Generic,
+    /// `{"text": ...}` as accepted by Slack and compatible incoming webhooks
+    Slack,
+}
+
+/// SMTP server and recipients for email notifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub server: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Where and when to send notifications for a job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub webhook_url: Option<String>,
+    pub webhook_format: WebhookFormat,
+    pub smtp: Option<SmtpConfig>,
+    /// Only notify when the job failed or some files failed
+    pub only_on_failure: bool,
+}
+
+/// Outcome of a job, as sent in notifications
+#[derive(Debug, Clone, Serialize)]
+pub struct JobReport {
+    pub success: bool,
+    pub sources: Vec<String>,
+    pub destination: String,
+    pub summary: String,
+    pub failures: Vec<String>,
+}
+
+#[cfg(feature = "notify")]
+impl JobReport {
+    fn subject(&self) -> String {
+        format!(
+            "RBCP job {}: {} -> {}",
+            if self.success { "completed" } else { "failed" },
+            self.sources.join(", "),
+            self.destination
+        )
+    }
+
+    fn text(&self) -> String {
+        let mut text = format!("{}\n\n{}", self.subject(), self.summary);
+        if !self.failures.is_empty() {
+            text.push_str("\nFailed files:\n");
+            for failure in &self.failures {
+                text.push_str(&format!("  {}\n", failure));
+            }
+        }
+        text
+    }
+}
+
+/// Send all notifications configured in `config`. Returns a description of
+/// every delivery that failed.
+pub fn send_notifications(config: &NotifyConfig, report: &JobReport) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if config.only_on_failure && report.success && report.failures.is_empty() {
+        return errors;
+    }
+
+    if let Some(url) = &config.webhook_url {
+        if let Err(e) = send_webhook(url, config.webhook_format, report) {
+            errors.push(format!("Webhook notification failed: {}", e));
+        }
+    }
+
+    if let Some(smtp) = &config.smtp {
+        if let Err(e) = send_email(smtp, report) {
+            errors.push(format!("Email notification failed: {}", e));
+        }
+    }
+
+    errors
+}
+
+#[cfg(feature = "notify")]
+fn send_webhook(url: &str, format: WebhookFormat, report: &JobReport) -> Result<(), String> {
+    let payload = match format {
+        WebhookFormat::Generic => serde_json::json!({
+            "event": "job_finished",
+            "report": report,
+        }),
+        WebhookFormat::Slack => serde_json::json!({ "text": report.text() }),
+    };
+
+    ureq::post(url)
+        .send_json(payload)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "notify")]
+fn send_email(smtp: &SmtpConfig, report: &JobReport) -> Result<(), String> {
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{Message, SmtpTransport, Transport};
+
+    let mut builder = Message::builder()
+        .from(smtp.from.parse().map_err(|e| format!("{}", e))?)
+        .subject(report.subject());
+    for to in &smtp.to {
+        builder = builder.to(to.parse().map_err(|e| format!("{}", e))?);
+    }
+    let message = builder.body(report.text()).map_err(|e| e.to_string())?;
+
+    let mut transport = SmtpTransport::starttls_relay(&smtp.server)
+        .map_err(|e| e.to_string())?
+        .port(smtp.port);
+    if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    transport
+        .build()
+        .send(&message)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "notify"))]
+fn send_webhook(_url: &str, _format: WebhookFormat, _report: &JobReport) -> Result<(), String> {
+    Err("notifications are not enabled in this build".to_string())
+}
+
+#[cfg(not(feature = "notify"))]
+fn send_email(_smtp: &SmtpConfig, _report: &JobReport) -> Result<(), String> {
+    Err("notifications are not enabled in this build".to_string())
+}
@@ -0,0 +1,579 @@
+//! Copying the owners of files between systems.
+//!
+//! With `/OWNER` each copy gets the owner and group of its source. Copied
+//! to another machine or domain, those owners may not exist there, or be
+//! other accounts with the same number, so `/OWNERMAP:file` maps them:
+//! each line `old = new` names an owner of the source and the account the
+//! copies get instead, by name, number or, on Windows, SID, and `#` starts
+//! a comment. `user:old` and `group:old` apply to one kind only, a bare
+//! name to both. Profiles give the same table as `owner_map`. Owners found
+//! without a mapping keep their number or SID and are listed at the end of
+//! the job with their file counts, as are mapped accounts that do not
+//! exist on this system, so the table can be completed.
+//!
+//! Setting another owner needs root on Unix, and on Windows the restore
+//! privilege that `/B` enables.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::tr;
+
+/// Whether an owner is the user or the group of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OwnerKind {
+    User,
+    Group,
+}
+
+impl OwnerKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            OwnerKind::User => "user",
+            OwnerKind::Group => "group",
+        }
+    }
+}
+
+/// An account as found on a file: its number or SID, and its name if this
+/// system knows it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Account {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({})", name, self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Read a mapping table, one `old = new` line per owner
+pub fn parse_owner_map(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut map = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (old, new) = line
+            .split_once('=')
+            .map(|(old, new)| (old.trim(), new.trim()))
+            .filter(|(old, new)| !old.is_empty() && !new.is_empty())
+            .ok_or_else(|| format!("Invalid owner mapping on line {}: {}", number + 1, line))?;
+        map.insert(old.to_string(), new.to_string());
+    }
+    Ok(map)
+}
+
+/// What happens to the owners of one kind with one id
+#[derive(Debug, Clone)]
+enum Target {
+    /// Set this id on the copies
+    Set(String),
+    /// The mapped account does not exist here, the copies keep the owner
+    /// of whoever writes them
+    Missing,
+}
+
+/// Gives each copy the owner and group of its source, as mapped
+#[derive(Debug, Default)]
+pub struct OwnerCopier {
+    /// Accounts of the source by the accounts of the copies, compared
+    /// ignoring case
+    map: HashMap<String, String>,
+    /// Target of each owner met so far
+    targets: Mutex<HashMap<(OwnerKind, String), Target>>,
+    /// Files of each owner without a mapping
+    unmatched: Mutex<BTreeMap<(OwnerKind, Account), u64>>,
+    /// Files of each mapped account missing on this system
+    missing: Mutex<BTreeMap<String, u64>>,
+    /// Files whose owner could not be set, and the first error
+    failed: Mutex<(u64, Option<String>)>,
+}
+
+impl OwnerCopier {
+    pub fn new(map: &BTreeMap<String, String>) -> Self {
+        OwnerCopier {
+            map: map
+                .iter()
+                .map(|(old, new)| (old.to_lowercase(), new.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Give `destination` the owners of the source `source`, whose
+    /// metadata is `meta`. Problems are counted for [`report`](Self::report)
+    /// instead of failing the file.
+    pub fn apply(&self, source: &Path, meta: &Metadata, destination: &Path) {
+        let owners = match sys::owners(source, meta) {
+            Ok(owners) => owners,
+            Err(e) => return self.add_failure(e),
+        };
+        let user = self.target(OwnerKind::User, &owners.0);
+        let group = self.target(OwnerKind::Group, &owners.1);
+        if user.is_none() && group.is_none() {
+            return;
+        }
+        if let Err(e) = sys::set_owners(destination, user.as_deref(), group.as_deref()) {
+            self.add_failure(e);
+        }
+    }
+
+    /// Id the copies of files owned by `account` get, if any
+    fn target(&self, kind: OwnerKind, account: &Account) -> Option<String> {
+        let mapped = self.lookup(kind, account);
+        if mapped.is_none() && !self.map.is_empty() {
+            *self
+                .unmatched
+                .lock()
+                .unwrap()
+                .entry((kind, account.clone()))
+                .or_default() += 1;
+        }
+
+        let mut targets = self.targets.lock().unwrap();
+        let target = targets
+            .entry((kind, account.id.clone()))
+            .or_insert_with(|| match mapped {
+                Some(new) => sys::find_account(kind, new).map_or(Target::Missing, Target::Set),
+                None => Target::Set(account.id.clone()),
+            });
+        match target {
+            Target::Set(id) => Some(id.clone()),
+            Target::Missing => {
+                drop(targets);
+                let new = mapped.unwrap_or_default().to_string();
+                *self.missing.lock().unwrap().entry(new).or_default() += 1;
+                None
+            }
+        }
+    }
+
+    /// The account mapped to `account`, most specific entry first
+    fn lookup(&self, kind: OwnerKind, account: &Account) -> Option<&str> {
+        let names = account.name.iter().chain([&account.id]);
+        names
+            .clone()
+            .map(|name| format!("{}:{}", kind.prefix(), name))
+            .chain(names.cloned())
+            .find_map(|key| self.map.get(&key.to_lowercase()))
+            .map(String::as_str)
+    }
+
+    fn add_failure(&self, error: io::Error) {
+        let mut failed = self.failed.lock().unwrap();
+        failed.0 += 1;
+        failed.1.get_or_insert_with(|| error.to_string());
+    }
+
+    /// Lines for the summary about the owners that could not be copied as
+    /// mapped, if there were any
+    pub fn report(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        let unmatched = self.unmatched.lock().unwrap();
+        if !unmatched.is_empty() {
+            lines.push(tr!("owners-unmatched"));
+            for ((kind, account), files) in unmatched.iter() {
+                lines.push(format!(
+                    "    {}:{}  {}",
+                    kind.prefix(),
+                    account,
+                    tr!("owners-files", files = *files)
+                ));
+            }
+        }
+        let missing = self.missing.lock().unwrap();
+        if !missing.is_empty() {
+            lines.push(tr!("owners-missing"));
+            for (account, files) in missing.iter() {
+                lines.push(format!(
+                    "    {}  {}",
+                    account,
+                    tr!("owners-files", files = *files)
+                ));
+            }
+        }
+        let failed = self.failed.lock().unwrap();
+        if let (files, Some(error)) = &*failed {
+            lines.push(tr!("owners-failed", files = *files, error = error.as_str()));
+        }
+        (!lines.is_empty()).then(|| lines.join("\n") + "\n")
+    }
+}
+
+/// Give the directory `destination` the owners of the source directory
+/// `source`, if `owners` is set
+pub fn copy_dir_owners(owners: Option<&OwnerCopier>, source: &Path, destination: &Path) {
+    if let (Some(owners), Ok(meta)) = (owners, fs::metadata(source)) {
+        owners.apply(source, &meta, destination);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{CStr, CString};
+    use std::fs::Metadata;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    use super::{Account, OwnerKind};
+
+    /// Room for the strings of a passwd or group entry
+    const BUFFER_SIZE: usize = 16 * 1024;
+
+    pub fn owners(_path: &Path, meta: &Metadata) -> io::Result<(Account, Account)> {
+        let user = Account {
+            id: meta.uid().to_string(),
+            name: user_name(meta.uid()),
+        };
+        let group = Account {
+            id: meta.gid().to_string(),
+            name: group_name(meta.gid()),
+        };
+        Ok((user, group))
+    }
+
+    /// Id of the user or group named or numbered `name`, if it exists
+    pub fn find_account(kind: OwnerKind, name: &str) -> Option<String> {
+        if let Ok(id) = name.parse::<u32>() {
+            return Some(id.to_string());
+        }
+        let name = CString::new(name).ok()?;
+        let mut buffer = vec![0 as libc::c_char; BUFFER_SIZE];
+        let id = match kind {
+            OwnerKind::User => {
+                let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+                let mut found = std::ptr::null_mut();
+                let rc = unsafe {
+                    libc::getpwnam_r(
+                        name.as_ptr(),
+                        &mut entry,
+                        buffer.as_mut_ptr(),
+                        buffer.len(),
+                        &mut found,
+                    )
+                };
+                (rc == 0 && !found.is_null()).then_some(entry.pw_uid)?
+            }
+            OwnerKind::Group => {
+                let mut entry: libc::group = unsafe { std::mem::zeroed() };
+                let mut found = std::ptr::null_mut();
+                let rc = unsafe {
+                    libc::getgrnam_r(
+                        name.as_ptr(),
+                        &mut entry,
+                        buffer.as_mut_ptr(),
+                        buffer.len(),
+                        &mut found,
+                    )
+                };
+                (rc == 0 && !found.is_null()).then_some(entry.gr_gid)?
+            }
+        };
+        Some(id.to_string())
+    }
+
+    pub fn set_owners(path: &Path, user: Option<&str>, group: Option<&str>) -> io::Result<()> {
+        // -1 leaves the id as it is
+        let id = |id: Option<&str>| id.and_then(|id| id.parse::<u32>().ok()).unwrap_or(u32::MAX);
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::lchown(path.as_ptr(), id(user), id(group)) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        let mut buffer = vec![0 as libc::c_char; BUFFER_SIZE];
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut found,
+            )
+        };
+        (rc == 0 && !found.is_null()).then(|| {
+            unsafe { CStr::from_ptr(entry.pw_name) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    fn group_name(gid: u32) -> Option<String> {
+        let mut buffer = vec![0 as libc::c_char; BUFFER_SIZE];
+        let mut entry: libc::group = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getgrgid_r(
+                gid,
+                &mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut found,
+            )
+        };
+        (rc == 0 && !found.is_null()).then(|| {
+            unsafe { CStr::from_ptr(entry.gr_name) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::fs::Metadata;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::{null, null_mut};
+
+    use super::{Account, OwnerKind};
+
+    type Sid = *mut c_void;
+
+    const SE_FILE_OBJECT: u32 = 1;
+    const OWNER_SECURITY_INFORMATION: u32 = 0x1;
+    const GROUP_SECURITY_INFORMATION: u32 = 0x2;
+    /// Longest SID, in bytes
+    const MAX_SID_SIZE: usize = 68;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn GetNamedSecurityInfoW(
+            name: *const u16,
+            object_type: u32,
+            info: u32,
+            owner: *mut Sid,
+            group: *mut Sid,
+            dacl: *mut *mut c_void,
+            sacl: *mut *mut c_void,
+            descriptor: *mut *mut c_void,
+        ) -> u32;
+        fn SetNamedSecurityInfoW(
+            name: *const u16,
+            object_type: u32,
+            info: u32,
+            owner: Sid,
+            group: Sid,
+            dacl: *const c_void,
+            sacl: *const c_void,
+        ) -> u32;
+        fn ConvertSidToStringSidW(sid: Sid, string: *mut *mut u16) -> i32;
+        fn ConvertStringSidToSidW(string: *const u16, sid: *mut Sid) -> i32;
+        fn LookupAccountSidW(
+            system: *const u16,
+            sid: Sid,
+            name: *mut u16,
+            name_len: *mut u32,
+            domain: *mut u16,
+            domain_len: *mut u32,
+            use_: *mut u32,
+        ) -> i32;
+        fn LookupAccountNameW(
+            system: *const u16,
+            name: *const u16,
+            sid: Sid,
+            sid_len: *mut u32,
+            domain: *mut u16,
+            domain_len: *mut u32,
+            use_: *mut u32,
+        ) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LocalFree(memory: *mut c_void) -> *mut c_void;
+    }
+
+    fn wide(text: &std::ffi::OsStr) -> Vec<u16> {
+        text.encode_wide().chain(std::iter::once(0)).collect()
+    }
+
+    fn from_wide(text: &[u16]) -> String {
+        let end = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+        String::from_utf16_lossy(&text[..end])
+    }
+
+    /// SID in its `S-1-...` form
+    fn sid_string(sid: Sid) -> io::Result<String> {
+        let mut string = null_mut();
+        if unsafe { ConvertSidToStringSidW(sid, &mut string) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = (0..)
+            .take_while(|&i| unsafe { *string.add(i) } != 0)
+            .count();
+        let text = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(string, len) });
+        unsafe { LocalFree(string as *mut c_void) };
+        Ok(text)
+    }
+
+    /// `DOMAIN\name` of a SID, if it resolves
+    fn account_name(sid: Sid) -> Option<String> {
+        let mut name = [0u16; 256];
+        let mut domain = [0u16; 256];
+        let (mut name_len, mut domain_len, mut use_) = (256, 256, 0);
+        let ok = unsafe {
+            LookupAccountSidW(
+                null(),
+                sid,
+                name.as_mut_ptr(),
+                &mut name_len,
+                domain.as_mut_ptr(),
+                &mut domain_len,
+                &mut use_,
+            )
+        };
+        (ok != 0).then(|| match from_wide(&domain) {
+            domain if domain.is_empty() => from_wide(&name),
+            domain => format!("{}\\{}", domain, from_wide(&name)),
+        })
+    }
+
+    pub fn owners(path: &Path, _meta: &Metadata) -> io::Result<(Account, Account)> {
+        let name = wide(path.as_os_str());
+        let (mut owner, mut group, mut descriptor) = (null_mut(), null_mut(), null_mut());
+        let status = unsafe {
+            GetNamedSecurityInfoW(
+                name.as_ptr(),
+                SE_FILE_OBJECT,
+                OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION,
+                &mut owner,
+                &mut group,
+                null_mut(),
+                null_mut(),
+                &mut descriptor,
+            )
+        };
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+        // The SIDs point into the descriptor, freed once they are read
+        let accounts = (|| -> io::Result<(Account, Account)> {
+            let user = Account {
+                id: sid_string(owner)?,
+                name: account_name(owner),
+            };
+            let group = Account {
+                id: sid_string(group)?,
+                name: account_name(group),
+            };
+            Ok((user, group))
+        })();
+        unsafe { LocalFree(descriptor) };
+        accounts
+    }
+
+    /// SID of the account named `name`, or `name` itself if it is a SID
+    pub fn find_account(_kind: OwnerKind, name: &str) -> Option<String> {
+        if name.starts_with("S-") || name.starts_with("s-") {
+            return Some(name.to_uppercase());
+        }
+        let name = wide(name.as_ref());
+        let mut sid = [0u8; MAX_SID_SIZE];
+        let mut domain = [0u16; 256];
+        let (mut sid_len, mut domain_len, mut use_) = (MAX_SID_SIZE as u32, 256, 0);
+        let ok = unsafe {
+            LookupAccountNameW(
+                null(),
+                name.as_ptr(),
+                sid.as_mut_ptr() as Sid,
+                &mut sid_len,
+                domain.as_mut_ptr(),
+                &mut domain_len,
+                &mut use_,
+            )
+        };
+        if ok == 0 {
+            return None;
+        }
+        sid_string(sid.as_mut_ptr() as Sid).ok()
+    }
+
+    pub fn set_owners(path: &Path, user: Option<&str>, group: Option<&str>) -> io::Result<()> {
+        let to_sid = |id: Option<&str>| -> io::Result<Sid> {
+            let Some(id) = id else {
+                return Ok(null_mut());
+            };
+            let mut sid = null_mut();
+            if unsafe { ConvertStringSidToSidW(wide(id.as_ref()).as_ptr(), &mut sid) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(sid)
+        };
+        let owner = to_sid(user)?;
+        let group = match to_sid(group) {
+            Ok(group) => group,
+            Err(e) => {
+                unsafe { LocalFree(owner) };
+                return Err(e);
+            }
+        };
+        let mut info = 0;
+        if !owner.is_null() {
+            info |= OWNER_SECURITY_INFORMATION;
+        }
+        if !group.is_null() {
+            info |= GROUP_SECURITY_INFORMATION;
+        }
+        let name = wide(path.as_os_str());
+        let status = unsafe {
+            SetNamedSecurityInfoW(
+                name.as_ptr(),
+                SE_FILE_OBJECT,
+                info,
+                owner,
+                group,
+                null(),
+                null(),
+            )
+        };
+        unsafe {
+            LocalFree(owner);
+            LocalFree(group);
+        }
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::fs::Metadata;
+    use std::io;
+    use std::path::Path;
+
+    use super::{Account, OwnerKind};
+
+    pub fn owners(_path: &Path, _meta: &Metadata) -> io::Result<(Account, Account)> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    pub fn find_account(_kind: OwnerKind, _name: &str) -> Option<String> {
+        None
+    }
+
+    pub fn set_owners(_path: &Path, _user: Option<&str>, _group: Option<&str>) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
@@ -0,0 +1,169 @@
+//! A quick look at which source files a job's filters pick.
+//!
+//! Walks the sources breadth-first for a limited number of entries,
+//! applying the patterns, `/XF` and `/XD` the way the copy does, so a
+//! filter can be checked against real names before a long job runs with it
+//! and copies nothing, or everything. `rbcp test-pattern` and the GUI's
+//! pattern tester list a whole folder the same way.
+//!
+//! The GUI's source tree lists the folders of a source one level at a
+//! time, and turns the folders unticked in it into `/XD` rules by full
+//! path, which leave out that folder and nothing else of the same name.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::args::CopyOptions;
+use crate::progress::ProgressCallback;
+use crate::tr;
+
+/// A file or folder the filters leave out
+#[derive(Debug, Clone, Serialize)]
+pub struct LeftOut {
+    /// Path below its source, with `/` between names
+    pub path: String,
+    /// The rule leaving it out
+    pub reason: String,
+}
+
+/// What a look at the first entries of the sources found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Peek {
+    /// Files the job copies, by path below their source
+    pub matched: Vec<String>,
+    pub left_out: Vec<LeftOut>,
+    /// Entries looked at
+    pub scanned: usize,
+    /// Whether the look stopped at the limit with entries left
+    pub truncated: bool,
+}
+
+/// A folder of the source tree
+#[derive(Debug, Clone, Serialize)]
+pub struct Subfolder {
+    pub name: String,
+    /// Full path, as `/XD` takes it
+    pub path: String,
+    /// Whether it has folders of its own to expand
+    pub has_subfolders: bool,
+}
+
+/// The folders directly in `dir`, by name. Links to folders are left out,
+/// as the copy does not follow them either.
+pub fn subfolders(dir: &Path) -> io::Result<Vec<Subfolder>> {
+    let dir = std::path::absolute(dir)?;
+    let is_folder =
+        |entry: &fs::DirEntry| entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+    let mut folders: Vec<Subfolder> = fs::read_dir(&dir)?
+        .flatten()
+        .filter(is_folder)
+        .map(|entry| {
+            let path = entry.path();
+            let has_subfolders = fs::read_dir(&path)
+                .is_ok_and(|mut entries| entries.any(|e| e.as_ref().is_ok_and(is_folder)));
+            Subfolder {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: path.to_string_lossy().to_string(),
+                has_subfolders,
+            }
+        })
+        .collect();
+    folders.sort_by_key(|folder| folder.name.to_lowercase());
+    Ok(folders)
+}
+
+/// Look at up to `limit` entries of the sources of `options`
+pub fn peek(options: &CopyOptions, limit: usize) -> Peek {
+    let mut result = Peek::default();
+    // Folders to look into, with their source and depth
+    let mut pending: VecDeque<(PathBuf, &Path, usize)> = options
+        .sources
+        .iter()
+        .map(|source| (PathBuf::from(source), Path::new(source), 0))
+        .collect();
+
+    while let Some((dir, source, depth)) = pending.pop_front() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if result.scanned >= limit {
+                result.truncated = true;
+                return result;
+            }
+            result.scanned += 1;
+
+            let path = entry.path();
+            let relative = relative_name(&path, source);
+            if path.is_dir() {
+                if !options.recursive || !options.descends_below(depth) {
+                    continue;
+                }
+                match options.dir_exclusion(&path) {
+                    Some(rule) => result.left_out.push(LeftOut {
+                        path: relative,
+                        reason: format!("/XD:{}", rule),
+                    }),
+                    None => pending.push_back((path, source, depth + 1)),
+                }
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(rule) = options.file_exclusion(&name) {
+                result.left_out.push(LeftOut {
+                    path: relative,
+                    reason: format!("/XF:{}", rule),
+                });
+            } else if options.selects_file(&name) {
+                result.matched.push(relative);
+            } else {
+                result.left_out.push(LeftOut {
+                    path: relative,
+                    reason: tr!("peek-no-pattern"),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// List which files below the sources of `options` match its patterns and
+/// which are left out and why, logging a line for each and a summary
+pub fn test_patterns(options: &CopyOptions, progress: &dyn ProgressCallback) -> Peek {
+    progress.on_log(&tr!(
+        "pattern-test-header",
+        dir = options.sources.join(";"),
+        patterns = options.patterns.join(" ")
+    ));
+    let result = peek(options, usize::MAX);
+    for path in &result.matched {
+        progress.on_log(&format!("  + {}", path));
+    }
+    for left_out in &result.left_out {
+        progress.on_log(&format!("  - {}  ({})", left_out.path, left_out.reason));
+    }
+    progress.on_log(&tr!(
+        "pattern-test-summary",
+        matched = result.matched.len(),
+        left_out = result.left_out.len()
+    ));
+    if result.matched.is_empty() {
+        progress.on_log(&tr!("pattern-test-none"));
+    }
+    result
+}
+
+/// `path` below `source` with `/` between names
+fn relative_name(path: &Path, source: &Path) -> String {
+    let relative = path.strip_prefix(source).unwrap_or(path);
+    relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,177 @@
+//! Sparing the battery of a laptop.
+//!
+//! A long mirror started on a laptop keeps the disk and network busy for
+//! hours and can run the battery flat before it is done. With
+//! `/BATTERY:pct` the engine pauses the job while the machine runs on
+//! battery with less than pct percent left, and with `/BATTERY:pct:mbps`
+//! slows it to mbps MB/s instead. The power source is checked every few
+//! seconds and the job goes back to its own speed once the machine is
+//! plugged in again.
+
+use serde::{Deserialize, Serialize};
+
+/// What runs the machine right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// Running on battery rather than from the mains
+    pub on_battery: bool,
+    /// Charge left, if known
+    pub percent: Option<u8>,
+}
+
+/// What a job does on a low battery. A threshold of 0 does nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryPolicy {
+    /// Act when on battery with less than this percentage left
+    pub below_percent: u8,
+    /// Bandwidth cap in MB/s meanwhile, or 0 to pause the job
+    pub throttle_mbps: f64,
+}
+
+impl BatteryPolicy {
+    pub fn is_active(&self) -> bool {
+        self.below_percent > 0
+    }
+
+    /// Whether the machine in `status` is low enough on battery to act.
+    /// An unknown charge counts as low.
+    pub fn is_low(&self, status: &PowerStatus) -> bool {
+        status.on_battery
+            && status
+                .percent
+                .is_none_or(|percent| percent < self.below_percent)
+    }
+
+    /// The policy as the `/BATTERY` argument
+    pub fn to_flag(&self) -> Option<String> {
+        if !self.is_active() {
+            None
+        } else if self.throttle_mbps > 0.0 {
+            Some(format!(
+                "/BATTERY:{}:{}",
+                self.below_percent, self.throttle_mbps
+            ))
+        } else {
+            Some(format!("/BATTERY:{}", self.below_percent))
+        }
+    }
+
+    /// Parse the value of `/BATTERY:pct[:mbps]`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (percent, mbps) = match value.split_once(':') {
+            Some((percent, mbps)) => (percent, Some(mbps)),
+            None => (value, None),
+        };
+        let below_percent = percent
+            .parse::<u8>()
+            .ok()
+            .filter(|percent| (1..=100).contains(percent))
+            .ok_or_else(|| format!("Invalid battery percentage: {}", percent))?;
+        let throttle_mbps = match mbps {
+            Some(mbps) => mbps
+                .parse::<f64>()
+                .ok()
+                .filter(|mbps| *mbps > 0.0)
+                .ok_or_else(|| format!("Invalid battery throttle: {}", mbps))?,
+            None => 0.0,
+        };
+        Ok(BatteryPolicy {
+            below_percent,
+            throttle_mbps,
+        })
+    }
+}
+
+/// Power source and charge of this machine, `None` if they cannot be told
+/// or it has no battery
+pub fn power_status() -> Option<PowerStatus> {
+    #[cfg(windows)]
+    {
+        #[repr(C)]
+        #[derive(Default)]
+        struct SystemPowerStatus {
+            ac_line_status: u8,
+            battery_flag: u8,
+            battery_life_percent: u8,
+            system_status_flag: u8,
+            battery_life_time: u32,
+            battery_full_life_time: u32,
+        }
+        const NO_SYSTEM_BATTERY: u8 = 128;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+        }
+
+        let mut status = SystemPowerStatus::default();
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0
+            || status.battery_flag & NO_SYSTEM_BATTERY != 0
+        {
+            return None;
+        }
+        Some(PowerStatus {
+            on_battery: status.ac_line_status == 0,
+            percent: (status.battery_life_percent <= 100).then_some(status.battery_life_percent),
+        })
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux_status()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // "Now drawing from 'Battery Power'" then a line with "85%;"
+        let output = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let (before, _) = text.lines().nth(1)?.split_once('%')?;
+        let digits = before.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+        Some(PowerStatus {
+            on_battery: text.contains("'Battery Power'"),
+            percent: digits.parse().ok(),
+        })
+    }
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        None
+    }
+}
+
+/// Power status from the supplies the kernel lists, leaving out the
+/// batteries of mice and other devices
+#[cfg(target_os = "linux")]
+fn linux_status() -> Option<PowerStatus> {
+    use std::fs;
+    use std::path::Path;
+
+    let read = |path: &Path, name: &str| {
+        fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut on_mains = false;
+    let mut batteries = Vec::new();
+    for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let supply = entry.path();
+        match read(&supply, "type").as_str() {
+            "Mains" | "USB" => on_mains |= read(&supply, "online") == "1",
+            "Battery" if read(&supply, "scope") != "Device" => {
+                batteries.push(read(&supply, "capacity").parse::<u8>().ok());
+            }
+            _ => {}
+        }
+    }
+    if batteries.is_empty() {
+        return None;
+    }
+    let known: Vec<u8> = batteries.iter().flatten().copied().collect();
+    Some(PowerStatus {
+        on_battery: !on_mains,
+        percent: (!known.is_empty())
+            .then(|| (known.iter().map(|&p| u32::from(p)).sum::<u32>() / known.len() as u32) as u8),
+    })
+}
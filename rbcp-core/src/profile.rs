@@ -0,0 +1,72 @@
+//! Saved job profiles.
+//!
+//! A profile is a named set of copy options stored as a JSON file in the
+//! profiles directory, optionally with a schedule on which the service mode
+//! runs it.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::queue::Priority;
+use crate::utils::data_dir;
+
+/// When a scheduled profile runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Every day at a local time given as `HH:MM`
+    Daily { at: String },
+    /// Repeatedly, this many minutes after the previous run started
+    Interval { minutes: u64 },
+}
+
+/// A named, saved copy job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobProfile {
+    pub name: String,
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    /// Profiles due at the same time run in order of priority
+    #[serde(default)]
+    pub priority: Priority,
+    pub options: CopyOptions,
+}
+
+impl JobProfile {
+    /// Default directory holding the profiles
+    pub fn profiles_dir() -> PathBuf {
+        data_dir().join("profiles")
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(io::Error::other)
+    }
+
+    /// Load every `*.json` profile in `dir`, skipping unreadable ones
+    pub fn load_all(dir: &Path) -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut profiles: Vec<Self> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| Self::load(&path).ok())
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    /// Save the profile as `<name>.json` in `dir`
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let file = BufWriter::new(File::create(dir.join(format!("{}.json", self.name)))?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+}
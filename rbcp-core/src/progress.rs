@@ -4,11 +4,20 @@
 //! different frontends (CLI, GUI) without coupling the core engine
 //! to any specific UI implementation.
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
+use crate::args::CopyOptions;
+use crate::stats::{CategoryTotals, PhaseTimes};
+use crate::utils::{format_duration, SizeFormat};
+
 /// Current state of a copy operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ProgressState {
@@ -18,10 +27,22 @@ pub enum ProgressState {
     Scanning,
     /// Actively copying files
     Copying,
+    /// Writing out the buffered data of a file at its end, which can take
+    /// a while on slow media after the bar has reached the file's end
+    Finalizing,
     /// Operation paused by user
     Paused,
+    /// Paused by the job's network policy until the connection allows
+    /// copying
+    NetworkPaused,
+    /// Paused while the machine runs on a low battery
+    BatteryPaused,
     /// Operation cancelled by user
     Cancelled,
+    /// Operation stopped at its deadline with files left to copy
+    Partial,
+    /// Nothing copied for longer than the stall timeout
+    Stalled,
     /// Operation completed successfully
     Completed,
     /// Operation failed with error
@@ -49,6 +70,21 @@ pub struct ProgressInfo {
     pub current_file_bytes_total: u64,
     /// Current speed in bytes per second
     pub speed: u64,
+    /// Directories scanned so far while scanning
+    #[serde(default)]
+    pub dirs_scanned: u64,
+    /// Directory being scanned
+    #[serde(default)]
+    pub scan_dir: String,
+    /// Time spent in each phase so far
+    #[serde(default)]
+    pub phases: PhaseTimes,
+    /// Copied files by category, filled in when the operation completes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<CategoryTotals>,
+    /// Files were denied access and the process is not elevated
+    #[serde(default)]
+    pub needs_elevation: bool,
 }
 impl Default for ProgressInfo {
     fn default() -> Self {
@@ -62,6 +98,11 @@ impl Default for ProgressInfo {
             current_file_bytes_done: 0,
             current_file_bytes_total: 0,
             speed: 0,
+            dirs_scanned: 0,
+            scan_dir: String::new(),
+            phases: PhaseTimes::default(),
+            categories: Vec::new(),
+            needs_elevation: false,
         }
     }
 }
@@ -89,6 +130,16 @@ impl ProgressInfo {
         }
     }
 
+    /// Time left at the current speed, once the total is known and
+    /// something was copied
+    pub fn eta(&self) -> Option<Duration> {
+        if self.bytes_total == 0 || self.speed == 0 {
+            return None;
+        }
+        let left = self.bytes_total.saturating_sub(self.bytes_done);
+        Some(Duration::from_secs(left / self.speed))
+    }
+
     /// Calculate current file progress as a percentage (0-100)
     pub fn file_percentage(&self) -> f32 {
         if self.current_file_bytes_total == 0 {
@@ -139,11 +190,17 @@ impl ProgressCallback for NullProgress {
     }
 }
 
-/// A CLI progress callback that prints to stdout.
+/// A CLI progress callback that prints to stdout, or to stderr when stdout
+/// carries other output.
 pub struct CliProgress {
     cancel_flag: Arc<AtomicBool>,
     show_progress: bool,
     show_file_names: bool,
+    to_stderr: bool,
+    size_format: SizeFormat,
+    /// Length of the progress line last printed, to blank what a shorter
+    /// one would leave of it
+    line_len: AtomicUsize,
 }
 
 impl CliProgress {
@@ -152,15 +209,89 @@ impl CliProgress {
             cancel_flag: Arc::new(AtomicBool::new(false)),
             show_progress,
             show_file_names,
+            to_stderr: false,
+            size_format: SizeFormat::default(),
+            line_len: AtomicUsize::new(0),
         }
     }
 
+    /// Progress as `options` ask for it: no progress line with /NP, no file
+    /// names with /NFL and sizes in the units chosen. With /PRINT0 it goes
+    /// to stderr, leaving stdout to the copied paths.
+    pub fn for_options(options: &CopyOptions) -> Self {
+        let progress = Self::new(options.show_progress, options.log_file_names)
+            .with_size_format(options.size_format);
+        if options.print0 {
+            progress.to_stderr()
+        } else {
+            progress
+        }
+    }
+
+    /// Print progress and log messages to stderr instead of stdout
+    pub fn to_stderr(mut self) -> Self {
+        self.to_stderr = true;
+        self
+    }
+
+    /// Write sizes and speeds in `size_format`
+    pub fn with_size_format(mut self, size_format: SizeFormat) -> Self {
+        self.size_format = size_format;
+        self
+    }
+
+    fn print(&self, text: &str) {
+        use std::io::Write;
+        if self.to_stderr {
+            let mut stderr = std::io::stderr();
+            let _ = stderr.write_all(text.as_bytes());
+            let _ = stderr.flush();
+        } else {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(text.as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+
+    /// Print `line` over the progress line printed last
+    fn print_line(&self, line: &str) {
+        let len = line.chars().count();
+        let last = self.line_len.swap(len, Ordering::Relaxed);
+        self.print(&format!(
+            "\r{}{}",
+            line,
+            " ".repeat(last.saturating_sub(len))
+        ));
+    }
+
     /// Get a handle to request cancellation
     pub fn cancel_handle(&self) -> Arc<AtomicBool> {
         self.cancel_flag.clone()
     }
 }
 
+/// Percentage, files and bytes done, speed and time left of a copy
+fn copy_line(info: &ProgressInfo, sizes: SizeFormat) -> String {
+    let mut line = format!(
+        "{:.0}% - {} of {} files, {}",
+        info.percentage(),
+        info.files_done,
+        info.files_total,
+        sizes.format(info.bytes_done)
+    );
+    // Without a scan first the total is not known
+    if info.bytes_total > 0 {
+        line.push_str(&format!(" of {}", sizes.format(info.bytes_total)));
+    }
+    if info.speed > 0 {
+        line.push_str(&format!(" at {}/s", sizes.format(info.speed)));
+    }
+    if let Some(eta) = info.eta() {
+        line.push_str(&format!(", {} left", format_duration(eta)));
+    }
+    line
+}
+
 impl ProgressCallback for CliProgress {
     fn on_progress(&self, info: &ProgressInfo) {
         if !self.show_progress {
@@ -169,19 +300,25 @@ impl ProgressCallback for CliProgress {
 
         match info.state {
             ProgressState::Scanning => {
-                print!("\rScanning: {} files found...", info.files_total);
-                let _ = std::io::Write::flush(&mut std::io::stdout());
+                self.print_line(&format!(
+                    "Scanning: {} files, {} in {} directories...",
+                    info.files_total,
+                    self.size_format.format(info.bytes_total),
+                    info.dirs_scanned
+                ));
             }
             ProgressState::Copying => {
-                let pct = info.percentage();
-                print!(
-                    "\r{:.0}% - {} of {} files",
-                    pct, info.files_done, info.files_total
-                );
-                let _ = std::io::Write::flush(&mut std::io::stdout());
+                self.print_line(&copy_line(info, self.size_format));
+            }
+            ProgressState::Finalizing => {
+                self.print_line(&format!(
+                    "{}, writing out...",
+                    copy_line(info, self.size_format)
+                ));
             }
             ProgressState::Completed => {
-                println!("\nCompleted!");
+                self.line_len.store(0, Ordering::Relaxed);
+                self.print("\nCompleted!\n");
             }
             _ => {}
         }
@@ -189,7 +326,81 @@ impl ProgressCallback for CliProgress {
 
     fn on_log(&self, message: &str) {
         if self.show_file_names {
-            println!("{}", message);
+            self.print(&format!("{}\n", message));
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_flag.load(Ordering::Relaxed)
+    }
+
+    fn is_paused(&self) -> bool {
+        false // CLI doesn't support pause
+    }
+}
+
+/// A CLI progress callback that prints progress snapshots as JSON lines.
+///
+/// One line is written to stdout per interval, plus one whenever the state
+/// changes, so wrappers can parse progress without handling the `\r`
+/// overwritten human-readable line. Log messages go to stderr.
+pub struct JsonProgress {
+    cancel_flag: Arc<AtomicBool>,
+    interval: Duration,
+    last: Mutex<Option<(Instant, ProgressState)>>,
+    show_file_names: bool,
+}
+
+#[derive(Serialize)]
+struct JsonProgressLine<'a> {
+    #[serde(flatten)]
+    info: &'a ProgressInfo,
+    percentage: f32,
+}
+
+impl JsonProgress {
+    pub fn new(interval: Duration, show_file_names: bool) -> Self {
+        Self {
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            interval,
+            last: Mutex::new(None),
+            show_file_names,
+        }
+    }
+
+    /// Get a handle to request cancellation
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        self.cancel_flag.clone()
+    }
+}
+
+impl ProgressCallback for JsonProgress {
+    fn on_progress(&self, info: &ProgressInfo) {
+        let now = Instant::now();
+        {
+            let mut last = self.last.lock().unwrap();
+            if let Some((at, state)) = *last {
+                if state == info.state && now.duration_since(at) < self.interval {
+                    return;
+                }
+            }
+            *last = Some((now, info.state));
+        }
+
+        let line = JsonProgressLine {
+            info,
+            percentage: info.percentage(),
+        };
+        if let Ok(json) = serde_json::to_string(&line) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", json);
+            let _ = stdout.flush();
+        }
+    }
+
+    fn on_log(&self, message: &str) {
+        if self.show_file_names {
+            eprintln!("{}", message);
         }
     }
 
@@ -202,6 +413,30 @@ impl ProgressCallback for CliProgress {
     }
 }
 
+/// Log lines kept in memory by default
+pub const DEFAULT_LOG_LINES: usize = 10_000;
+
+/// The latest log lines, up to a capacity so a job logging a line per file
+/// does not fill the memory. Every line also goes to the spool file, when
+/// there is one, for the full log to be saved.
+struct LogBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+    spool: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl LogBuffer {
+    fn push(&mut self, message: &str) {
+        if let Some((_, file)) = &mut self.spool {
+            let _ = writeln!(file, "{}", message);
+        }
+        if self.lines.len() >= self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(message.to_string());
+    }
+}
+
 /// Shared progress state that can be accessed by both the engine and UI.
 /// This is useful for GUI applications where the UI thread needs to
 /// poll the current progress.
@@ -210,7 +445,7 @@ pub struct SharedProgress {
     cancel_flag: Arc<AtomicBool>,
     pause_flag: Arc<AtomicBool>,
     info: Arc<std::sync::Mutex<ProgressInfo>>,
-    log_messages: Arc<std::sync::Mutex<Vec<String>>>,
+    log_messages: Arc<std::sync::Mutex<LogBuffer>>,
 }
 
 impl SharedProgress {
@@ -219,7 +454,11 @@ impl SharedProgress {
             cancel_flag: Arc::new(AtomicBool::new(false)),
             pause_flag: Arc::new(AtomicBool::new(false)),
             info: Arc::new(std::sync::Mutex::new(ProgressInfo::default())),
-            log_messages: Arc::new(std::sync::Mutex::new(Vec::new())),
+            log_messages: Arc::new(std::sync::Mutex::new(LogBuffer {
+                lines: VecDeque::new(),
+                capacity: DEFAULT_LOG_LINES,
+                spool: None,
+            })),
         }
     }
 
@@ -251,20 +490,66 @@ impl SharedProgress {
     /// Get and clear log messages
     pub fn take_logs(&self) -> Vec<String> {
         let mut logs = self.log_messages.lock().unwrap();
-        std::mem::take(&mut *logs)
+        std::mem::take(&mut logs.lines).into()
     }
 
     /// Get log messages without clearing
     pub fn peek_logs(&self) -> Vec<String> {
-        self.log_messages.lock().unwrap().clone()
+        self.log_messages
+            .lock()
+            .unwrap()
+            .lines
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Keep at most `lines` log lines in memory, dropping the oldest
+    pub fn set_log_capacity(&self, lines: usize) {
+        let mut logs = self.log_messages.lock().unwrap();
+        logs.capacity = lines.max(1);
+        let excess = logs.lines.len().saturating_sub(logs.capacity);
+        logs.lines.drain(..excess);
+    }
+
+    /// Write every log line from now on to `path` as well, replacing what
+    /// it held, so the full log can be saved whatever the capacity
+    pub fn spool_logs(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        self.log_messages.lock().unwrap().spool = Some((path.to_path_buf(), file));
+        Ok(())
+    }
+
+    /// Save the full log to `path`: the spool file if there is one, else
+    /// the lines still in memory
+    pub fn save_log(&self, path: &Path) -> io::Result<()> {
+        let mut logs = self.log_messages.lock().unwrap();
+        match &mut logs.spool {
+            Some((spool, file)) => {
+                file.flush()?;
+                fs::copy(spool, path)?;
+            }
+            None => {
+                let mut file = BufWriter::new(File::create(path)?);
+                for line in &logs.lines {
+                    writeln!(file, "{}", line)?;
+                }
+                file.flush()?;
+            }
+        }
+        Ok(())
     }
 
-    /// Reset the progress state for a new operation
+    /// Reset the progress state for a new operation. The spool file goes
+    /// on, holding the log of every operation.
     pub fn reset(&self) {
         self.cancel_flag.store(false, Ordering::Relaxed);
         self.pause_flag.store(false, Ordering::Relaxed);
         *self.info.lock().unwrap() = ProgressInfo::default();
-        self.log_messages.lock().unwrap().clear();
+        self.log_messages.lock().unwrap().lines.clear();
     }
 }
 
@@ -280,7 +565,7 @@ impl ProgressCallback for SharedProgress {
     }
 
     fn on_log(&self, message: &str) {
-        self.log_messages.lock().unwrap().push(message.to_string());
+        self.log_messages.lock().unwrap().push(message);
     }
 
     fn is_cancelled(&self) -> bool {
@@ -0,0 +1,212 @@
+//! Files that fail run after run.
+//!
+//! A file that cannot be copied tonight usually cannot be copied tomorrow
+//! either: a corrupt sector, a name the destination refuses. Retried on
+//! every run it waits out the retries and delays each time and buries the
+//! new failures in the old. With `/QUARANTINE:n` a job counts the runs in a
+//! row each of its files failed in, in `quarantine.json` in the data
+//! directory. A file failing n runs in a row is quarantined: later runs of
+//! the job skip it without trying, and list it in a section of their
+//! summary until `rbcp quarantine release` lets it be copied again. With
+//! `/QUARANTINE:n:SKIPLIST` it also goes on the skip-list, so no job copies
+//! it.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::Failure;
+use crate::tr;
+use crate::utils::data_dir;
+
+/// A file that failed in the latest runs of a job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailingFile {
+    /// Runs in a row the file failed in
+    pub runs: u32,
+    /// Why it failed the last time
+    pub reason: String,
+    /// Unix timestamp of when it was quarantined, if it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_at: Option<i64>,
+}
+
+/// The failing files of each job, by [`job_key`](crate::history::job_key)
+/// and absolute source path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Quarantine {
+    pub jobs: BTreeMap<String, BTreeMap<PathBuf, FailingFile>>,
+}
+
+impl Quarantine {
+    /// Default location of the quarantine
+    pub fn default_path() -> PathBuf {
+        data_dir().join("quarantine.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// Quarantined files of `job`, with why they failed
+    pub fn quarantined(&self, job: &str) -> Vec<(&Path, &FailingFile)> {
+        self.jobs
+            .get(job)
+            .into_iter()
+            .flatten()
+            .filter(|(_, file)| file.quarantined_at.is_some())
+            .map(|(path, file)| (path.as_path(), file))
+            .collect()
+    }
+
+    /// Count a run of `job` that failed on `failures`, quarantining the
+    /// files that failed `runs` runs in a row. Files that did not fail
+    /// start over if the run went through all of them, as `completed`
+    /// says. Returns the files quarantined by this run.
+    pub fn record_run(
+        &mut self,
+        job: &str,
+        failures: &[Failure],
+        completed: bool,
+        runs: u32,
+    ) -> Vec<PathBuf> {
+        let files = self.jobs.entry(job.to_string()).or_default();
+        let failed: HashSet<PathBuf> = failures.iter().map(|f| absolute(&f.path)).collect();
+        if completed {
+            files.retain(|path, file| file.quarantined_at.is_some() || failed.contains(path));
+        }
+
+        let mut quarantined = Vec::new();
+        for failure in failures {
+            let path = absolute(&failure.path);
+            let file = files.entry(path.clone()).or_insert(FailingFile {
+                runs: 0,
+                reason: String::new(),
+                quarantined_at: None,
+            });
+            file.runs += 1;
+            file.reason = failure.reason.clone();
+            if file.runs >= runs && file.quarantined_at.is_none() {
+                file.quarantined_at = Some(now());
+                quarantined.push(path);
+            }
+        }
+        if files.is_empty() {
+            self.jobs.remove(job);
+        }
+        quarantined
+    }
+
+    /// Let the file at `path` be copied again by every job, returning
+    /// whether it was quarantined
+    pub fn release(&mut self, path: &Path) -> bool {
+        let path = absolute(path);
+        let mut released = false;
+        for files in self.jobs.values_mut() {
+            released |= files.remove(&path).is_some();
+        }
+        self.jobs.retain(|_, files| !files.is_empty());
+        released
+    }
+}
+
+/// The quarantined files of a job, which it skips
+#[derive(Debug, Default)]
+pub struct QuarantineSet {
+    paths: HashSet<PathBuf>,
+}
+
+impl QuarantineSet {
+    pub fn new(quarantine: &Quarantine, job: &str) -> Self {
+        QuarantineSet {
+            paths: quarantine
+                .quarantined(job)
+                .into_iter()
+                .map(|(path, _)| path.to_path_buf())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Whether the source file at `path` is quarantined
+    pub fn contains(&self, path: &Path) -> bool {
+        !self.paths.is_empty() && self.paths.contains(&absolute(path))
+    }
+}
+
+/// `path` made absolute without resolving links, as jobs name sources
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// What `rbcp quarantine` is asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuarantineCommand {
+    /// List the quarantined files of every job
+    Show,
+    /// Let these files be copied again
+    Release { paths: Vec<String> },
+}
+
+impl QuarantineCommand {
+    /// Carry out the command on the quarantine at `path`, returning the
+    /// lines to print
+    pub fn run(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut quarantine = Quarantine::load(path);
+        let mut lines = Vec::new();
+        match self {
+            QuarantineCommand::Show => {
+                for job in quarantine.jobs.keys() {
+                    let files = quarantine.quarantined(job);
+                    if files.is_empty() {
+                        continue;
+                    }
+                    lines.push(job.clone());
+                    for (path, file) in files {
+                        lines.push(format!("    {}: {}", path.display(), file.reason));
+                    }
+                }
+                if lines.is_empty() {
+                    lines.push(tr!("quarantine-empty"));
+                }
+            }
+            QuarantineCommand::Release { paths } => {
+                for entry in paths {
+                    let id = if quarantine.release(Path::new(entry)) {
+                        "quarantine-released"
+                    } else {
+                        "quarantine-not-found"
+                    };
+                    lines.push(tr!(id, path = entry.as_str()));
+                }
+                quarantine.save(path)?;
+            }
+        }
+        Ok(lines)
+    }
+}
@@ -0,0 +1,402 @@
+//! Persistent job queue for frontends that run several copies in turn.
+//!
+//! The queue is saved to disk whenever it changes, replacing the file in one
+//! step, so queued work survives closing the application or a crash. Each job records a restart journal
+//! under its own name, so a job that was running when the application went
+//! away can continue where it stopped.
+//!
+//! Jobs of higher priority start first, up to a limit of jobs running at
+//! once. When the limit is reached, a waiting job of higher priority makes
+//! room for itself: the running job of lowest priority is stopped and
+//! queued again, to resume from its journal once there is room. A paused
+//! queue starts no jobs and lets the running ones finish.
+//!
+//! Until a job starts, its filters, thread count and bandwidth cap can still
+//! be changed, so a mistake spotted while an earlier job runs does not mean
+//! removing the job and queueing it again.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::checkpoint::Checkpoint;
+use crate::tr;
+use crate::utils::{data_dir, replace_file};
+
+/// State of a queued job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    /// Waiting to be run
+    Pending,
+    /// Currently being copied
+    Running,
+    /// Was running when the application stopped
+    Interrupted,
+    /// Being stopped to make room for a job of higher priority, and queued
+    /// again once it has
+    Preempting,
+}
+
+/// How urgent a queued job is
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// A copy job waiting in the queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    pub id: u64,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    pub options: CopyOptions,
+}
+
+/// The settings of a queued job that can change until it starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobEdit {
+    pub patterns: Vec<String>,
+    pub exclude_files: Vec<String>,
+    pub exclude_dirs: Vec<String>,
+    pub threads: usize,
+    /// Bandwidth cap in MB/s, 0 for none
+    pub throttle_mbps: f64,
+}
+
+impl JobEdit {
+    /// The settings of `options` an edit changes
+    pub fn of(options: &CopyOptions) -> Self {
+        JobEdit {
+            patterns: options.patterns.clone(),
+            exclude_files: options.exclude_files.clone(),
+            exclude_dirs: options.exclude_dirs.clone(),
+            threads: options.threads,
+            throttle_mbps: options.throttle_mbps,
+        }
+    }
+
+    /// What is wrong with the edit, nothing if it can be applied
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .patterns
+            .iter()
+            .chain(&self.exclude_files)
+            .filter_map(|pattern| rbcp_plan::check_pattern(pattern).err())
+            .collect();
+        // Full paths of folders are compared, not matched
+        problems.extend(
+            self.exclude_dirs
+                .iter()
+                .filter(|pattern| !Path::new(pattern).is_absolute())
+                .filter_map(|pattern| rbcp_plan::check_pattern(pattern).err()),
+        );
+        if self.threads == 0 {
+            problems.push(tr!("filter-invalid-threads"));
+        }
+        if self.throttle_mbps < 0.0 {
+            problems.push(tr!("filter-invalid-throttle"));
+        }
+        problems
+    }
+
+    /// Change the settings of `options`, copying all files if no pattern
+    /// is left
+    pub fn apply(&self, options: &mut CopyOptions) {
+        options.patterns = if self.patterns.is_empty() {
+            vec!["*.*".to_string()]
+        } else {
+            self.patterns.clone()
+        };
+        options.exclude_files = self.exclude_files.clone();
+        options.exclude_dirs = self.exclude_dirs.clone();
+        // A thread count chosen by hand replaces the automatic one
+        if self.threads != options.threads {
+            options.threads = self.threads.max(1);
+            options.auto_threads = false;
+        }
+        options.throttle_mbps = self.throttle_mbps.max(0.0);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct QueueFile {
+    next_id: u64,
+    jobs: Vec<QueuedJob>,
+    paused: bool,
+    max_running: usize,
+}
+
+impl Default for QueueFile {
+    fn default() -> Self {
+        QueueFile {
+            next_id: 0,
+            jobs: Vec::new(),
+            paused: false,
+            max_running: 1,
+        }
+    }
+}
+
+/// Queue of jobs that have not finished yet
+pub struct JobQueue {
+    path: PathBuf,
+    next_id: u64,
+    jobs: Vec<QueuedJob>,
+    /// No more jobs are started while set
+    paused: bool,
+    /// Most jobs running at once
+    max_running: usize,
+}
+
+impl JobQueue {
+    /// Default location of the queue file
+    pub fn default_path() -> PathBuf {
+        data_dir().join("queue.json")
+    }
+
+    /// Load the queue from `path`. Jobs that were running when the queue
+    /// was last saved are marked as interrupted. A file that cannot be read
+    /// is moved aside to `<path>.unreadable` rather than overwritten by the
+    /// next save.
+    pub fn load(path: &Path) -> Self {
+        let mut file = match File::open(path) {
+            Ok(file) => match serde_json::from_reader::<_, QueueFile>(BufReader::new(file)) {
+                Ok(file) => file,
+                Err(e) => {
+                    let mut kept = path.as_os_str().to_os_string();
+                    kept.push(".unreadable");
+                    let kept = PathBuf::from(kept);
+                    tracing::warn!(error = %e, kept = %kept.display(), "queue file unreadable");
+                    let _ = fs::rename(path, &kept);
+                    QueueFile::default()
+                }
+            },
+            Err(_) => QueueFile::default(),
+        };
+
+        for job in &mut file.jobs {
+            if matches!(job.status, JobStatus::Running | JobStatus::Preempting) {
+                job.status = JobStatus::Interrupted;
+            }
+        }
+
+        Self {
+            path: path.to_path_buf(),
+            next_id: file.next_id,
+            jobs: file.jobs,
+            paused: file.paused,
+            max_running: file.max_running.max(1),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = QueueFile {
+            next_id: self.next_id,
+            jobs: self.jobs.clone(),
+            paused: self.paused,
+            max_running: self.max_running,
+        };
+        replace_file(&self.path, |writer| {
+            serde_json::to_writer_pretty(writer, &file).map_err(io::Error::other)
+        })
+    }
+
+    pub fn jobs(&self) -> &[QueuedJob] {
+        &self.jobs
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop starting jobs, or start them again
+    pub fn set_paused(&mut self, paused: bool) -> io::Result<()> {
+        self.paused = paused;
+        self.save()
+    }
+
+    pub fn max_running(&self) -> usize {
+        self.max_running
+    }
+
+    /// Set the most jobs running at once. Jobs over a lower limit finish.
+    pub fn set_max_running(&mut self, max_running: usize) -> io::Result<()> {
+        self.max_running = max_running.max(1);
+        self.save()
+    }
+
+    pub fn set_priority(&mut self, id: u64, priority: Priority) -> io::Result<()> {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.priority = priority;
+        }
+        self.save()
+    }
+
+    /// Apply `edit` to job `id`, which must be pending and not started
+    /// before, as one resuming from its journal would copy the rest of its
+    /// files with other filters than the first part
+    pub fn edit(&mut self, id: u64, edit: &JobEdit) -> io::Result<()> {
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No job {}", id)))?;
+        if job.status != JobStatus::Pending || job.options.resume {
+            return Err(io::Error::other(tr!("queue-job-started")));
+        }
+        let problems = edit.problems();
+        if !problems.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                problems.join("; "),
+            ));
+        }
+        edit.apply(&mut job.options);
+        self.save()
+    }
+
+    /// Jobs taking up a place among those running at once
+    fn occupied(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| matches!(job.status, JobStatus::Running | JobStatus::Preempting))
+            .count()
+    }
+
+    /// The pending job to start next: the first of the highest priority
+    fn next_pending(&mut self) -> Option<&mut QueuedJob> {
+        // The last of equal maximums is kept, so look from the end
+        self.jobs
+            .iter_mut()
+            .rev()
+            .filter(|job| job.status == JobStatus::Pending)
+            .max_by_key(|job| job.priority)
+    }
+
+    /// Add a job to the end of the queue and return its id
+    pub fn push(&mut self, mut options: CopyOptions, priority: Priority) -> io::Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // Every queued job keeps its own restart journal
+        options.job_name = Some(format!("queue-{}", id));
+        options.resume = false;
+
+        self.jobs.push(QueuedJob {
+            id,
+            status: JobStatus::Pending,
+            priority,
+            options,
+        });
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Take the next pending job and mark it as running, if the queue is
+    /// not paused and fewer than the most jobs at once are running
+    pub fn start_next(&mut self) -> io::Result<Option<QueuedJob>> {
+        if self.paused || self.occupied() >= self.max_running {
+            return Ok(None);
+        }
+        let Some(job) = self.next_pending() else {
+            return Ok(None);
+        };
+
+        job.status = JobStatus::Running;
+        let job = job.clone();
+        self.save()?;
+        Ok(Some(job))
+    }
+
+    /// Queue interrupted jobs again so they resume from their journal
+    pub fn resume_interrupted(&mut self) -> io::Result<usize> {
+        let mut count = 0;
+        for job in &mut self.jobs {
+            if job.status == JobStatus::Interrupted {
+                job.status = JobStatus::Pending;
+                job.options.resume =
+                    job.options.job_name.as_deref().is_some_and(|name| {
+                        Checkpoint::path_for(name).is_ok_and(|path| path.exists())
+                    });
+                count += 1;
+            }
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    /// The running job to stop to make room for a waiting job of higher
+    /// priority, marked as preempting, if there is one and no room
+    pub fn preempt(&mut self) -> io::Result<Option<u64>> {
+        let waiting = self
+            .jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Pending)
+            .map(|job| job.priority)
+            .max();
+        let Some(waiting) = waiting else {
+            return Ok(None);
+        };
+        // Room is free or being made already
+        if self.paused
+            || self.occupied() < self.max_running
+            || self
+                .jobs
+                .iter()
+                .any(|job| job.status == JobStatus::Preempting)
+        {
+            return Ok(None);
+        }
+
+        // Of the lowest priority, the job queued last has the least done
+        let Some(job) = self
+            .jobs
+            .iter_mut()
+            .rev()
+            .filter(|job| job.status == JobStatus::Running && job.priority < waiting)
+            .min_by_key(|job| job.priority)
+        else {
+            return Ok(None);
+        };
+        job.status = JobStatus::Preempting;
+        let id = job.id;
+        self.save()?;
+        Ok(Some(id))
+    }
+
+    /// Remove a job that has stopped, or queue it again to resume if it was
+    /// stopped to make room
+    pub fn finish(&mut self, id: u64) -> io::Result<()> {
+        match self.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) if job.status == JobStatus::Preempting => {
+                job.status = JobStatus::Pending;
+                job.options.resume =
+                    job.options.job_name.as_deref().is_some_and(|name| {
+                        Checkpoint::path_for(name).is_ok_and(|path| path.exists())
+                    });
+                self.save()
+            }
+            _ => self.remove(id),
+        }
+    }
+
+    /// Remove a job, e.g. once it has finished
+    pub fn remove(&mut self, id: u64) -> io::Result<()> {
+        self.jobs.retain(|job| job.id != id);
+        self.save()
+    }
+}
@@ -0,0 +1,432 @@
+//! Rewriting destination paths during a copy.
+//!
+//! Remap rules restructure a tree while it is copied, e.g. to strip a
+//! prefix or merge directory levels when migrating a media library. Each
+//! rule is a find/replace, literal or regex, applied in order to the path
+//! relative to the destination root, with `/` as the separator. Directory
+//! paths end with a `/`, so a rule removing `Music/` moves the files below
+//! the `Music` directory up a level and no empty `Music` directory is left
+//! behind.
+//!
+//! Flattening drops the directory structure altogether and puts every file
+//! in the destination root, renaming files whose names collide. The names
+//! are given out in sorted order before the walk, so each run renames the
+//! same files the same way however threads reach them. Names can
+//! also be forced to one case, for case-sensitive destinations, and routed
+//! by pattern or file type to folders such as `Photos/` and `Videos/`.
+//!
+//! The mapper is also where each source's folder in the destination is
+//! decided, so the walk, the purge and the reports agree on where a file
+//! goes.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::cycles::Ancestors;
+use crate::stats::FileCategory;
+use crate::utils::matches_pattern;
+
+/// A find/replace on destination paths
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemapRule {
+    pub find: String,
+    pub replace: String,
+    /// `find` is a regular expression and `replace` may refer to its
+    /// groups as `$1` or `${name}`
+    #[serde(default)]
+    pub regex: bool,
+}
+
+impl RemapRule {
+    /// Parse `find=replace`
+    pub fn parse(spec: &str, regex: bool) -> Result<Self, String> {
+        let (find, replace) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Remap rule must be find=replace: {}", spec))?;
+        let rule = RemapRule {
+            find: find.to_string(),
+            replace: replace.to_string(),
+            regex,
+        };
+        rule.compile()?;
+        Ok(rule)
+    }
+
+    fn compile(&self) -> Result<Matcher, String> {
+        if self.regex {
+            Regex::new(&self.find)
+                .map(Matcher::Regex)
+                .map_err(|e| format!("Invalid remap pattern {}: {}", self.find, e))
+        } else {
+            Ok(Matcher::Literal(self.find.clone()))
+        }
+    }
+}
+
+enum Matcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+/// How a flattened file is renamed when its name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlattenCollision {
+    /// Append a number, e.g. `app (2).log`
+    #[default]
+    Suffix,
+    /// Prefix the source directories, e.g. `server1_logs_app.log`
+    Prefix,
+}
+
+impl FlattenCollision {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_uppercase().as_str() {
+            "SUFFIX" => Ok(FlattenCollision::Suffix),
+            "PREFIX" => Ok(FlattenCollision::Prefix),
+            _ => Err(format!(
+                "Unknown collision policy: {} (expected suffix or prefix)",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FlattenCollision::Suffix => "SUFFIX",
+            FlattenCollision::Prefix => "PREFIX",
+        }
+    }
+}
+
+/// Case forced on destination names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameCase {
+    Lower,
+    Upper,
+}
+
+impl NameCase {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_uppercase().as_str() {
+            "LOWER" => Ok(NameCase::Lower),
+            "UPPER" => Ok(NameCase::Upper),
+            _ => Err(format!("Unknown case: {} (expected lower or upper)", name)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NameCase::Lower => "LOWER",
+            NameCase::Upper => "UPPER",
+        }
+    }
+
+    fn apply(self, text: &str) -> String {
+        match self {
+            NameCase::Lower => text.to_lowercase(),
+            NameCase::Upper => text.to_uppercase(),
+        }
+    }
+}
+
+/// Sends files matching any of a set of patterns to a destination folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// File name patterns, matched ignoring case, or file categories such as
+    /// `@images`
+    pub patterns: Vec<String>,
+    /// Folder below the destination, e.g. `Photos`
+    pub folder: String,
+}
+
+impl RouteRule {
+    /// Parse `patterns=folder` with patterns separated by `;`, e.g.
+    /// `*.jpg;*.png=Photos` or `@video=Videos`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (patterns, folder) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Route must be patterns=folder: {}", spec))?;
+        let patterns: Vec<String> = patterns
+            .split(';')
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+        for pattern in &patterns {
+            if let Some(category) = pattern.strip_prefix('@') {
+                FileCategory::parse(category)?;
+            }
+        }
+        Ok(RouteRule {
+            patterns,
+            folder: folder.to_string(),
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let lower = name.to_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| match pattern.strip_prefix('@') {
+                Some(category) => FileCategory::parse(category)
+                    .is_ok_and(|c| FileCategory::of(Path::new(name)) == c),
+                None => matches_pattern(&lower, &pattern.to_lowercase()),
+            })
+    }
+}
+
+impl fmt::Display for RouteRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.patterns.join(";"), self.folder)
+    }
+}
+
+/// Computes destination paths: the folder each source is copied to, and
+/// the rewrites of paths below the destination root set by the remap,
+/// route, flatten and case options. Rewrites apply in the order: remap
+/// rules, case, routes, flattening.
+pub struct PathMapper {
+    root: PathBuf,
+    preserve_root: bool,
+    source_subdirs: BTreeMap<String, String>,
+    /// Whether any rewrite is set
+    rewrites: bool,
+    rules: Vec<(Matcher, String)>,
+    case: Option<NameCase>,
+    routes: Vec<RouteRule>,
+    flatten: Option<FlattenCollision>,
+    /// Lowercased paths given out while flattening, so names differing only
+    /// in case don't collide on case-insensitive destinations
+    claimed: Mutex<HashSet<String>>,
+    /// Flattened paths given out before the walk, by the path they replace
+    flattened: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl PathMapper {
+    /// Mapper for the destination `root` of a job with `options`
+    pub fn new(root: &Path, options: &CopyOptions) -> Result<Self, String> {
+        Ok(PathMapper {
+            root: root.to_path_buf(),
+            preserve_root: options.preserve_root,
+            source_subdirs: options.source_subdirs.clone(),
+            rewrites: options.rewrites_paths(),
+            rules: options
+                .remap
+                .iter()
+                .map(|rule| Ok((rule.compile()?, rule.replace.clone())))
+                .collect::<Result<_, String>>()?,
+            case: options.name_case,
+            routes: options.routes.clone(),
+            flatten: options.flatten,
+            claimed: Mutex::new(HashSet::new()),
+            flattened: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether paths below the destination root are rewritten
+    pub fn rewrites(&self) -> bool {
+        self.rewrites
+    }
+
+    /// Where the files of the source at `source` go: its subdirectory if
+    /// one is set, a folder named after it with root preservation, or the
+    /// destination root
+    pub fn source_root(&self, source: &Path) -> PathBuf {
+        if let Some(subdir) = self.source_subdirs.get(&*source.to_string_lossy()) {
+            self.root.join(subdir)
+        } else if self.preserve_root && source.is_dir() {
+            self.root.join(source.file_name().unwrap_or_default())
+        } else {
+            self.root.clone()
+        }
+    }
+
+    /// Where the child folder `name` of `source` goes with /CHILDONLY,
+    /// which copies each child as a source of its own
+    pub fn child_root(&self, source: &Path, name: &OsStr) -> PathBuf {
+        match self.source_subdirs.get(&*source.to_string_lossy()) {
+            Some(subdir) => self.root.join(subdir).join(name),
+            None => self.root.join(name),
+        }
+    }
+
+    /// Where the file that would be copied to `path` goes
+    pub fn map_file(&self, path: PathBuf) -> PathBuf {
+        if !self.rewrites {
+            return path;
+        }
+        if let Some(mapped) = self.flattened.lock().unwrap().get(&path) {
+            return mapped.clone();
+        }
+        self.map(&path, false)
+    }
+
+    /// Give out the flattened names of `files`, the paths files would be
+    /// copied to without rewrites, in sorted order. Which of the files
+    /// sharing a name keeps it, and the numbers the others get, then do
+    /// not depend on the order the walk reaches them.
+    pub fn claim_in_order(&self, mut files: Vec<PathBuf>) {
+        if self.flatten.is_none() {
+            return;
+        }
+        files.sort();
+        files.dedup();
+        for file in files {
+            let mapped = self.map(&file, false);
+            self.flattened.lock().unwrap().insert(file, mapped);
+        }
+    }
+
+    /// Where the directory that would be created at `path` goes. None when
+    /// files are routed to other folders, so directories are only created
+    /// as files need them.
+    pub fn map_dir(&self, path: &Path) -> Option<PathBuf> {
+        if !self.rewrites {
+            return Some(path.to_path_buf());
+        }
+        self.routes.is_empty().then(|| self.map(path, true))
+    }
+
+    fn map(&self, path: &Path, is_dir: bool) -> PathBuf {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return path.to_path_buf();
+        };
+
+        let mut text = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if is_dir {
+            text.push('/');
+        }
+
+        for (matcher, replace) in &self.rules {
+            text = match matcher {
+                Matcher::Literal(find) => text.replace(find.as_str(), replace),
+                Matcher::Regex(regex) => regex.replace_all(&text, replace.as_str()).into_owned(),
+            };
+        }
+
+        if let Some(case) = self.case {
+            text = case.apply(&text);
+        }
+
+        let parts = components(&text);
+        let folder = match parts.last() {
+            Some(name) if !is_dir => self
+                .routes
+                .iter()
+                .find(|route| route.matches(name))
+                .map(|route| components(&route.folder))
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
+        let mut mapped = self.root.clone();
+        mapped.extend(&folder);
+        match (self.flatten, parts.split_last()) {
+            (Some(_), _) if is_dir => {}
+            (Some(collision), Some((name, dirs))) => {
+                mapped.push(self.claim(&folder, name, dirs, collision))
+            }
+            _ => mapped.extend(parts),
+        }
+        mapped
+    }
+
+    /// Name for a flattened file in `folder`, renamed if an earlier file
+    /// took `name`
+    fn claim(
+        &self,
+        folder: &[&str],
+        name: &str,
+        dirs: &[&str],
+        collision: FlattenCollision,
+    ) -> String {
+        let mut claimed = self.claimed.lock().unwrap();
+        let mut claim = |candidate: &str| {
+            claimed.insert(format!("{}/{}", folder.join("/"), candidate).to_lowercase())
+        };
+        if claim(name) {
+            return name.to_string();
+        }
+
+        let base = match collision {
+            FlattenCollision::Prefix if !dirs.is_empty() => {
+                let prefixed = format!("{}_{}", dirs.join("_"), name);
+                if claim(&prefixed) {
+                    return prefixed;
+                }
+                prefixed
+            }
+            _ => name.to_string(),
+        };
+
+        let path = Path::new(&base);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        (2..)
+            .map(|n| format!("{} ({}){}", stem, n, extension))
+            .find(|candidate| claim(candidate))
+            .unwrap()
+    }
+}
+
+/// Add to `files` the paths the files below `dir`, `depth` levels below its
+/// source, are copied to below `destination`, applying the filters of
+/// `options` as the walk does
+pub fn list_files(
+    dir: &Path,
+    destination: &Path,
+    depth: usize,
+    options: &CopyOptions,
+    parent: Option<&Ancestors>,
+    files: &mut Vec<PathBuf>,
+) {
+    // The walk reports link cycles, listing just stops at them
+    let Ok(here) = Ancestors::enter(dir, parent, None) else {
+        return;
+    };
+    let parent = here.as_ref().or(parent);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() {
+            if options.descends_below(depth) && !options.excludes_dir(&path) {
+                let below = destination.join(&name);
+                list_files(&path, &below, depth + 1, options, parent, files);
+            }
+        } else if path.is_file() && options.selects_file(&name.to_string_lossy()) {
+            files.push(destination.join(&name));
+        }
+    }
+}
+
+/// The parts of a `/` separated path that name something, so rewritten
+/// paths cannot lead outside the destination
+fn components(text: &str) -> Vec<&str> {
+    text.split(['/', '\\'])
+        .filter(|part| {
+            matches!(
+                Path::new(part).components().next(),
+                Some(Component::Normal(_))
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,259 @@
+//! Reports for people who do not read logs.
+//!
+//! `/HTML:file` writes a single self-contained page at the end of a job:
+//! what the job was, its statistics, the files that failed, and every file
+//! and folder it created, replaced or removed, in tables sorted by
+//! clicking a column. Run with `/L`, the page shows the planned changes
+//! instead, ready to be mailed for approval before the real run.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::args::CopyOptions;
+use crate::history::RunOutcome;
+use crate::stats::Statistics;
+use crate::tr;
+use crate::utils::{format_elapsed, format_time};
+
+/// Most actions listed in a report, so the page stays small enough to open
+pub const MAX_REPORTED_ACTIONS: usize = 50_000;
+
+/// A change made, or planned by a list-only run, to the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    NewFile,
+    ReplaceFile,
+    NewDir,
+    RemoveFile,
+    RemoveDir,
+}
+
+impl ActionKind {
+    fn label(self) -> String {
+        match self {
+            ActionKind::NewFile => tr!("report-action-new-file"),
+            ActionKind::ReplaceFile => tr!("report-action-replace-file"),
+            ActionKind::NewDir => tr!("report-action-new-dir"),
+            ActionKind::RemoveFile => tr!("report-action-remove-file"),
+            ActionKind::RemoveDir => tr!("report-action-remove-dir"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Action {
+    kind: ActionKind,
+    path: PathBuf,
+    bytes: u64,
+}
+
+/// Changes to the destination, collected for the report
+#[derive(Debug, Default)]
+pub struct ActionLog {
+    actions: Mutex<Vec<Action>>,
+    /// Actions past `MAX_REPORTED_ACTIONS`, counted but not listed
+    dropped: AtomicUsize,
+}
+
+impl ActionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a change of `kind` to the destination `path`, of `bytes` for a
+    /// file
+    pub fn record(&self, kind: ActionKind, path: &Path, bytes: u64) {
+        let mut actions = self.actions.lock().unwrap();
+        if actions.len() >= MAX_REPORTED_ACTIONS {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        actions.push(Action {
+            kind,
+            path: path.to_path_buf(),
+            bytes,
+        });
+    }
+}
+
+/// Write the report of a job that ran with `options` from `started` for
+/// `elapsed` to `path`
+pub fn write_html(
+    path: &Path,
+    options: &CopyOptions,
+    stats: &Statistics,
+    actions: &ActionLog,
+    started: SystemTime,
+    elapsed: Duration,
+    outcome: RunOutcome,
+) -> io::Result<()> {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!(
+        "<title>{}</title>\n",
+        escape(&tr!("report-title"))
+    ));
+    html.push_str(STYLE);
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", escape(&tr!("report-title"))));
+    if options.list_only {
+        html.push_str(&format!(
+            "<p class=\"notice\">{}</p>\n",
+            escape(&tr!("report-dry-run"))
+        ));
+    }
+
+    let outcome = match outcome {
+        RunOutcome::Completed => tr!("report-outcome-completed"),
+        RunOutcome::Partial => tr!("report-outcome-partial"),
+        RunOutcome::Cancelled => tr!("report-outcome-cancelled"),
+        RunOutcome::Failed => tr!("report-outcome-failed"),
+    };
+    let job = [
+        (
+            tr!("report-job"),
+            options.job_name.clone().unwrap_or_default(),
+        ),
+        (tr!("report-sources"), options.sources.join(", ")),
+        (tr!("report-destination"), options.destination.clone()),
+        (tr!("report-started"), format_time(started)),
+        (tr!("report-elapsed"), format_elapsed(elapsed)),
+        (tr!("report-outcome"), outcome),
+        (tr!("report-options"), options.to_string_flags()),
+    ];
+    html.push_str("<table class=\"summary\">\n");
+    for (label, value) in job.iter().filter(|(_, value)| !value.is_empty()) {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape(label),
+            escape(value)
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(&format!("<h2>{}</h2>\n", escape(&tr!("report-statistics"))));
+    html.push_str("<table class=\"summary\">\n");
+    for (label, value) in stats.rows() {
+        let class = if value > 0 && label == tr!("stats-files-failed") {
+            " class=\"failed\""
+        } else {
+            ""
+        };
+        html.push_str(&format!(
+            "<tr{}><th>{}</th><td>{}</td></tr>\n",
+            class,
+            escape(label.trim_end_matches(':')),
+            escape(&stats.row_text(&label, value))
+        ));
+    }
+    html.push_str("</table>\n");
+
+    let failures = stats.failures.lock().unwrap();
+    if !failures.is_empty() {
+        html.push_str(&format!(
+            "<h2 class=\"failed\">{}</h2>\n",
+            escape(&tr!("report-failures", count = failures.len()))
+        ));
+        html.push_str("<ul class=\"failed\">\n");
+        for failure in failures.iter() {
+            html.push_str(&format!("<li>{}</li>\n", escape(&failure.to_string())));
+        }
+        html.push_str("</ul>\n");
+    }
+    drop(failures);
+
+    let mut listed = actions.actions.lock().unwrap().clone();
+    listed.sort_by(|a, b| a.path.cmp(&b.path));
+    html.push_str(&format!(
+        "<h2>{}</h2>\n",
+        escape(&tr!("report-actions", count = listed.len()))
+    ));
+    if !listed.is_empty() {
+        html.push_str(&format!(
+            "<table class=\"sortable\">\n<thead><tr><th>{}</th><th>{}</th><th>{}</th></tr></thead>\n<tbody>\n",
+            escape(&tr!("report-column-action")),
+            escape(&tr!("report-column-path")),
+            escape(&tr!("report-column-size"))
+        ));
+        for action in &listed {
+            let size = match action.kind {
+                ActionKind::NewDir | ActionKind::RemoveDir => String::new(),
+                _ => options.size_format.format(action.bytes),
+            };
+            html.push_str(&format!(
+                "<tr class=\"{:?}\"><td>{}</td><td>{}</td><td data-sort=\"{}\">{}</td></tr>\n",
+                action.kind,
+                escape(&action.kind.label()),
+                escape(&action.path.display().to_string()),
+                action.bytes,
+                size
+            ));
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+    let dropped = actions.dropped.load(Ordering::Relaxed);
+    if dropped > 0 {
+        html.push_str(&format!(
+            "<p>{}</p>\n",
+            escape(&tr!("report-actions-dropped", count = dropped))
+        ));
+    }
+
+    html.push_str(SCRIPT);
+    html.push_str("</body>\n</html>\n");
+    fs::write(path, html)
+}
+
+/// `text` with the characters special to HTML escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = r#"<style>
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+table.summary th { background: #f4f4f4; font-weight: normal; }
+table.sortable th { background: #e8eef6; cursor: pointer; user-select: none; }
+table.sortable th[data-order="asc"]::after { content: " \25B2"; }
+table.sortable th[data-order="desc"]::after { content: " \25BC"; }
+td[data-sort] { text-align: right; }
+.notice { background: #fff6d6; border: 1px solid #e8c84a; padding: 0.5em 1em; }
+.failed { color: #b00020; }
+tr.failed td, tr.failed th { background: #fde8eb; }
+tr.RemoveFile td, tr.RemoveDir td { color: #b00020; }
+tr.NewFile td, tr.NewDir td { color: #1b6e2a; }
+</style>
+"#;
+
+const SCRIPT: &str = r#"<script>
+document.querySelectorAll('table.sortable th').forEach(th => th.addEventListener('click', () => {
+    const table = th.closest('table');
+    const ascending = th.dataset.order !== 'asc';
+    table.querySelectorAll('th').forEach(other => delete other.dataset.order);
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    const key = row => row.cells[th.cellIndex].dataset.sort ?? row.cells[th.cellIndex].textContent;
+    const rows = [...table.tBodies[0].rows].sort((a, b) => {
+        const [x, y] = [key(a), key(b)];
+        const order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+        return ascending ? order : -order;
+    });
+    table.tBodies[0].append(...rows);
+}));
+</script>
+"#;
@@ -0,0 +1,118 @@
+//! Copying again only the files a job failed on.
+//!
+//! A job of millions of files with a handful of failures should not walk
+//! the whole source again to retry them. With `/FAILED:file` a job writes
+//! a failure report at its end: its options and, for each file that
+//! failed, the source it belongs to and its path below it. `rbcp
+//! --retry-failed file` copies exactly those files again with the same
+//! options, going straight to each one the way `/FILESFROM` does, and
+//! writes the files still failing back to the same report. The GUI keeps
+//! a report for every job with failures, up to the latest hundred, and
+//! retries it from the list of failed files.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::stats::Failure;
+
+/// A file that failed, by the source it was copied from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedFile {
+    /// The source as the job names it
+    pub source: String,
+    /// Path below the source with `/` between names, empty when the source
+    /// is the file itself
+    pub path: String,
+    pub reason: String,
+}
+
+/// The options of a job and the files it failed on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureReport {
+    pub options: CopyOptions,
+    pub failures: Vec<FailedFile>,
+}
+
+impl FailureReport {
+    /// Report of a job run with `options` that failed on `failures`
+    pub fn new(options: &CopyOptions, failures: &[Failure]) -> Self {
+        let mut options = options.clone();
+        options.retry_files.clear();
+        FailureReport {
+            failures: failures
+                .iter()
+                .filter_map(|failure| failed_file(&options, failure))
+                .collect(),
+            options,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(io::Error::other)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// Options copying the failed files again and nothing else. Nothing is
+    /// purged, as the rest of the source is not looked at.
+    pub fn retry_options(&self) -> CopyOptions {
+        let mut options = self.options.clone();
+        options.sources.retain(|source| {
+            self.failures
+                .iter()
+                .any(|failure| failure.source == *source)
+        });
+        options.retry_files.clear();
+        for failure in self.failures.iter().filter(|f| !f.path.is_empty()) {
+            options
+                .retry_files
+                .entry(failure.source.clone())
+                .or_default()
+                .push(failure.path.clone());
+        }
+        options.files_from = None;
+        options.purge = false;
+        options.mirror = false;
+        // Listed files go below the source root, which is where the
+        // children of /CHILDONLY go without a folder of the source's name
+        if options.child_only {
+            options.child_only = false;
+            options.preserve_root = false;
+        }
+        options.resume = false;
+        options
+    }
+}
+
+/// `failure` by the source of `options` it is below
+fn failed_file(options: &CopyOptions, failure: &Failure) -> Option<FailedFile> {
+    let (source, relative) = options
+        .sources
+        .iter()
+        .filter_map(|source| Some((source, failure.path.strip_prefix(source).ok()?)))
+        .max_by_key(|(source, _)| source.len())?;
+    let path = relative
+        .components()
+        .filter_map(|part| match part {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    Some(FailedFile {
+        source: source.clone(),
+        path,
+        reason: failure.reason.clone(),
+    })
+}
@@ -0,0 +1,106 @@
+//! Scheduling the trees a job copies.
+//!
+//! A job copies one tree per source, or with `/CHILDONLY` one per child
+//! folder of each source. All of them go to the job's own thread pool at
+//! once: with `/MT` the trees start together and their folders and files
+//! share the pool's workers, which take work from whichever tree has some,
+//! instead of one tree after the other each starting a nested walk on a
+//! pool shared with every other job in the process. Trees whose
+//! destinations overlap are still copied one after the other, in the order
+//! the sources are listed, so where several sources hold the same path the
+//! last one copied wins as before.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+/// A tree copied on its own: a source, or a child folder of one with
+/// `/CHILDONLY`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Root {
+    /// The source as given
+    pub source: PathBuf,
+    /// The folder copied, the source itself unless it is a child
+    pub path: PathBuf,
+    /// Where its files go
+    pub destination: PathBuf,
+    /// Name of the child folder, with `/CHILDONLY`
+    pub child: Option<String>,
+}
+
+impl Root {
+    /// The tree of the whole source at `source`
+    pub fn source(source: &Path, destination: PathBuf) -> Self {
+        Root {
+            source: source.to_path_buf(),
+            path: source.to_path_buf(),
+            destination,
+            child: None,
+        }
+    }
+
+    /// The tree of the child folder at `path`, named `name`, of `source`
+    pub fn child(source: &Path, path: PathBuf, name: String, destination: PathBuf) -> Self {
+        Root {
+            source: source.to_path_buf(),
+            path,
+            destination,
+            child: Some(name),
+        }
+    }
+
+    fn overlaps(&self, other: &Root) -> bool {
+        self.destination.starts_with(&other.destination)
+            || other.destination.starts_with(&self.destination)
+    }
+}
+
+/// Trees that can be copied side by side, each a list of trees copied in
+/// order because their destinations overlap, directly or through other
+/// trees of the list
+pub fn group(roots: Vec<Root>) -> Vec<Vec<Root>> {
+    // Each tree points towards the first tree of its group
+    let mut leader: Vec<usize> = (0..roots.len()).collect();
+    fn find(leader: &mut [usize], mut index: usize) -> usize {
+        while leader[index] != index {
+            leader[index] = leader[leader[index]];
+            index = leader[index];
+        }
+        index
+    }
+    for i in 0..roots.len() {
+        for j in 0..i {
+            if roots[i].overlaps(&roots[j]) {
+                let (a, b) = (find(&mut leader, i), find(&mut leader, j));
+                leader[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<Root>> = Vec::new();
+    let mut positions = vec![usize::MAX; roots.len()];
+    for (index, root) in roots.into_iter().enumerate() {
+        let first = find(&mut leader, index);
+        if positions[first] == usize::MAX {
+            positions[first] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[positions[first]].push(root);
+    }
+    groups
+}
+
+/// Copy the trees of `groups` with `copy`, the groups side by side on the
+/// current thread pool when `parallel` and the trees of each in order
+pub fn copy_all<F>(groups: &[Vec<Root>], parallel: bool, copy: F) -> io::Result<()>
+where
+    F: Fn(&Root) -> io::Result<()> + Sync + Send,
+{
+    let copy_group = |group: &Vec<Root>| group.iter().try_for_each(&copy);
+    if parallel {
+        groups.par_iter().try_for_each(copy_group)
+    } else {
+        groups.iter().try_for_each(copy_group)
+    }
+}
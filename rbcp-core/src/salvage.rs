@@ -0,0 +1,180 @@
+//! Rescuing what can be read off a failing source disk.
+//!
+//! A copy normally fails a file at its first read error, so a single bad
+//! sector loses a whole video. With `/SALVAGE` a chunk that cannot be read
+//! is read again a sector at a time; the sectors that still fail are
+//! written as zeros and the copy goes on after them. Each file with holes
+//! is logged and listed in a damage report with the byte ranges lost, so
+//! the damaged copies can be told from the good ones afterwards.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Default name of the damage report, in the destination
+pub const DEFAULT_DAMAGE_REPORT: &str = "rbcp-damage.txt";
+
+/// Smallest unit read again after an error
+const SECTOR: u64 = 4096;
+
+/// Bytes read a sector at a time after an error, as bad sectors come in
+/// clusters and a large read spanning one fails as a whole
+const CAREFUL_BYTES: u64 = 1024 * 1024;
+
+/// Reads a file, replacing the sectors that cannot be read with zeros
+pub struct SalvageReader<'a> {
+    file: File,
+    position: u64,
+    size: u64,
+    /// Reads stay a sector long up to here
+    careful_until: u64,
+    /// Ranges written as zeros, in order
+    damage: &'a mut Vec<Range<u64>>,
+}
+
+impl<'a> SalvageReader<'a> {
+    /// Read `file`, `size` bytes long and positioned at `position`,
+    /// recording the ranges lost in `damage`
+    pub fn new(file: File, position: u64, size: u64, damage: &'a mut Vec<Range<u64>>) -> Self {
+        SalvageReader {
+            file,
+            position,
+            size,
+            careful_until: 0,
+            damage,
+        }
+    }
+
+    /// Length of a read of one sector at the current position
+    fn sector_len(&self, max: usize) -> usize {
+        (SECTOR - self.position % SECTOR)
+            .min(self.size.saturating_sub(self.position))
+            .min(max as u64) as usize
+    }
+
+    fn mark_damaged(&mut self, range: Range<u64>) {
+        match self.damage.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => self.damage.push(range),
+        }
+    }
+}
+
+impl Read for SalvageReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = if self.position < self.careful_until {
+            self.sector_len(buf.len()).max(1)
+        } else {
+            buf.len()
+        };
+        match self.file.read(&mut buf[..len]) {
+            Ok(read) => {
+                self.position += read as u64;
+                return Ok(read);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+            Err(_) => {}
+        }
+
+        // The copy ends at the size the file had when it was opened, so an
+        // error past it is taken as the end
+        let len = self.sector_len(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        self.careful_until = self.position + CAREFUL_BYTES;
+        self.file.seek(SeekFrom::Start(self.position))?;
+        if let Ok(read @ 1..) = self.file.read(&mut buf[..len]) {
+            self.position += read as u64;
+            return Ok(read);
+        }
+
+        buf[..len].fill(0);
+        let start = self.position;
+        self.position += len as u64;
+        self.mark_damaged(start..self.position);
+        self.file.seek(SeekFrom::Start(self.position))?;
+        Ok(len)
+    }
+}
+
+/// Total length of `ranges`
+pub fn damaged_bytes(ranges: &[Range<u64>]) -> u64 {
+    ranges.iter().map(|range| range.end - range.start).sum()
+}
+
+/// `ranges` as inclusive byte offsets, e.g. "4096-8191, 65536-69631"
+pub fn describe(ranges: &[Range<u64>]) -> String {
+    ranges
+        .iter()
+        .map(|range| format!("{}-{}", range.start, range.end - 1))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// List of the files copied with holes, written as they are found
+pub struct DamageReport {
+    path: PathBuf,
+    /// The report once its first line is written, and the files listed
+    file: Mutex<(Option<File>, usize)>,
+}
+
+impl DamageReport {
+    /// A report written to `path` when the first damaged file is found,
+    /// replacing any report of an earlier run
+    pub fn new(path: PathBuf) -> Self {
+        DamageReport {
+            path,
+            file: Mutex::new((None, 0)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of files listed so far
+    pub fn files(&self) -> usize {
+        self.file.lock().unwrap().1
+    }
+
+    /// Add the copy of `src_path` at `dst_path`, with `ranges` lost
+    pub fn record(
+        &self,
+        src_path: &Path,
+        dst_path: &Path,
+        ranges: &[Range<u64>],
+    ) -> io::Result<()> {
+        let mut state = self.file.lock().unwrap();
+        let (file, listed) = &mut *state;
+        let file = match file {
+            Some(file) => file,
+            None => {
+                // Unlinked rather than truncated, as in a /SWAP staging
+                // tree it is linked to the report in the live tree
+                match fs::remove_file(&self.path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+                file.insert(
+                    OpenOptions::new()
+                        .create_new(true)
+                        .write(true)
+                        .open(&self.path)?,
+                )
+            }
+        };
+        writeln!(
+            file,
+            "{} -> {}: {} bytes unreadable at {}",
+            src_path.display(),
+            dst_path.display(),
+            damaged_bytes(ranges),
+            describe(ranges)
+        )?;
+        *listed += 1;
+        Ok(())
+    }
+}
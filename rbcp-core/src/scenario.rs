@@ -0,0 +1,89 @@
+//! Presets for common copy scenarios.
+//!
+//! Frontends ask a few questions (see [`ScenarioAnswers`]) and turn the
+//! answers into complete copy options, so users don't need to know which
+//! flags a backup or a mirror needs. Choices that delete data come with a
+//! warning the user should confirm before they are enabled.
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::CopyOptions;
+use crate::tr;
+
+/// A common task the GUI wizard offers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    /// Copy a folder into a backup location without deleting anything
+    Backup,
+    /// Keep a copy of a folder on a network share up to date
+    MirrorToNas,
+    /// Move a folder to another drive
+    MoveToNewDrive,
+}
+
+/// Answers to the wizard's questions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenarioAnswers {
+    pub source: String,
+    pub destination: String,
+    /// Also recreate empty folders
+    pub include_empty: bool,
+    /// Delete destination files that are no longer in the source
+    pub purge: bool,
+}
+
+impl Scenario {
+    /// Copy options carrying out this scenario
+    pub fn options(self, answers: &ScenarioAnswers) -> CopyOptions {
+        let mut options = CopyOptions {
+            sources: vec![answers.source.clone()],
+            destination: answers.destination.clone(),
+            patterns: vec!["*.*".to_string()],
+            recursive: true,
+            include_empty: answers.include_empty,
+            preserve_root: true,
+            threads: 8,
+            retries: 3,
+            wait_time: 5,
+            ..Default::default()
+        };
+
+        match self {
+            Scenario::Backup => {}
+            Scenario::MirrorToNas => {
+                // Network copies get more patient retries and resume
+                // large files after a dropped connection
+                options.include_empty = true;
+                options.mirror = answers.purge;
+                options.purge = answers.purge;
+                options.restartable = true;
+                options.retries = 10;
+                options.wait_time = 30;
+            }
+            Scenario::MoveToNewDrive => {
+                options.move_files = true;
+                options.move_dirs = true;
+            }
+        }
+
+        options
+    }
+
+    /// Explanations of what the chosen options delete, for the user to
+    /// confirm. Empty when nothing is deleted.
+    pub fn warnings(self, answers: &ScenarioAnswers) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self == Scenario::MirrorToNas && answers.purge {
+            warnings.push(tr!(
+                "scenario-warn-purge",
+                destination = answers.destination.as_str()
+            ));
+        }
+        if self == Scenario::MoveToNewDrive {
+            warnings.push(tr!("scenario-warn-move", source = answers.source.as_str()));
+        }
+        warnings
+    }
+}
@@ -0,0 +1,422 @@
+//! Checking that copies come out right on this machine.
+//!
+//! `rbcp self-test` generates random trees in a scratch directory, with a
+//! destination already holding some of the files, stale or newer copies
+//! and extras, copies them with random options and checks the result
+//! against what the options promise: matched files copied, newer copies
+//! kept, extras removed by a purge and only then, nothing touched by a
+//! list-only run. Discrepancies are reported with the seed of the round,
+//! so `/SEED` replays it, and the trees of failed rounds are kept.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use filetime::FileTime;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::args::CopyOptions;
+use crate::cancel::CancellationToken;
+use crate::engine::CopyEngine;
+use crate::progress::{NullProgress, ProgressCallback};
+use crate::tr;
+use crate::utils::matches_pattern;
+
+/// Rounds run when not given
+pub const DEFAULT_ROUNDS: u32 = 20;
+
+/// What a self-test does
+#[derive(Debug, Clone)]
+pub struct SelfTest {
+    pub rounds: u32,
+    /// Seed of the first round, the next rounds counting up from it
+    pub seed: u64,
+    /// Where the scratch trees are made
+    pub dir: PathBuf,
+}
+
+impl Default for SelfTest {
+    fn default() -> Self {
+        SelfTest {
+            rounds: DEFAULT_ROUNDS,
+            seed: rand::thread_rng().next_u64() >> 16,
+            dir: std::env::temp_dir(),
+        }
+    }
+}
+
+/// How a destination file related to its source before the copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Existing {
+    Identical,
+    /// Older, with other contents
+    Stale,
+    /// Newer, with other contents, so kept
+    Newer,
+}
+
+/// Files by path with `/` between names, and folders
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Tree {
+    files: BTreeMap<String, Vec<u8>>,
+    dirs: BTreeSet<String>,
+}
+
+impl Tree {
+    fn write(&self, root: &Path) -> io::Result<()> {
+        fs::create_dir_all(root)?;
+        for dir in &self.dirs {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        for (path, contents) in &self.files {
+            fs::write(root.join(path), contents)?;
+        }
+        Ok(())
+    }
+
+    fn read(root: &Path) -> io::Result<Self> {
+        let mut tree = Tree::default();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let relative = relative(root, &path);
+                // Manifests and the like of rbcp itself
+                if relative.starts_with(".rbcp") {
+                    continue;
+                }
+                if path.is_dir() {
+                    tree.dirs.insert(relative);
+                    pending.push(path);
+                } else {
+                    tree.files.insert(relative, fs::read(&path)?);
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Folder holding `path`, "" at the root
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn depth(path: &str) -> usize {
+    path.matches('/').count()
+}
+
+/// One generated case
+struct Round {
+    source: Tree,
+    destination: Tree,
+    existing: BTreeMap<String, Existing>,
+    /// Destination files and folders missing from the source
+    extras: BTreeSet<String>,
+    options: CopyOptions,
+}
+
+impl Round {
+    fn generate(rng: &mut StdRng) -> Self {
+        let mut source = Tree::default();
+        let mut dirs = vec![String::new()];
+        for i in 0..rng.gen_range(0..7) {
+            let parent = dirs[rng.gen_range(0..dirs.len())].clone();
+            if depth(&parent) >= 2 {
+                continue;
+            }
+            let dir = join(&parent, &format!("d{}", i));
+            source.dirs.insert(dir.clone());
+            dirs.push(dir);
+        }
+        for i in 0..rng.gen_range(1..30) {
+            let dir = &dirs[rng.gen_range(0..dirs.len())];
+            let extension = if rng.gen_bool(0.5) { "txt" } else { "dat" };
+            source
+                .files
+                .insert(join(dir, &format!("f{}.{}", i, extension)), contents(rng));
+        }
+
+        let mut destination = Tree::default();
+        let mut existing = BTreeMap::new();
+        for (path, data) in &source.files {
+            let state = match rng.gen_range(0..10) {
+                0..=1 => Existing::Identical,
+                2..=3 => Existing::Stale,
+                4 => Existing::Newer,
+                _ => continue,
+            };
+            let copy = match state {
+                Existing::Identical => data.clone(),
+                _ => contents(rng),
+            };
+            add_parents(&mut destination, path);
+            destination.files.insert(path.clone(), copy);
+            existing.insert(path.clone(), state);
+        }
+        let mut extras = BTreeSet::new();
+        for i in 0..rng.gen_range(0..5) {
+            let dir = &dirs[rng.gen_range(0..dirs.len())];
+            let extra = if rng.gen_bool(0.3) {
+                let extra_dir = join(dir, &format!("x{}", i));
+                destination.dirs.insert(extra_dir.clone());
+                destination
+                    .files
+                    .insert(join(&extra_dir, "inside.txt"), contents(rng));
+                extra_dir
+            } else {
+                let file = join(dir, &format!("x{}.txt", i));
+                destination.files.insert(file.clone(), contents(rng));
+                file
+            };
+            add_parents(&mut destination, &extra);
+            extras.insert(extra);
+        }
+
+        let (recursive, include_empty) = match rng.gen_range(0..3) {
+            0 => (false, false),
+            1 => (true, false),
+            _ => (true, true),
+        };
+        let options = CopyOptions {
+            patterns: vec![if rng.gen_bool(0.7) { "*.*" } else { "*.txt" }.to_string()],
+            recursive,
+            include_empty,
+            purge: rng.gen_bool(0.5),
+            threads: rng.gen_range(1..=8),
+            verify: rng.gen_bool(0.2),
+            list_only: rng.gen_bool(0.1),
+            record_history: false,
+            // The user's skip-list would leave files the check expects
+            no_skip_list: true,
+            quiet: true,
+            ..Default::default()
+        };
+
+        Round {
+            source,
+            destination,
+            existing,
+            extras,
+            options,
+        }
+    }
+
+    /// Whether the job walks the folder `dir`
+    fn walks(&self, dir: &str) -> bool {
+        dir.is_empty() || (self.options.recursive && self.source.dirs.contains(dir))
+    }
+
+    /// The destination the options promise
+    fn expected(&self) -> Tree {
+        let mut expected = Tree {
+            files: self.destination.files.clone(),
+            dirs: self.destination.dirs.clone(),
+        };
+        if self.options.list_only {
+            return expected;
+        }
+        for (path, data) in &self.source.files {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            let matched = self
+                .options
+                .patterns
+                .iter()
+                .any(|p| matches_pattern(name, p));
+            if !matched || !self.walks(parent(path)) {
+                continue;
+            }
+            if self.existing.get(path) != Some(&Existing::Newer) {
+                add_parents(&mut expected, path);
+                expected.files.insert(path.clone(), data.clone());
+            }
+        }
+        if self.options.include_empty {
+            for dir in &self.source.dirs {
+                expected.dirs.insert(dir.clone());
+            }
+        }
+        if self.options.purge {
+            for extra in self.extras.iter().filter(|extra| self.walks(parent(extra))) {
+                let below = format!("{}/", extra);
+                expected
+                    .files
+                    .retain(|path, _| path != extra && !path.starts_with(&below));
+                expected
+                    .dirs
+                    .retain(|path| path != extra && !path.starts_with(&below));
+            }
+        }
+        expected
+    }
+
+    /// Differences between the `expected` destination and the `actual` one
+    fn compare(&self, expected: &Tree, actual: &Tree) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (path, data) in &expected.files {
+            match actual.files.get(path) {
+                None => problems.push(format!("{} is missing", path)),
+                Some(found) if found != data => {
+                    let state = self.existing.get(path);
+                    problems.push(format!(
+                        "{} has the wrong contents ({:?} before)",
+                        path, state
+                    ))
+                }
+                _ => {}
+            }
+        }
+        for path in actual.files.keys() {
+            if !expected.files.contains_key(path) {
+                problems.push(format!("{} should not be there", path));
+            }
+        }
+        // Folders are only checked where the options decide them
+        for dir in &expected.dirs {
+            if self.options.include_empty && !actual.dirs.contains(dir) {
+                problems.push(format!("Folder {} is missing", dir));
+            }
+        }
+        for extra in &self.extras {
+            if actual.dirs.contains(extra) && !expected.dirs.contains(extra) {
+                problems.push(format!("Folder {} should have been removed", extra));
+            }
+        }
+        problems
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn add_parents(tree: &mut Tree, path: &str) {
+    let mut dir = parent(path);
+    while !dir.is_empty() {
+        tree.dirs.insert(dir.to_string());
+        dir = parent(dir);
+    }
+}
+
+fn contents(rng: &mut StdRng) -> Vec<u8> {
+    let mut data = vec![0; rng.gen_range(0..4096)];
+    rng.fill_bytes(&mut data);
+    data
+}
+
+/// Give the files of the round the times that make them stale or newer
+fn set_times(round: &Round, src: &Path, dst: &Path) -> io::Result<()> {
+    let base = SystemTime::now() - Duration::from_secs(86_400);
+    let time = |offset: i64| {
+        let time = if offset < 0 {
+            base - Duration::from_secs(offset.unsigned_abs())
+        } else {
+            base + Duration::from_secs(offset as u64)
+        };
+        FileTime::from_system_time(time)
+    };
+    for path in round.source.files.keys() {
+        filetime::set_file_mtime(src.join(path), time(0))?;
+    }
+    for (path, state) in &round.existing {
+        let offset = match state {
+            Existing::Identical => 0,
+            Existing::Stale => -3600,
+            Existing::Newer => 3600,
+        };
+        filetime::set_file_mtime(dst.join(path), time(offset))?;
+    }
+    Ok(())
+}
+
+impl SelfTest {
+    /// Run the rounds, logging each, and return the discrepancies found
+    pub fn run(&self, progress: &dyn ProgressCallback) -> io::Result<Vec<String>> {
+        progress.on_log(&tr!(
+            "selftest-start",
+            rounds = self.rounds,
+            seed = self.seed
+        ));
+        let mut failures = Vec::new();
+        for number in 1..=self.rounds {
+            if progress.is_cancelled() {
+                break;
+            }
+            let seed = self.seed + u64::from(number - 1);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut round = Round::generate(&mut rng);
+
+            let dir = self
+                .dir
+                .join(format!("rbcp-selftest-{}-{}", std::process::id(), seed));
+            let (src, dst) = (dir.join("src"), dir.join("dst"));
+            round.source.write(&src)?;
+            round.destination.write(&dst)?;
+            set_times(&round, &src, &dst)?;
+            round.options.sources = vec![src.display().to_string()];
+            round.options.destination = dst.display().to_string();
+
+            let expected = round.expected();
+            let flags = format!(
+                "{} {}",
+                round.options.patterns.join(" "),
+                round.options.to_string_flags()
+            );
+            let engine = CopyEngine::new(
+                round.options.clone(),
+                Arc::new(NullProgress),
+                CancellationToken::new(),
+            );
+            let problems = match engine.run() {
+                Err(e) => vec![format!("The copy failed: {}", e)],
+                Ok(_) => round.compare(&expected, &Tree::read(&dst)?),
+            };
+
+            if problems.is_empty() {
+                progress.on_log(&tr!(
+                    "selftest-round-passed",
+                    round = number,
+                    seed = seed,
+                    flags = flags.as_str()
+                ));
+                let _ = fs::remove_dir_all(&dir);
+                continue;
+            }
+            progress.on_log(&tr!(
+                "selftest-round-failed",
+                round = number,
+                seed = seed,
+                flags = flags.as_str(),
+                count = problems.len(),
+                dir = dir.display().to_string()
+            ));
+            for problem in problems {
+                progress.on_log(&format!("    {}", problem));
+                failures.push(format!("Seed {}: {}", seed, problem));
+            }
+        }
+
+        let msg = if failures.is_empty() {
+            tr!("selftest-passed")
+        } else {
+            tr!("selftest-failed", count = failures.len())
+        };
+        progress.on_log(&msg);
+        Ok(failures)
+    }
+}
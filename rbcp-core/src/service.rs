@@ -0,0 +1,193 @@
+//! Service mode: run scheduled job profiles unattended.
+//!
+//! The service reloads the profiles directory on every poll, runs each
+//! scheduled profile when it is due and records the outcome in a status
+//! file, which is how other tools query a running service. The
+//! `rbcp-service` binary hosts it as a systemd unit or a Windows service so
+//! scheduled mirrors keep running without a logged-in user.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancellationToken;
+use crate::engine::CopyEngine;
+use crate::profile::{JobProfile, Schedule};
+use crate::progress::NullProgress;
+use crate::utils::data_dir;
+
+/// How often the service checks for due profiles
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of the runs of one profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileStatus {
+    /// Unix timestamp of the start of the last run
+    pub last_started: Option<i64>,
+    /// Unix timestamp of the end of the last run
+    pub last_finished: Option<i64>,
+    pub last_result: Option<String>,
+    pub running: bool,
+}
+
+/// Status of all profiles known to the service
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub profiles: BTreeMap<String, ProfileStatus>,
+}
+
+impl ServiceStatus {
+    /// Default location of the status file
+    pub fn default_path() -> PathBuf {
+        data_dir().join("service-status.json")
+    }
+
+    pub fn load(path: &Path) -> Self {
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+}
+
+/// Scheduler running job profiles until stopped
+pub struct Service {
+    profiles_dir: PathBuf,
+    status_path: PathBuf,
+    stop: CancellationToken,
+}
+
+impl Service {
+    pub fn new(profiles_dir: PathBuf, status_path: PathBuf) -> Self {
+        Self {
+            profiles_dir,
+            status_path,
+            stop: CancellationToken::new(),
+        }
+    }
+
+    /// Get a handle to stop the service, e.g. from a signal handler
+    pub fn stop_handle(&self) -> CancellationToken {
+        self.stop.clone()
+    }
+
+    /// Run due profiles until the service is stopped (blocking)
+    pub fn run(&self) -> io::Result<()> {
+        if let Some(parent) = self.status_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut status = ServiceStatus::load(&self.status_path);
+
+        // A run that was in progress when the service stopped is over
+        for profile_status in status.profiles.values_mut() {
+            profile_status.running = false;
+        }
+
+        while !self.stop.is_cancelled() {
+            let mut profiles = JobProfile::load_all(&self.profiles_dir);
+            profiles.sort_by_key(|profile| Reverse(profile.priority));
+            for profile in profiles {
+                if self.stop.is_cancelled() {
+                    break;
+                }
+
+                let Some(schedule) = &profile.schedule else {
+                    continue;
+                };
+                let profile_status = status.profiles.entry(profile.name.clone()).or_default();
+                if !is_due(schedule, profile_status, Local::now()) {
+                    continue;
+                }
+
+                profile_status.last_started = Some(Local::now().timestamp());
+                profile_status.running = true;
+                status.save(&self.status_path)?;
+
+                let result = self.run_profile(&profile);
+
+                let profile_status = status.profiles.entry(profile.name.clone()).or_default();
+                profile_status.last_finished = Some(Local::now().timestamp());
+                profile_status.last_result = Some(result);
+                profile_status.running = false;
+                status.save(&self.status_path)?;
+            }
+
+            self.sleep(POLL_INTERVAL);
+        }
+
+        status.save(&self.status_path)
+    }
+
+    fn run_profile(&self, profile: &JobProfile) -> String {
+        // Output goes to the job's log only
+        let engine = CopyEngine::new(
+            profile.options.clone(),
+            Arc::new(NullProgress),
+            self.stop.clone(),
+        );
+
+        match engine.run() {
+            Ok(_) if self.stop.is_cancelled() => "Cancelled".to_string(),
+            Ok(stats) => format!(
+                "Completed: {} files copied, {} failed",
+                stats.files_copied.load(Ordering::Relaxed),
+                stats.files_failed.load(Ordering::Relaxed)
+            ),
+            Err(e) => format!("Failed: {}", e),
+        }
+    }
+
+    /// Sleep for `duration`, waking early when stopped
+    fn sleep(&self, duration: Duration) {
+        let step = Duration::from_millis(500);
+        let mut slept = Duration::ZERO;
+        while slept < duration && !self.stop.is_cancelled() {
+            std::thread::sleep(step);
+            slept += step;
+        }
+    }
+}
+
+/// Whether a profile with `schedule` should run at `now`
+fn is_due(schedule: &Schedule, status: &ProfileStatus, now: DateTime<Local>) -> bool {
+    if status.running {
+        return false;
+    }
+
+    match schedule {
+        Schedule::Interval { minutes } => match status.last_started {
+            Some(last) => now.timestamp() - last >= (*minutes as i64) * 60,
+            None => true,
+        },
+        Schedule::Daily { at } => {
+            let Ok(time) = NaiveTime::parse_from_str(at, "%H:%M") else {
+                return false;
+            };
+            let Some(scheduled) = Local
+                .from_local_datetime(&now.date_naive().and_time(time))
+                .earliest()
+            else {
+                return false;
+            };
+
+            now >= scheduled
+                && status
+                    .last_started
+                    .is_none_or(|last| last < scheduled.timestamp())
+        }
+    }
+}
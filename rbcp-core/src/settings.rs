@@ -0,0 +1,134 @@
+//! Frontend settings and moving them between machines.
+//!
+//! GUI preferences are kept in the data directory rather than in the
+//! webview, so they follow portable mode. An exported settings archive holds
+//! the job profiles, their run history and the GUI preferences in a single
+//! JSON file that can be imported on another machine.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::JobProfile;
+use crate::service::ServiceStatus;
+use crate::utils::data_dir;
+
+/// Format version of the settings archive
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Preferences of a frontend, as string key/value pairs
+pub type GuiSettings = BTreeMap<String, String>;
+
+/// Location of the GUI preferences
+pub fn gui_settings_path() -> PathBuf {
+    data_dir().join("gui-settings.json")
+}
+
+pub fn load_gui_settings(path: &Path) -> GuiSettings {
+    File::open(path)
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_gui_settings(path: &Path, settings: &GuiSettings) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(file, settings).map_err(io::Error::other)
+}
+
+/// Everything a user carries to another machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsArchive {
+    pub version: u32,
+    pub profiles: Vec<JobProfile>,
+    #[serde(default)]
+    pub history: ServiceStatus,
+    #[serde(default)]
+    pub gui: GuiSettings,
+}
+
+/// What an import changed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub profiles_added: usize,
+    pub profiles_replaced: usize,
+    pub history_entries: usize,
+}
+
+/// Write the profiles, run history and GUI preferences to `path`
+pub fn export_settings(path: &Path) -> io::Result<()> {
+    let archive = SettingsArchive {
+        version: ARCHIVE_VERSION,
+        profiles: JobProfile::load_all(&JobProfile::profiles_dir()),
+        history: ServiceStatus::load(&ServiceStatus::default_path()),
+        gui: load_gui_settings(&gui_settings_path()),
+    };
+
+    let file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(file, &archive).map_err(io::Error::other)
+}
+
+/// Merge an archive written by `export_settings` into this machine's
+/// settings. Imported profiles replace local ones of the same name, and
+/// history is only taken for profiles whose last run is newer.
+pub fn import_settings(path: &Path) -> io::Result<ImportSummary> {
+    let archive: SettingsArchive = serde_json::from_reader(BufReader::new(File::open(path)?))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if archive.version > ARCHIVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Settings archive version {} is newer than supported",
+                archive.version
+            ),
+        ));
+    }
+
+    let mut summary = ImportSummary::default();
+
+    let profiles_dir = JobProfile::profiles_dir();
+    let existing: Vec<String> = JobProfile::load_all(&profiles_dir)
+        .into_iter()
+        .map(|profile| profile.name)
+        .collect();
+    for profile in &archive.profiles {
+        if existing.contains(&profile.name) {
+            summary.profiles_replaced += 1;
+        } else {
+            summary.profiles_added += 1;
+        }
+        profile.save(&profiles_dir)?;
+    }
+
+    let status_path = ServiceStatus::default_path();
+    let mut history = ServiceStatus::load(&status_path);
+    for (name, imported) in archive.history.profiles {
+        let local = history.profiles.entry(name).or_default();
+        if imported.last_finished > local.last_finished {
+            *local = imported;
+            local.running = false;
+            summary.history_entries += 1;
+        }
+    }
+    if summary.history_entries > 0 {
+        if let Some(parent) = status_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        history.save(&status_path)?;
+    }
+
+    if !archive.gui.is_empty() {
+        let settings_path = gui_settings_path();
+        let mut gui = load_gui_settings(&settings_path);
+        gui.extend(archive.gui);
+        save_gui_settings(&settings_path, &gui)?;
+    }
+
+    Ok(summary)
+}
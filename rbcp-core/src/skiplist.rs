@@ -0,0 +1,260 @@
+//! Files never to be copied again.
+//!
+//! Some source files should be left behind by every job: a video known to
+//! be corrupt, a dump nobody wants in the backup. The skip-list,
+//! `skip-list.json` in the data directory, names them by path, a folder
+//! standing for everything below it, or by the BLAKE3 hash of their
+//! contents, which catches the same file wherever it is copied from.
+//! `rbcp skip-list add|remove|show` maintains it. Every job reads it as it
+//! starts, unless run with `/NOSKIPLIST`, and skips the files it lists,
+//! counting them apart in the summary. Only files of the size of a listed
+//! hash are hashed, so a list of paths costs nothing.
+
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tr;
+use crate::utils::data_dir;
+use crate::verify::hash_file;
+
+/// A file or folder never to be copied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkipEntry {
+    /// Absolute path of the file or folder, or of the file that was hashed
+    pub path: PathBuf,
+    /// BLAKE3 hash of the contents in hex, matching them at any path
+    /// instead of `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Size of the hashed file
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub reason: String,
+    /// Unix timestamp of when the entry was added
+    #[serde(default)]
+    pub added_at: i64,
+}
+
+/// The files and folders no job copies
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkipList {
+    pub entries: Vec<SkipEntry>,
+}
+
+impl SkipList {
+    /// Default location of the skip-list
+    pub fn default_path() -> PathBuf {
+        data_dir().join("skip-list.json")
+    }
+
+    /// The list at `path`, empty if there is none yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    /// List the file or folder at `path`, or with `by_hash` the contents
+    /// of the file. Returns false if it was listed already.
+    pub fn add(&mut self, path: &Path, by_hash: bool, reason: &str) -> io::Result<bool> {
+        let path = absolute(path);
+        let (hash, size) = if by_hash {
+            let meta = fs::metadata(&path)?;
+            if !meta.is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    tr!("skip-list-not-file", path = path.display().to_string()),
+                ));
+            }
+            (Some(hash_file(&path)?.to_hex().to_string()), meta.len())
+        } else {
+            (None, 0)
+        };
+
+        let listed = self.entries.iter().any(|entry| match (&entry.hash, &hash) {
+            (Some(listed), Some(hash)) => listed == hash,
+            (None, None) => path_key(&entry.path) == path_key(&path),
+            _ => false,
+        });
+        if listed {
+            return Ok(false);
+        }
+        self.entries.push(SkipEntry {
+            path,
+            hash,
+            size,
+            reason: reason.to_string(),
+            added_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+        });
+        Ok(true)
+    }
+
+    /// Remove the entries for the path or hash `key`, returning how many
+    /// there were
+    pub fn remove(&mut self, key: &str) -> usize {
+        let path = path_key(&absolute(Path::new(key)));
+        let before = self.entries.len();
+        self.entries.retain(|entry| match &entry.hash {
+            Some(hash) => !hash.eq_ignore_ascii_case(key) && path_key(&entry.path) != path,
+            None => path_key(&entry.path) != path,
+        });
+        before - self.entries.len()
+    }
+}
+
+/// `path` made absolute without resolving links, as jobs name sources
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `path` as compared with listed paths, ignoring case where names do
+fn path_key(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The skip-list of a job, ready to check each of its files against
+#[derive(Debug, Default)]
+pub struct SkipMatcher {
+    /// Listed files and folders by [`path_key`], with the reasons given
+    paths: HashMap<PathBuf, String>,
+    /// Listed hashes with their reasons, by the size of their files
+    hashes: HashMap<u64, HashMap<String, String>>,
+}
+
+impl SkipMatcher {
+    pub fn new(list: &SkipList) -> Self {
+        let mut matcher = SkipMatcher::default();
+        for entry in &list.entries {
+            match &entry.hash {
+                Some(hash) => {
+                    matcher
+                        .hashes
+                        .entry(entry.size)
+                        .or_default()
+                        .insert(hash.to_lowercase(), entry.reason.clone());
+                }
+                None => {
+                    matcher
+                        .paths
+                        .insert(path_key(&entry.path), entry.reason.clone());
+                }
+            }
+        }
+        matcher
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len() + self.hashes.values().map(HashMap::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The reason the source file at `path` is listed, by itself, a
+    /// folder above it or its contents, if it is
+    pub fn skips(&self, path: &Path, meta: &Metadata) -> Option<&str> {
+        if !self.paths.is_empty() {
+            let path = path_key(&absolute(path));
+            if let Some(reason) = path.ancestors().find_map(|listed| self.paths.get(listed)) {
+                return Some(reason);
+            }
+        }
+        let hashes = self.hashes.get(&meta.len())?;
+        // An unreadable file fails when it is copied instead
+        let hash = hash_file(path).ok()?;
+        hashes.get(hash.to_hex().as_str()).map(String::as_str)
+    }
+}
+
+/// What `rbcp skip-list` is asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipListCommand {
+    /// List files or folders, or with `by_hash` the contents of files
+    Add {
+        paths: Vec<String>,
+        by_hash: bool,
+        reason: String,
+    },
+    /// Remove the entries for these paths or hashes
+    Remove {
+        keys: Vec<String>,
+    },
+    Show,
+}
+
+impl SkipListCommand {
+    /// Carry out the command on the list at `path`, returning the lines
+    /// to print
+    pub fn run(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut list = SkipList::load(path)?;
+        let mut lines = Vec::new();
+        match self {
+            SkipListCommand::Add {
+                paths,
+                by_hash,
+                reason,
+            } => {
+                for entry in paths {
+                    let id = if list.add(Path::new(entry), *by_hash, reason)? {
+                        "skip-list-added"
+                    } else {
+                        "skip-list-present"
+                    };
+                    lines.push(tr!(id, path = entry.as_str()));
+                }
+                list.save(path)?;
+            }
+            SkipListCommand::Remove { keys } => {
+                for key in keys {
+                    let removed = list.remove(key);
+                    lines.push(tr!(
+                        "skip-list-removed",
+                        key = key.as_str(),
+                        count = removed
+                    ));
+                }
+                list.save(path)?;
+            }
+            SkipListCommand::Show => {
+                if list.entries.is_empty() {
+                    lines.push(tr!("skip-list-empty", path = path.display().to_string()));
+                }
+                for entry in &list.entries {
+                    let mut line = match &entry.hash {
+                        Some(hash) => format!("{}  {}", hash, entry.path.display()),
+                        None => entry.path.display().to_string(),
+                    };
+                    if !entry.reason.is_empty() {
+                        line.push_str(&format!("  ({})", entry.reason));
+                    }
+                    lines.push(line);
+                }
+            }
+        }
+        Ok(lines)
+    }
+}
@@ -0,0 +1,250 @@
+//! Dangling symlinks and special files.
+//!
+//! A directory walk finds more than files and directories: symlinks whose
+//! target is gone, and on Unix FIFOs, sockets and device nodes. None of them
+//! have content to copy. Each kind has a policy saying whether it is
+//! skipped, recreated at the destination or counted as a failure, so backing
+//! up something like a Linux home directory gives a predictable result.
+
+use std::fmt;
+use std::fs::{self, DirEntry};
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// A directory entry that is neither a regular file nor a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKind {
+    /// Symlink whose target does not exist
+    DanglingSymlink,
+    Fifo,
+    Socket,
+    /// Block or character device
+    Device,
+}
+
+impl SpecialKind {
+    pub const ALL: [SpecialKind; 4] = [
+        SpecialKind::DanglingSymlink,
+        SpecialKind::Fifo,
+        SpecialKind::Socket,
+        SpecialKind::Device,
+    ];
+
+    /// Name used in the `/SPECIAL` option
+    pub fn name(self) -> &'static str {
+        match self {
+            SpecialKind::DanglingSymlink => "dangling",
+            SpecialKind::Fifo => "fifo",
+            SpecialKind::Socket => "socket",
+            SpecialKind::Device => "device",
+        }
+    }
+
+    /// Message ID of the localized plural label
+    pub fn message_id(self) -> &'static str {
+        match self {
+            SpecialKind::DanglingSymlink => "special-dangling",
+            SpecialKind::Fifo => "special-fifo",
+            SpecialKind::Socket => "special-socket",
+            SpecialKind::Device => "special-device",
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Kind of `entry`, if it is special
+    pub fn of(entry: &DirEntry) -> Option<SpecialKind> {
+        let file_type = entry.file_type().ok()?;
+        if file_type.is_file() || file_type.is_dir() {
+            return None;
+        }
+        // Symlinks are followed, only their target's type matters
+        let metadata = match fs::metadata(entry.path()) {
+            Ok(metadata) => metadata,
+            Err(_) if file_type.is_symlink() => return Some(SpecialKind::DanglingSymlink),
+            Err(_) => return None,
+        };
+        if metadata.is_file() || metadata.is_dir() {
+            return None;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            let file_type = metadata.file_type();
+            if file_type.is_fifo() {
+                return Some(SpecialKind::Fifo);
+            }
+            if file_type.is_socket() {
+                return Some(SpecialKind::Socket);
+            }
+            if file_type.is_block_device() || file_type.is_char_device() {
+                return Some(SpecialKind::Device);
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What to do with a special entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecialPolicy {
+    /// Leave it out of the copy
+    #[default]
+    Skip,
+    /// Create the same symlink or node at the destination
+    Recreate,
+    /// Count it as a failed file
+    Error,
+}
+
+impl SpecialPolicy {
+    pub const ALL: [SpecialPolicy; 3] = [
+        SpecialPolicy::Skip,
+        SpecialPolicy::Recreate,
+        SpecialPolicy::Error,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpecialPolicy::Skip => "skip",
+            SpecialPolicy::Recreate => "recreate",
+            SpecialPolicy::Error => "error",
+        }
+    }
+
+    /// Message ID of the localized outcome
+    pub fn message_id(self) -> &'static str {
+        match self {
+            SpecialPolicy::Skip => "special-skipped",
+            SpecialPolicy::Recreate => "special-recreated",
+            SpecialPolicy::Error => "special-failed",
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Policy for each kind of special entry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpecialFiles {
+    pub dangling_symlinks: SpecialPolicy,
+    pub fifos: SpecialPolicy,
+    pub sockets: SpecialPolicy,
+    pub devices: SpecialPolicy,
+}
+
+impl SpecialFiles {
+    pub fn policy(&self, kind: SpecialKind) -> SpecialPolicy {
+        match kind {
+            SpecialKind::DanglingSymlink => self.dangling_symlinks,
+            SpecialKind::Fifo => self.fifos,
+            SpecialKind::Socket => self.sockets,
+            SpecialKind::Device => self.devices,
+        }
+    }
+
+    fn policy_mut(&mut self, kind: SpecialKind) -> &mut SpecialPolicy {
+        match kind {
+            SpecialKind::DanglingSymlink => &mut self.dangling_symlinks,
+            SpecialKind::Fifo => &mut self.fifos,
+            SpecialKind::Socket => &mut self.sockets,
+            SpecialKind::Device => &mut self.devices,
+        }
+    }
+
+    /// Apply `policy` to all kinds, or `kind=policy` to one, e.g.
+    /// `fifo=recreate`
+    pub fn parse_into(&mut self, spec: &str) -> Result<(), String> {
+        let parse_policy = |name: &str| {
+            SpecialPolicy::ALL
+                .into_iter()
+                .find(|policy| policy.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown special file policy: {} (expected skip, recreate or error)",
+                        name
+                    )
+                })
+        };
+
+        match spec.split_once('=') {
+            Some((kind, policy)) => {
+                let kind = SpecialKind::ALL
+                    .into_iter()
+                    .find(|k| k.name().eq_ignore_ascii_case(kind))
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown special file kind: {} (expected dangling, fifo, socket or device)",
+                            kind
+                        )
+                    })?;
+                *self.policy_mut(kind) = parse_policy(policy)?;
+            }
+            None => {
+                let policy = parse_policy(spec)?;
+                for kind in SpecialKind::ALL {
+                    *self.policy_mut(kind) = policy;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Create at `dst` the same symlink or node as `src`, replacing what is
+/// there
+pub fn recreate(src: &Path, dst: &Path, kind: SpecialKind) -> io::Result<()> {
+    if fs::symlink_metadata(dst).is_ok() {
+        fs::remove_file(dst)?;
+    }
+
+    if kind == SpecialKind::DanglingSymlink {
+        let target = fs::read_link(src)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, dst);
+        #[cfg(windows)]
+        return std::os::windows::fs::symlink_file(target, dst);
+        #[cfg(not(any(unix, windows)))]
+        return Err(io::Error::from(io::ErrorKind::Unsupported));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(src)?;
+        let path = CString::new(dst.as_os_str().as_bytes())?;
+        let result = unsafe {
+            libc::mknod(
+                path.as_ptr(),
+                metadata.mode() as libc::mode_t,
+                metadata.rdev() as libc::dev_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
@@ -0,0 +1,270 @@
+//! Splitting files too large for the destination filesystem.
+//!
+//! FAT32, still the format of most camera cards and older USB sticks, cannot
+//! hold files of 4 GB or more, so copying a large video or disk image to one
+//! fails part way. In split mode such files are written as numbered parts,
+//! `movie.mkv.part001`, `movie.mkv.part002` and so on, followed by a
+//! manifest `movie.mkv.rbcp-split` describing them. The manifest is written
+//! last, so a file is only complete when its manifest exists. The parts are
+//! written by the same copy as any other file, so throttling, retries,
+//! resuming and verification apply to them. Copying the manifest back in
+//! rejoin mode restores the original file.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::durability::{sync_file, Durability};
+use crate::progress::{ProgressCallback, ProgressInfo, ProgressState};
+use crate::utils::PathContext;
+
+/// Part size used by `/SPLIT` without a size: the largest whole number of
+/// MB a FAT32 file can hold
+pub const DEFAULT_PART_MB: u64 = 4095;
+
+/// Extension of the manifest, appended to the original name
+pub const MANIFEST_EXTENSION: &str = "rbcp-split";
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Describes how a file was split
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitManifest {
+    /// Name of the original file
+    pub name: String,
+    /// Size of the original file in bytes
+    pub size: u64,
+    /// Size of each part but the last in bytes
+    pub part_size: u64,
+    pub parts: usize,
+}
+
+impl SplitManifest {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Manifest written for a file split at `path`
+pub fn manifest_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(MANIFEST_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Path of part `number`, counting from 1, of a file split at `path`
+pub fn part_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".part{:03}", number));
+    PathBuf::from(name)
+}
+
+/// Whether `path` is a split manifest
+pub fn is_manifest(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(MANIFEST_EXTENSION))
+}
+
+/// Whether `path` is a part of a split file whose manifest is next to it
+pub fn is_part(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    let Some((original, number)) = name.rsplit_once(".part") else {
+        return false;
+    };
+    number.len() >= 3
+        && number.bytes().all(|b| b.is_ascii_digit())
+        && manifest_path(Path::new(original)).is_file()
+}
+
+/// Whether the split copy of a `size` byte file modified at `modified` is
+/// already complete at `path`
+pub fn is_current(path: &Path, size: u64, modified: SystemTime) -> bool {
+    let manifest = manifest_path(path);
+    let written = fs::metadata(&manifest)
+        .and_then(|m| m.modified())
+        .is_ok_and(|time| time >= modified);
+    written && SplitManifest::load(&manifest).is_ok_and(|m| m.size == size)
+}
+
+/// Opens a part at its path, keeping its first bytes as given, or
+/// creating it anew when none are kept
+pub type OpenPart<'a> = Box<dyn Fn(&Path, u64) -> io::Result<File> + 'a>;
+
+/// Writes a file as parts of at most `part_size` bytes next to its
+/// destination, moving on to the next part as each fills up
+pub struct PartWriter<'a> {
+    dst: PathBuf,
+    part_size: u64,
+    durability: Durability,
+    open: OpenPart<'a>,
+    /// Part being written, counting from 1
+    number: usize,
+    /// Bytes in that part so far
+    written: u64,
+    file: File,
+    /// Earlier parts that `durability` did not sync as they filled up,
+    /// synced along with the part being written by [`PartWriter::sync`]
+    unsynced: Vec<File>,
+}
+
+impl<'a> PartWriter<'a> {
+    /// Writer of the parts of `dst` going on from `offset`, keeping the
+    /// bytes before it
+    pub fn open(
+        dst: &Path,
+        part_size: u64,
+        offset: u64,
+        durability: Durability,
+        open: OpenPart<'a>,
+    ) -> io::Result<Self> {
+        // A manifest from an earlier copy no longer describes the parts
+        let manifest = manifest_path(dst);
+        match fs::remove_file(&manifest) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).context("removing", &manifest)
+            }
+            _ => {}
+        }
+
+        let number = (offset / part_size) as usize + 1;
+        let written = offset % part_size;
+        let file = open(&part_path(dst, number), written)?;
+        Ok(PartWriter {
+            dst: dst.to_path_buf(),
+            part_size,
+            durability,
+            open,
+            number,
+            written,
+            file,
+            unsynced: Vec::new(),
+        })
+    }
+
+    /// Number of parts written so far
+    pub fn parts(&self) -> usize {
+        self.number
+    }
+
+    /// Write out what the system caches of the parts, as `durability`
+    /// asks
+    pub fn sync(&mut self, durability: Durability) -> io::Result<()> {
+        if durability >= Durability::File {
+            for part in self.unsynced.drain(..) {
+                sync_file(&part, durability)?;
+            }
+        }
+        sync_file(&self.file, durability)
+    }
+}
+
+impl Write for PartWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.written == self.part_size {
+            sync_file(&self.file, self.durability)?;
+            self.number += 1;
+            let next = (self.open)(&part_path(&self.dst, self.number), 0)?;
+            let full = std::mem::replace(&mut self.file, next);
+            if self.durability < Durability::File {
+                self.unsynced.push(full);
+            }
+            self.written = 0;
+        }
+        let room = (self.part_size - self.written).min(buf.len() as u64) as usize;
+        let written = self.file.write(&buf[..room])?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Bytes already written to the parts of `dst`, counting the parts in
+/// order up to the first missing one
+pub fn written_len(dst: &Path) -> u64 {
+    (1..)
+        .map_while(|number| fs::metadata(part_path(dst, number)).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// Complete the parts of `dst` once all are written: remove parts left
+/// over from an earlier, longer version of the file, then write `manifest`
+/// to `file`, created for it at [`manifest_path`]
+pub fn finish(
+    dst: &Path,
+    manifest: &SplitManifest,
+    mut file: File,
+    durability: Durability,
+) -> io::Result<()> {
+    let mut stale = manifest.parts + 1;
+    while fs::remove_file(part_path(dst, stale)).is_ok() {
+        stale += 1;
+    }
+
+    let content = serde_json::to_string_pretty(manifest).map_err(io::Error::other)?;
+    file.write_all(content.as_bytes())?;
+    sync_file(&file, durability)
+}
+
+/// Join the parts described by `manifest` into `dst`, checking that they
+/// add up to the original size
+pub fn rejoin(
+    manifest_path: &Path,
+    manifest: &SplitManifest,
+    dst: &Path,
+    durability: Durability,
+    progress: &dyn ProgressCallback,
+) -> io::Result<()> {
+    let original = manifest_path.with_extension("");
+    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, File::create(dst)?);
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut progress_info = ProgressInfo {
+        state: ProgressState::Copying,
+        current_file: original.to_string_lossy().to_string(),
+        current_file_bytes_total: manifest.size,
+        ..Default::default()
+    };
+
+    for number in 1..=manifest.parts {
+        let mut reader = File::open(part_path(&original, number))?;
+        loop {
+            if progress.is_cancelled() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+            }
+            progress.wait_if_paused();
+
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..bytes_read])?;
+
+            progress_info.current_file_bytes_done += bytes_read as u64;
+            progress.on_progress(&progress_info);
+        }
+    }
+    writer.flush()?;
+    sync_file(writer.get_ref(), durability)?;
+
+    if progress_info.current_file_bytes_done != manifest.size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "parts add up to {} bytes instead of {}",
+                progress_info.current_file_bytes_done, manifest.size
+            ),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Noticing a job that has stopped moving.
+//!
+//! A hung SMB handle or a disk spinning up from sleep leaves a job showing
+//! the same file for minutes, with nothing in the log to tell it from a
+//! large file copying slowly. With `/STALL:n` the engine watches for copied
+//! data and finished files, and when neither has moved for n seconds while
+//! files are left it logs a warning and reports the job as stalled until
+//! work resumes.
+//!
+//! `/STALL:n:RETRY` also reads sources with a timeout of n seconds, so the
+//! read that hung fails and the file is retried with a fresh handle.
+
+use std::time::{Duration, Instant};
+
+/// A change of state reported by [`StallWatch::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallChange {
+    /// Nothing moved for the whole timeout
+    Stalled,
+    /// Work moved again after a stall
+    Resumed,
+}
+
+/// Tracks how long a measure of work done has stayed the same
+pub struct StallWatch {
+    timeout: Duration,
+    last_work: u64,
+    last_change: Instant,
+    stalled: bool,
+}
+
+impl StallWatch {
+    pub fn new(timeout: Duration) -> Self {
+        StallWatch {
+            timeout,
+            last_work: 0,
+            last_change: Instant::now(),
+            stalled: false,
+        }
+    }
+
+    /// Start the timeout over, after time in which the job was not meant
+    /// to move such as a pause
+    pub fn reset(&mut self) {
+        self.last_change = Instant::now();
+    }
+
+    /// Compare `work` with the last value seen, reporting the job stalling
+    /// or resuming
+    pub fn check(&mut self, work: u64) -> Option<StallChange> {
+        if work != self.last_work {
+            self.last_work = work;
+            self.last_change = Instant::now();
+            if self.stalled {
+                self.stalled = false;
+                return Some(StallChange::Resumed);
+            }
+            return None;
+        }
+        if !self.stalled && self.last_change.elapsed() >= self.timeout {
+            self.stalled = true;
+            return Some(StallChange::Stalled);
+        }
+        None
+    }
+
+    /// Time since work last moved
+    pub fn idle(&self) -> Duration {
+        self.last_change.elapsed()
+    }
+}
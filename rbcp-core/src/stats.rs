@@ -1,29 +1,391 @@
 use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::links::{LinkKind, LinkPolicy};
+use crate::special::{SpecialKind, SpecialPolicy};
+use crate::tr;
+use crate::utils::{failed_at, format_duration, format_elapsed, PathError, SizeFormat};
+
+/// Maximum number of failures kept for reporting
+const MAX_RECORDED_FAILURES: usize = 1000;
+
+/// A file that failed, by its source path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub reason: String,
+    /// Operation that failed, when the error names it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+    /// Path the operation failed on, which may be the destination or a
+    /// folder rather than the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_path: Option<PathBuf>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+/// Kind of content a file holds, judged by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileCategory {
+    Images,
+    Video,
+    Documents,
+    Code,
+    Other,
+}
+
+impl FileCategory {
+    pub const ALL: [FileCategory; 5] = [
+        FileCategory::Images,
+        FileCategory::Video,
+        FileCategory::Documents,
+        FileCategory::Code,
+        FileCategory::Other,
+    ];
+
+    pub fn of(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "heif"
+            | "svg" | "ico" | "raw" | "cr2" | "nef" | "arw" | "dng" | "psd" => FileCategory::Images,
+            "mp4" | "mkv" | "mov" | "avi" | "wmv" | "webm" | "m4v" | "mpg" | "mpeg" | "flv"
+            | "3gp" | "mts" | "m2ts" | "vob" => FileCategory::Video,
+            "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "odp"
+            | "rtf" | "txt" | "md" | "csv" | "epub" | "pages" | "numbers" | "key" => {
+                FileCategory::Documents
+            }
+            "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "cs" | "java" | "kt" | "go" | "py" | "js"
+            | "ts" | "jsx" | "tsx" | "rb" | "php" | "swift" | "sh" | "ps1" | "bat" | "html"
+            | "css" | "json" | "toml" | "yaml" | "yml" | "xml" | "sql" | "lua" => {
+                FileCategory::Code
+            }
+            _ => FileCategory::Other,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Name used in options, e.g. `images`
+    pub fn name(self) -> &'static str {
+        match self {
+            FileCategory::Images => "images",
+            FileCategory::Video => "video",
+            FileCategory::Documents => "documents",
+            FileCategory::Code => "code",
+            FileCategory::Other => "other",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown file category: {} (expected images, video, documents, code or other)",
+                    name
+                )
+            })
+    }
+
+    /// Message id of the category's label
+    pub fn message_id(self) -> &'static str {
+        match self {
+            FileCategory::Images => "category-images",
+            FileCategory::Video => "category-video",
+            FileCategory::Documents => "category-documents",
+            FileCategory::Code => "category-code",
+            FileCategory::Other => "category-other",
+        }
+    }
+}
+
+/// Files and bytes copied of one category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryTotals {
+    pub category: FileCategory,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Files copied and failed below one child folder of a source, and how
+/// long it took, with /CHILDONLY
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildTotals {
+    pub name: String,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    pub files_failed: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug)]
+struct ChildCounter {
+    root: PathBuf,
+    name: String,
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    failed: AtomicUsize,
+    duration_ms: AtomicU64,
+}
+
+/// The largest of the entries offered to it, biggest first
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Largest {
+    limit: usize,
+    entries: Vec<(u64, PathBuf)>,
+}
+
+impl Largest {
+    pub fn new(limit: usize) -> Self {
+        Largest {
+            limit,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn offer(&mut self, size: u64, path: &Path) {
+        if self.entries.len() == self.limit
+            && self.entries.last().is_none_or(|(last, _)| size <= *last)
+        {
+            return;
+        }
+        let position = self.entries.partition_point(|(other, _)| *other >= size);
+        self.entries.insert(position, (size, path.to_path_buf()));
+        self.entries.truncate(self.limit);
+    }
+
+    pub fn entries(&self) -> &[(u64, PathBuf)] {
+        &self.entries
+    }
+}
+
+/// Time spent in each phase of a job, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTimes {
+    /// Pre-scan of the sources
+    pub scan_ms: u64,
+    /// Walking the sources and copying, excluding purging
+    pub copy_ms: u64,
+    /// Removing destination entries missing from the source, summed over
+    /// the worker threads
+    pub purge_ms: u64,
+}
+
+impl fmt::Display for PhaseTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = [
+            (tr!("phase-scan"), self.scan_ms),
+            (tr!("phase-copy"), self.copy_ms),
+            (tr!("phase-purge"), self.purge_ms),
+        ];
+        let parts: Vec<String> = phases
+            .iter()
+            .filter(|(_, ms)| *ms > 0)
+            .map(|(label, ms)| format!("{} {}", label, format_elapsed(Duration::from_millis(*ms))))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Counters of [`Statistics`] at one moment, for writing out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub dirs_created: u64,
+    pub root_dirs_created: u64,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    pub dirs_skipped: u64,
+    pub files_skipped: u64,
+    pub files_failed: u64,
+    pub errors_ignored: u64,
+    pub files_skip_listed: u64,
+    pub files_placeholders: u64,
+    pub files_attributes_updated: u64,
+    pub files_hydrated: u64,
+    pub files_sampled: u64,
+    pub files_quarantined: u64,
+    pub files_blocked: u64,
+    pub dirs_removed: u64,
+    pub files_removed: u64,
+    pub retries: u64,
+    pub files_salvaged: u64,
+    pub files_rotted: u64,
+    /// Time spent in each phase so far
+    #[serde(default)]
+    pub phases: PhaseTimes,
+    /// Time the whole job took, 0 until it finishes
+    #[serde(default)]
+    pub elapsed_ms: u64,
+}
+
+/// Largest files and directories found while scanning the sources
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TopSizes {
+    pub files: Largest,
+    /// Directories by the total size of the files below them
+    pub dirs: Largest,
+    #[serde(skip)]
+    size_format: SizeFormat,
+}
+
+impl TopSizes {
+    pub fn new(limit: usize, size_format: SizeFormat) -> Self {
+        TopSizes {
+            files: Largest::new(limit),
+            dirs: Largest::new(limit),
+            size_format,
+        }
+    }
+}
+
+impl fmt::Display for TopSizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            (tr!("top-files-title"), &self.files),
+            (tr!("top-dirs-title"), &self.dirs),
+        ];
+        for (title, largest) in sections {
+            if largest.entries().is_empty() {
+                continue;
+            }
+            let sizes: Vec<String> = largest
+                .entries()
+                .iter()
+                .map(|(size, _)| self.size_format.format(*size))
+                .collect();
+            let width = sizes.iter().map(String::len).max().unwrap_or(0);
+            writeln!(f, "{}", title)?;
+            for (size, (_, path)) in sizes.iter().zip(largest.entries()) {
+                writeln!(f, "    {:>width$}  {}", size, path.display(), width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct CategoryCounter {
+    files: AtomicUsize,
+    bytes: AtomicU64,
+}
 
 #[derive(Debug)]
 pub struct Statistics {
+    /// Directories created below the destinations of the sources
     pub dirs_created: AtomicUsize,
+    /// Destinations of sources and child folders created by the job
+    pub root_dirs_created: AtomicUsize,
     pub files_copied: AtomicUsize,
     pub bytes_copied: AtomicU64,
     pub dirs_skipped: AtomicUsize,
     pub files_skipped: AtomicUsize,
     pub files_failed: AtomicUsize,
+    /// Failed files that were denied access
+    pub files_denied: AtomicUsize,
+    /// Files and directories skipped because of an error matching an
+    /// ignore rule
+    pub errors_ignored: AtomicUsize,
+    /// Skipped files on the skip-list, also counted as skipped
+    pub files_skip_listed: AtomicUsize,
+    /// Copies given the attributes of their source without being copied
+    pub files_attributes_updated: AtomicUsize,
+    /// Cloud placeholders skipped or only listed, with /CLOUD
+    pub files_placeholders: AtomicUsize,
+    /// Cloud placeholders downloaded to be copied, with /CLOUD:HYDRATE
+    pub files_hydrated: AtomicUsize,
+    /// Copies checked against their source by /SAMPLE
+    pub files_sampled: AtomicUsize,
+    /// Skipped files in quarantine, also counted as skipped
+    pub files_quarantined: AtomicUsize,
+    /// Files a content filter did not let be copied
+    pub files_blocked: AtomicUsize,
     pub dirs_removed: AtomicUsize,
     pub files_removed: AtomicUsize,
+    /// Attempts at copying a file that failed and were tried again
+    pub retries: AtomicUsize,
+    /// Files copied with unreadable parts written as zeros, with /SALVAGE
+    pub files_salvaged: AtomicUsize,
+    /// Copies whose contents changed although their size and time did not,
+    /// with /BITROT
+    pub files_rotted: AtomicUsize,
+    /// Files handled at once chosen by /MT:AUTO, in order
+    pub thread_limits: Mutex<Vec<usize>>,
+    /// Failed files, capped at `MAX_RECORDED_FAILURES`
+    pub failures: Mutex<Vec<Failure>>,
+    /// Every failed file, kept only for a failure report
+    pub reported_failures: Option<Mutex<Vec<Failure>>>,
+    /// Copied files by category, indexed like `FileCategory::ALL`
+    categories: [CategoryCounter; 5],
+    /// Special entries met, indexed by `SpecialKind` and `SpecialPolicy`
+    special: [[AtomicUsize; 3]; 4],
+    /// Links met, indexed by `LinkKind` and `LinkPolicy`
+    links: [[AtomicUsize; 4]; 3],
+    /// Child folders copied with /CHILDONLY, in the order they started
+    children: RwLock<Vec<ChildCounter>>,
+    pub scan_ms: AtomicU64,
+    pub copy_ms: AtomicU64,
+    pub purge_ms: AtomicU64,
+    /// Time the whole job took, set when it finishes
+    pub elapsed_ms: AtomicU64,
+    /// How the summary writes sizes
+    size_format: SizeFormat,
 }
 
 impl Default for Statistics {
     fn default() -> Self {
         Statistics {
             dirs_created: AtomicUsize::new(0),
+            root_dirs_created: AtomicUsize::new(0),
             files_copied: AtomicUsize::new(0),
             bytes_copied: AtomicU64::new(0),
             dirs_skipped: AtomicUsize::new(0),
             files_skipped: AtomicUsize::new(0),
             files_failed: AtomicUsize::new(0),
+            files_denied: AtomicUsize::new(0),
+            errors_ignored: AtomicUsize::new(0),
+            files_skip_listed: AtomicUsize::new(0),
+            files_placeholders: AtomicUsize::new(0),
+            files_attributes_updated: AtomicUsize::new(0),
+            files_hydrated: AtomicUsize::new(0),
+            files_sampled: AtomicUsize::new(0),
+            files_quarantined: AtomicUsize::new(0),
+            files_blocked: AtomicUsize::new(0),
             dirs_removed: AtomicUsize::new(0),
             files_removed: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+            files_salvaged: AtomicUsize::new(0),
+            files_rotted: AtomicUsize::new(0),
+            thread_limits: Mutex::new(Vec::new()),
+            failures: Mutex::new(Vec::new()),
+            reported_failures: None,
+            categories: Default::default(),
+            special: Default::default(),
+            links: Default::default(),
+            children: RwLock::new(Vec::new()),
+            scan_ms: AtomicU64::new(0),
+            copy_ms: AtomicU64::new(0),
+            purge_ms: AtomicU64::new(0),
+            elapsed_ms: AtomicU64::new(0),
+            size_format: SizeFormat::default(),
         }
     }
 }
@@ -33,13 +395,124 @@ impl Statistics {
         Self::default()
     }
 
+    /// Write sizes in the summary in `size_format`
+    pub fn with_size_format(mut self, size_format: SizeFormat) -> Self {
+        self.size_format = size_format;
+        self
+    }
+
+    /// Keep every failed file for a failure report, past the cap of the
+    /// summary
+    pub fn with_failure_report(mut self) -> Self {
+        self.reported_failures = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    pub fn size_format(&self) -> SizeFormat {
+        self.size_format
+    }
+
     pub fn add_dir_created(&self) {
         self.dirs_created.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn add_file_copied(&self, bytes: u64) {
+    pub fn add_root_dir_created(&self) {
+        self.root_dirs_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_copied(&self, path: &Path, bytes: u64) {
         self.files_copied.fetch_add(1, Ordering::Relaxed);
         self.bytes_copied.fetch_add(bytes, Ordering::Relaxed);
+
+        let counter = &self.categories[FileCategory::of(path).index()];
+        counter.files.fetch_add(1, Ordering::Relaxed);
+        counter.bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        self.count_child(path, |child| {
+            child.files.fetch_add(1, Ordering::Relaxed);
+            child.bytes.fetch_add(bytes, Ordering::Relaxed);
+        });
+    }
+
+    /// Count the files below `root` towards the child folder `name`
+    pub fn start_child(&self, root: &Path, name: &str) {
+        let mut children = self.children.write().unwrap_or_else(|e| e.into_inner());
+        children.push(ChildCounter {
+            root: root.to_path_buf(),
+            name: name.to_string(),
+            files: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            failed: AtomicUsize::new(0),
+            duration_ms: AtomicU64::new(0),
+        });
+    }
+
+    /// Record that the child folder at `root` took `elapsed` to copy
+    pub fn finish_child(&self, root: &Path, elapsed: Duration) {
+        self.count_child(root, |child| {
+            child
+                .duration_ms
+                .store(elapsed.as_millis() as u64, Ordering::Relaxed);
+        });
+    }
+
+    /// Apply `count` to the child folder holding `path`, if there is one
+    fn count_child(&self, path: &Path, count: impl FnOnce(&ChildCounter)) {
+        let children = self.children.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(child) = children.iter().find(|child| path.starts_with(&child.root)) {
+            count(child);
+        }
+    }
+
+    /// Totals of each child folder copied with /CHILDONLY
+    pub fn children(&self) -> Vec<ChildTotals> {
+        let children = self.children.read().unwrap_or_else(|e| e.into_inner());
+        children
+            .iter()
+            .map(|child| ChildTotals {
+                name: child.name.clone(),
+                files_copied: child.files.load(Ordering::Relaxed) as u64,
+                bytes_copied: child.bytes.load(Ordering::Relaxed),
+                files_failed: child.failed.load(Ordering::Relaxed) as u64,
+                duration_ms: child.duration_ms.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Copied files and bytes of each category that had any files
+    pub fn categories(&self) -> Vec<CategoryTotals> {
+        FileCategory::ALL
+            .iter()
+            .map(|&category| {
+                let counter = &self.categories[category.index()];
+                CategoryTotals {
+                    category,
+                    files: counter.files.load(Ordering::Relaxed) as u64,
+                    bytes: counter.bytes.load(Ordering::Relaxed),
+                }
+            })
+            .filter(|totals| totals.files > 0)
+            .collect()
+    }
+
+    /// Count a special entry handled according to `policy`
+    pub fn add_special(&self, kind: SpecialKind, policy: SpecialPolicy) {
+        self.special[kind.index()][policy.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Special entries of `kind` handled according to `policy`
+    pub fn special(&self, kind: SpecialKind, policy: SpecialPolicy) -> u64 {
+        self.special[kind.index()][policy.index()].load(Ordering::Relaxed) as u64
+    }
+
+    /// Count a link handled according to `policy`
+    pub fn add_link(&self, kind: LinkKind, policy: LinkPolicy) {
+        self.links[kind.index()][policy.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Links of `kind` handled according to `policy`
+    pub fn link(&self, kind: LinkKind, policy: LinkPolicy) -> u64 {
+        self.links[kind.index()][policy.index()].load(Ordering::Relaxed) as u64
     }
 
     pub fn add_dir_skipped(&self) {
@@ -54,6 +527,99 @@ impl Statistics {
         self.files_failed.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn add_error_ignored(&self) {
+        self.errors_ignored.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_skip_listed(&self) {
+        self.add_file_skipped();
+        self.files_skip_listed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_attributes_updated(&self) {
+        self.add_file_skipped();
+        self.files_attributes_updated
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_placeholder(&self) {
+        self.add_file_skipped();
+        self.files_placeholders.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_hydrated(&self) {
+        self.files_hydrated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_sampled(&self) {
+        self.files_sampled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_blocked(&self) {
+        self.files_blocked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_quarantined(&self) {
+        self.add_file_skipped();
+        self.files_quarantined.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_salvaged(&self) {
+        self.files_salvaged.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_rotted(&self) {
+        self.files_rotted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_file_denied(&self) {
+        self.files_denied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a failed file and remember why it failed
+    pub fn add_failure(&self, path: &Path, reason: impl fmt::Display) {
+        self.record_failure(path, reason.to_string(), None);
+    }
+
+    /// Count a file that failed with `error`, described by `reason`, and
+    /// remember the operation and path the error first failed on
+    pub fn add_io_failure(&self, path: &Path, error: &io::Error, reason: impl fmt::Display) {
+        self.record_failure(path, reason.to_string(), failed_at(error));
+    }
+
+    fn record_failure(&self, path: &Path, reason: String, at: Option<&PathError>) {
+        self.add_file_failed();
+        self.count_child(path, |child| {
+            child.failed.fetch_add(1, Ordering::Relaxed);
+        });
+        let failure = Failure {
+            path: path.to_path_buf(),
+            reason,
+            operation: at.map(|at| at.operation.to_string()),
+            operation_path: at.map(|at| at.path.clone()),
+        };
+        // Still usable after a panic of another file while recording
+        if let Some(reported) = &self.reported_failures {
+            reported
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(failure.clone());
+        }
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        if failures.len() < MAX_RECORDED_FAILURES {
+            failures.push(failure);
+        }
+    }
+
+    pub fn add_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a limit chosen by /MT:AUTO
+    pub fn add_thread_limit(&self, limit: usize) {
+        self.thread_limits.lock().unwrap().push(limit);
+    }
+
     pub fn add_dir_removed(&self) {
         self.dirs_removed.fetch_add(1, Ordering::Relaxed);
     }
@@ -61,50 +627,299 @@ impl Statistics {
     pub fn add_file_removed(&self) {
         self.files_removed.fetch_add(1, Ordering::Relaxed);
     }
+
+    pub fn add_scan_time(&self, elapsed: Duration) {
+        self.scan_ms
+            .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_copy_time(&self, elapsed: Duration) {
+        self.copy_ms
+            .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_purge_time(&self, elapsed: Duration) {
+        self.purge_ms
+            .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Labelled counters of the summary, in display order
+    pub fn rows(&self) -> Vec<(String, u64)> {
+        let mut rows = vec![
+            (
+                tr!("stats-dirs-created"),
+                self.dirs_created.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-files-copied"),
+                self.files_copied.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-bytes-copied"),
+                self.bytes_copied.load(Ordering::Relaxed),
+            ),
+            (
+                tr!("stats-dirs-skipped"),
+                self.dirs_skipped.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-files-skipped"),
+                self.files_skipped.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-files-failed"),
+                self.files_failed.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-dirs-removed"),
+                self.dirs_removed.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                tr!("stats-files-removed"),
+                self.files_removed.load(Ordering::Relaxed) as u64,
+            ),
+        ];
+        // Only shown when the skip-list, quarantine, content filters, cloud
+        // placeholders, ignore rules, /SALVAGE or /BITROT are in use
+        let blocked = self.files_blocked.load(Ordering::Relaxed) as u64;
+        if blocked > 0 {
+            rows.insert(6, (tr!("stats-files-blocked"), blocked));
+        }
+        let ignored = self.errors_ignored.load(Ordering::Relaxed) as u64;
+        if ignored > 0 {
+            rows.insert(6, (tr!("stats-errors-ignored"), ignored));
+        }
+        let salvaged = self.files_salvaged.load(Ordering::Relaxed) as u64;
+        if salvaged > 0 {
+            rows.insert(6, (tr!("stats-files-salvaged"), salvaged));
+        }
+        let rotted = self.files_rotted.load(Ordering::Relaxed) as u64;
+        if rotted > 0 {
+            rows.insert(6, (tr!("stats-files-rotted"), rotted));
+        }
+        // Right below the files skipped, which include them
+        let attributes_updated = self.files_attributes_updated.load(Ordering::Relaxed) as u64;
+        if attributes_updated > 0 {
+            rows.insert(5, (tr!("stats-attributes-updated"), attributes_updated));
+        }
+        let placeholders = self.files_placeholders.load(Ordering::Relaxed) as u64;
+        if placeholders > 0 {
+            rows.insert(5, (tr!("stats-placeholders"), placeholders));
+        }
+        let skip_listed = self.files_skip_listed.load(Ordering::Relaxed) as u64;
+        if skip_listed > 0 {
+            rows.insert(5, (tr!("stats-skip-listed"), skip_listed));
+        }
+        let quarantined = self.files_quarantined.load(Ordering::Relaxed) as u64;
+        if quarantined > 0 {
+            rows.insert(5, (tr!("stats-quarantined"), quarantined));
+        }
+        // Right below the files copied, which include them
+        let sampled = self.files_sampled.load(Ordering::Relaxed) as u64;
+        if sampled > 0 {
+            rows.insert(2, (tr!("stats-sampled"), sampled));
+        }
+        let hydrated = self.files_hydrated.load(Ordering::Relaxed) as u64;
+        if hydrated > 0 {
+            rows.insert(2, (tr!("stats-hydrated"), hydrated));
+        }
+        // Right below the directories created, which do not include them
+        let root_dirs_created = self.root_dirs_created.load(Ordering::Relaxed) as u64;
+        if root_dirs_created > 0 {
+            rows.insert(1, (tr!("stats-root-dirs-created"), root_dirs_created));
+        }
+
+        rows
+    }
+
+    /// The `value` of the row of [`Statistics::rows`] labelled `label` as
+    /// the summary writes it, the bytes copied as a size
+    pub fn row_text(&self, label: &str, value: u64) -> String {
+        if label == tr!("stats-bytes-copied") {
+            self.size_format.format(value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed) as u64;
+        StatsSnapshot {
+            dirs_created: load(&self.dirs_created),
+            root_dirs_created: load(&self.root_dirs_created),
+            files_copied: load(&self.files_copied),
+            bytes_copied: self.bytes_copied.load(Ordering::Relaxed),
+            dirs_skipped: load(&self.dirs_skipped),
+            files_skipped: load(&self.files_skipped),
+            files_failed: load(&self.files_failed),
+            errors_ignored: load(&self.errors_ignored),
+            files_skip_listed: load(&self.files_skip_listed),
+            files_placeholders: load(&self.files_placeholders),
+            files_attributes_updated: load(&self.files_attributes_updated),
+            files_hydrated: load(&self.files_hydrated),
+            files_sampled: load(&self.files_sampled),
+            files_quarantined: load(&self.files_quarantined),
+            files_blocked: load(&self.files_blocked),
+            dirs_removed: load(&self.dirs_removed),
+            files_removed: load(&self.files_removed),
+            retries: load(&self.retries),
+            files_salvaged: load(&self.files_salvaged),
+            files_rotted: load(&self.files_rotted),
+            phases: self.phase_times(),
+            elapsed_ms: self.elapsed_ms.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn phase_times(&self) -> PhaseTimes {
+        PhaseTimes {
+            scan_ms: self.scan_ms.load(Ordering::Relaxed),
+            copy_ms: self.copy_ms.load(Ordering::Relaxed),
+            purge_ms: self.purge_ms.load(Ordering::Relaxed),
+        }
+    }
 }
 
 impl fmt::Display for Statistics {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Statistics:")?;
-        writeln!(
-            f,
-            "    Directories created: {}",
-            self.dirs_created.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Files copied:        {}",
-            self.files_copied.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Bytes copied:        {}",
-            self.bytes_copied.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Directories skipped: {}",
-            self.dirs_skipped.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Files skipped:       {}",
-            self.files_skipped.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Files failed:        {}",
-            self.files_failed.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Directories removed: {}",
-            self.dirs_removed.load(Ordering::Relaxed)
-        )?;
-        writeln!(
-            f,
-            "    Files removed:       {}",
-            self.files_removed.load(Ordering::Relaxed)
-        )
+        let rows = self.rows();
+
+        // Align the values whatever the length of the translated labels
+        let width = rows
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        writeln!(f, "{}", tr!("stats-title"))?;
+        for (label, value) in &rows {
+            writeln!(
+                f,
+                "    {:<width$} {}",
+                label,
+                self.row_text(label, *value),
+                width = width
+            )?;
+        }
+
+        let limits = self.thread_limits.lock().unwrap();
+        if let (Some(first), Some(last)) = (limits.first(), limits.last()) {
+            writeln!(
+                f,
+                "{}",
+                tr!(
+                    "stats-auto-threads",
+                    start = *first,
+                    end = *last,
+                    min = limits.iter().min().copied().unwrap_or(0),
+                    max = limits.iter().max().copied().unwrap_or(0),
+                    changes = limits.len() - 1
+                )
+            )?;
+        }
+        drop(limits);
+
+        let phases = self.phase_times();
+        if phases != PhaseTimes::default() {
+            writeln!(f, "{}", tr!("stats-phases", phases = phases.to_string()))?;
+        }
+
+        // Links by kind, e.g. "Junctions  3 recreated"
+        let links: Vec<(String, String)> = LinkKind::ALL
+            .iter()
+            .filter_map(|&kind| {
+                let outcomes: Vec<String> = LinkPolicy::ALL
+                    .iter()
+                    .map(|&policy| (policy, self.link(kind, policy)))
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(policy, count)| tr!(policy.message_id(), count = count))
+                    .collect();
+                (!outcomes.is_empty()).then(|| (tr!(kind.message_id()), outcomes.join(", ")))
+            })
+            .collect();
+        write_outcomes(f, "stats-links", &links)?;
+
+        // Special entries by kind, e.g. "FIFOs  2 skipped, 1 recreated"
+        let special: Vec<(String, String)> = SpecialKind::ALL
+            .iter()
+            .filter_map(|&kind| {
+                let outcomes: Vec<String> = SpecialPolicy::ALL
+                    .iter()
+                    .map(|&policy| (policy, self.special(kind, policy)))
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(policy, count)| tr!(policy.message_id(), count = count))
+                    .collect();
+                (!outcomes.is_empty()).then(|| (tr!(kind.message_id()), outcomes.join(", ")))
+            })
+            .collect();
+        write_outcomes(f, "stats-special", &special)?;
+
+        // Child folders, e.g. "alice  120 files, 3.3 KiB, 2 failed in 0:01:05"
+        let children: Vec<(String, String)> = self
+            .children()
+            .into_iter()
+            .map(|child| {
+                let totals = tr!(
+                    "stats-child-row",
+                    files = child.files_copied,
+                    size = self.size_format.format(child.bytes_copied),
+                    failed = child.files_failed,
+                    duration = format_duration(Duration::from_millis(child.duration_ms))
+                );
+                (child.name, totals)
+            })
+            .collect();
+        write_outcomes(f, "stats-by-child", &children)?;
+
+        let categories = self.categories();
+        if !categories.is_empty() {
+            let labels: Vec<String> = categories
+                .iter()
+                .map(|totals| tr!(totals.category.message_id()))
+                .collect();
+            let width = labels
+                .iter()
+                .map(|label| label.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            writeln!(f, "{}", tr!("stats-by-category"))?;
+            for (label, totals) in labels.iter().zip(&categories) {
+                writeln!(
+                    f,
+                    "    {:<width$} {}",
+                    label,
+                    tr!(
+                        "stats-category-row",
+                        files = totals.files,
+                        size = self.size_format.format(totals.bytes)
+                    ),
+                    width = width
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the section titled `title_id` of labels and their outcomes, if
+/// there are any
+fn write_outcomes(
+    f: &mut fmt::Formatter<'_>,
+    title_id: &str,
+    rows: &[(String, String)],
+) -> fmt::Result {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    writeln!(f, "{}", tr!(title_id))?;
+    for (label, outcomes) in rows {
+        writeln!(f, "    {:<width$} {}", label, outcomes, width = width)?;
     }
+    Ok(())
 }
@@ -0,0 +1,104 @@
+//! A progress file for monitoring from outside.
+//!
+//! A headless run started from cron has no window and no service to ask how
+//! it is doing. With `/STATUS:file` the engine keeps a JSON file with the
+//! current progress and statistics, rewritten every few seconds and on each
+//! change of state, so other tools can watch the job by reading a file. The
+//! file is written next to its final name and renamed over it, so a reader
+//! never sees half of it. A file whose `updated_at` stops moving while its
+//! state is still running belongs to a job that hung or was killed.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::progress::{ProgressInfo, ProgressState};
+use crate::stats::{Statistics, StatsSnapshot};
+
+/// Shortest time between two writes of the file in the same state
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The file a status file at `path` is written to before it is renamed
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+#[derive(Serialize)]
+struct StatusContents<'a> {
+    /// Unix timestamp of this write
+    updated_at: i64,
+    #[serde(flatten)]
+    info: &'a ProgressInfo,
+    percentage: f32,
+    stats: StatsSnapshot,
+}
+
+#[derive(Default)]
+struct WriteState {
+    last: Option<(Instant, ProgressState)>,
+    /// A write failed and was reported
+    warned: bool,
+}
+
+/// The status file of a job
+pub struct StatusFile {
+    path: PathBuf,
+    stats: Arc<Statistics>,
+    state: Mutex<WriteState>,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf, stats: Arc<Statistics>) -> Self {
+        StatusFile {
+            path,
+            stats,
+            state: Mutex::new(WriteState::default()),
+        }
+    }
+
+    /// Rewrite the file with `info` if the state changed or it was last
+    /// written `STATUS_INTERVAL` ago. Returns the first error writing it;
+    /// later ones are not reported again.
+    pub fn update(&self, info: &ProgressInfo) -> Option<io::Error> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some((at, last_state)) = state.last {
+            if last_state == info.state && now.duration_since(at) < STATUS_INTERVAL {
+                return None;
+            }
+        }
+        state.last = Some((now, info.state));
+
+        let error = self.write(info).err()?;
+        if state.warned {
+            return None;
+        }
+        state.warned = true;
+        Some(error)
+    }
+
+    fn write(&self, info: &ProgressInfo) -> io::Result<()> {
+        let contents = StatusContents {
+            updated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+            info,
+            percentage: info.percentage(),
+            stats: self.stats.snapshot(),
+        };
+        let json = serde_json::to_vec_pretty(&contents).map_err(io::Error::other)?;
+
+        let temp = temp_path(&self.path);
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &self.path)
+    }
+}
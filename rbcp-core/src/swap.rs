@@ -0,0 +1,134 @@
+//! Updating a live destination in one step.
+//!
+//! In swap mode the job copies into a staging directory next to the
+//! destination instead of into the destination itself. The staging
+//! directory starts out as hard links of the destination, so unchanged
+//! files are not copied again, and files are unlinked before they are
+//! rewritten so the live tree is never modified. When the job completes the
+//! staging directory takes the place of the destination, so readers such as
+//! a web server see either the old tree or the new one, never a mix.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Staging directory used for `destination`
+pub fn staging_path(destination: &Path) -> PathBuf {
+    sibling(destination, "rbcp-staging")
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Create the staging directory for `destination`. A staging directory left
+/// by an interrupted run is kept if `keep_existing` is set, e.g. when the
+/// job is resumed, and rebuilt otherwise.
+pub fn prepare_staging(destination: &Path, staging: &Path, keep_existing: bool) -> io::Result<()> {
+    if staging.exists() {
+        if keep_existing {
+            return Ok(());
+        }
+        fs::remove_dir_all(staging)?;
+    }
+
+    if destination.is_dir() {
+        link_tree(destination, staging)
+    } else {
+        fs::create_dir_all(staging)
+    }
+}
+
+/// Recreate the tree below `src` in `dst`, hard linking files where the
+/// filesystem allows and copying them otherwise
+fn link_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_tree(&entry.path(), &target)?;
+        } else if fs::hard_link(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Give the staging file at `path` its own copy of the contents, so changes
+/// made in place, such as to its attributes, leave the live file alone
+pub fn detach(path: &Path) -> io::Result<()> {
+    let copy = sibling(path, "rbcp-detach");
+    let modified = fs::metadata(path)?.modified()?;
+    fs::copy(path, &copy)?;
+    let kept = filetime::set_file_mtime(&copy, filetime::FileTime::from_system_time(modified))
+        .and_then(|()| fs::rename(&copy, path));
+    if let Err(e) = kept {
+        let _ = fs::remove_file(&copy);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Put `staging` in the place of `destination` and remove the old tree
+pub fn swap_into_place(staging: &Path, destination: &Path) -> io::Result<()> {
+    if !destination.exists() {
+        return fs::rename(staging, destination);
+    }
+
+    // After the exchange the staging path holds the old tree
+    if exchange(staging, destination)? {
+        return fs::remove_dir_all(staging);
+    }
+
+    // Without an atomic exchange the destination is briefly missing
+    let old = sibling(destination, "rbcp-old");
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    fs::rename(destination, &old)?;
+    if let Err(e) = fs::rename(staging, destination) {
+        let _ = fs::rename(&old, destination);
+        return Err(e);
+    }
+    fs::remove_dir_all(&old)
+}
+
+/// Atomically exchange two directories. Returns false where the platform
+/// cannot do that.
+fn exchange(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let a = CString::new(a.as_os_str().as_bytes())?;
+        let b = CString::new(b.as_os_str().as_bytes())?;
+        let result = unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                a.as_ptr(),
+                libc::AT_FDCWD,
+                b.as_ptr(),
+                libc::RENAME_EXCHANGE,
+            )
+        };
+        if result == 0 {
+            return Ok(true);
+        }
+        let error = io::Error::last_os_error();
+        // Older kernels and some filesystems don't support the exchange
+        match error.raw_os_error() {
+            Some(libc::EINVAL) | Some(libc::ENOSYS) => Ok(false),
+            _ => Err(error),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (a, b);
+        Ok(false)
+    }
+}
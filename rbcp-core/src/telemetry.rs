@@ -0,0 +1,78 @@
+//! Tracing subscriber setup for frontends.
+//!
+//! The core reports its work through the `tracing` crate: a span per job,
+//! per directory and per file, with byte counts and durations recorded as
+//! fields, and every log line mirrored as an event. The plain-text output of
+//! [`Logger`](crate::utils::Logger) is unaffected; this module only decides
+//! where the structured data goes. Filtering follows `RUST_LOG` and defaults
+//! to warnings. With the `otel` feature, spans can also be exported to an
+//! OpenTelemetry collector over OTLP/HTTP.
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Keeps the exporter alive; pending spans are flushed when it is dropped
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            let _ = provider.shutdown();
+        }
+    }
+}
+
+/// Install the global subscriber. Formatted events go to stderr; when
+/// `otlp_endpoint` is given (e.g. `http://localhost:4318/v1/traces`), spans
+/// are exported there as well.
+pub fn init(otlp_endpoint: Option<&str>) -> Result<TelemetryGuard, String> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let fmt = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let registry = tracing_subscriber::registry().with(filter).with(fmt);
+
+    match otlp_endpoint {
+        #[cfg(feature = "otel")]
+        Some(endpoint) => {
+            use opentelemetry::trace::TracerProvider;
+            use opentelemetry_otlp::WithExportConfig;
+
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(
+                    opentelemetry_sdk::Resource::builder()
+                        .with_service_name("rbcp")
+                        .build(),
+                )
+                .build();
+            let tracer = provider.tracer("rbcp");
+
+            registry
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .try_init()
+                .map_err(|e| e.to_string())?;
+
+            Ok(TelemetryGuard {
+                provider: Some(provider),
+            })
+        }
+        #[cfg(not(feature = "otel"))]
+        Some(_) => Err("OTLP export is not enabled in this build".to_string()),
+        None => {
+            registry.try_init().map_err(|e| e.to_string())?;
+            Ok(TelemetryGuard {
+                #[cfg(feature = "otel")]
+                provider: None,
+            })
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Support for testing code that runs copy jobs.
+//!
+//! Retries, resumes and cancellation are hard to test against a real disk,
+//! which fails when it likes. Built with the `testing` feature, an engine
+//! given a [`FaultPlan`] with `CopyEngine::with_faults` checks it before
+//! each open, read and write of the files it copies, so a test can fail the
+//! third write, make a file look locked for two attempts or slow every read
+//! down, and get the same run every time. Each engine has a plan of its
+//! own, so tests using faults run side by side.
+//!
+//! There is no in-memory file system behind the engine, which works on
+//! real paths: trees to copy are described in memory with [`MemoryTree`],
+//! written to a [`TempDir`] and read back into a `MemoryTree` to compare. A `TempDir` is on the RAM-backed file system
+//! where there is one, `/dev/shm` on Linux, so tests do not wait on a disk.
+//! [`RecordingProgress`] keeps the log of a job and can cancel it after a
+//! given number of progress reports.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::progress::{ProgressCallback, ProgressInfo, ProgressState};
+use crate::utils::matches_pattern;
+
+/// File operations a fault can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOp {
+    /// Opening a source file
+    Open,
+    /// A read from a source file
+    Read,
+    /// A write to a destination file
+    Write,
+}
+
+/// What an injected fault does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// Fail with an error of this kind
+    Error(io::ErrorKind),
+    /// Fail with the error the system gives for a file open elsewhere
+    SharingViolation,
+    /// Wait this long, then go on
+    Latency(Duration),
+    /// Panic, as a bug in the engine would
+    Panic,
+}
+
+#[derive(Debug)]
+struct Fault {
+    op: FaultOp,
+    /// File names matched, or all files
+    pattern: Option<String>,
+    /// Operation counted from 1 the fault starts at, among those matched
+    nth: usize,
+    /// Operations it affects from there, 0 for all
+    times: usize,
+    kind: FaultKind,
+    seen: AtomicUsize,
+}
+
+impl Fault {
+    /// What to do for an operation on `path`, counting it
+    fn check(&self, op: FaultOp, path: &Path) -> Option<FaultKind> {
+        if op != self.op {
+            return None;
+        }
+        if let Some(pattern) = &self.pattern {
+            let name = path.file_name()?.to_string_lossy();
+            if !matches_pattern(&name, pattern) {
+                return None;
+            }
+        }
+        let count = self.seen.fetch_add(1, Ordering::Relaxed) + 1;
+        let hit = count >= self.nth && (self.times == 0 || count < self.nth + self.times);
+        hit.then_some(self.kind)
+    }
+}
+
+/// Faults injected into the copies of a job
+#[derive(Debug, Default)]
+pub struct FaultPlan {
+    faults: Vec<Fault>,
+    injected: AtomicUsize,
+}
+
+impl FaultPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with(
+        mut self,
+        op: FaultOp,
+        pattern: Option<&str>,
+        nth: usize,
+        times: usize,
+        kind: FaultKind,
+    ) -> Self {
+        self.faults.push(Fault {
+            op,
+            pattern: pattern.map(str::to_string),
+            nth: nth.max(1),
+            times,
+            kind,
+            seen: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    /// Fail the `n`th write to any destination file, counted from 1
+    pub fn fail_nth_write(self, n: usize) -> Self {
+        self.with(
+            FaultOp::Write,
+            None,
+            n,
+            1,
+            FaultKind::Error(io::ErrorKind::Other),
+        )
+    }
+
+    /// Fail the `n`th read from any source file, counted from 1
+    pub fn fail_nth_read(self, n: usize) -> Self {
+        self.with(
+            FaultOp::Read,
+            None,
+            n,
+            1,
+            FaultKind::Error(io::ErrorKind::Other),
+        )
+    }
+
+    /// Fail the first `times` opens of source files named like `pattern`
+    /// with an error of `kind`
+    pub fn fail_open(self, pattern: &str, kind: io::ErrorKind, times: usize) -> Self {
+        self.with(
+            FaultOp::Open,
+            Some(pattern),
+            1,
+            times,
+            FaultKind::Error(kind),
+        )
+    }
+
+    /// Make source files named like `pattern` look open in another program
+    /// for the first `times` attempts at them
+    pub fn sharing_violation(self, pattern: &str, times: usize) -> Self {
+        self.with(
+            FaultOp::Open,
+            Some(pattern),
+            1,
+            times,
+            FaultKind::SharingViolation,
+        )
+    }
+
+    /// Panic when opening source files named like `pattern`
+    pub fn panic_on(self, pattern: &str) -> Self {
+        self.with(FaultOp::Open, Some(pattern), 1, 0, FaultKind::Panic)
+    }
+
+    /// Delay every `op` by `delay`
+    pub fn latency(self, op: FaultOp, delay: Duration) -> Self {
+        self.with(op, None, 1, 0, FaultKind::Latency(delay))
+    }
+
+    /// Number of faults injected so far, delays included
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    /// Apply the plan to `op` on `path`, called by the engine
+    pub(crate) fn inject(&self, op: FaultOp, path: &Path) -> io::Result<()> {
+        for fault in &self.faults {
+            let Some(kind) = fault.check(op, path) else {
+                continue;
+            };
+            self.injected.fetch_add(1, Ordering::Relaxed);
+            match kind {
+                FaultKind::Latency(delay) => thread::sleep(delay),
+                FaultKind::SharingViolation => return Err(sharing_violation_error()),
+                FaultKind::Panic => panic!("Injected panic on {}", path.display()),
+                FaultKind::Error(kind) => {
+                    return Err(io::Error::new(
+                        kind,
+                        format!("Injected {:?} fault on {}", op, path.display()),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error the system gives for a file open in another program
+fn sharing_violation_error() -> io::Error {
+    #[cfg(windows)]
+    return io::Error::from_raw_os_error(32); // ERROR_SHARING_VIOLATION
+    #[cfg(unix)]
+    return io::Error::from_raw_os_error(libc::EBUSY);
+    #[cfg(not(any(unix, windows)))]
+    return io::Error::new(io::ErrorKind::Other, "Sharing violation");
+}
+
+/// A tree of files and folders described in memory, by paths with `/`
+/// between names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryTree {
+    /// File contents, or `None` for a folder
+    entries: BTreeMap<String, Option<Vec<u8>>>,
+}
+
+impl MemoryTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, and the folders above it
+    pub fn file(mut self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.add_parents(path);
+        self.entries.insert(path.to_string(), Some(contents.into()));
+        self
+    }
+
+    /// Add a folder, and the folders above it
+    pub fn dir(mut self, path: &str) -> Self {
+        self.add_parents(path);
+        self.entries.insert(path.to_string(), None);
+        self
+    }
+
+    fn add_parents(&mut self, path: &str) {
+        let mut parent = path;
+        while let Some((above, _)) = parent.rsplit_once('/') {
+            self.entries.insert(above.to_string(), None);
+            parent = above;
+        }
+    }
+
+    /// Contents of the file at `path`
+    pub fn contents(&self, path: &str) -> Option<&[u8]> {
+        self.entries.get(path)?.as_deref()
+    }
+
+    /// Paths of the files and folders, in order
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Write the tree below `root`
+    pub fn write_to(&self, root: &Path) -> io::Result<()> {
+        fs::create_dir_all(root)?;
+        for (path, contents) in &self.entries {
+            let target = root.join(path);
+            match contents {
+                Some(contents) => fs::write(target, contents)?,
+                None => fs::create_dir_all(target)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The tree below `root` on disk
+    pub fn read_from(root: &Path) -> io::Result<Self> {
+        let mut tree = MemoryTree::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let relative = path
+                    .strip_prefix(root)
+                    .map_err(io::Error::other)?
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                if path.is_dir() {
+                    tree.entries.insert(relative, None);
+                    pending.push(path);
+                } else {
+                    tree.entries.insert(relative, Some(fs::read(&path)?));
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+/// A directory removed with everything in it when dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// A new empty directory in memory, or in the system's temporary
+    /// directory where there is no RAM-backed file system
+    pub fn new() -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let shm = Path::new("/dev/shm");
+        let base = if cfg!(target_os = "linux") && shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let path = base.join(format!(
+            "rbcp-test-{}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Progress callback keeping what a job reports, optionally cancelling it
+#[derive(Default)]
+pub struct RecordingProgress {
+    logs: Mutex<Vec<String>>,
+    states: Mutex<Vec<ProgressState>>,
+    reports: AtomicUsize,
+    cancel_after: Option<usize>,
+    cancelled: AtomicBool,
+}
+
+impl RecordingProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the job once it has reported progress `reports` times
+    pub fn cancel_after(reports: usize) -> Self {
+        RecordingProgress {
+            cancel_after: Some(reports),
+            ..Self::default()
+        }
+    }
+
+    /// Log messages so far
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lock().unwrap().clone()
+    }
+
+    /// Whether a log message so far contains `text`
+    pub fn logged(&self, text: &str) -> bool {
+        self.logs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|log| log.contains(text))
+    }
+
+    /// States reported so far, without repeats
+    pub fn states(&self) -> Vec<ProgressState> {
+        self.states.lock().unwrap().clone()
+    }
+}
+
+impl ProgressCallback for RecordingProgress {
+    fn on_progress(&self, info: &ProgressInfo) {
+        let mut states = self.states.lock().unwrap();
+        if states.last() != Some(&info.state) {
+            states.push(info.state);
+        }
+        let reports = self.reports.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.cancel_after.is_some_and(|after| reports >= after) {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+    fn on_log(&self, message: &str) {
+        self.logs.lock().unwrap().push(message.to_string());
+    }
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    fn is_paused(&self) -> bool {
+        false
+    }
+}
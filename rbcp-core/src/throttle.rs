@@ -0,0 +1,86 @@
+//! Capping the bandwidth of a job.
+//!
+//! With `/THROTTLE:n` the threads of a job share a budget of n MB/s, so a
+//! copy to a NAS leaves room on the link for everyone else. Each chunk is
+//! given the next free slot of the time its size takes at that rate, and
+//! its thread waits for the slot before writing it, which keeps the threads
+//! together at the rate however many there are. The rate can change while
+//! the job runs: the user may set another cap, and a policy such as the
+//! battery one may hold the job below it for a while.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::progress::ProgressCallback;
+
+/// Longest wait between checks for a cancelled job
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bandwidth budget shared by the threads of a job
+pub struct Throttle {
+    /// Bits of the rate in MB/s, 0 for no cap
+    mbps: AtomicU64,
+    /// Bits of a lower rate a policy holds the job to for now, 0 for none
+    limit: AtomicU64,
+    /// When the time given out so far runs out
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    /// Budget of `mbps` MB (of 1,000,000 bytes) per second, 0 for no cap
+    pub fn new(mbps: f64) -> Self {
+        Throttle {
+            mbps: AtomicU64::new(mbps.to_bits()),
+            limit: AtomicU64::new(0f64.to_bits()),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn mbps(&self) -> f64 {
+        f64::from_bits(self.mbps.load(Ordering::Relaxed))
+    }
+
+    /// Change the budget to `mbps`, 0 lifting the cap
+    pub fn set_mbps(&self, mbps: f64) {
+        self.mbps.store(mbps.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Hold the job to at most `mbps` whatever its own cap, 0 releasing it
+    pub fn set_limit(&self, mbps: f64) {
+        self.limit.store(mbps.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The rate the job is held to now, 0 for no cap
+    pub fn rate(&self) -> f64 {
+        let limit = f64::from_bits(self.limit.load(Ordering::Relaxed));
+        match self.mbps() {
+            mbps if mbps > 0.0 && limit > 0.0 => mbps.min(limit),
+            mbps if mbps > 0.0 => mbps,
+            _ => limit,
+        }
+    }
+
+    /// Wait until `bytes` more may be written, or the job is cancelled
+    pub fn take(&self, bytes: u64, progress: &dyn ProgressCallback) {
+        let mbps = self.rate();
+        if mbps <= 0.0 {
+            return;
+        }
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            // Time not used while idle is not saved up for a burst
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(bytes as f64 / (mbps * 1_000_000.0));
+            start
+        };
+        loop {
+            let now = Instant::now();
+            if now >= start || progress.is_cancelled() {
+                return;
+            }
+            thread::sleep((start - now).min(CHECK_INTERVAL));
+        }
+    }
+}
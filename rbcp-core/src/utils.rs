@@ -1,85 +1,550 @@
-use glob::Pattern;
+use chrono::{DateTime, Local};
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{self, Seek, Write};
-use std::path::Path;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime};
+
+pub use rbcp_plan::matches_pattern;
 
 #[derive(Clone)]
 pub struct Logger {
     file: Arc<Mutex<Option<File>>>,
+    to_stderr: bool,
+    quiet: bool,
 }
 
 impl Logger {
     pub fn new(file: Option<File>) -> Self {
         Logger {
             file: Arc::new(Mutex::new(file)),
+            to_stderr: false,
+            quiet: false,
         }
     }
 
+    /// Print nothing, only writing to the file
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    /// Print messages to stderr instead of stdout, keeping stdout free
+    /// for machine-readable output
+    pub fn to_stderr(mut self) -> Self {
+        self.to_stderr = true;
+        self
+    }
+
     pub fn log(&self, message: &str) {
-        // Print to stdout
-        println!("{}", message);
+        tracing::info!(target: "rbcp::log", "{}", message.trim());
 
-        // Write to file if it exists
-        if let Ok(mut file_guard) = self.file.lock() {
-            if let Some(file) = file_guard.as_mut() {
-                let _ = writeln!(file, "{}", message);
+        // Print to stdout (or stderr)
+        if !self.quiet {
+            if self.to_stderr {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
             }
         }
+
+        self.write_file(message);
     }
 
     // Log only to file, not stdout
     pub fn log_file_only(&self, message: &str) {
+        tracing::debug!(target: "rbcp::log", "{}", message.trim());
+        self.write_file(message);
+    }
+
+    /// Write to the file if there is one, stamped with the date and time
+    fn write_file(&self, message: &str) {
         if let Ok(mut file_guard) = self.file.lock() {
             if let Some(file) = file_guard.as_mut() {
-                let _ = writeln!(file, "{}", message);
+                let _ = writeln!(file, "[{}] {}", format_time(SystemTime::now()), message);
             }
         }
     }
 }
 
+/// Marker file next to the executable that enables portable mode
+pub const PORTABLE_MARKER: &str = "rbcp.portable";
+
+/// Directory for RBCP's persistent state such as job checkpoints.
+/// `RBCP_DATA_DIR` moves it anywhere, e.g. out of the way of tests.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("RBCP_DATA_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = portable_dir() {
+        return dir;
+    }
+
+    #[cfg(windows)]
+    let base = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME").map(|home| {
+        PathBuf::from(home)
+            .join("Library")
+            .join("Application Support")
+    });
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")));
+
+    base.unwrap_or_else(std::env::temp_dir).join("rbcp")
+}
+
+/// In portable mode, the `rbcp-data` folder next to the executable. Portable
+/// mode is on when `PORTABLE_MARKER` exists beside the executable or
+/// `RBCP_PORTABLE=1` is set, so settings travel with the program.
+pub fn portable_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    let enabled = std::env::var_os("RBCP_PORTABLE").is_some_and(|value| value == "1")
+        || exe_dir.join(PORTABLE_MARKER).exists();
+    enabled.then(|| exe_dir.join("rbcp-data"))
+}
+
+/// Bytes available to the current user on the volume holding `path`, or on
+/// the volume of its nearest existing ancestor. `None` if it cannot be
+/// determined.
+pub fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        // The field types differ between platforms
+        #[allow(clippy::unnecessary_cast)]
+        let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+        Some(free)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetDiskFreeSpaceExW(
+                directory: *const u16,
+                free_to_caller: *mut u64,
+                total: *mut u64,
+                total_free: *mut u64,
+            ) -> i32;
+        }
+
+        let wide: Vec<u16> = existing
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut free = 0u64;
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut free,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        (ok != 0).then_some(free)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = existing;
+        None
+    }
+}
+
+/// Identifier of the volume holding `path`, or the volume of its nearest
+/// existing ancestor, that stays the same wherever it is mounted: the
+/// serial number on Windows (e.g. `1A2B-3C4D`), the file system UUID on
+/// Linux. `None` if it cannot be determined, as for network shares.
+pub fn volume_id(path: &Path) -> Option<String> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // Links named after the UUIDs of the block devices
+        let device = fs::metadata(existing).ok()?.dev();
+        fs::read_dir("/dev/disk/by-uuid")
+            .ok()?
+            .flatten()
+            .find(|entry| fs::metadata(entry.path()).is_ok_and(|meta| meta.rdev() == device))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetVolumePathNameW(file_name: *const u16, volume_path: *mut u16, len: u32) -> i32;
+            fn GetVolumeInformationW(
+                root: *const u16,
+                name: *mut u16,
+                name_len: u32,
+                serial: *mut u32,
+                max_component_len: *mut u32,
+                flags: *mut u32,
+                file_system: *mut u16,
+                file_system_len: u32,
+            ) -> i32;
+        }
+
+        let wide: Vec<u16> = existing
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut root = [0u16; 261];
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+            return None;
+        }
+        let mut serial = 0u32;
+        let ok = unsafe {
+            GetVolumeInformationW(
+                root.as_ptr(),
+                std::ptr::null_mut(),
+                0,
+                &mut serial,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        (ok != 0).then(|| format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = existing;
+        None
+    }
+}
+
+/// An I/O error with the operation that failed and the path it failed on,
+/// both named in its message ahead of the error itself. An error given
+/// context on its way up reads as a chain, from the outermost step in.
+#[derive(Debug)]
+pub struct PathError {
+    pub operation: &'static str,
+    pub path: PathBuf,
+    pub source: io::Error,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.operation,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Context for the error of an I/O call
+pub trait PathContext<T> {
+    /// Name `operation` and `path` in the error, if any, keeping its kind
+    fn context(self, operation: &'static str, path: &Path) -> io::Result<T>;
+}
+
+impl<T> PathContext<T> for io::Result<T> {
+    fn context(self, operation: &'static str, path: &Path) -> io::Result<T> {
+        self.map_err(|source| {
+            // Already said by the call that failed
+            if failed_at(&source).is_some_and(|at| at.operation == operation && at.path == path) {
+                return source;
+            }
+            io::Error::new(
+                source.kind(),
+                PathError {
+                    operation,
+                    path: path.to_path_buf(),
+                    source,
+                },
+            )
+        })
+    }
+}
+
+/// The operation and path named by the outermost context of `error`
+fn outer_context(error: &io::Error) -> Option<&PathError> {
+    error.get_ref()?.downcast_ref::<PathError>()
+}
+
+/// The operation and path `error` first failed on, if it was given context
+pub fn failed_at(error: &io::Error) -> Option<&PathError> {
+    let mut at = outer_context(error)?;
+    while let Some(inner) = outer_context(&at.source) {
+        at = inner;
+    }
+    Some(at)
+}
+
+/// The error below the contexts of `error`, which has its OS error code
+pub fn root_cause(error: &io::Error) -> &io::Error {
+    failed_at(error).map_or(error, |at| &at.source)
+}
+
+/// Whether an error means the file is open or locked by another process,
+/// which usually clears up after a while
+pub fn is_locked_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    const LOCKED_ERRORS: &[i32] = &[
+        32, // ERROR_SHARING_VIOLATION
+        33, // ERROR_LOCK_VIOLATION
+    ];
+    #[cfg(unix)]
+    const LOCKED_ERRORS: &[i32] = &[libc::EBUSY, libc::ETXTBSY];
+    #[cfg(not(any(unix, windows)))]
+    const LOCKED_ERRORS: &[i32] = &[];
+
+    root_cause(error)
+        .raw_os_error()
+        .is_some_and(|code| LOCKED_ERRORS.contains(&code))
+}
+
+/// Whether an error may pass if the call is made again, as when a network
+/// share or disk stops answering for a moment
+pub fn is_transient_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    const TRANSIENT_ERRORS: &[i32] = &[
+        53,   // ERROR_BAD_NETPATH
+        54,   // ERROR_NETWORK_BUSY
+        59,   // ERROR_UNEXP_NET_ERR
+        64,   // ERROR_NETNAME_DELETED
+        121,  // ERROR_SEM_TIMEOUT
+        1231, // ERROR_NETWORK_UNREACHABLE
+    ];
+    #[cfg(unix)]
+    const TRANSIENT_ERRORS: &[i32] = &[
+        libc::EIO,
+        libc::ESTALE,
+        libc::EAGAIN,
+        libc::ETIMEDOUT,
+        libc::ENETDOWN,
+        libc::ENETUNREACH,
+        libc::EHOSTUNREACH,
+    ];
+    #[cfg(not(any(unix, windows)))]
+    const TRANSIENT_ERRORS: &[i32] = &[];
+
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+    ) || root_cause(error)
+        .raw_os_error()
+        .is_some_and(|code| TRANSIENT_ERRORS.contains(&code))
+}
+
+/// Make sure files can be created and deleted in `dir` by doing so with a
+/// probe file
+pub fn check_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".rbcp-write-test-{}.tmp", std::process::id()));
+    let written = File::create(&probe).and_then(|mut file| file.write_all(b"rbcp"));
+    let removed = fs::remove_file(&probe);
+    written?;
+    removed
+}
+
+/// Suffix of the file a manifest or state file is written to before it is
+/// renamed over the old one
+pub const REPLACE_SUFFIX: &str = ".rbcp-tmp";
+
+/// Write the file at `path` with `write`, to a file next to it that is
+/// synced and then renamed over it. A crash leaves the old contents or the
+/// new ones, never half of them, and other hard links to the old file, such
+/// as the live tree's files behind a /SWAP staging tree, are left alone.
+pub fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut io::BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(REPLACE_SUFFIX);
+    let temp = PathBuf::from(temp);
+
+    let written = (|| {
+        // One left by a crash may be linked to a file elsewhere
+        match fs::remove_file(&temp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let mut writer = io::BufWriter::new(File::create(&temp)?);
+        write(&mut writer)?;
+        let file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        file.sync_data()?;
+        fs::rename(&temp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Whether an error means the volume is mounted or write protected read-only
+pub fn is_read_only_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    const READ_ONLY_ERRORS: &[i32] = &[
+        19, // ERROR_WRITE_PROTECT
+    ];
+    #[cfg(not(windows))]
+    const READ_ONLY_ERRORS: &[i32] = &[];
+
+    error.kind() == io::ErrorKind::ReadOnlyFilesystem
+        || root_cause(error)
+            .raw_os_error()
+            .is_some_and(|code| READ_ONLY_ERRORS.contains(&code))
+}
+
+/// Write `path` to stdout terminated by a NUL byte, for `xargs -0` and
+/// similar consumers
+pub fn print_null_separated(path: &Path) {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().as_bytes().to_vec();
+
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(&bytes);
+    let _ = stdout.write_all(b"\0");
+    let _ = stdout.flush();
+}
+
+/// Format a time as the local date and time, e.g. `2024-03-01 14:05:09`
 pub fn format_time(time: SystemTime) -> String {
-    let duration = time
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::from_secs(0));
-    let secs = duration.as_secs();
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
 
-    let (hour, remainder) = (secs / 3600, secs % 3600);
-    let (min, sec) = (remainder / 60, remainder % 60);
+/// Format a time as the local date and time for a file name, e.g.
+/// `2024-03-01_140509`, so names sort by time
+pub fn format_file_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d_%H%M%S")
+        .to_string()
+}
 
-    format!("{:02}:{:02}:{:02}", hour % 24, min, sec)
+/// How sizes in bytes are written in summaries, logs and progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeFormat {
+    /// In the largest unit of 1024 reached, e.g. `1.5 GiB`
+    #[default]
+    Binary,
+    /// In the largest unit of 1000 reached, e.g. `1.6 GB`
+    Si,
+    /// As a plain number of bytes, e.g. `1610612736 B`
+    Bytes,
 }
 
-pub fn matches_pattern(entry_name: &str, pattern: &str) -> bool {
-    // Try glob first
-    if let Ok(compiled_pattern) = Pattern::new(pattern) {
-        if compiled_pattern.matches(entry_name) {
-            return true;
+impl SizeFormat {
+    pub const ALL: [SizeFormat; 3] = [SizeFormat::Binary, SizeFormat::Si, SizeFormat::Bytes];
+
+    /// Name used in the `/UNITS` option
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeFormat::Binary => "binary",
+            SizeFormat::Si => "si",
+            SizeFormat::Bytes => "bytes",
         }
     }
 
-    // Fallback/Legacy support
-    if pattern == "*" || pattern == "*.*" {
-        return true;
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown size units: {} (expected binary, si or bytes)",
+                    name
+                )
+            })
     }
 
-    if let Some(suffix) = pattern.strip_prefix('*') {
-        if pattern.ends_with('*') {
-            // *contains*
-            let substr = &suffix[..suffix.len() - 1];
-            entry_name.contains(substr)
+    /// Write `bytes` in this format
+    pub fn format(self, bytes: u64) -> String {
+        let (base, units) = match self {
+            SizeFormat::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB"]),
+            SizeFormat::Si => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB"]),
+            SizeFormat::Bytes => return format!("{} B", bytes),
+        };
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= base && unit < units.len() - 1 {
+            size /= base;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} {}", bytes, units[0])
         } else {
-            // *ends_with
-            entry_name.ends_with(suffix)
+            format!("{:.1} {}", size, units[unit])
         }
-    } else if let Some(prefix) = pattern.strip_suffix('*') {
-        // starts_with*
-        entry_name.starts_with(prefix)
+    }
+}
+
+/// Format a duration as H:MM:SS
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// Format a duration as H:MM:SS.mmm, for the times a job and its phases
+/// took, which are often under a second
+pub fn format_elapsed(duration: Duration) -> String {
+    format!(
+        "{}.{:03}",
+        format_duration(duration),
+        duration.subsec_millis()
+    )
+}
+
+/// Read a list of relative paths from `source`, or from stdin if it is `-`.
+/// The list is NUL-separated if it contains any NUL bytes (as written by
+/// `find -print0`), otherwise one path per line.
+pub fn read_file_list(source: &str) -> io::Result<Vec<String>> {
+    let mut content = String::new();
+    if source == "-" {
+        io::stdin().read_to_string(&mut content)?;
     } else {
-        entry_name == pattern
+        File::open(source)?.read_to_string(&mut content)?;
     }
+
+    let separator = if content.contains('\0') { '\0' } else { '\n' };
+    Ok(content
+        .split(separator)
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
 }
 
 pub fn securely_delete_file(path: &Path, logger: &Logger) -> io::Result<()> {
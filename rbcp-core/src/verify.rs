@@ -0,0 +1,143 @@
+//! Checking copies against their source.
+//!
+//! Verification hashes each file with BLAKE3, whose SIMD implementation
+//! hashes faster than most disks and networks deliver data. The source is
+//! hashed from the buffers as they are copied, so it is never read twice;
+//! only the destination is read back, and large files are hashed on all
+//! worker threads. Verifying therefore adds little time to a copy.
+//!
+//! Migrating many terabytes of media, reading every copy back is still a
+//! second pass over the disk. `/SAMPLE` checks less for much less: either
+//! a random share of the copies in full, or the first and last 64 KB of
+//! each, where truncated and misplaced writes show.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub use blake3::{Hash, Hasher};
+
+/// Bytes at each end of a file that `/SAMPLE:ENDS` compares
+pub const SAMPLE_ENDS_BYTES: u64 = 64 * 1024;
+
+/// Which copies `/SAMPLE` checks, and how much of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// Verify this percentage of the copies in full, picked at random
+    Percent(u8),
+    /// Compare the ends of every copy with those of its source
+    Ends,
+}
+
+impl Sampling {
+    /// Parse the value of `/SAMPLE:`, a percentage or `ENDS`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value.eq_ignore_ascii_case("ends") {
+            return Ok(Sampling::Ends);
+        }
+        match value.trim_end_matches('%').parse::<u8>() {
+            Ok(percent) if (1..=100).contains(&percent) => Ok(Sampling::Percent(percent)),
+            _ => Err(format!(
+                "Invalid sample: {} (expected a percentage from 1 to 100 or ENDS)",
+                value
+            )),
+        }
+    }
+
+    /// Value of `/SAMPLE:` that gives this sampling
+    pub fn name(self) -> String {
+        match self {
+            Sampling::Percent(percent) => percent.to_string(),
+            Sampling::Ends => "ENDS".to_string(),
+        }
+    }
+
+    /// Whether the next copy is checked
+    pub fn picks(self) -> bool {
+        match self {
+            Sampling::Percent(percent) => rand::random::<f64>() * 100.0 < f64::from(percent),
+            Sampling::Ends => true,
+        }
+    }
+}
+
+/// Hash the file at `path`, on several threads if it is large
+pub fn hash_file(path: &Path) -> io::Result<Hash> {
+    let mut hasher = Hasher::new();
+    hasher.update_mmap_rayon(path)?;
+    Ok(hasher.finalize())
+}
+
+/// Check that the file at `dst` hashes to `expected`, the hash of its source
+pub fn verify_copy(expected: Hash, dst: &Path) -> io::Result<()> {
+    check(expected, hash_file(dst)?)
+}
+
+/// Check that the parts of a split copy, joined in order, hash to
+/// `expected`, the hash of its source
+pub fn verify_parts(expected: Hash, parts: impl IntoIterator<Item = PathBuf>) -> io::Result<()> {
+    let mut hasher = Hasher::new();
+    for part in parts {
+        hasher.update_mmap_rayon(part)?;
+    }
+    check(expected, hasher.finalize())
+}
+
+fn check(expected: Hash, actual: Hash) -> io::Result<()> {
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "verification failed, the copy hashes to {} instead of {}",
+                actual.to_hex(),
+                expected.to_hex()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the first and last [`SAMPLE_ENDS_BYTES`] of the file at
+/// `dst` match those of its source at `src`
+pub fn verify_ends(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut src = File::open(src)?;
+    let mut dst = File::open(dst)?;
+    let size = src.metadata()?.len();
+    let copied = dst.metadata()?.len();
+    if copied != size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "sampled verification failed, the copy has {} bytes instead of {}",
+                copied, size
+            ),
+        ));
+    }
+
+    let head = size.min(SAMPLE_ENDS_BYTES);
+    let tail_start = size.saturating_sub(SAMPLE_ENDS_BYTES).max(head);
+    for (offset, len) in [(0, head), (tail_start, size - tail_start)] {
+        if len == 0 {
+            continue;
+        }
+        let mut expected = vec![0; len as usize];
+        let mut actual = vec![0; len as usize];
+        src.seek(SeekFrom::Start(offset))?;
+        src.read_exact(&mut expected)?;
+        dst.seek(SeekFrom::Start(offset))?;
+        dst.read_exact(&mut actual)?;
+        if actual != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "sampled verification failed, the copy differs from its source within {} bytes of offset {}",
+                    len, offset
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,15 @@
+//! Setup shared by the tests running copy jobs
+
+use std::path::Path;
+use std::sync::Once;
+
+/// Keep the restart journals, run history and destination profiles of the
+/// jobs a test runs in Cargo's temporary directory, away from the user's
+/// data folder. Called before a test touches anything that reads it.
+pub fn isolate_data_dir() {
+    static ISOLATED: Once = Once::new();
+    ISOLATED.call_once(|| {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("rbcp-data");
+        std::env::set_var("RBCP_DATA_DIR", dir);
+    });
+}
@@ -0,0 +1,217 @@
+//! Retry, resume and cancellation of copy jobs, driven by fault plans
+
+mod common;
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rbcp_core::checkpoint::Checkpoint;
+use rbcp_core::testing::{FaultOp, FaultPlan, MemoryTree, RecordingProgress, TempDir};
+use rbcp_core::{CancellationToken, CopyEngine, CopyOptions, ProgressCallback};
+
+fn tree() -> MemoryTree {
+    MemoryTree::new()
+        .file("a.txt", "first")
+        .file("docs/b.txt", "second")
+        .file("docs/c.pst", vec![7; 200 * 1024])
+}
+
+/// Options copying `src` to `dst` with `args` besides
+fn options(src: &Path, dst: &Path, args: &[&str]) -> CopyOptions {
+    common::isolate_data_dir();
+    // Paths starting with `/` would read as options
+    let mut all = vec!["src", "dst", "/E", "/W:0"];
+    all.extend(args);
+    let mut options = CopyOptions::parse_args(all.into_iter().map(String::from).collect()).unwrap();
+    options.sources = vec![src.to_string_lossy().into_owned()];
+    options.destination = dst.to_string_lossy().into_owned();
+    options
+}
+
+fn engine_with(
+    options: CopyOptions,
+    plan: &Arc<FaultPlan>,
+) -> (CopyEngine, Arc<RecordingProgress>) {
+    let progress = Arc::new(RecordingProgress::new());
+    let engine = CopyEngine::new(options, progress.clone(), CancellationToken::new())
+        .with_faults(plan.clone());
+    (engine, progress)
+}
+
+/// A job name of this test run, whose restart journal is removed when
+/// dropped
+struct Job(String);
+
+impl Job {
+    fn new(test: &str) -> Self {
+        common::isolate_data_dir();
+        Job(format!("test-{}-{}", test, std::process::id()))
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(Checkpoint::path_for(&self.0).unwrap());
+    }
+}
+
+#[test]
+fn failed_write_is_retried() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+
+    let plan = Arc::new(FaultPlan::new().fail_nth_write(1));
+    let (engine, _) = engine_with(options(&src, &dst, &["/R:3"]), &plan);
+    let stats = engine.run().unwrap().snapshot();
+
+    assert_eq!(plan.injected(), 1);
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.files_copied, 3);
+    assert_eq!(stats.files_failed, 0);
+    assert_eq!(MemoryTree::read_from(&dst).unwrap(), tree());
+}
+
+#[test]
+fn locked_file_is_retried_until_free() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+
+    let plan = Arc::new(FaultPlan::new().sharing_violation("*.pst", 2));
+    let (engine, _) = engine_with(options(&src, &dst, &["/R:3"]), &plan);
+    let stats = engine.run().unwrap().snapshot();
+
+    assert_eq!(plan.injected(), 2);
+    assert_eq!(stats.retries, 2);
+    assert_eq!(stats.files_failed, 0);
+    assert_eq!(MemoryTree::read_from(&dst).unwrap(), tree());
+}
+
+#[test]
+fn file_fails_once_retries_run_out() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+
+    let plan = Arc::new(FaultPlan::new().fail_open("b.txt", io::ErrorKind::Other, 0));
+    let (engine, _) = engine_with(options(&src, &dst, &["/R:2"]), &plan);
+    let stats = engine.stats();
+    assert!(engine.run().is_err());
+
+    assert_eq!(plan.injected(), 2);
+    assert_eq!(stats.snapshot().files_failed, 1);
+    assert!(!dst.join("docs/b.txt").exists());
+}
+
+#[test]
+fn panicking_file_fails_alone() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+
+    let plan = Arc::new(FaultPlan::new().panic_on("b.txt"));
+    let (engine, _) = engine_with(options(&src, &dst, &["/MT:4"]), &plan);
+    let stats = engine.run().unwrap();
+
+    assert_eq!(stats.snapshot().files_failed, 1);
+    assert_eq!(stats.snapshot().files_copied, 2);
+    let failures = stats.failures.lock().unwrap();
+    assert!(failures[0].path.ends_with("docs/b.txt"));
+    assert!(failures[0].reason.starts_with("internal error"));
+    assert!(dst.join("a.txt").exists());
+    assert!(dst.join("docs/c.pst").exists());
+}
+
+#[test]
+fn resumed_job_copies_only_what_is_left() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+    let job = Job::new("resume");
+
+    let failing = Arc::new(FaultPlan::new().fail_open("b.txt", io::ErrorKind::Other, 0));
+    let job_arg = format!("/JOB:{}", job.0);
+    let (engine, _) = engine_with(options(&src, &dst, &["/R:1", &job_arg]), &failing);
+    let stats = engine.stats();
+    assert!(engine.run().is_err());
+    let left = 3 - stats.snapshot().files_copied;
+    assert!(left >= 1);
+
+    // Each open is counted, so only the files left are opened again
+    let opens = Arc::new(FaultPlan::new().latency(FaultOp::Open, Duration::ZERO));
+    let resume = CopyOptions::parse_args(vec![format!("/RESUME:{}", job.0)]).unwrap();
+    let (engine, progress) = engine_with(resume, &opens);
+    engine.run().unwrap();
+
+    assert!(progress.logged("Resuming job"));
+    assert_eq!(opens.injected() as u64, left);
+    assert_eq!(MemoryTree::read_from(&dst).unwrap(), tree());
+}
+
+#[test]
+fn cancelled_job_stops_and_resumes() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    tree().write_to(&src).unwrap();
+    let job = Job::new("cancel");
+
+    // Slow reads keep the job busy until the first progress report
+    // cancels it
+    let slow = Arc::new(FaultPlan::new().latency(FaultOp::Read, Duration::from_millis(20)));
+    let job_arg = format!("/JOB:{}", job.0);
+    let progress = Arc::new(RecordingProgress::cancel_after(1));
+    let engine = CopyEngine::new(
+        options(&src, &dst, &["/BUF:64", &job_arg]),
+        progress.clone(),
+        CancellationToken::new(),
+    )
+    .with_faults(slow.clone());
+    let stats = engine.run().unwrap().snapshot();
+    assert!(progress.is_cancelled());
+    assert!(stats.files_copied < 3);
+    assert_ne!(MemoryTree::read_from(&dst).ok(), Some(tree()));
+
+    let resume = CopyOptions::parse_args(vec![format!("/RESUME:{}", job.0)]).unwrap();
+    let (engine, _) = engine_with(resume, &Arc::new(FaultPlan::new()));
+    engine.run().unwrap();
+    assert_eq!(MemoryTree::read_from(&dst).unwrap(), tree());
+}
+
+#[test]
+fn split_parts_are_retried_and_verified() {
+    let dir = TempDir::new().unwrap();
+    let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+    let video: Vec<u8> = (0..5 * 512 * 1024).map(|i| (i % 251) as u8).collect();
+    let tree = MemoryTree::new().file("video.mkv", video.clone());
+    tree.write_to(&src).unwrap();
+
+    let plan = Arc::new(FaultPlan::new().fail_nth_write(2));
+    let (engine, _) = engine_with(options(&src, &dst, &["/SPLIT:1", "/V", "/R:3"]), &plan);
+    let stats = engine.run().unwrap().snapshot();
+
+    assert_eq!(plan.injected(), 1);
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.files_copied, 1);
+    let copied = MemoryTree::read_from(&dst).unwrap();
+    let parts: Vec<u8> = [
+        "video.mkv.part001",
+        "video.mkv.part002",
+        "video.mkv.part003",
+    ]
+    .iter()
+    .flat_map(|part| copied.contents(part).unwrap().to_vec())
+    .collect();
+    assert_eq!(parts, video);
+    assert!(copied.contents("video.mkv.rbcp-split").is_some());
+
+    let restored = dir.path().join("restored");
+    let (engine, _) = engine_with(
+        options(&dst, &restored, &["/REJOIN"]),
+        &Arc::new(FaultPlan::new()),
+    );
+    engine.run().unwrap();
+    assert_eq!(MemoryTree::read_from(&restored).unwrap(), tree);
+}
@@ -0,0 +1,36 @@
+//! Names given to flattened files whose names collide
+
+mod common;
+
+use rbcp_core::testing::{MemoryTree, TempDir};
+use rbcp_core::{CancellationToken, CopyEngine, CopyOptions, NullProgress};
+use std::sync::Arc;
+
+#[test]
+fn colliding_names_follow_the_sorted_source_paths() {
+    common::isolate_data_dir();
+    let dir = TempDir::new().unwrap();
+    let src = dir.path().join("src");
+    let mut tree = MemoryTree::new();
+    for server in ["server3", "server1", "server2", "server4"] {
+        tree = tree.file(&format!("{}/logs/app.log", server), server);
+    }
+    tree.write_to(&src).unwrap();
+
+    for run in 0..3 {
+        let dst = dir.path().join(format!("dst{}", run));
+        let args = ["src", "dst", "/E", "/FLAT", "/MT:8"];
+        let mut options = CopyOptions::parse_args(args.map(String::from).to_vec()).unwrap();
+        options.sources = vec![src.to_string_lossy().into_owned()];
+        options.destination = dst.to_string_lossy().into_owned();
+        CopyEngine::new(options, Arc::new(NullProgress), CancellationToken::new())
+            .run()
+            .unwrap();
+
+        let copied = MemoryTree::read_from(&dst).unwrap();
+        assert_eq!(copied.contents("app.log"), Some(&b"server1"[..]));
+        assert_eq!(copied.contents("app (2).log"), Some(&b"server2"[..]));
+        assert_eq!(copied.contents("app (3).log"), Some(&b"server3"[..]));
+        assert_eq!(copied.contents("app (4).log"), Some(&b"server4"[..]));
+    }
+}
@@ -0,0 +1,332 @@
+//! Deciding what a copy would do, without touching the disk.
+//!
+//! The rules choosing which files a job copies, replaces, skips and removes
+//! live here apart from the engine, with no I/O and no dependencies that
+//! keep it off `wasm32`. The engine applies them to what it reads from
+//! disk; built for the web with the `wasm` feature, the GUI applies them to
+//! listings it already has, so previews and pattern checks update on every
+//! keystroke without asking the backend.
+
+use std::collections::{HashMap, HashSet};
+
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+/// Whether the file or folder name `entry_name` matches `pattern`, a glob
+/// or one of the `*text`, `text*` and `*text*` forms of older versions
+pub fn matches_pattern(entry_name: &str, pattern: &str) -> bool {
+    // Try glob first
+    if let Ok(compiled_pattern) = Pattern::new(pattern) {
+        if compiled_pattern.matches(entry_name) {
+            return true;
+        }
+    }
+
+    // Fallback/Legacy support
+    if pattern == "*" || pattern == "*.*" {
+        return true;
+    }
+
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        if pattern.ends_with('*') {
+            // *contains*
+            let substr = &suffix[..suffix.len() - 1];
+            entry_name.contains(substr)
+        } else {
+            // *ends_with
+            entry_name.ends_with(suffix)
+        }
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        // starts_with*
+        entry_name.starts_with(prefix)
+    } else {
+        entry_name == pattern
+    }
+}
+
+/// Check that `pattern` is a valid glob, with the reason if not
+pub fn check_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.is_empty() {
+        return Err("Empty pattern".to_string());
+    }
+    Pattern::new(pattern)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid pattern {}: {}", pattern, e))
+}
+
+/// Windows attributes told apart when comparing files: read-only, hidden
+/// and system. The others describe where and how the data is stored and
+/// change on their own, e.g. offline, recall and pinned as files move
+/// between archive tiers, or archive as backups run.
+pub const COMPARED_ATTRIBUTES: u32 = 0x1 | 0x2 | 0x4;
+
+/// Size and modification time of a file, the time in nanoseconds since
+/// the Unix epoch, its Windows attributes, 0 elsewhere, and the hash of its
+/// contents when [`CompareStrategy::Hash`] needs it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileState {
+    pub size: u64,
+    pub mtime: u64,
+    pub attributes: u32,
+    pub hash: Option<[u8; 32]>,
+}
+
+/// What copying a source file over its destination has to change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    Unchanged,
+    /// Only the compared attributes differ
+    Attributes,
+    Contents,
+}
+
+/// What tells a copy that is up to date from one that is not
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareStrategy {
+    /// Any copy is up to date
+    Existence,
+    /// A copy of the same size is up to date
+    Size,
+    /// A copy is out of date when older, or as old but not the same size
+    #[default]
+    SizeAndTime,
+    /// A copy is up to date when its contents hash the same
+    Hash,
+}
+
+impl CompareStrategy {
+    pub const ALL: [CompareStrategy; 4] = [
+        CompareStrategy::Existence,
+        CompareStrategy::Size,
+        CompareStrategy::SizeAndTime,
+        CompareStrategy::Hash,
+    ];
+
+    /// Name used in the `/COMPARE` option
+    pub fn name(self) -> &'static str {
+        match self {
+            CompareStrategy::Existence => "existence",
+            CompareStrategy::Size => "size",
+            CompareStrategy::SizeAndTime => "time",
+            CompareStrategy::Hash => "hash",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown comparison: {} (expected existence, size, time or hash)",
+                    name
+                )
+            })
+    }
+}
+
+/// How a source file is compared with its copy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompareOptions {
+    /// Copy every file whatever its copy is like
+    pub force_overwrite: bool,
+    /// Leave copies differing from their source only in attributes alone
+    pub ignore_attributes: bool,
+    /// Modification times at most this many nanoseconds apart count as the
+    /// same, for clocks that drift and filesystems that round them
+    pub mtime_tolerance: u64,
+    pub strategy: CompareStrategy,
+}
+
+/// Whether a source file in `src` state is newer than its copy in `dst`
+/// state, or as new but not the same size
+fn newer(src: &FileState, dst: &FileState, tolerance: u64) -> bool {
+    let newer = src.mtime > dst.mtime.saturating_add(tolerance);
+    let same_time = src.mtime.abs_diff(dst.mtime) <= tolerance;
+    newer || (same_time && src.size != dst.size)
+}
+
+/// What a source file in `src` state changes in a destination in `dst`
+/// state, or `None` if missing: its contents when the strategy finds the
+/// copy out of date, or else its attributes if they differ and are not
+/// ignored. States without hashes are compared by size and time.
+pub fn compare(src: &FileState, dst: Option<&FileState>, options: &CompareOptions) -> FileChange {
+    let Some(dst) = dst else {
+        return FileChange::Contents;
+    };
+    let tolerance = options.mtime_tolerance;
+    let contents = match options.strategy {
+        CompareStrategy::Existence => false,
+        CompareStrategy::Size => src.size != dst.size,
+        CompareStrategy::SizeAndTime => newer(src, dst, tolerance),
+        CompareStrategy::Hash => match (src.hash, dst.hash) {
+            _ if src.size != dst.size => true,
+            (Some(src_hash), Some(dst_hash)) => src_hash != dst_hash,
+            _ => newer(src, dst, tolerance),
+        },
+    };
+    if options.force_overwrite || contents {
+        FileChange::Contents
+    } else if !options.ignore_attributes
+        && (src.attributes ^ dst.attributes) & COMPARED_ATTRIBUTES != 0
+    {
+        FileChange::Attributes
+    } else {
+        FileChange::Unchanged
+    }
+}
+
+/// A file or folder in a listing, by its path below the root with `/`
+/// between names
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Entry {
+    pub path: String,
+    pub is_dir: bool,
+    #[serde(flatten)]
+    pub state: FileState,
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+}
+
+/// The options of a job that matter to its plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanOptions {
+    /// Names of the files copied
+    pub patterns: Vec<String>,
+    /// Copy subfolders
+    pub recursive: bool,
+    /// Remove destination entries missing from the source
+    pub purge: bool,
+    #[serde(flatten)]
+    pub compare: CompareOptions,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        PlanOptions {
+            patterns: vec!["*.*".to_string()],
+            recursive: false,
+            purge: false,
+            compare: CompareOptions::default(),
+        }
+    }
+}
+
+/// What happens to one path of the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    NewFile,
+    ReplaceFile,
+    /// Give the copy the attributes of its source without copying it
+    UpdateAttributes,
+    SkipFile,
+    NewDir,
+    RemoveFile,
+    RemoveDir,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedStep {
+    pub step: Step,
+    pub path: String,
+    /// Bytes copied or removed, 0 for folders
+    pub bytes: u64,
+}
+
+/// The steps copying the `source` listing over the `destination` listing
+/// with `options` takes, in the order of `source` then the removals. Folders
+/// come before their contents in both listings.
+pub fn plan(source: &[Entry], destination: &[Entry], options: &PlanOptions) -> Vec<PlannedStep> {
+    let existing: HashMap<&str, &Entry> = destination
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let walked = |entry: &Entry| options.recursive || entry.depth() == 0;
+
+    let mut steps = Vec::new();
+    for entry in source.iter().filter(|entry| walked(entry)) {
+        let dst = existing.get(entry.path.as_str());
+        if entry.is_dir {
+            if dst.is_none() {
+                steps.push(PlannedStep {
+                    step: Step::NewDir,
+                    path: entry.path.clone(),
+                    bytes: 0,
+                });
+            }
+            continue;
+        }
+        if !options
+            .patterns
+            .iter()
+            .any(|pattern| matches_pattern(entry.name(), pattern))
+        {
+            continue;
+        }
+        let dst_state = dst.filter(|dst| !dst.is_dir).map(|dst| &dst.state);
+        let change = compare(&entry.state, dst_state, &options.compare);
+        let step = match change {
+            FileChange::Unchanged => Step::SkipFile,
+            FileChange::Attributes => Step::UpdateAttributes,
+            FileChange::Contents if dst.is_some() => Step::ReplaceFile,
+            FileChange::Contents => Step::NewFile,
+        };
+        steps.push(PlannedStep {
+            step,
+            path: entry.path.clone(),
+            bytes: if step == Step::UpdateAttributes {
+                0
+            } else {
+                entry.state.size
+            },
+        });
+    }
+
+    if options.purge {
+        let in_source: HashSet<&str> = source.iter().map(|entry| entry.path.as_str()).collect();
+        // Everything below a removed folder goes with it
+        let mut removed_dirs: Vec<&str> = Vec::new();
+        for entry in destination.iter().filter(|entry| walked(entry)) {
+            if in_source.contains(entry.path.as_str())
+                || removed_dirs.iter().any(|dir| {
+                    entry
+                        .path
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                })
+            {
+                continue;
+            }
+            let step = if entry.is_dir {
+                removed_dirs.push(&entry.path);
+                Step::RemoveDir
+            } else {
+                Step::RemoveFile
+            };
+            steps.push(PlannedStep {
+                step,
+                path: entry.path.clone(),
+                bytes: if entry.is_dir { 0 } else { entry.state.size },
+            });
+        }
+    }
+    steps
+}
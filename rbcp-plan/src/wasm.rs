@@ -0,0 +1,28 @@
+//! Functions for JavaScript, taking and returning JSON where the data is
+//! structured.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Entry, PlanOptions};
+
+#[wasm_bindgen(js_name = matchesPattern)]
+pub fn matches_pattern(entry_name: &str, pattern: &str) -> bool {
+    crate::matches_pattern(entry_name, pattern)
+}
+
+/// The reason `pattern` is invalid, or `undefined`
+#[wasm_bindgen(js_name = checkPattern)]
+pub fn check_pattern(pattern: &str) -> Option<String> {
+    crate::check_pattern(pattern).err()
+}
+
+/// The planned steps as a JSON array, from JSON arrays of entries and a
+/// JSON object of options
+#[wasm_bindgen]
+pub fn plan(source: &str, destination: &str, options: &str) -> Result<String, JsValue> {
+    let parse_error = |e: serde_json::Error| JsValue::from_str(&e.to_string());
+    let source: Vec<Entry> = serde_json::from_str(source).map_err(parse_error)?;
+    let destination: Vec<Entry> = serde_json::from_str(destination).map_err(parse_error)?;
+    let options: PlanOptions = serde_json::from_str(options).map_err(parse_error)?;
+    serde_json::to_string(&crate::plan(&source, &destination, &options)).map_err(parse_error)
+}
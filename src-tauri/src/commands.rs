@@ -1,9 +1,59 @@
-use rbcp_core::{CopyEngine, CopyOptions, ProgressCallback, ProgressInfo, SharedProgress};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter, State};
+use rbcp_core::checkpoint::Checkpoint;
+use rbcp_core::conflicts::{self, ConflictList};
+use rbcp_core::controls::JobControls;
+use rbcp_core::elevation;
+use rbcp_core::i18n;
+use rbcp_core::limits::{self, EntryLimits};
+use rbcp_core::peek::{self, Peek, Subfolder};
+use rbcp_core::queue::{JobEdit, JobQueue, Priority, QueuedJob};
+use rbcp_core::retry::FailureReport;
+use rbcp_core::scenario::{Scenario, ScenarioAnswers};
+use rbcp_core::settings::{self, GuiSettings, ImportSummary};
+use rbcp_core::stats::Failure;
+use rbcp_core::utils::{data_dir, format_file_time, portable_dir};
+use rbcp_core::{
+    CancellationToken, CopyEngine, CopyOptions, ProgressCallback, ProgressInfo, SharedProgress,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::desktop;
+use crate::taskbar::TaskbarProgress;
 
 pub struct AppState {
     pub progress: SharedProgress,
+    pub queue: Arc<Mutex<JobQueue>>,
+    /// Tokens stopping the running jobs, by job id
+    pub running: Arc<Mutex<HashMap<u64, CancellationToken>>>,
+    /// Speed controls of the running jobs, by job id
+    pub controls: Arc<Mutex<HashMap<u64, Arc<JobControls>>>>,
+    pub taskbar: Arc<TaskbarProgress>,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        // The full log of the session, the window keeping only the latest
+        // lines
+        let progress = SharedProgress::new();
+        let _ = progress.spool_logs(&data_dir().join("gui.log"));
+        Self {
+            progress,
+            queue: Arc::new(Mutex::new(JobQueue::load(&JobQueue::default_path()))),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            controls: Arc::new(Mutex::new(HashMap::new())),
+            taskbar: Arc::new(TaskbarProgress::default()),
+        }
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[tauri::command]
@@ -11,29 +61,506 @@ pub async fn start_copy(
     app: AppHandle,
     state: State<'_, AppState>,
     options: CopyOptions,
+    priority: Option<Priority>,
 ) -> Result<(), String> {
-    let options = options.clone();
-    let progress = state.progress.clone();
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .push(options, priority.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+    let _ = app.emit("queue-changed", queued_count(&state));
+
+    dispatch(&app, &state);
+    Ok(())
+}
+
+/// Queue the files a job failed on, listed in its failure `report`, to be
+/// copied again with the job's options
+#[tauri::command]
+pub async fn retry_failed(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    report: String,
+) -> Result<usize, String> {
+    let failures = FailureReport::load(Path::new(&report)).map_err(|e| e.to_string())?;
+    if failures.failures.is_empty() {
+        return Ok(0);
+    }
+    let mut options = failures.retry_options();
+    // Files failing again replace the report's list
+    options.failure_report = Some(report);
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .push(options, Priority::default())
+        .map_err(|e| e.to_string())?;
+    let _ = app.emit("queue-changed", queued_count(&state));
+
+    dispatch(&app, &state);
+    Ok(failures.failures.len())
+}
+
+/// Jobs left in the queue by a previous session
+#[tauri::command]
+pub fn queued_jobs(state: State<'_, AppState>) -> Vec<QueuedJob> {
+    state.queue.lock().unwrap().jobs().to_vec()
+}
 
-    // Reset progress before starting
-    progress.reset();
+/// Run the jobs left by a previous session, resuming interrupted ones
+#[tauri::command]
+pub fn resume_queue(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .resume_interrupted()
+        .map_err(|e| e.to_string())?;
+
+    dispatch(&app, &state);
+    Ok(())
+}
+
+/// Whether the queue is paused and how many jobs run at once
+#[derive(serde::Serialize)]
+pub struct QueueSettings {
+    pub paused: bool,
+    pub max_running: usize,
+}
 
-    // Span a thread for the copy operation
+#[tauri::command]
+pub fn queue_settings(state: State<'_, AppState>) -> QueueSettings {
+    let queue = state.queue.lock().unwrap();
+    QueueSettings {
+        paused: queue.is_paused(),
+        max_running: queue.max_running(),
+    }
+}
+
+/// Stop starting queued jobs, or start them again. Running jobs go on.
+#[tauri::command]
+pub fn set_queue_paused(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    paused: bool,
+) -> Result<(), String> {
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .set_paused(paused)
+        .map_err(|e| e.to_string())?;
+    dispatch(&app, &state);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_queue_limit(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    max_running: usize,
+) -> Result<(), String> {
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .set_max_running(max_running)
+        .map_err(|e| e.to_string())?;
+    dispatch(&app, &state);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_job_priority(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: u64,
+    priority: Priority,
+) -> Result<(), String> {
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .set_priority(id, priority)
+        .map_err(|e| e.to_string())?;
+    dispatch(&app, &state);
+    Ok(())
+}
+
+/// Source entries looked at to show which files an edited job picks
+const PEEK_LIMIT: usize = 2000;
+
+/// What is wrong with an edit of a queued job, and a look at the files it
+/// would pick
+#[derive(serde::Serialize)]
+pub struct JobEditPreview {
+    pub problems: Vec<String>,
+    pub peek: Peek,
+}
+
+#[tauri::command]
+pub async fn preview_job_edit(
+    state: State<'_, AppState>,
+    id: u64,
+    edit: JobEdit,
+) -> Result<JobEditPreview, String> {
+    let mut options = state
+        .queue
+        .lock()
+        .unwrap()
+        .jobs()
+        .iter()
+        .find(|job| job.id == id)
+        .map(|job| job.options.clone())
+        .ok_or_else(|| format!("No job {}", id))?;
+    let problems = edit.problems();
+    if !problems.is_empty() {
+        return Ok(JobEditPreview {
+            problems,
+            peek: Peek::default(),
+        });
+    }
+    edit.apply(&mut options);
+    Ok(JobEditPreview {
+        problems,
+        peek: peek::peek(&options, PEEK_LIMIT),
+    })
+}
+
+/// Source entries the pattern tester looks at
+const PATTERN_TEST_LIMIT: usize = 20_000;
+
+/// Which files below `dir` the patterns and exclusions pick, as
+/// `rbcp test-pattern` lists them
+#[tauri::command]
+pub async fn test_patterns(
+    dir: String,
+    patterns: Vec<String>,
+    exclude_files: Vec<String>,
+    exclude_dirs: Vec<String>,
+    recursive: bool,
+) -> Peek {
+    let options = CopyOptions {
+        sources: vec![dir],
+        patterns: if patterns.is_empty() {
+            vec!["*.*".to_string()]
+        } else {
+            patterns
+        },
+        exclude_files,
+        exclude_dirs,
+        recursive,
+        ..Default::default()
+    };
+    peek::peek(&options, PATTERN_TEST_LIMIT)
+}
+
+/// The folders directly in `dir`, for the source tree
+#[tauri::command]
+pub async fn source_folders(dir: String) -> Result<Vec<Subfolder>, String> {
+    peek::subfolders(Path::new(&dir)).map_err(|e| e.to_string())
+}
+
+/// Bandwidth cap and files at once of a running job
+#[derive(serde::Serialize)]
+pub struct JobSpeed {
+    pub throttle_mbps: f64,
+    pub threads: usize,
+    pub max_threads: usize,
+    /// Whether /MT:AUTO still picks the files at once
+    pub auto_threads: bool,
+}
+
+impl JobSpeed {
+    fn of(controls: &JobControls) -> Self {
+        JobSpeed {
+            throttle_mbps: controls.throttle_mbps(),
+            threads: controls.threads(),
+            max_threads: controls.max_threads(),
+            auto_threads: controls.is_auto(),
+        }
+    }
+}
+
+fn job_controls(state: &AppState, id: u64) -> Result<Arc<JobControls>, String> {
+    state
+        .controls
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("Job {} is not running", id))
+}
+
+#[tauri::command]
+pub fn job_speed(state: State<'_, AppState>, id: u64) -> Result<JobSpeed, String> {
+    Ok(JobSpeed::of(&job_controls(&state, id)?))
+}
+
+/// Change the bandwidth cap or the files at once of a running job, leaving
+/// the one not given as it is
+#[tauri::command]
+pub fn set_job_speed(
+    state: State<'_, AppState>,
+    id: u64,
+    throttle_mbps: Option<f64>,
+    threads: Option<usize>,
+) -> Result<JobSpeed, String> {
+    let controls = job_controls(&state, id)?;
+    if let Some(mbps) = throttle_mbps {
+        controls.set_throttle_mbps(mbps)?;
+    }
+    if let Some(threads) = threads {
+        controls.set_threads(threads)?;
+    }
+    Ok(JobSpeed::of(&controls))
+}
+
+/// Change the filters, threads and bandwidth cap of a job not started yet
+#[tauri::command]
+pub fn edit_job(state: State<'_, AppState>, id: u64, edit: JobEdit) -> Result<(), String> {
+    state
+        .queue
+        .lock()
+        .unwrap()
+        .edit(id, &edit)
+        .map_err(|e| e.to_string())
+}
+
+fn queued_count(state: &AppState) -> usize {
+    state.queue.lock().unwrap().jobs().len()
+}
+
+/// Start queued jobs while there is room for them, and stop a running job
+/// to make room for a waiting one of higher priority
+fn dispatch(app: &AppHandle, state: &AppState) {
+    let mut running = state.running.lock().unwrap();
+    // Cancelled jobs are let finish before the next one starts, as the
+    // cancel request would stop it too
+    if state.progress.is_cancelled() && !running.is_empty() {
+        return;
+    }
+
+    let mut queue = state.queue.lock().unwrap();
+    if let Ok(Some(id)) = queue.preempt() {
+        if let Some(token) = running.get(&id) {
+            token.cancel();
+        }
+    }
+    let mut started = false;
+    while let Ok(Some(job)) = queue.start_next() {
+        // Progress starts over when nothing else is running
+        if running.is_empty() {
+            state.progress.reset();
+            state.taskbar.set_paused(app, false);
+        }
+        let token = CancellationToken::new();
+        running.insert(job.id, token.clone());
+        spawn_job(app.clone(), job, token);
+        started = true;
+    }
+    // Started jobs can no longer be edited
+    if started {
+        let _ = app.emit("queue-changed", queue.jobs().len());
+    }
+}
+
+/// Failure reports of GUI jobs kept in the data folder, the oldest going
+/// first
+const KEPT_FAILURE_REPORTS: usize = 100;
+
+/// Remove all but the latest `KEPT_FAILURE_REPORTS` reports in `dir`, whose
+/// names sort by time
+fn prune_failure_reports(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut reports: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    reports.sort();
+    let old = reports.len().saturating_sub(KEPT_FAILURE_REPORTS);
+    for report in &reports[..old] {
+        let _ = std::fs::remove_file(report);
+    }
+}
+
+/// Run a queued job on its own thread, then make room for the next
+fn spawn_job(app: AppHandle, mut job: QueuedJob, token: CancellationToken) {
+    // Every job keeps the files it failed on, to be retried from the list,
+    // named by when it started so the lists sort by time
+    let reports = data_dir().join("failed");
+    if job.options.failure_report.is_none() && !job.options.list_only {
+        let name = format!("{}-{}.json", format_file_time(SystemTime::now()), job.id);
+        let path = reports.join(name);
+        job.options.failure_report = Some(path.to_string_lossy().into_owned());
+    }
     std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let stopped = token.clone();
         let engine = CopyEngine::new(
-            options,
+            job.options.clone(),
             Arc::new(TauriProgress {
                 app: app.clone(),
-                shared: progress,
+                shared: state.progress.clone(),
+                taskbar: state.taskbar.clone(),
+                job: job.id,
             }),
+            token,
         );
+        let stats = engine.stats();
+        state
+            .controls
+            .lock()
+            .unwrap()
+            .insert(job.id, engine.controls());
+        let result = engine.run();
+        state.controls.lock().unwrap().remove(&job.id);
 
-        let _ = engine.run();
+        // Reports of the GUI's own with nothing to retry are not kept
+        let report = job.options.failure_report.as_deref().map(Path::new);
+        if let Some(report) = report.filter(|report| report.starts_with(&reports)) {
+            if stats.files_failed.load(Ordering::Relaxed) == 0 {
+                let _ = std::fs::remove_file(report);
+            }
+            prune_failure_reports(&reports);
+        }
+
+        let failures = stats.failures.lock().unwrap().clone();
+        if !failures.is_empty() {
+            let _ = app.emit(
+                "job-failures",
+                JobFailures {
+                    job: job.id,
+                    failures,
+                    report: job.options.failure_report.clone(),
+                },
+            );
+        }
+
+        let completed = result.is_ok()
+            && !stopped.is_cancelled()
+            && !state.progress.is_cancelled()
+            && !job.options.list_only
+            && !job.options.eject;
+        let open_when_done = settings::load_gui_settings(&settings::gui_settings_path())
+            .get("openWhenDone")
+            .is_some_and(|value| value == "true");
+        if completed && open_when_done {
+            let _ = desktop::open_folder(Path::new(&job.options.destination));
+        }
+
+        // A cancelled job is not resumed later
+        if state.progress.is_cancelled() {
+            if let Some(Ok(path)) = job.options.job_name.as_deref().map(Checkpoint::path_for) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+
+        state.running.lock().unwrap().remove(&job.id);
+        state.taskbar.finish(&app, job.id);
+        {
+            let mut queue = state.queue.lock().unwrap();
+            let _ = queue.finish(job.id);
+            let _ = app.emit("queue-changed", queue.jobs().len());
+        }
+        dispatch(&app, &state);
     });
+}
+
+/// GUI strings in the user's language
+#[derive(serde::Serialize)]
+pub struct Translations {
+    pub locale: String,
+    pub messages: BTreeMap<String, String>,
+}
+
+#[tauri::command]
+pub fn translations() -> Translations {
+    Translations {
+        locale: i18n::locale(),
+        messages: i18n::messages("gui-"),
+    }
+}
+
+#[tauri::command]
+pub fn load_settings() -> GuiSettings {
+    settings::load_gui_settings(&settings::gui_settings_path())
+}
 
+#[tauri::command]
+pub fn save_settings(values: GuiSettings) -> Result<(), String> {
+    settings::save_gui_settings(&settings::gui_settings_path(), &values).map_err(|e| e.to_string())
+}
+
+/// Data folder in use when running in portable mode
+#[tauri::command]
+pub fn portable_location() -> Option<String> {
+    portable_dir().map(|dir| dir.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn export_settings(path: String) -> Result<(), String> {
+    settings::export_settings(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_settings(path: String) -> Result<ImportSummary, String> {
+    settings::import_settings(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// Options proposed by the setup wizard
+#[derive(serde::Serialize)]
+pub struct ScenarioPlan {
+    pub options: CopyOptions,
+    pub flags: String,
+    pub warnings: Vec<String>,
+}
+
+#[tauri::command]
+pub fn plan_scenario(scenario: Scenario, answers: ScenarioAnswers) -> ScenarioPlan {
+    let options = scenario.options(&answers);
+    ScenarioPlan {
+        flags: options.to_string_flags(),
+        warnings: scenario.warnings(&answers),
+        options,
+    }
+}
+
+/// Start the GUI again with administrator rights and close this instance
+#[tauri::command]
+pub fn relaunch_elevated(app: AppHandle) -> Result<(), String> {
+    elevation::relaunch_elevated().map_err(|e| e.to_string())?;
+    app.exit(0);
     Ok(())
 }
 
+/// Open `path`, a folder, in the file manager
+#[tauri::command]
+pub async fn open_folder(path: String) -> Result<(), String> {
+    desktop::open_folder(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// Show `path` selected in its folder in the file manager
+#[tauri::command]
+pub async fn reveal_file(path: String) -> Result<(), String> {
+    desktop::reveal(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// Keep at most `lines` log lines in memory
+#[tauri::command]
+pub fn set_log_capacity(state: State<'_, AppState>, lines: usize) {
+    state.progress.set_log_capacity(lines);
+}
+
+/// Save the full log of the session to `path`
+#[tauri::command]
+pub fn save_log(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    state
+        .progress
+        .save_log(Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn cancel_copy(state: State<'_, AppState>) -> Result<(), String> {
     state.progress.cancel();
@@ -41,43 +568,74 @@ pub fn cancel_copy(state: State<'_, AppState>) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn toggle_pause(state: State<'_, AppState>) -> Result<(), String> {
+pub fn toggle_pause(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     state.progress.toggle_pause();
+    state.taskbar.set_paused(&app, state.progress.is_paused());
     Ok(())
 }
 
+/// Conflicts listed in the GUI's table at most; the files past them are
+/// compared as usual
+const CONFLICT_LIMIT: usize = 5000;
+
+/// The source files whose destination already exists, for the GUI to ask
+/// what happens to each
 #[tauri::command]
-pub fn check_conflicts(sources: Vec<String>, destination: String) -> Result<bool, String> {
-    use std::path::Path;
+pub async fn list_conflicts(options: CopyOptions) -> Result<ConflictList, String> {
+    conflicts::find_conflicts(&options, CONFLICT_LIMIT)
+}
 
-    let dest_path = Path::new(&destination);
-    if !dest_path.exists() {
-        return Ok(false); // Destination doesn't exist, no conflicts
-    }
+/// Warnings about the folders and files of a job past its entry limits,
+/// or past the GUI's own if it sets none, for the GUI to confirm
+#[tauri::command]
+pub async fn check_limits(options: CopyOptions) -> Vec<String> {
+    let limits = if options.limits.is_active() {
+        options.limits
+    } else {
+        EntryLimits::GUI
+    };
+    limits::check(&options, &limits)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
 
-    for source in &sources {
-        let src_path = Path::new(source);
-        if let Some(name) = src_path.file_name() {
-            let target = dest_path.join(name);
-            if target.exists() {
-                return Ok(true); // Found a conflict
-            }
-        }
-    }
+/// Files a job failed to copy, as sent to the frontend
+#[derive(serde::Serialize)]
+struct JobFailures {
+    job: u64,
+    failures: Vec<Failure>,
+    /// Failure report the job wrote, to retry its files from
+    report: Option<String>,
+}
 
-    Ok(false)
+/// Progress of a job as sent to the frontend
+#[derive(serde::Serialize)]
+struct JobProgress<'a> {
+    job: u64,
+    #[serde(flatten)]
+    info: &'a ProgressInfo,
 }
 
 // Wrapper to emit events to frontend
 struct TauriProgress {
     app: AppHandle,
     shared: SharedProgress,
+    taskbar: Arc<TaskbarProgress>,
+    job: u64,
 }
 
 impl ProgressCallback for TauriProgress {
     fn on_progress(&self, info: &ProgressInfo) {
         self.shared.on_progress(info);
-        let _ = self.app.emit("copy-progress", info);
+        self.taskbar.update(&self.app, self.job, info);
+        let _ = self.app.emit(
+            "copy-progress",
+            JobProgress {
+                job: self.job,
+                info,
+            },
+        );
     }
 
     fn on_log(&self, message: &str) {
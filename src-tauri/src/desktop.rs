@@ -0,0 +1,88 @@
+//! Handing folders and files to the desktop's file manager.
+//!
+//! Explorer on Windows, Finder on macOS and whatever `xdg-open` picks on
+//! Linux, so the GUI can open a destination or point at a file the way the
+//! system would.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Open `path`, a folder, in the file manager
+pub fn open_folder(path: &Path) -> io::Result<()> {
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    spawn(Command::new(program).arg(path))
+}
+
+/// Show the folder holding `path` in the file manager with the file
+/// selected, or just open the folder where selecting is not supported
+pub fn reveal(path: &Path) -> io::Result<()> {
+    let path = path.canonicalize()?;
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        // Explorer wants the path quoted right after the comma
+        spawn(Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        spawn(Command::new("open").arg("-R").arg(&path))
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        // The file manager interface of Nautilus, Dolphin, Nemo and others
+        let shown = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.FileManager1",
+                "/org/freedesktop/FileManager1",
+                "org.freedesktop.FileManager1.ShowItems",
+            ])
+            .arg(format!("array:string:{}", file_uri(&path)))
+            .arg("string:")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if shown {
+            return Ok(());
+        }
+        open_folder(path.parent().unwrap_or(&path))
+    }
+}
+
+/// `file://` URI of the absolute `path`
+#[cfg(not(any(windows, target_os = "macos")))]
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Start `command` without waiting for it, reaping it in the background
+/// as the file manager may take its time
+fn spawn(command: &mut Command) -> io::Result<()> {
+    let mut child = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -2,20 +2,50 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod desktop;
+mod taskbar;
 
 fn main() {
+    // Structured tracing; set RBCP_OTLP_ENDPOINT to export spans
+    let otlp_endpoint = std::env::var("RBCP_OTLP_ENDPOINT").ok();
+    let _telemetry = rbcp_core::telemetry::init(otlp_endpoint.as_deref()).ok();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .manage(commands::AppState {
-            progress: rbcp_core::SharedProgress::new(),
-        })
+        .manage(commands::AppState::new())
         .invoke_handler(tauri::generate_handler![
             commands::start_copy,
+            commands::retry_failed,
             commands::cancel_copy,
             commands::toggle_pause,
-            commands::check_conflicts
+            commands::list_conflicts,
+            commands::check_limits,
+            commands::queued_jobs,
+            commands::resume_queue,
+            commands::queue_settings,
+            commands::set_queue_paused,
+            commands::set_queue_limit,
+            commands::set_job_priority,
+            commands::preview_job_edit,
+            commands::edit_job,
+            commands::job_speed,
+            commands::set_job_speed,
+            commands::test_patterns,
+            commands::source_folders,
+            commands::translations,
+            commands::load_settings,
+            commands::save_settings,
+            commands::portable_location,
+            commands::export_settings,
+            commands::import_settings,
+            commands::plan_scenario,
+            commands::relaunch_elevated,
+            commands::open_folder,
+            commands::reveal_file,
+            commands::set_log_capacity,
+            commands::save_log
         ])
         .setup(|_app| Ok(()))
         .run(tauri::generate_context!())
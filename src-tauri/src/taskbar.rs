@@ -0,0 +1,138 @@
+//! Progress of the running jobs on the taskbar button.
+//!
+//! Windows shows it on the taskbar button of the window (`ITaskbarList3`),
+//! macOS on the Dock icon, and Linux desktops implementing the Unity
+//! launcher API (Ubuntu's dock, KDE Plasma) on the launcher icon of
+//! `rbcp-gui.desktop`, so a long copy can be watched without keeping the
+//! window on top. The button turns yellow while the jobs are paused or held
+//! and red when one fails, and flashes (bounces in the Dock) when the last
+//! job finishes while the window is in the background.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rbcp_core::{ProgressInfo, ProgressState};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Manager, UserAttentionType};
+
+/// What the button shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bar {
+    Hidden,
+    /// Still scanning, size unknown
+    Busy,
+    Normal(u64),
+    Paused(u64),
+    Error(u64),
+}
+
+/// Latest progress of a running job
+struct JobBar {
+    state: ProgressState,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+/// The taskbar progress of all running jobs together
+#[derive(Default)]
+pub struct TaskbarProgress {
+    jobs: Mutex<HashMap<u64, JobBar>>,
+    /// Paused from the window
+    paused: Mutex<bool>,
+    /// What the button shows now, to leave it alone when nothing changed
+    shown: Mutex<Option<Bar>>,
+}
+
+impl TaskbarProgress {
+    /// Take in the progress of job `job`
+    pub fn update(&self, app: &AppHandle, job: u64, info: &ProgressInfo) {
+        self.jobs.lock().unwrap().insert(
+            job,
+            JobBar {
+                state: info.state,
+                bytes_done: info.bytes_done,
+                bytes_total: info.bytes_total,
+            },
+        );
+        self.show(app);
+    }
+
+    /// Take in the pause button of the window
+    pub fn set_paused(&self, app: &AppHandle, paused: bool) {
+        *self.paused.lock().unwrap() = paused;
+        self.show(app);
+    }
+
+    /// Forget job `job`, which has ended, flashing the button when it was
+    /// the last one
+    pub fn finish(&self, app: &AppHandle, job: u64) {
+        let idle = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.remove(&job);
+            jobs.is_empty()
+        };
+        self.show(app);
+        if !idle {
+            return;
+        }
+        if let Some(window) = app.get_webview_window("main") {
+            if !window.is_focused().unwrap_or(true) {
+                let _ = window.request_user_attention(Some(UserAttentionType::Informational));
+            }
+        }
+    }
+
+    fn bar(&self) -> Bar {
+        let jobs = self.jobs.lock().unwrap();
+        if jobs.is_empty() {
+            return Bar::Hidden;
+        }
+        let (done, total) = jobs.values().fold((0, 0), |(done, total), job| {
+            (done + job.bytes_done, total + job.bytes_total)
+        });
+        if total == 0 {
+            return Bar::Busy;
+        }
+        let percent = (done.min(total) * 100 / total).min(100);
+        let held = |job: &JobBar| {
+            matches!(
+                job.state,
+                ProgressState::Paused
+                    | ProgressState::NetworkPaused
+                    | ProgressState::BatteryPaused
+                    | ProgressState::Stalled
+            )
+        };
+        if jobs.values().any(|job| job.state == ProgressState::Failed) {
+            Bar::Error(percent)
+        } else if *self.paused.lock().unwrap() || jobs.values().all(held) {
+            Bar::Paused(percent)
+        } else {
+            Bar::Normal(percent)
+        }
+    }
+
+    fn show(&self, app: &AppHandle) {
+        let bar = self.bar();
+        {
+            let mut shown = self.shown.lock().unwrap();
+            if *shown == Some(bar) {
+                return;
+            }
+            *shown = Some(bar);
+        }
+        let (status, progress) = match bar {
+            Bar::Hidden => (ProgressBarStatus::None, None),
+            Bar::Busy => (ProgressBarStatus::Indeterminate, None),
+            Bar::Normal(percent) => (ProgressBarStatus::Normal, Some(percent)),
+            Bar::Paused(percent) => (ProgressBarStatus::Paused, Some(percent)),
+            Bar::Error(percent) => (ProgressBarStatus::Error, Some(percent)),
+        };
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.set_progress_bar(ProgressBarState {
+                status: Some(status),
+                progress,
+            });
+        }
+    }
+}
@@ -1,7 +1,7 @@
 
-document.addEventListener('DOMContentLoaded', () => {
+document.addEventListener('DOMContentLoaded', async () => {
     const { invoke } = window.__TAURI__.core;
-    const { open } = window.__TAURI__.dialog;
+    const { open, ask } = window.__TAURI__.dialog;
     const { listen } = window.__TAURI__.event;
 
     // UI Elements
@@ -13,13 +13,35 @@ document.addEventListener('DOMContentLoaded', () => {
     const btnStart = document.getElementById('btn-start');
     const btnCancel = document.getElementById('btn-cancel');
     const btnPause = document.getElementById('btn-pause');
+    const btnResume = document.getElementById('btn-resume');
+    const btnHoldQueue = document.getElementById('btn-hold-queue');
     const progressRing = document.getElementById('progress-ring');
     const progressPct = document.getElementById('progress-pct');
+    const progressBar = document.getElementById('progress-bar');
     const currentFileText = document.getElementById('current-file');
     const speedText = document.getElementById('speed');
     const fileCountText = document.getElementById('file-count');
+    const categoryList = document.getElementById('category-breakdown');
+    const optPatterns = document.getElementById('opt-patterns');
+    const optExcludeFiles = document.getElementById('opt-exclude-files');
+    const optExcludeDirs = document.getElementById('opt-exclude-dirs');
+    const btnOpenDest = document.getElementById('btn-open-dest');
+    const btnRevealFile = document.getElementById('btn-reveal-file');
+    const failuresBox = document.getElementById('failures');
+    const failureList = document.getElementById('failure-list');
+    const btnRetryFailed = document.getElementById('btn-retry-failed');
     const statusText = document.getElementById('status-text');
     const logContent = document.getElementById('log-content');
+    const logSpacer = document.getElementById('log-spacer');
+    const logRows = document.getElementById('log-rows');
+    const logAnnouncer = document.getElementById('log-announcer');
+    const logSearch = document.getElementById('log-search');
+    const logLevelSelect = document.getElementById('log-level');
+    const logFollow = document.getElementById('log-follow');
+    const copyLog = document.getElementById('copy-log');
+    const saveLog = document.getElementById('save-log');
+    const logLimitSelect = document.getElementById('log-limit');
+    const sizeUnitsSelect = document.getElementById('size-units');
     const clearLog = document.getElementById('clear-log');
     const toggleOptions = document.getElementById('toggle-options');
     const optionsPanel = document.getElementById('options-panel');
@@ -28,6 +50,67 @@ document.addEventListener('DOMContentLoaded', () => {
     const threadVal = document.getElementById('thread-val');
     const retrySlider = document.getElementById('retry-count');
     const retryVal = document.getElementById('retry-val');
+    const queueLimitSlider = document.getElementById('queue-limit');
+    const queueLimitVal = document.getElementById('queue-limit-val');
+    const prioritySelect = document.getElementById('job-priority');
+
+    // Localized strings for the OS locale, provided by the core.
+    // Variables appear as {$name} and are filled in by t().
+    let messages = {};
+    try {
+        const translations = await invoke('translations');
+        messages = translations.messages;
+        document.documentElement.lang = translations.locale;
+    } catch (e) {
+        // Keep the English text built into the page
+    }
+
+    // GUI settings are stored by the core in its data directory, which is
+    // next to the executable in portable mode. Settings of older versions
+    // are taken over from localStorage.
+    let settings = {};
+    try {
+        settings = await invoke('load_settings');
+    } catch (e) {
+        // Start with defaults
+    }
+    for (const key of ['lastSourceDir', 'lastDestDir', 'theme', 'accentColor']) {
+        const legacy = localStorage.getItem(key);
+        if (settings[key] === undefined && legacy !== null) {
+            settings[key] = legacy;
+        }
+    }
+
+    const getSetting = (key) => settings[key] ?? null;
+    const setSetting = (key, value) => {
+        if (value === null) {
+            delete settings[key];
+        } else {
+            settings[key] = value;
+        }
+        invoke('save_settings', { values: settings }).catch(() => { });
+    };
+
+    const t = (id, vars = {}) => {
+        let text = messages[id] ?? id;
+        for (const [name, value] of Object.entries(vars)) {
+            text = text.replaceAll(`{$${name}}`, value);
+        }
+        return text;
+    };
+
+    for (const el of document.querySelectorAll('[data-i18n]')) {
+        if (messages[el.dataset.i18n]) el.textContent = messages[el.dataset.i18n];
+    }
+    for (const el of document.querySelectorAll('[data-i18n-placeholder]')) {
+        if (messages[el.dataset.i18nPlaceholder]) el.placeholder = messages[el.dataset.i18nPlaceholder];
+    }
+    for (const el of document.querySelectorAll('[data-i18n-title]')) {
+        if (messages[el.dataset.i18nTitle]) el.title = messages[el.dataset.i18nTitle];
+    }
+    for (const el of document.querySelectorAll('[data-i18n-aria-label]')) {
+        if (messages[el.dataset.i18nAriaLabel]) el.setAttribute('aria-label', messages[el.dataset.i18nAriaLabel]);
+    }
 
     // Security: Disable common key combinations except essential ones
     document.addEventListener('keydown', (e) => {
@@ -73,13 +156,107 @@ document.addEventListener('DOMContentLoaded', () => {
     let isPaused = false;
     let statusTimer = null;
 
+    // The log is kept as a list of events and only the lines in view are
+    // rendered, so a job logging a line per file keeps the window responsive.
+    // Only the latest lines are kept; the core writes the full log to a file
+    // that Save copies.
+    const LOG_ROW_HEIGHT = 18; // px, as .log-row
+    const LOG_OVERSCAN = 20; // lines rendered beyond the view
+    const LOG_LEVELS = ['info', 'warning', 'error'];
+    const DEFAULT_LOG_LIMIT = 10000;
+    let logLimit = Number(getSetting('logLimit')) || DEFAULT_LOG_LIMIT;
+    let logEvents = [];
+    let logShown = []; // the events passing the filters
+    let logSelection = null; // { anchor, focus } as positions in logShown
+    let logSeq = 0;
+    let followLog = true;
+    let logFrame = 0;
+
+    // Messages carry their level as a prefix, as in the log file
+    const levelOf = (msg) => {
+        if (/^(ERROR|Error|Failed)\b/.test(msg)) return 'error';
+        if (/^(Warning|Note)\b/.test(msg)) return 'warning';
+        return 'info';
+    };
+
+    const logFilter = () => {
+        const min = LOG_LEVELS.indexOf(logLevelSelect.value);
+        const query = logSearch.value.trim().toLowerCase();
+        return (event) => LOG_LEVELS.indexOf(event.level) >= min
+            && (!query || event.message.toLowerCase().includes(query));
+    };
+    let logPasses = logFilter();
+
+    const logLine = (event) => `[${event.time}] ${event.message}`;
+
+    const selectionRange = () => logSelection
+        ? [Math.min(logSelection.anchor, logSelection.focus), Math.max(logSelection.anchor, logSelection.focus)]
+        : [-1, -1];
+
+    const renderLog = () => {
+        logFrame = 0;
+        logSpacer.style.height = `${logShown.length * LOG_ROW_HEIGHT}px`;
+        if (followLog) {
+            logContent.scrollTop = logContent.scrollHeight;
+        }
+        const top = logContent.scrollTop;
+        const first = Math.max(0, Math.floor(top / LOG_ROW_HEIGHT) - LOG_OVERSCAN);
+        const last = Math.min(logShown.length,
+            Math.ceil((top + logContent.clientHeight) / LOG_ROW_HEIGHT) + LOG_OVERSCAN);
+        const [low, high] = selectionRange();
+        const rows = [];
+        for (let i = first; i < last; i++) {
+            const event = logShown[i];
+            const row = document.createElement('div');
+            row.className = `log-row ${event.level}`;
+            row.classList.toggle('selected', i >= low && i <= high);
+            row.dataset.index = i;
+            row.textContent = logLine(event);
+            row.title = event.message;
+            rows.push(row);
+        }
+        logRows.style.top = `${first * LOG_ROW_HEIGHT}px`;
+        logRows.replaceChildren(...rows);
+    };
+
+    const scheduleLogRender = () => {
+        if (!logFrame) logFrame = requestAnimationFrame(renderLog);
+    };
+
+    // Drop the oldest lines beyond the limit, keeping the view and the
+    // selection on the same lines
+    const trimLog = () => {
+        const drop = logEvents.length - logLimit;
+        if (drop <= 0) return;
+        const firstKept = logEvents[drop].seq;
+        logEvents = logEvents.slice(drop);
+        let shownDrop = logShown.findIndex((event) => event.seq >= firstKept);
+        if (shownDrop < 0) shownDrop = logShown.length;
+        logShown = logShown.slice(shownDrop);
+        if (logSelection) {
+            const anchor = logSelection.anchor - shownDrop;
+            const focus = logSelection.focus - shownDrop;
+            logSelection = anchor < 0 && focus < 0 ? null
+                : { anchor: Math.max(anchor, 0), focus: Math.max(focus, 0) };
+        }
+        if (!followLog) {
+            logContent.scrollTop -= shownDrop * LOG_ROW_HEIGHT;
+        }
+    };
+
     // Helpers
     const addLog = (msg) => {
-        const div = document.createElement('div');
-        const now = new Date().toLocaleTimeString([], { hour12: false });
-        div.textContent = `[${now}] ${msg}`;
-        logContent.appendChild(div);
-        logContent.scrollTop = logContent.scrollHeight;
+        const time = new Date().toLocaleTimeString([], { hour12: false });
+        const level = levelOf(msg);
+        for (const message of String(msg).split('\n')) {
+            const event = { seq: logSeq++, time, level, message };
+            logEvents.push(event);
+            if (logPasses(event)) logShown.push(event);
+        }
+        // In batches, not on every line
+        if (logEvents.length > logLimit * 1.1) trimLog();
+        logAnnouncer.textContent = msg;
+        scheduleLogRender();
     };
 
     const setProgress = (pct) => {
@@ -91,9 +268,10 @@ document.addEventListener('DOMContentLoaded', () => {
         progressRing.style.strokeDasharray = `${circumference}`;
         progressRing.style.strokeDashoffset = `${offset}`;
         progressPct.textContent = `${Math.round(clampedPct)}%`;
+        progressBar.setAttribute('aria-valuenow', Math.round(clampedPct));
     };
 
-    const setStatus = (msg, color = 'var(--emerald)') => {
+    const setStatus = (msg, color = 'var(--accent)') => {
         statusText.textContent = msg;
         statusText.style.color = color;
         if (statusTimer) {
@@ -104,7 +282,7 @@ document.addEventListener('DOMContentLoaded', () => {
 
     // Initialize progress bar
     setProgress(0);
-    setStatus("ready");
+    setStatus(t('gui-status-ready'));
 
     // Hide object count initially
     fileCountText.style.visibility = 'hidden';
@@ -120,7 +298,7 @@ document.addEventListener('DOMContentLoaded', () => {
 
     // Event Listeners
     browseSource.onclick = async () => {
-        const defaultPath = localStorage.getItem('lastSourceDir');
+        const defaultPath = getSetting('lastSourceDir');
         const selected = await open({
             directory: true,
             multiple: false,
@@ -128,12 +306,12 @@ document.addEventListener('DOMContentLoaded', () => {
         });
         if (selected) {
             sourceInput.value = selected;
-            localStorage.setItem('lastSourceDir', selected);
+            setSetting('lastSourceDir', selected);
         }
     };
 
     browseFiles.onclick = async () => {
-        const defaultPath = localStorage.getItem('lastSourceDir');
+        const defaultPath = getSetting('lastSourceDir');
         const selected = await open({
             directory: false,
             multiple: true,
@@ -156,7 +334,7 @@ document.addEventListener('DOMContentLoaded', () => {
     };
 
     browseDest.onclick = async () => {
-        const defaultPath = localStorage.getItem('lastDestDir');
+        const defaultPath = getSetting('lastDestDir');
         const selected = await open({
             directory: true,
             multiple: false,
@@ -164,12 +342,13 @@ document.addEventListener('DOMContentLoaded', () => {
         });
         if (selected) {
             destInput.value = selected;
-            localStorage.setItem('lastDestDir', selected);
+            setSetting('lastDestDir', selected);
         }
     };
 
     toggleOptions.onclick = () => {
         const isShown = optionsPanel.classList.toggle('show');
+        toggleOptions.setAttribute('aria-expanded', isShown);
         const arrow = toggleOptions.querySelector('.arrow');
         if (arrow) {
             arrow.textContent = isShown ? '▲' : '▼';
@@ -190,74 +369,632 @@ document.addEventListener('DOMContentLoaded', () => {
         retryVal.textContent = retrySlider.value;
     };
 
+    // Queued jobs start by priority, up to the limit at once; a held
+    // queue starts none and lets the running ones finish
+    let queueHeld = false;
+    const showQueueHeld = () => {
+        btnHoldQueue.textContent = queueHeld ? t('gui-release-queue') : t('gui-hold-queue');
+    };
+    invoke('queue_settings').then((queue) => {
+        queueHeld = queue.paused;
+        queueLimitSlider.value = queue.max_running;
+        queueLimitVal.textContent = queue.max_running;
+        showQueueHeld();
+    }).catch(() => { });
+
+    queueLimitSlider.oninput = () => {
+        queueLimitVal.textContent = queueLimitSlider.value;
+    };
+    queueLimitSlider.onchange = () => {
+        invoke('set_queue_limit', { maxRunning: Number(queueLimitSlider.value) })
+            .catch((e) => addLog(t('gui-log-error', { error: e })));
+    };
+
+    btnHoldQueue.onclick = async () => {
+        try {
+            await invoke('set_queue_paused', { paused: !queueHeld });
+            queueHeld = !queueHeld;
+            showQueueHeld();
+            addLog(queueHeld ? t('gui-log-queue-held') : t('gui-log-queue-released'));
+        } catch (e) {
+            addLog(t('gui-log-error', { error: e }));
+        }
+    };
+
+    // Theme: "system" follows the OS dark/light and contrast settings.
+    // The custom accent does not apply to the high-contrast preset.
+    const darkQuery = window.matchMedia('(prefers-color-scheme: dark)');
+    const contrastQuery = window.matchMedia('(prefers-contrast: more), (forced-colors: active)');
+    const themeSelect = document.getElementById('theme-select');
+    const accentPicker = document.getElementById('accent-color');
+    const defaultAccent = accentPicker.value;
+
+    const applyTheme = () => {
+        const mode = getSetting('theme') || 'system';
+        let theme = mode;
+        if (mode === 'system') {
+            theme = contrastQuery.matches ? 'high-contrast' : darkQuery.matches ? 'dark' : 'light';
+        }
+
+        document.body.classList.remove('dark-theme', 'light-theme', 'high-contrast-theme');
+        document.body.classList.add(`${theme}-theme`);
+
+        const accent = getSetting('accentColor');
+        if (accent && theme !== 'high-contrast') {
+            document.body.style.setProperty('--accent', accent);
+        } else {
+            document.body.style.removeProperty('--accent');
+        }
+
+        themeSelect.value = mode;
+        accentPicker.value = accent || defaultAccent;
+        themeToggle.textContent = theme === 'light' ? '☀️' : '🌙';
+    };
+
+    applyTheme();
+    darkQuery.addEventListener('change', applyTheme);
+    contrastQuery.addEventListener('change', applyTheme);
+
     themeToggle.onclick = () => {
-        document.body.classList.toggle('dark-theme');
-        document.body.classList.toggle('light-theme');
-        themeToggle.textContent = document.body.classList.contains('dark-theme') ? '🌙' : '☀️';
+        const isLight = document.body.classList.contains('light-theme');
+        setSetting('theme', isLight ? 'dark' : 'light');
+        applyTheme();
+    };
+
+    themeSelect.onchange = () => {
+        setSetting('theme', themeSelect.value);
+        applyTheme();
+    };
+
+    accentPicker.oninput = () => {
+        setSetting('accentColor', accentPicker.value);
+        applyTheme();
+    };
+
+    document.getElementById('accent-reset').onclick = () => {
+        setSetting('accentColor', null);
+        applyTheme();
+    };
+
+    // Carry profiles, run history and these settings to another machine
+    const settingsFilter = [{ name: t('gui-settings-file'), extensions: ['json'] }];
+
+    document.getElementById('settings-export').onclick = async () => {
+        const path = await window.__TAURI__.dialog.save({
+            defaultPath: 'rbcp-settings.json',
+            filters: settingsFilter
+        });
+        if (!path) return;
+        try {
+            await invoke('export_settings', { path });
+            addLog(t('gui-log-settings-exported', { path }));
+        } catch (e) {
+            addLog(t('gui-log-error', { error: e }));
+        }
+    };
+
+    document.getElementById('settings-import').onclick = async () => {
+        const path = await open({ multiple: false, directory: false, filters: settingsFilter });
+        if (!path) return;
+        try {
+            const summary = await invoke('import_settings', { path });
+            settings = await invoke('load_settings');
+            applyTheme();
+            addLog(t('gui-log-settings-imported', {
+                added: summary.profiles_added,
+                replaced: summary.profiles_replaced,
+                history: summary.history_entries
+            }));
+        } catch (e) {
+            addLog(t('gui-log-error', { error: e }));
+        }
     };
 
+    invoke('portable_location').then((dir) => {
+        if (dir) addLog(t('gui-log-portable', { path: dir }));
+    }).catch(() => { });
+
     clearLog.onclick = () => {
-        logContent.innerHTML = '';
+        logEvents = [];
+        logShown = [];
+        logSelection = null;
+        scheduleLogRender();
+    };
+
+    const refilterLog = () => {
+        logPasses = logFilter();
+        logShown = logEvents.filter(logPasses);
+        logSelection = null;
+        scheduleLogRender();
+    };
+    logSearch.addEventListener('input', refilterLog);
+    logLevelSelect.addEventListener('change', refilterLog);
+
+    const setFollow = (follow) => {
+        followLog = follow;
+        logFollow.setAttribute('aria-pressed', String(follow));
+    };
+    logFollow.onclick = () => {
+        setFollow(!followLog);
+        scheduleLogRender();
+    };
+
+    // Scrolling up stops following the log, scrolling back to the end
+    // follows it again
+    logContent.addEventListener('scroll', () => {
+        const atEnd = logContent.scrollTop + logContent.clientHeight >= logContent.scrollHeight - LOG_ROW_HEIGHT;
+        if (atEnd !== followLog) setFollow(atEnd);
+        scheduleLogRender();
+    });
+    window.addEventListener('resize', scheduleLogRender);
+
+    // Click selects a line, Shift+click extends the selection to it
+    logRows.addEventListener('click', (e) => {
+        const row = e.target.closest('.log-row');
+        if (!row) return;
+        const index = Number(row.dataset.index);
+        logSelection = e.shiftKey && logSelection
+            ? { anchor: logSelection.anchor, focus: index }
+            : { anchor: index, focus: index };
+        scheduleLogRender();
+    });
+
+    // Copy the selected lines, or all lines shown when none are
+    const copyLogLines = () => {
+        const [low, high] = logSelection ? selectionRange() : [0, logShown.length - 1];
+        const text = logShown.slice(low, high + 1).map(logLine).join('\n');
+        navigator.clipboard.writeText(text).catch(() => { });
+    };
+    copyLog.onclick = copyLogLines;
+
+    saveLog.onclick = async () => {
+        const path = await window.__TAURI__.dialog.save({
+            defaultPath: 'rbcp.log',
+            filters: [{ name: t('gui-log-file'), extensions: ['log', 'txt'] }]
+        });
+        if (!path) return;
+        try {
+            await invoke('save_log', { path });
+            addLog(t('gui-log-saved', { path }));
+        } catch (e) {
+            addLog(t('gui-log-error', { error: e }));
+        }
+    };
+
+    logLimitSelect.value = String(logLimit);
+    invoke('set_log_capacity', { lines: logLimit }).catch(() => { });
+    logLimitSelect.onchange = () => {
+        logLimit = Number(logLimitSelect.value);
+        setSetting('logLimit', logLimit === DEFAULT_LOG_LIMIT ? null : String(logLimit));
+        invoke('set_log_capacity', { lines: logLimit }).catch(() => { });
+        trimLog();
+        scheduleLogRender();
+    };
+
+    // Sizes in the GUI and in the summaries of the jobs it starts
+    let sizeUnits = getSetting('sizeUnits') || 'binary';
+    sizeUnitsSelect.value = sizeUnits;
+    sizeUnitsSelect.onchange = () => {
+        sizeUnits = sizeUnitsSelect.value;
+        setSetting('sizeUnits', sizeUnits === 'binary' ? null : sizeUnits);
+    };
+
+    logContent.addEventListener('keydown', (e) => {
+        if (!(e.ctrlKey || e.metaKey)) return;
+        const key = e.key.toLowerCase();
+        if (key === 'c') {
+            e.preventDefault();
+            copyLogLines();
+        } else if (key === 'a' && logShown.length > 0) {
+            e.preventDefault();
+            logSelection = { anchor: 0, focus: logShown.length - 1 };
+            scheduleLogRender();
+        }
+    });
+
+    const CONFLICT_GROUPS = [
+        ['source_newer', 'gui-conflict-source-newer'],
+        ['destination_newer', 'gui-conflict-destination-newer'],
+        ['same', 'gui-conflict-same'],
+    ];
+
+    // As SizeFormat in the core
+    const formatSize = (bytes) => {
+        if (sizeUnits === 'bytes') return `${bytes} B`;
+        const [base, units] = sizeUnits === 'si'
+            ? [1000, ['B', 'kB', 'MB', 'GB', 'TB', 'PB']]
+            : [1024, ['B', 'KiB', 'MiB', 'GiB', 'TiB', 'PiB']];
+        let unit = 0;
+        while (bytes >= base && unit < units.length - 1) {
+            bytes /= base;
+            unit++;
+        }
+        return `${unit === 0 ? bytes : bytes.toFixed(1)} ${units[unit]}`;
+    };
+
+    const actionSelect = (label, mixed) => {
+        const select = document.createElement('select');
+        select.setAttribute('aria-label', label);
+        const choices = mixed ? ['mixed', 'overwrite', 'skip'] : ['overwrite', 'skip'];
+        for (const action of choices) {
+            const option = document.createElement('option');
+            option.value = action;
+            option.textContent = t(`gui-conflict-${action}`);
+            // Mixed describes the rows and cannot be picked
+            option.disabled = action === 'mixed';
+            select.append(option);
+        }
+        return select;
     };
 
-    const showOverwriteModal = () => {
+    // Files already in the destination, grouped by which copy is newer,
+    // each overwritten or skipped as chosen. Resolves to 'cancel',
+    // 'overwrite' for everything, or the action for each source file.
+    const showConflictModal = (list) => {
         const modal = document.getElementById('overwrite-modal');
+        const table = document.getElementById('conflict-table');
+        const previousFocus = document.activeElement;
+        const rows = [];
+
+        document.getElementById('overwrite-body').textContent =
+            t('gui-overwrite-body', { count: list.conflicts.length });
+        document.getElementById('conflict-more').hidden = !list.truncated;
+        table.querySelectorAll('tbody').forEach((body) => body.remove());
+        for (const [kind, key] of CONFLICT_GROUPS) {
+            const conflicts = list.conflicts.filter((c) => c.kind === kind);
+            if (conflicts.length === 0) continue;
+            const body = document.createElement('tbody');
+            const header = document.createElement('tr');
+            header.className = 'conflict-group';
+            const title = document.createElement('th');
+            title.colSpan = 3;
+            title.scope = 'rowgroup';
+            title.textContent = t(key, { count: conflicts.length });
+            const groupCell = document.createElement('th');
+            const groupSelect = actionSelect(t('gui-conflict-group-action', { group: title.textContent }), true);
+            groupCell.append(groupSelect);
+            header.append(title, groupCell);
+            body.append(header);
+
+            const selects = [];
+            const showGroup = () => {
+                const actions = new Set(selects.map((select) => select.value));
+                groupSelect.value = actions.size === 1 ? [...actions][0] : 'mixed';
+            };
+            for (const conflict of conflicts) {
+                const row = document.createElement('tr');
+                const path = document.createElement('td');
+                path.className = 'conflict-path';
+                path.textContent = conflict.path;
+                path.title = conflict.source;
+                const source = document.createElement('td');
+                source.textContent = `${new Date(conflict.source_modified).toLocaleString()} · ${formatSize(conflict.source_size)}`;
+                const destination = document.createElement('td');
+                destination.textContent = `${new Date(conflict.destination_modified).toLocaleString()} · ${formatSize(conflict.destination_size)}`;
+                const actionCell = document.createElement('td');
+                const select = actionSelect(t('gui-conflict-row-action', { path: conflict.path }), false);
+                select.value = conflict.default_action;
+                select.onchange = showGroup;
+                actionCell.append(select);
+                row.append(path, source, destination, actionCell);
+                body.append(row);
+                selects.push(select);
+                rows.push([conflict.source, select]);
+            }
+            groupSelect.onchange = () => {
+                for (const select of selects) select.value = groupSelect.value;
+            };
+            showGroup();
+            table.append(body);
+        }
+
         modal.classList.add('show');
+        // Start on the choice that copies nothing unasked
+        document.getElementById('modal-cancel').focus();
+
         return new Promise((resolve) => {
-            document.getElementById('modal-yes-all').onclick = () => {
-                modal.classList.remove('show');
-                resolve('overwrite');
-            };
-            document.getElementById('modal-no-all').onclick = () => {
+            const close = (choice) => {
                 modal.classList.remove('show');
-                resolve('skip');
+                modal.onkeydown = null;
+                if (previousFocus) previousFocus.focus();
+                resolve(choice);
             };
-            document.getElementById('modal-cancel').onclick = () => {
-                modal.classList.remove('show');
-                resolve('cancel');
+            const chosen = (action) => Object.fromEntries(
+                rows.map(([source, select]) => [source, action || select.value]));
+            document.getElementById('modal-yes-all').onclick = () => close('overwrite');
+            document.getElementById('modal-no-all').onclick = () => close(chosen('skip'));
+            document.getElementById('modal-copy').onclick = () => close(chosen(null));
+            document.getElementById('modal-cancel').onclick = () => close('cancel');
+
+            // Escape cancels, Tab stays within the dialog
+            modal.onkeydown = (e) => {
+                if (e.key === 'Escape') {
+                    e.preventDefault();
+                    close('cancel');
+                } else if (e.key === 'Tab') {
+                    const focusable = [...modal.querySelectorAll('button, select')];
+                    const index = focusable.indexOf(document.activeElement);
+                    const last = focusable.length - 1;
+                    if (e.shiftKey && index <= 0) {
+                        e.preventDefault();
+                        focusable[last].focus();
+                    } else if (!e.shiftKey && index === last) {
+                        e.preventDefault();
+                        focusable[0].focus();
+                    }
+                }
             };
         });
     };
 
+    // Offered when files were denied access and the GUI is not elevated
+    const showElevationModal = () => {
+        const modal = document.getElementById('elevation-modal');
+        const buttons = [...modal.querySelectorAll('button')];
+        const previousFocus = document.activeElement;
+        modal.classList.add('show');
+        document.getElementById('elevation-later').focus();
+
+        const close = () => {
+            modal.classList.remove('show');
+            modal.onkeydown = null;
+            if (previousFocus) previousFocus.focus();
+        };
+        document.getElementById('elevation-later').onclick = close;
+        document.getElementById('elevation-relaunch').onclick = async () => {
+            close();
+            try {
+                await invoke('relaunch_elevated');
+            } catch (e) {
+                addLog(t('gui-log-relaunch-failed', { error: e }));
+            }
+        };
+
+        // Escape closes, Tab stays within the dialog
+        modal.onkeydown = (e) => {
+            if (e.key === 'Escape') {
+                e.preventDefault();
+                close();
+            } else if (e.key === 'Tab') {
+                e.preventDefault();
+                const step = e.shiftKey ? -1 : 1;
+                const index = buttons.indexOf(document.activeElement);
+                buttons[(index + step + buttons.length) % buttons.length].focus();
+            }
+        };
+    };
+
+    // Keyboard operation: Enter starts a copy (or queues one while running)
+    // and Space pauses, unless a control that uses the key has focus
+    document.addEventListener('keydown', (e) => {
+        if (document.querySelector('.modal-overlay.show')) {
+            return;
+        }
+        const control = e.target.closest('button, input, select, textarea, [role="log"]');
+        const inTextField = e.target.matches('input[type="text"]');
+
+        if (e.key === 'Enter' && (!control || inTextField)) {
+            e.preventDefault();
+            if (!btnStart.disabled) btnStart.click();
+        } else if (e.key === ' ' && !control) {
+            e.preventDefault();
+            if (!btnPause.disabled) btnPause.click();
+        }
+    });
+
+    // Setup wizard: pick a scenario and answer a few questions; the core
+    // turns the answers into options. Choices that delete data are
+    // explained up front and must be confirmed before they are applied.
+    const wizardModal = document.getElementById('wizard-modal');
+    const wizardSteps = [...wizardModal.querySelectorAll('.wizard-step')];
+    const wizardSource = document.getElementById('wizard-source');
+    const wizardDest = document.getElementById('wizard-dest');
+    const wizardPurge = document.getElementById('wizard-purge');
+    const wizardConfirm = document.getElementById('wizard-confirm');
+    const wizardBack = document.getElementById('wizard-back');
+    const wizardNext = document.getElementById('wizard-next');
+    let wizardScenario = null;
+    let wizardPlan = null;
+    let wizardStep = 0;
+
+    // Options the wizard chose that the form has no control for
+    let scenarioExtras = {};
+    sourceInput.addEventListener('input', () => { scenarioExtras = {}; });
+    destInput.addEventListener('input', () => { scenarioExtras = {}; });
+
+    const wizardAnswers = () => ({
+        source: wizardSource.value.trim(),
+        destination: wizardDest.value.trim(),
+        include_empty: document.getElementById('wizard-empty').checked,
+        purge: wizardScenario === 'mirror_to_nas' && wizardPurge.checked
+    });
+
+    const updateWizardNext = () => {
+        if (wizardStep === 1) {
+            const answers = wizardAnswers();
+            wizardNext.disabled = !answers.source || !answers.destination;
+        } else if (wizardStep === 2) {
+            wizardNext.disabled = wizardPlan.warnings.length > 0 && !wizardConfirm.checked;
+        }
+    };
+
+    const showWizardStep = async (step) => {
+        if (step === 2) {
+            try {
+                wizardPlan = await invoke('plan_scenario', {
+                    scenario: wizardScenario,
+                    answers: wizardAnswers()
+                });
+            } catch (e) {
+                addLog(t('gui-log-error', { error: e }));
+                return;
+            }
+            document.getElementById('wizard-flags').textContent = wizardPlan.flags;
+            document.getElementById('wizard-warnings').replaceChildren(...wizardPlan.warnings.map((warning) => {
+                const item = document.createElement('li');
+                item.textContent = warning;
+                return item;
+            }));
+            document.getElementById('wizard-confirm-row').hidden = wizardPlan.warnings.length === 0;
+            wizardConfirm.checked = false;
+        }
+
+        wizardStep = step;
+        wizardSteps.forEach((el, index) => { el.hidden = index !== step; });
+        wizardBack.hidden = step === 0;
+        wizardNext.hidden = step === 0;
+        wizardNext.textContent = step === 2 ? t('gui-wizard-apply') : t('gui-wizard-next');
+        document.getElementById('wizard-purge-row').hidden = wizardScenario !== 'mirror_to_nas';
+        document.getElementById('wizard-purge-note').hidden = !wizardPurge.checked;
+        updateWizardNext();
+
+        const first = wizardSteps[step].querySelector('button, input');
+        if (first) first.focus();
+    };
+
+    const openWizard = () => {
+        wizardSource.value = sourceInput.value;
+        wizardDest.value = destInput.value;
+        wizardModal.classList.add('show');
+        showWizardStep(0);
+    };
+
+    const closeWizard = () => {
+        wizardModal.classList.remove('show');
+        setSetting('wizardDone', 'true');
+        document.getElementById('wizard-open').focus();
+    };
+
+    const applyWizardPlan = () => {
+        const options = wizardPlan.options;
+        sourceInput.value = options.sources.join(';');
+        destInput.value = options.destination;
+        showPatterns(optPatterns, options.patterns);
+        optExcludeFiles.value = options.exclude_files.join('; ');
+        optExcludeDirs.value = options.exclude_dirs.join('; ');
+        document.getElementById('opt-recursive').checked = options.recursive;
+        document.getElementById('opt-mirror').checked = options.mirror;
+        document.getElementById('opt-move').checked = options.move_files;
+        document.getElementById('opt-shred').checked = options.shred_files;
+        document.getElementById('opt-empty').checked = options.empty_files;
+        document.getElementById('opt-childonly').checked = options.child_only;
+        document.getElementById('opt-eject').checked = options.eject;
+        document.getElementById('opt-deep-pause').checked = options.deep_pause;
+        threadSlider.value = options.threads;
+        threadVal.textContent = options.threads;
+        retrySlider.value = options.retries;
+        retryVal.textContent = options.retries;
+        scenarioExtras = {
+            include_empty: options.include_empty,
+            restartable: options.restartable,
+            wait_time: options.wait_time
+        };
+        addLog(t('gui-log-wizard-applied', { flags: wizardPlan.flags }));
+    };
+
+    for (const choice of wizardModal.querySelectorAll('.wizard-choice')) {
+        choice.onclick = () => {
+            wizardScenario = choice.dataset.scenario;
+            wizardPurge.checked = false;
+            showWizardStep(1);
+        };
+    }
+
+    wizardSource.addEventListener('input', updateWizardNext);
+    wizardDest.addEventListener('input', updateWizardNext);
+    wizardConfirm.addEventListener('change', updateWizardNext);
+    wizardPurge.addEventListener('change', () => {
+        document.getElementById('wizard-purge-note').hidden = !wizardPurge.checked;
+    });
+
+    document.getElementById('wizard-browse-source').onclick = async () => {
+        const selected = await open({ directory: true, multiple: false });
+        if (selected) {
+            wizardSource.value = selected;
+            updateWizardNext();
+        }
+    };
+
+    document.getElementById('wizard-browse-dest').onclick = async () => {
+        const selected = await open({ directory: true, multiple: false });
+        if (selected) {
+            wizardDest.value = selected;
+            updateWizardNext();
+        }
+    };
+
+    wizardBack.onclick = () => showWizardStep(wizardStep - 1);
+    wizardNext.onclick = () => {
+        if (wizardStep < 2) {
+            showWizardStep(wizardStep + 1);
+        } else {
+            applyWizardPlan();
+            closeWizard();
+        }
+    };
+    document.getElementById('wizard-skip').onclick = closeWizard;
+    document.getElementById('wizard-open').onclick = openWizard;
+
+    // Escape closes, Tab stays within the wizard
+    wizardModal.addEventListener('keydown', (e) => {
+        if (e.key === 'Escape') {
+            e.preventDefault();
+            closeWizard();
+        } else if (e.key === 'Tab') {
+            const focusable = [...wizardModal.querySelectorAll('button, input')]
+                .filter((el) => !el.disabled && el.offsetParent !== null);
+            const index = focusable.indexOf(document.activeElement);
+            const last = focusable.length - 1;
+            if (e.shiftKey && index <= 0) {
+                e.preventDefault();
+                focusable[last].focus();
+            } else if (!e.shiftKey && index === last) {
+                e.preventDefault();
+                focusable[0].focus();
+            }
+        }
+    });
+
+    // Opening the destination is a preference of the GUI, not of the job
+    const openWhenDone = document.getElementById('opt-open-when-done');
+    openWhenDone.checked = getSetting('openWhenDone') === 'true';
+    openWhenDone.onchange = () => {
+        setSetting('openWhenDone', openWhenDone.checked ? 'true' : null);
+    };
+
+    // Offer the wizard on first run
+    if (!getSetting('wizardDone')) {
+        openWizard();
+    }
+
     // Start Copy
     btnStart.onclick = async () => {
         const sourceVal = sourceInput.value;
         const dest = destInput.value;
 
         if (!sourceVal || !dest) {
-            addLog("ERROR: Source and Destination must be specified.");
+            addLog(t('gui-log-missing-paths'));
             return;
         }
 
-        // Handle multiple sources separated by semicolon
-        const sources = sourceVal.split(';').map(s => s.trim()).filter(s => s.length > 0);
-
-        // Check for conflicts and ask user
-        let overwriteMode = 'ask'; // 'ask', 'overwrite', 'skip'
-        try {
-            const hasConflicts = await invoke('check_conflicts', {
-                sources: sources,
-                destination: dest
-            });
-
-            if (hasConflicts) {
-                const choice = await showOverwriteModal();
-
-                if (choice === 'cancel') {
-                    addLog("Operation cancelled by user.");
-                    return;
-                }
-                overwriteMode = choice;
+        // Handle multiple sources separated by semicolon, each optionally
+        // copied into a subdirectory of the destination with "source>subdir"
+        const sourceSubdirs = {};
+        const sources = sourceVal.split(';').map(s => s.trim()).filter(s => s.length > 0).map(s => {
+            const [path, subdir] = s.split('>').map(p => p.trim());
+            if (subdir) {
+                sourceSubdirs[path] = subdir;
             }
-        } catch (e) {
-            // If check fails, proceed anyway
-            addLog(`Note: Could not check for conflicts: ${e}`);
-        }
+            return path;
+        });
 
         const options = {
             sources: sources,
+            source_subdirs: sourceSubdirs,
             destination: dest,
-            patterns: ["*.*"],
+            patterns: splitList(optPatterns.value).length > 0 ? splitList(optPatterns.value) : ["*.*"],
+            exclude_files: splitList(optExcludeFiles.value),
+            exclude_dirs: splitList(optExcludeDirs.value),
             recursive: document.getElementById('opt-recursive').checked,
             include_empty: document.getElementById('opt-recursive').checked,
             restartable: false,
@@ -278,24 +1015,741 @@ document.addEventListener('DOMContentLoaded', () => {
             empty_files: document.getElementById('opt-empty').checked,
             child_only: document.getElementById('opt-childonly').checked,
             shred_files: document.getElementById('opt-shred').checked,
-            force_overwrite: overwriteMode === 'overwrite',
-            preserve_root: true
+            eject: document.getElementById('opt-eject').checked,
+            deep_pause: document.getElementById('opt-deep-pause').checked,
+            force_overwrite: false,
+            preserve_root: true,
+            record_history: true,
+            size_format: sizeUnits,
+            ...scenarioExtras
         };
 
+        // A job far bigger than meant, as from picking a drive root, only
+        // starts once confirmed
+        try {
+            const warnings = await invoke('check_limits', { options });
+            if (warnings.length > 0) {
+                const proceed = await ask(`${warnings.join('\n')}\n\n${t('gui-limits-question')}`, {
+                    title: t('gui-limits-title'),
+                    kind: 'warning'
+                });
+                if (!proceed) {
+                    addLog(t('gui-log-cancelled-by-user'));
+                    return;
+                }
+            }
+        } catch (e) {
+            addLog(t('gui-log-limit-check-failed', { error: e }));
+        }
+
+        // Ask what happens to the files already in the destination
+        try {
+            const list = await invoke('list_conflicts', { options });
+            if (list.conflicts.length > 0) {
+                const choice = await showConflictModal(list);
+                if (choice === 'cancel') {
+                    addLog(t('gui-log-cancelled-by-user'));
+                    return;
+                }
+                if (choice === 'overwrite') {
+                    options.force_overwrite = true;
+                } else {
+                    options.conflict_actions = choice;
+                }
+            }
+        } catch (e) {
+            // If check fails, proceed anyway
+            addLog(t('gui-log-conflict-check-failed', { error: e }));
+        }
+
         try {
-            isRunning = true;
-            btnStart.disabled = true;
-            btnCancel.disabled = false;
-            btnPause.disabled = false;
-            setStatus("waiting command...");
-            btnStart.textContent = "Running...";
-            fileCountText.style.visibility = 'visible'; // Show object count during copy
-
-            await invoke('start_copy', { options });
-            addLog("Initiating copy operation...");
-            setStatus("scanning...");
+            const priority = prioritySelect.value;
+            if (isRunning) {
+                await invoke('start_copy', { options, priority });
+                addLog(t('gui-log-queued'));
+                return;
+            }
+            setRunning();
+            await invoke('start_copy', { options, priority });
+            addLog(t('gui-log-initiating'));
+            setStatus(t('gui-status-scanning'));
         } catch (e) {
-            addLog(`ERROR: ${e}`);
+            addLog(t('gui-log-error', { error: e }));
+            isRunning = false;
+            btnStart.disabled = false;
+        }
+    };
+
+    const setRunning = () => {
+        isRunning = true;
+        btnStart.disabled = false;
+        btnCancel.disabled = false;
+        btnPause.disabled = false;
+        btnResume.hidden = true;
+        setStatus(t('gui-status-waiting'));
+        btnStart.textContent = t('gui-add-to-queue');
+        fileCountText.style.visibility = 'visible'; // Show object count during copy
+        categoryList.hidden = true;
+        failureList.replaceChildren();
+        failureReports.clear();
+        failuresBox.hidden = true;
+    };
+
+    // Where the copied bytes went, by file type
+    const showCategories = (categories) => {
+        categoryList.replaceChildren();
+        const totalBytes = categories.reduce((sum, c) => sum + c.bytes, 0);
+        for (const c of categories) {
+            const item = document.createElement('li');
+            const label = document.createElement('span');
+            label.textContent = t(`gui-category-${c.category}`);
+            const value = document.createElement('span');
+            value.textContent = t('gui-category-row', {
+                files: c.files,
+                size: formatSize(c.bytes),
+            });
+            const track = document.createElement('div');
+            track.className = 'category-track';
+            const bar = document.createElement('div');
+            bar.className = 'category-bar';
+            bar.style.width = `${totalBytes === 0 ? 0 : (c.bytes / totalBytes) * 100}%`;
+            track.append(bar);
+            item.append(label, value, track);
+            categoryList.append(item);
+        }
+        categoryList.hidden = categories.length === 0;
+    };
+
+    // Show files and folders in the file manager
+    const revealPath = (command, path) => {
+        invoke(command, { path }).catch((e) => addLog(t('gui-log-error', { error: e })));
+    };
+
+    // Source file being copied, to reveal
+    let currentFile = '';
+
+    btnOpenDest.onclick = () => {
+        const dest = destInput.value.trim();
+        if (dest) {
+            revealPath('open_folder', dest);
+        }
+    };
+
+    btnRevealFile.onclick = () => {
+        if (currentFile) {
+            revealPath('reveal_file', currentFile);
+        }
+    };
+
+    // Failure reports of the jobs listed, whose files are copied again by
+    // Retry Failed Files
+    const failureReports = new Set();
+
+    // Files the jobs failed to copy, each revealed on click
+    const addFailures = (failures, report) => {
+        if (report) {
+            failureReports.add(report);
+        }
+        for (const failure of failures) {
+            const item = document.createElement('li');
+            const button = document.createElement('button');
+            button.textContent = failure.path;
+            button.title = `${failure.path}: ${failure.reason}`;
+            button.onclick = () => revealPath('reveal_file', failure.path);
+            item.append(button);
+            failureList.append(item);
+        }
+        failuresBox.hidden = failureList.children.length === 0;
+        btnRetryFailed.hidden = failureReports.size === 0;
+    };
+
+    btnRetryFailed.onclick = async () => {
+        const reports = [...failureReports];
+        failureReports.clear();
+        btnRetryFailed.hidden = true;
+        if (!isRunning) {
+            setRunning();
+        }
+        for (const report of reports) {
+            try {
+                const count = await invoke('retry_failed', { report });
+                addLog(t('gui-log-retrying', { count }));
+            } catch (e) {
+                addLog(t('gui-log-error', { error: e }));
+            }
+        }
+    };
+
+    // Queued jobs, whose filters, threads and bandwidth cap can be changed
+    // until they start
+    const queueSection = document.getElementById('queue-section');
+    const queueList = document.getElementById('queue-list');
+    const jobEditModal = document.getElementById('job-edit-modal');
+    const editPatterns = document.getElementById('edit-patterns');
+    const editExcludeFiles = document.getElementById('edit-exclude-files');
+    const editExcludeDirs = document.getElementById('edit-exclude-dirs');
+    const editThreads = document.getElementById('edit-threads');
+    const editThrottle = document.getElementById('edit-throttle');
+    const editProblems = document.getElementById('edit-problems');
+    const editPeek = document.getElementById('edit-peek');
+    const editSamples = document.getElementById('edit-samples');
+    const editSave = document.getElementById('edit-save');
+    const JOB_STATES = {
+        Pending: 'gui-job-pending',
+        Running: 'gui-job-running',
+        Preempting: 'gui-job-running',
+        Interrupted: 'gui-job-interrupted',
+    };
+    const EDIT_SAMPLES = 10;
+    let editingJob = null;
+    let previewTimer = null;
+    let previewSeq = 0;
+
+    const splitList = (text) => text.split(';').map((s) => s.trim()).filter((s) => s.length > 0);
+
+    const refreshQueue = async () => {
+        let jobs = [];
+        try {
+            jobs = await invoke('queued_jobs');
+        } catch (e) {
+            // Leave the list empty
+        }
+        queueList.replaceChildren();
+        for (const job of jobs) {
+            const item = document.createElement('li');
+            const label = document.createElement('span');
+            label.className = 'job-label';
+            label.textContent = `${job.options.sources.join('; ')} -> ${job.options.destination}`;
+            label.title = label.textContent;
+            const state = document.createElement('span');
+            state.className = 'job-state';
+            state.textContent = t(JOB_STATES[job.status]);
+            item.append(label, state);
+            // A running job's speed can be changed, e.g. for a video call
+            if (job.status === 'Running') {
+                const button = document.createElement('button');
+                button.className = 'log-tool';
+                button.textContent = t('gui-speed');
+                button.onclick = () => openJobSpeed(job);
+                item.append(button);
+            }
+            // A job resuming from its journal keeps the filters it started with
+            if (job.status === 'Pending' && !job.options.resume) {
+                const button = document.createElement('button');
+                button.className = 'log-tool';
+                button.textContent = t('gui-edit');
+                button.onclick = () => openJobEdit(job);
+                item.append(button);
+            }
+            queueList.append(item);
+        }
+        queueSection.hidden = jobs.length === 0;
+    };
+
+    const currentEdit = () => ({
+        patterns: splitList(editPatterns.value),
+        exclude_files: splitList(editExcludeFiles.value),
+        exclude_dirs: splitList(editExcludeDirs.value),
+        threads: parseInt(editThreads.value) || 0,
+        throttle_mbps: parseFloat(editThrottle.value) || 0,
+    });
+
+    // Check the edit and show which files it picks from a quick look at the
+    // sources, shortly after typing stops
+    const previewEdit = () => {
+        editSave.disabled = true;
+        clearTimeout(previewTimer);
+        previewTimer = setTimeout(async () => {
+            const seq = ++previewSeq;
+            let preview;
+            try {
+                preview = await invoke('preview_job_edit', { id: editingJob.id, edit: currentEdit() });
+            } catch (e) {
+                preview = { problems: [String(e)], peek: null };
+            }
+            // A newer preview is on its way
+            if (seq !== previewSeq) return;
+
+            editProblems.replaceChildren(...preview.problems.map((problem) => {
+                const item = document.createElement('li');
+                item.textContent = problem;
+                return item;
+            }));
+            editSamples.replaceChildren();
+            editSave.disabled = preview.problems.length > 0;
+            if (preview.problems.length > 0) {
+                editPeek.textContent = '';
+                return;
+            }
+
+            const peek = preview.peek;
+            editPeek.textContent = t(peek.truncated ? 'gui-edit-peek' : 'gui-edit-peek-all', {
+                matched: peek.matched.length,
+                scanned: peek.scanned,
+            });
+            for (const path of peek.matched.slice(0, EDIT_SAMPLES)) {
+                const item = document.createElement('li');
+                item.textContent = path;
+                editSamples.append(item);
+            }
+            for (const left of peek.left_out.slice(0, EDIT_SAMPLES)) {
+                const item = document.createElement('li');
+                item.className = 'left-out';
+                item.textContent = t('gui-edit-left-out', { path: left.path, reason: left.reason });
+                editSamples.append(item);
+            }
+        }, 300);
+    };
+
+    const openJobEdit = (job) => {
+        editingJob = job;
+        editPatterns.value = job.options.patterns.join('; ');
+        editExcludeFiles.value = job.options.exclude_files.join('; ');
+        editExcludeDirs.value = job.options.exclude_dirs.join('; ');
+        editThreads.value = job.options.threads;
+        editThrottle.value = job.options.throttle_mbps;
+        editProblems.replaceChildren();
+        editPeek.textContent = '';
+        editSamples.replaceChildren();
+        jobEditModal.classList.add('show');
+        editPatterns.focus();
+        previewEdit();
+    };
+
+    const closeJobEdit = () => {
+        clearTimeout(previewTimer);
+        previewSeq++;
+        editingJob = null;
+        jobEditModal.classList.remove('show');
+        queueList.querySelector('button')?.focus();
+    };
+
+    for (const field of [editPatterns, editExcludeFiles, editExcludeDirs, editThreads, editThrottle]) {
+        field.addEventListener('input', previewEdit);
+    }
+
+    document.getElementById('edit-cancel').onclick = closeJobEdit;
+    editSave.onclick = async () => {
+        const job = editingJob;
+        try {
+            await invoke('edit_job', { id: job.id, edit: currentEdit() });
+            addLog(t('gui-log-job-edited', {
+                sources: job.options.sources.join(', '),
+                destination: job.options.destination
+            }));
+            closeJobEdit();
+            refreshQueue();
+        } catch (e) {
+            editProblems.replaceChildren();
+            const item = document.createElement('li');
+            item.textContent = String(e);
+            editProblems.append(item);
+        }
+    };
+
+    jobEditModal.addEventListener('keydown', (e) => {
+        if (e.key === 'Escape') {
+            e.preventDefault();
+            closeJobEdit();
+        } else if (e.key === 'Tab') {
+            const focusable = [...jobEditModal.querySelectorAll('button, input')]
+                .filter((el) => !el.disabled);
+            const index = focusable.indexOf(document.activeElement);
+            const last = focusable.length - 1;
+            if (e.shiftKey && index <= 0) {
+                e.preventDefault();
+                focusable[last].focus();
+            } else if (!e.shiftKey && index === last) {
+                e.preventDefault();
+                focusable[0].focus();
+            }
+        }
+    });
+
+    // Bandwidth cap and files at once of a running job
+    const jobSpeedModal = document.getElementById('job-speed-modal');
+    const speedThreads = document.getElementById('speed-threads');
+    const speedThrottle = document.getElementById('speed-throttle');
+    const speedProblems = document.getElementById('speed-problems');
+    let speedJob = null;
+    let speedShown = null;
+
+    const showSpeedProblem = (problem) => {
+        const item = document.createElement('li');
+        item.textContent = problem;
+        speedProblems.replaceChildren(item);
+    };
+
+    const openJobSpeed = async (job) => {
+        let speed;
+        try {
+            speed = await invoke('job_speed', { id: job.id });
+        } catch (e) {
+            // The job ended meanwhile
+            refreshQueue();
+            return;
+        }
+        speedJob = job;
+        speedShown = speed;
+        document.getElementById('speed-job').textContent =
+            `${job.options.sources.join('; ')} -> ${job.options.destination}`;
+        speedThreads.max = speed.max_threads;
+        speedThreads.value = speed.threads;
+        speedThrottle.value = speed.throttle_mbps;
+        document.getElementById('speed-auto').hidden = !speed.auto_threads;
+        speedProblems.replaceChildren();
+        jobSpeedModal.classList.add('show');
+        speedThrottle.focus();
+    };
+
+    const closeJobSpeed = () => {
+        speedJob = null;
+        jobSpeedModal.classList.remove('show');
+        queueList.querySelector('button')?.focus();
+    };
+
+    document.getElementById('speed-cancel').onclick = closeJobSpeed;
+    document.getElementById('speed-apply').onclick = async () => {
+        const job = speedJob;
+        // Empty fields are sent as values the engine refuses, with its reason
+        const threads = parseInt(speedThreads.value) || 0;
+        const throttle = speedThrottle.value === '' ? -1 : parseFloat(speedThrottle.value);
+        try {
+            // What was left alone stays as the job has it, e.g. auto threads
+            const speed = await invoke('set_job_speed', {
+                id: job.id,
+                threads: threads !== speedShown.threads ? threads : null,
+                throttleMbps: throttle !== speedShown.throttle_mbps ? throttle : null,
+            });
+            addLog(t('gui-log-speed-changed', {
+                sources: job.options.sources.join(', '),
+                destination: job.options.destination,
+                threads: speed.threads,
+                throttle: speed.throttle_mbps > 0
+                    ? t('gui-speed-capped', { mbps: speed.throttle_mbps })
+                    : t('gui-speed-unlimited'),
+            }));
+            closeJobSpeed();
+        } catch (e) {
+            showSpeedProblem(String(e));
+        }
+    };
+
+    jobSpeedModal.addEventListener('keydown', (e) => {
+        if (e.key === 'Escape') {
+            e.preventDefault();
+            closeJobSpeed();
+        } else if (e.key === 'Enter' && e.target.matches('input')) {
+            e.preventDefault();
+            document.getElementById('speed-apply').click();
+        } else if (e.key === 'Tab') {
+            const focusable = [...jobSpeedModal.querySelectorAll('button, input')];
+            const index = focusable.indexOf(document.activeElement);
+            const last = focusable.length - 1;
+            if (e.shiftKey && index <= 0) {
+                e.preventDefault();
+                focusable[last].focus();
+            } else if (!e.shiftKey && index === last) {
+                e.preventDefault();
+                focusable[0].focus();
+            }
+        }
+    });
+
+    refreshQueue();
+
+    // Source tree: folders unticked in it become /XD rules by full path,
+    // next to the patterns typed in the exclude field
+    const folderTreeModal = document.getElementById('folder-tree-modal');
+    const folderTree = document.getElementById('folder-tree');
+    let unticked = new Set();
+    let treeRoots = [];
+    let treeNodes = 0;
+
+    const isAbsolute = (path) => /^([A-Za-z]:)?[\\/]/.test(path);
+    const isBelow = (path, dir) => path.length > dir.length && path.startsWith(dir)
+        && (/[\\/]$/.test(dir) || '\\/'.includes(path[dir.length]));
+    const belowRoots = (path) => treeRoots.some((root) => isBelow(path, root));
+
+    // Tick state of every folder shown: unticked below an unticked folder,
+    // and partly ticked with unticked folders below
+    const refreshTreeChecks = () => {
+        for (const box of folderTree.querySelectorAll('input[type="checkbox"]')) {
+            const path = box.dataset.path;
+            const parentOut = [...unticked].some((dir) => isBelow(path, dir));
+            box.disabled = parentOut;
+            box.checked = !parentOut && !unticked.has(path);
+            box.indeterminate = box.checked && [...unticked].some((dir) => isBelow(dir, path));
+        }
+    };
+
+    const loadFolders = async (list, dir) => {
+        const loading = document.createElement('li');
+        loading.className = 'tree-note';
+        loading.textContent = t('gui-folder-tree-loading');
+        list.replaceChildren(loading);
+        let folders;
+        try {
+            folders = await invoke('source_folders', { dir });
+        } catch (e) {
+            loading.textContent = String(e);
+            return;
+        }
+        list.replaceChildren(...folders.map(folderNode));
+        refreshTreeChecks();
+    };
+
+    const folderNode = (folder) => {
+        const item = document.createElement('li');
+        const row = document.createElement('div');
+        row.className = 'tree-row';
+        const toggle = document.createElement('button');
+        toggle.className = 'tree-toggle';
+        toggle.textContent = '▸';
+        toggle.disabled = !folder.has_subfolders;
+        toggle.setAttribute('aria-expanded', 'false');
+        toggle.setAttribute('aria-label', t('gui-expand-folder', { name: folder.name }));
+        const box = document.createElement('input');
+        box.type = 'checkbox';
+        box.id = `tree-${++treeNodes}`;
+        box.dataset.path = folder.path;
+        const label = document.createElement('label');
+        label.htmlFor = box.id;
+        label.textContent = folder.name;
+        row.append(toggle, box, label);
+        item.append(row);
+
+        let children = null;
+        toggle.onclick = () => {
+            const expanded = toggle.getAttribute('aria-expanded') === 'true';
+            toggle.setAttribute('aria-expanded', String(!expanded));
+            toggle.textContent = expanded ? '▸' : '▾';
+            if (!children) {
+                children = document.createElement('ul');
+                item.append(children);
+                loadFolders(children, folder.path);
+            }
+            children.hidden = expanded;
+        };
+        box.onchange = () => {
+            if (box.checked) {
+                unticked.delete(folder.path);
+            } else {
+                // The folder's own rule covers whatever was unticked in it
+                for (const dir of unticked) {
+                    if (isBelow(dir, folder.path)) unticked.delete(dir);
+                }
+                unticked.add(folder.path);
+            }
+            refreshTreeChecks();
+        };
+        return item;
+    };
+
+    const closeFolderTree = () => {
+        folderTreeModal.classList.remove('show');
+        document.getElementById('open-folder-tree').focus();
+    };
+
+    document.getElementById('open-folder-tree').onclick = () => {
+        treeRoots = splitList(sourceInput.value).map((source) => source.split('>')[0].trim());
+        if (treeRoots.length === 0) {
+            addLog(t('gui-log-missing-paths'));
+            return;
+        }
+        unticked = new Set(splitList(optExcludeDirs.value)
+            .filter((rule) => isAbsolute(rule) && belowRoots(rule)));
+        folderTree.replaceChildren(...treeRoots.map((root) => {
+            const item = document.createElement('li');
+            const row = document.createElement('div');
+            row.className = 'tree-row';
+            row.textContent = root;
+            const children = document.createElement('ul');
+            item.append(row, children);
+            loadFolders(children, root);
+            return item;
+        }));
+        folderTreeModal.classList.add('show');
+        document.getElementById('tree-cancel').focus();
+    };
+
+    document.getElementById('tree-cancel').onclick = closeFolderTree;
+    document.getElementById('tree-apply').onclick = () => {
+        const rules = splitList(optExcludeDirs.value)
+            .filter((rule) => !(isAbsolute(rule) && belowRoots(rule)));
+        // Folders unticked below another unticked one need no rule
+        const chosen = [...unticked].filter((path) => ![...unticked].some((dir) => isBelow(path, dir)));
+        optExcludeDirs.value = [...rules, ...chosen].join('; ');
+        addLog(t('gui-log-folders-chosen', { count: chosen.length }));
+        closeFolderTree();
+    };
+
+    folderTreeModal.addEventListener('keydown', (e) => {
+        if (e.key === 'Escape') {
+            e.preventDefault();
+            closeFolderTree();
+        } else if (e.key === 'Tab') {
+            const focusable = [...folderTreeModal.querySelectorAll('button, input')]
+                .filter((el) => !el.disabled);
+            const index = focusable.indexOf(document.activeElement);
+            const last = focusable.length - 1;
+            if (e.shiftKey && index <= 0) {
+                e.preventDefault();
+                focusable[last].focus();
+            } else if (!e.shiftKey && index === last) {
+                e.preventDefault();
+                focusable[0].focus();
+            }
+        }
+    });
+
+    // Pattern tester: which files of a folder the include patterns and
+    // exclusions pick, to find out why a filter copies nothing
+    const patternTestModal = document.getElementById('pattern-test-modal');
+    const testDir = document.getElementById('test-dir');
+    const testPatterns = document.getElementById('test-patterns');
+    const testExcludeFiles = document.getElementById('test-exclude-files');
+    const testExcludeDirs = document.getElementById('test-exclude-dirs');
+    const testSummary = document.getElementById('test-summary');
+    const testResults = document.getElementById('test-results');
+    let testTimer = null;
+    let testSeq = 0;
+
+    // The catch-all pattern shows as an empty field
+    const showPatterns = (field, patterns) => {
+        const catchAll = patterns.length === 1 && (patterns[0] === '*.*' || patterns[0] === '*');
+        field.value = catchAll ? '' : patterns.join('; ');
+    };
+
+    const runPatternTest = () => {
+        clearTimeout(testTimer);
+        testTimer = setTimeout(async () => {
+            const seq = ++testSeq;
+            const dir = testDir.value.trim();
+            if (!dir) {
+                testSummary.textContent = '';
+                testResults.replaceChildren();
+                return;
+            }
+            let peek;
+            try {
+                peek = await invoke('test_patterns', {
+                    dir,
+                    patterns: splitList(testPatterns.value),
+                    excludeFiles: splitList(testExcludeFiles.value),
+                    excludeDirs: splitList(testExcludeDirs.value),
+                    recursive: document.getElementById('opt-recursive').checked,
+                });
+            } catch (e) {
+                if (seq === testSeq) testSummary.textContent = String(e);
+                return;
+            }
+            // A newer test is on its way
+            if (seq !== testSeq) return;
+
+            let summary = t('gui-pattern-test-summary', {
+                matched: peek.matched.length,
+                left_out: peek.left_out.length,
+            });
+            if (peek.truncated) summary += ' ' + t('gui-pattern-test-more', { scanned: peek.scanned });
+            if (peek.matched.length === 0) summary += ' ' + t('gui-pattern-test-none');
+            testSummary.textContent = summary;
+            testResults.replaceChildren();
+            for (const path of peek.matched) {
+                const item = document.createElement('li');
+                item.textContent = `+ ${path}`;
+                testResults.append(item);
+            }
+            for (const left of peek.left_out) {
+                const item = document.createElement('li');
+                item.className = 'left-out';
+                item.textContent = `- ${t('gui-edit-left-out', { path: left.path, reason: left.reason })}`;
+                testResults.append(item);
+            }
+        }, 300);
+    };
+
+    const closePatternTest = () => {
+        clearTimeout(testTimer);
+        testSeq++;
+        patternTestModal.classList.remove('show');
+        document.getElementById('open-pattern-test').focus();
+    };
+
+    document.getElementById('open-pattern-test').onclick = () => {
+        testDir.value = splitList(sourceInput.value)[0] || '';
+        testPatterns.value = optPatterns.value;
+        testExcludeFiles.value = optExcludeFiles.value;
+        testExcludeDirs.value = optExcludeDirs.value;
+        testSummary.textContent = '';
+        testResults.replaceChildren();
+        patternTestModal.classList.add('show');
+        (testDir.value ? testPatterns : testDir).focus();
+        runPatternTest();
+    };
+
+    for (const field of [testDir, testPatterns, testExcludeFiles, testExcludeDirs]) {
+        field.addEventListener('input', runPatternTest);
+    }
+
+    document.getElementById('test-browse-dir').onclick = async () => {
+        const selected = await open({ directory: true, multiple: false });
+        if (selected) {
+            testDir.value = selected;
+            runPatternTest();
+        }
+    };
+
+    document.getElementById('test-close').onclick = closePatternTest;
+    document.getElementById('test-use').onclick = () => {
+        optPatterns.value = testPatterns.value;
+        optExcludeFiles.value = testExcludeFiles.value;
+        optExcludeDirs.value = testExcludeDirs.value;
+        closePatternTest();
+    };
+
+    patternTestModal.addEventListener('keydown', (e) => {
+        if (e.key === 'Escape') {
+            e.preventDefault();
+            closePatternTest();
+        } else if (e.key === 'Tab') {
+            const focusable = [...patternTestModal.querySelectorAll('button, input')]
+                .filter((el) => !el.disabled);
+            const index = focusable.indexOf(document.activeElement);
+            const last = focusable.length - 1;
+            if (e.shiftKey && index <= 0) {
+                e.preventDefault();
+                focusable[last].focus();
+            } else if (!e.shiftKey && index === last) {
+                e.preventDefault();
+                focusable[0].focus();
+            }
+        }
+    });
+
+    // Offer to resume jobs left in the queue by a crash or by closing the app
+    invoke('queued_jobs').then((jobs) => {
+        if (jobs.length > 0 && !isRunning) {
+            btnResume.hidden = false;
+            for (const job of jobs) {
+                const state = job.status === 'Interrupted' ? t('gui-job-interrupted') : t('gui-job-pending');
+                addLog(t('gui-log-queued-job', {
+                    state,
+                    sources: job.options.sources.join(', '),
+                    destination: job.options.destination
+                }));
+            }
+        }
+    }).catch(() => { });
+
+    btnResume.onclick = async () => {
+        try {
+            setRunning();
+            await invoke('resume_queue');
+            addLog(t('gui-log-resuming'));
+            setStatus(t('gui-status-scanning'));
+        } catch (e) {
+            addLog(t('gui-log-error', { error: e }));
             isRunning = false;
             btnStart.disabled = false;
         }
@@ -303,14 +1757,14 @@ document.addEventListener('DOMContentLoaded', () => {
 
     btnCancel.onclick = async () => {
         await invoke('cancel_copy');
-        addLog("Cancellation requested.");
+        addLog(t('gui-log-cancel-requested'));
     };
 
     btnPause.onclick = async () => {
         await invoke('toggle_pause');
         isPaused = !isPaused;
-        btnPause.textContent = isPaused ? "Continue" : "Pause";
-        addLog(isPaused ? "Operation paused." : "Operation resumed.");
+        btnPause.textContent = isPaused ? t('gui-continue') : t('gui-pause');
+        addLog(isPaused ? t('gui-log-paused') : t('gui-log-resumed'));
     };
 
     // Tauri Events
@@ -319,40 +1773,81 @@ document.addEventListener('DOMContentLoaded', () => {
         const pct = info.bytes_total === 0 ? 0 : (info.bytes_done / info.bytes_total) * 100;
         setProgress(pct);
 
-        currentFileText.textContent = info.current_file || "Scanning...";
-        speedText.textContent = `${(info.speed / 1024 / 1024).toFixed(2)} MB/s`;
-        fileCountText.textContent = `${info.files_done} of ${info.files_total} objects`;
+        if (info.state === 'Scanning') {
+            // Show the scan moving along, it can take minutes on a large share
+            currentFileText.textContent = info.scan_dir || t('gui-scanning');
+            speedText.textContent = formatSize(info.bytes_total);
+            fileCountText.textContent = t('gui-scan-count', { files: info.files_total, dirs: info.dirs_scanned });
+        } else {
+            currentFileText.textContent = info.current_file || t('gui-scanning');
+            currentFile = info.current_file;
+            btnRevealFile.disabled = !currentFile;
+            speedText.textContent = `${formatSize(info.speed)}/s`;
+            fileCountText.textContent = t('gui-object-count', { done: info.files_done, total: info.files_total });
+        }
 
         if (info.state === 'Scanning') {
-            setStatus("scanning...");
+            setStatus(t('gui-status-scanning'));
         } else if (info.state === 'Copying') {
-            setStatus(isPaused ? "paused" : "copying...");
+            setStatus(isPaused ? t('gui-status-paused') : t('gui-status-copying'));
         } else if (info.state === 'Paused') {
-            setStatus("paused", "var(--yellow)");
+            setStatus(t('gui-status-paused'), "var(--yellow)");
+        } else if (info.state === 'Finalizing') {
+            setStatus(t('gui-status-finalizing'));
+        } else if (info.state === 'Stalled') {
+            setStatus(t('gui-status-stalled'), "var(--yellow)");
+        } else if (info.state === 'NetworkPaused') {
+            setStatus(t('gui-status-networkpaused'), "var(--yellow)");
+        } else if (info.state === 'BatteryPaused') {
+            setStatus(t('gui-status-batterypaused'), "var(--yellow)");
+        }
+
+        // The next queued job has started
+        if (['Scanning', 'Copying', 'Finalizing'].includes(info.state) && !isRunning) {
+            setRunning();
         }
 
-        if (info.state === 'Completed' || info.state === 'Failed' || info.state === 'Cancelled') {
+        const finished = ['Completed', 'Partial', 'Failed', 'Cancelled'].includes(info.state);
+        if (finished && queuedCount <= 1) {
             isRunning = false;
             btnStart.disabled = false;
             btnCancel.disabled = true;
             btnPause.disabled = true;
-            btnStart.textContent = "Start Copy";
+            btnStart.textContent = t('gui-start-copy');
 
-            const finalStatus = info.state === 'Completed' ? "finished" : info.state.toLowerCase();
-            const statusColor = info.state === 'Completed' ? 'var(--emerald)' : 'var(--red)';
+            const finalStatus = info.state === 'Completed' ? t('gui-status-finished') : t(`gui-status-${info.state.toLowerCase()}`);
+            const statusColor = info.state === 'Completed' ? 'var(--accent)'
+                : info.state === 'Partial' ? 'var(--yellow)' : 'var(--red)';
             setStatus(finalStatus, statusColor);
 
-            addLog(`Operation finished with state: ${info.state}`);
+            addLog(t('gui-log-finished', { state: info.state }));
+            showCategories(info.categories ?? []);
+            if (info.needs_elevation) {
+                showElevationModal();
+            }
 
             // Reset back to "ready" after 10 seconds
             statusTimer = setTimeout(() => {
-                setStatus("ready");
-                currentFileText.textContent = "Ready to copy";
+                setStatus(t('gui-status-ready'));
+                currentFileText.textContent = t('gui-ready-to-copy');
+                currentFile = '';
+                btnRevealFile.disabled = true;
                 fileCountText.style.visibility = 'hidden'; // Hide object count when idle
             }, 10000);
         }
     });
 
+    // Number of jobs in the queue, including the running one
+    let queuedCount = 0;
+    listen('queue-changed', (event) => {
+        queuedCount = event.payload;
+        refreshQueue();
+    });
+
+    listen('job-failures', (event) => {
+        addFailures(event.payload.failures, event.payload.report);
+    });
+
     listen('copy-log', (event) => {
         addLog(event.payload);
     });